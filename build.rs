@@ -0,0 +1,20 @@
+use std::process::Command;
+
+fn main() {
+    // Embed the commit the binary was built from in run manifests
+    let hash = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok());
+    if let Some(hash) = hash {
+        println!("cargo:rustc-env=GIT_HASH={}", hash.trim());
+    }
+    // gRPC control interface
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/distributor.proto").expect("compile gRPC protos");
+
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+}
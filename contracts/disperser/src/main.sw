@@ -0,0 +1,35 @@
+contract;
+
+use std::{asset::transfer, call_frames::msg_asset_id, context::msg_amount};
+
+abi Disperser {
+    /// Sends `amounts[i]` of the forwarded asset to `recipients[i]`. The amounts must
+    /// add up to exactly what was forwarded, so nothing is left in the contract.
+    #[payable]
+    fn disperse(recipients: Vec<Address>, amounts: Vec<u64>);
+}
+
+impl Disperser for Contract {
+    #[payable]
+    fn disperse(recipients: Vec<Address>, amounts: Vec<u64>) {
+        assert(recipients.len() == amounts.len());
+        let mut total = 0;
+        let mut i = 0;
+        while i < amounts.len() {
+            total += amounts.get(i).unwrap();
+            i += 1;
+        }
+        assert(total == msg_amount());
+
+        let asset_id = msg_asset_id();
+        i = 0;
+        while i < recipients.len() {
+            transfer(
+                Identity::Address(recipients.get(i).unwrap()),
+                asset_id,
+                amounts.get(i).unwrap(),
+            );
+            i += 1;
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! Idle wallet detection: the change in a wallet's balance between cycles, less the
+//! top-ups it was sent, is what its bot spent. A wallet that has spent nothing for
+//! `IDLE_AFTER` is flagged idle and no longer topped up until it spends again.
+//! With `FUNDING_RUNWAY`, top-ups are sized from the same spending. A balance above
+//! what the wallet was left with is income from elsewhere, reported as inbound.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+/// Longest `FUNDING_RUNWAY`, and how long spending is remembered for it.
+pub const MAX_RUNWAY: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// Spending of one wallet since continual funding first saw it.
+struct WalletActivity {
+    /// Balance at the last observation plus the top-ups sent since.
+    expected: u64,
+    /// Unix time of the first observation.
+    first_seen: u64,
+    /// Unix time spending was last seen, or of the first observation.
+    last_spent: u64,
+    /// Total spent since the first observation, in base units.
+    spent: u128,
+    /// What was spent within [`MAX_RUNWAY`]: (unix time, amount).
+    recent: VecDeque<(u64, u64)>,
+}
+
+/// Spending of every wallet checked by continual funding.
+#[derive(Default)]
+pub struct Activity {
+    wallets: HashMap<usize, WalletActivity>,
+}
+
+impl Activity {
+    /// Records `balance` of `wallet_index` at `now`. Returns what it spent since the
+    /// last observation.
+    pub fn observe(&mut self, wallet_index: usize, balance: u64, now: u64) -> u64 {
+        let wallet = self.wallets.entry(wallet_index).or_insert(WalletActivity {
+            expected: balance,
+            first_seen: now,
+            last_spent: now,
+            spent: 0,
+            recent: VecDeque::new(),
+        });
+        let spent = wallet.expected.saturating_sub(balance);
+        if spent > 0 {
+            wallet.last_spent = now;
+            wallet.spent += u128::from(spent);
+            wallet.recent.push_back((now, spent));
+        }
+        while wallet
+            .recent
+            .front()
+            .is_some_and(|(at, _)| *at + MAX_RUNWAY.as_secs() <= now)
+        {
+            wallet.recent.pop_front();
+        }
+        wallet.expected = balance;
+        spent
+    }
+
+    /// Records a top-up of `amount` sent to `wallet_index`, so it is not mistaken for
+    /// the wallet's own income.
+    pub fn funded(&mut self, wallet_index: usize, amount: u64) {
+        if let Some(wallet) = self.wallets.get_mut(&wallet_index) {
+            wallet.expected = wallet.expected.saturating_add(amount);
+        }
+    }
+
+    /// Records `amount` taken out of `wallet_index` by the tool itself, such as an
+    /// excess swept back, so it is not mistaken for spending.
+    pub fn withdrawn(&mut self, wallet_index: usize, amount: u64) {
+        if let Some(wallet) = self.wallets.get_mut(&wallet_index) {
+            wallet.expected = wallet.expected.saturating_sub(amount);
+        }
+    }
+
+    /// What `wallet_index` holding `balance` received beyond its last balance and the
+    /// top-ups sent since; zero before its first observation.
+    pub fn received(&self, wallet_index: usize, balance: u64) -> u64 {
+        self.wallets
+            .get(&wallet_index)
+            .map_or(0, |wallet| balance.saturating_sub(wallet.expected))
+    }
+
+    /// Whether `wallet_index` has spent nothing within `idle_after` of `now`.
+    pub fn is_idle(&self, wallet_index: usize, idle_after: Duration, now: u64) -> bool {
+        self.wallets
+            .get(&wallet_index)
+            .is_some_and(|wallet| now.saturating_sub(wallet.last_spent) >= idle_after.as_secs())
+    }
+
+    /// Wallets idle at `now`, in index order.
+    pub fn idle(&self, idle_after: Duration, now: u64) -> Vec<usize> {
+        let mut idle: Vec<usize> = self
+            .wallets
+            .keys()
+            .copied()
+            .filter(|index| self.is_idle(*index, idle_after, now))
+            .collect();
+        idle.sort_unstable();
+        idle
+    }
+
+    /// Average spending of `wallet_index` per hour since it was first seen, or `None`
+    /// before an hour has passed.
+    pub fn hourly_spend(&self, wallet_index: usize, now: u64) -> Option<u128> {
+        let wallet = self.wallets.get(&wallet_index)?;
+        let observed = now.saturating_sub(wallet.first_seen);
+        (observed >= 3600).then(|| wallet.spent * 3600 / u128::from(observed))
+    }
+
+    /// What `wallet_index` spent within `window` of `now`, and over how many seconds of
+    /// that window it was observed.
+    fn recent_spend(&self, wallet_index: usize, window: Duration, now: u64) -> Option<(u128, u64)> {
+        let wallet = self.wallets.get(&wallet_index)?;
+        let since = now.saturating_sub(window.as_secs());
+        let spent = wallet
+            .recent
+            .iter()
+            .filter(|(at, _)| *at > since)
+            .map(|(_, amount)| u128::from(*amount))
+            .sum();
+        Some((spent, now - wallet.first_seen.max(since)))
+    }
+}
+
+/// Top-ups sized to last `duration` at each wallet's recent spending
+/// (`FUNDING_RUNWAY`), instead of a fixed `TOP_UP_AMOUNT`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Runway {
+    pub duration: Duration,
+    /// Smallest top-up (`MIN_TOP_UP_AMOUNT`).
+    pub min_amount: u64,
+    /// Largest top-up (`MAX_TOP_UP_AMOUNT`).
+    pub max_amount: u64,
+}
+
+impl Runway {
+    /// The top-up that lets `wallet_index`, holding `balance`, keep spending at the
+    /// rate of the last `duration` for another `duration`, and that at least brings
+    /// it to `threshold`, within the bounds. `None` until the wallet was observed for
+    /// an hour, or the whole runway if shorter.
+    pub fn top_up(
+        &self,
+        activity: &Activity,
+        wallet_index: usize,
+        balance: u64,
+        threshold: u64,
+        now: u64,
+    ) -> Option<u64> {
+        let (spent, observed) = activity.recent_spend(wallet_index, self.duration, now)?;
+        if observed == 0 || observed < self.duration.as_secs().min(3600) {
+            return None;
+        }
+        let needed = spent * u128::from(self.duration.as_secs()) / u128::from(observed);
+        let amount = needed
+            .saturating_sub(balance.into())
+            .max(threshold.saturating_sub(balance).into())
+            .max(self.min_amount.into())
+            .min(self.max_amount.into());
+        Some(amount as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_wallets_that_stop_spending() {
+        let idle_after = Duration::from_secs(3600);
+        let mut activity = Activity::default();
+        assert_eq!(activity.observe(1, 1_000, 0), 0);
+        assert_eq!(activity.observe(2, 1_000, 0), 0);
+
+        // Wallet 1 spends, and its top-up is not counted as income
+        assert_eq!(activity.observe(1, 400, 1_800), 600);
+        activity.funded(1, 1_000);
+        assert_eq!(activity.observe(1, 1_400, 2_000), 0);
+        assert_eq!(activity.observe(2, 1_000, 2_000), 0);
+
+        assert!(!activity.is_idle(1, idle_after, 3_600));
+        assert_eq!(activity.idle(idle_after, 3_600), vec![2]);
+        assert_eq!(activity.hourly_spend(1, 7_200), Some(300));
+
+        // Spending again clears the flag
+        assert_eq!(activity.observe(2, 900, 7_200), 100);
+        assert_eq!(activity.idle(idle_after, 7_200), vec![1]);
+    }
+
+    #[test]
+    fn tells_income_from_top_ups() {
+        let mut activity = Activity::default();
+        assert_eq!(activity.received(1, 5_000), 0);
+        activity.observe(1, 1_000, 0);
+        activity.funded(1, 500);
+        assert_eq!(activity.received(1, 1_500), 0);
+        assert_eq!(activity.received(1, 4_000), 2_500);
+        assert_eq!(activity.observe(1, 4_000, 60), 0);
+
+        // An excess swept back is not spending
+        activity.withdrawn(1, 3_000);
+        assert_eq!(activity.observe(1, 1_000, 120), 0);
+        assert_eq!(activity.received(1, 1_000), 0);
+    }
+
+    #[test]
+    fn sizes_top_ups_to_the_runway() {
+        let runway = Runway {
+            duration: Duration::from_secs(6 * 3600),
+            min_amount: 1_000,
+            max_amount: 50_000,
+        };
+        let mut activity = Activity::default();
+        activity.observe(1, 20_000, 0);
+        // Not observed for an hour yet
+        assert_eq!(runway.top_up(&activity, 1, 20_000, 5_000, 1_800), None);
+
+        // 12_000 spent in two hours: six hours need 36_000, of which 8_000 are left
+        activity.observe(1, 8_000, 7_200);
+        assert_eq!(
+            runway.top_up(&activity, 1, 8_000, 5_000, 7_200),
+            Some(28_000)
+        );
+        // Bounded by the largest top-up
+        activity.observe(1, 0, 7_300);
+        assert_eq!(runway.top_up(&activity, 1, 0, 5_000, 7_300), Some(50_000));
+
+        // A wallet that barely spends still gets back to the threshold
+        activity.observe(2, 4_000, 0);
+        activity.observe(2, 3_990, 7_200);
+        assert_eq!(
+            runway.top_up(&activity, 2, 3_990, 5_000, 7_200),
+            Some(1_010)
+        );
+    }
+}
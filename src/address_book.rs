@@ -0,0 +1,107 @@
+use crate::recipients::parse_address;
+use fuels::types::{bech32::Bech32Address, AssetId};
+use serde::Deserialize;
+use std::{collections::HashMap, env, error::Error, fs, str::FromStr};
+
+/// Requirements of a known destination, such as an exchange deposit address.
+#[derive(Debug, Deserialize)]
+pub struct Destination {
+    pub name: String,
+    pub address: String,
+    /// Smallest amount the destination credits, in base units.
+    #[serde(default)]
+    pub min_amount: u64,
+    /// Transfers without a memo (deposit tag) are lost or need manual recovery.
+    #[serde(default)]
+    pub requires_memo: bool,
+    /// Assets the destination accepts; empty means any.
+    #[serde(default)]
+    pub allowed_assets: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct AddressBookFile {
+    destinations: Vec<Destination>,
+}
+
+/// Destination profiles loaded from `ADDRESS_BOOK`, keyed by address.
+#[derive(Debug, Default)]
+pub struct AddressBook {
+    destinations: HashMap<Bech32Address, (Destination, Vec<AssetId>)>,
+}
+
+impl AddressBook {
+    /// Loads the JSON address book named by `ADDRESS_BOOK`, or an empty one if unset.
+    pub fn from_env() -> Result<Self, Box<dyn Error>> {
+        match env::var("ADDRESS_BOOK") {
+            Ok(path) => Self::load(&path),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let file: AddressBookFile = serde_json::from_str(&fs::read_to_string(path)?)
+            .map_err(|e| format!("Invalid address book {}: {}", path, e))?;
+
+        let mut destinations = HashMap::new();
+        for destination in file.destinations {
+            let address = parse_address(&destination.address)
+                .map_err(|e| format!("Address book entry '{}': {}", destination.name, e))?;
+            let allowed_assets = destination
+                .allowed_assets
+                .iter()
+                .map(|asset| {
+                    AssetId::from_str(asset).map_err(|_| {
+                        format!(
+                            "Address book entry '{}': invalid asset id '{}'",
+                            destination.name, asset
+                        )
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            if destinations
+                .insert(address, (destination, allowed_assets))
+                .is_some()
+            {
+                return Err(format!("Address book {} lists an address twice", path).into());
+            }
+        }
+        Ok(Self { destinations })
+    }
+
+    /// Checks a transfer against the destination's profile, if it has one.
+    /// `asset_id` may be omitted when the asset is not known yet.
+    pub fn check(
+        &self,
+        to_address: &Bech32Address,
+        asset_id: Option<&AssetId>,
+        amount: u64,
+        memo: Option<&str>,
+    ) -> Result<(), String> {
+        let Some((destination, allowed_assets)) = self.destinations.get(to_address) else {
+            return Ok(());
+        };
+
+        if amount < destination.min_amount {
+            return Err(format!(
+                "{} ({}) requires at least {} base units, got {}",
+                destination.name, to_address, destination.min_amount, amount
+            ));
+        }
+        if destination.requires_memo && memo.map_or(true, str::is_empty) {
+            return Err(format!(
+                "{} ({}) requires a memo",
+                destination.name, to_address
+            ));
+        }
+        if let Some(asset_id) = asset_id {
+            if !allowed_assets.is_empty() && !allowed_assets.contains(asset_id) {
+                return Err(format!(
+                    "{} ({}) does not accept asset {}",
+                    destination.name, to_address, asset_id
+                ));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,314 @@
+use crate::{daemon::unix_now, report::ReportWriter, wallets::Fleet};
+use clap::ValueEnum;
+use fuels::{
+    accounts::{wallet::WalletUnlocked, ViewOnlyAccount},
+    core::traits::Signer,
+    crypto::{Message, PublicKey, Signature},
+    types::{
+        bech32::{Bech32Address, FUEL_BECH32_HRP},
+        Address,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fs::{self, File},
+    ops::Range,
+    path::Path,
+    str::FromStr,
+};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    /// One JSON object per line.
+    Jsonl,
+    Csv,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletAddress {
+    pub index: usize,
+    pub path: String,
+    pub bech32: String,
+    pub hex: String,
+}
+
+/// Derives the address of one HD wallet without connecting to a provider.
+pub fn derive_address(fleet: &Fleet, index: usize) -> Result<WalletAddress, Box<dyn Error>> {
+    let wallet = fleet.wallet(index, None)?;
+    Ok(WalletAddress {
+        index,
+        path: fleet.derivation.path(index),
+        bech32: wallet.address().to_string(),
+        hex: format!("{:#x}", Address::from(wallet.address())),
+    })
+}
+
+/// Prints the addresses of the HD wallets in `wallets` and optionally writes them to `output`.
+///
+/// Addresses are written as they are derived, so memory use does not grow with the fleet.
+pub fn export_addresses(
+    fleet: &Fleet,
+    wallets: Range<usize>,
+    output: Option<&Path>,
+    format: ExportFormat,
+) -> Result<(), Box<dyn Error>> {
+    let mut report = match output {
+        Some(output) => Some(ReportWriter::new(
+            File::create(output)?,
+            format,
+            None,
+            "addresses",
+        )?),
+        None => None,
+    };
+
+    for index in wallets {
+        let address = derive_address(fleet, index)?;
+        println!("{}\t{}\t{}", address.index, address.bech32, address.hex);
+        if let Some(report) = &mut report {
+            report.write(&address)?;
+        }
+    }
+
+    if let (Some(report), Some(output)) = (report, output) {
+        let rows = report.finish()?;
+        println!("Wrote {} addresses to {}", rows, output.display());
+    }
+
+    Ok(())
+}
+
+/// Version of the `export-manifest` format.
+const MANIFEST_VERSION: u32 = 1;
+
+/// Index to address mapping of a fleet, signed by its main wallet (wallet 0) so
+/// services that whitelist the bot addresses can check where it came from.
+///
+/// The signature covers the SHA-256 of the compact JSON of every field before
+/// `signer`, in the order written.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FleetManifest {
+    #[serde(flatten)]
+    body: ManifestBody,
+    /// Bech32 address of the main wallet that signed the manifest.
+    pub signer: String,
+    pub signature: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestBody {
+    version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fleet: Option<String>,
+    /// Derivation path template, e.g. `m/44'/1179993420'/<index>'/0/0`.
+    derivation: String,
+    created_at: u64,
+    wallets: Vec<ManifestWallet>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestWallet {
+    #[serde(flatten)]
+    address: WalletAddress,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    public_key: Option<String>,
+}
+
+impl ManifestBody {
+    fn message(&self) -> Result<Message, Box<dyn Error>> {
+        Ok(Message::new(serde_json::to_vec(self)?))
+    }
+}
+
+/// Builds the signed manifest of the HD wallets in `wallets`, with their public
+/// keys if `public_keys` is set.
+pub async fn build_manifest(
+    fleet: &Fleet,
+    wallets: Range<usize>,
+    public_keys: bool,
+) -> Result<FleetManifest, Box<dyn Error>> {
+    let mut entries = Vec::with_capacity(wallets.len());
+    for index in wallets {
+        let public_key = if public_keys {
+            Some(public_key(&fleet.wallet(index, None)?).await?.to_string())
+        } else {
+            None
+        };
+        entries.push(ManifestWallet {
+            address: derive_address(fleet, index)?,
+            public_key,
+        });
+    }
+    let body = ManifestBody {
+        version: MANIFEST_VERSION,
+        fleet: fleet.name.clone(),
+        derivation: fleet.derivation.to_string(),
+        created_at: unix_now(),
+        wallets: entries,
+    };
+
+    let main_wallet = fleet.wallet(0, None)?;
+    let signature = main_wallet.sign(body.message()?).await?;
+    Ok(FleetManifest {
+        body,
+        signer: main_wallet.address().to_string(),
+        signature: signature.to_string(),
+    })
+}
+
+/// Writes the signed manifest of the HD wallets in `wallets` to `output`, or prints it.
+pub async fn export_manifest(
+    fleet: &Fleet,
+    wallets: Range<usize>,
+    public_keys: bool,
+    output: Option<&Path>,
+) -> Result<(), Box<dyn Error>> {
+    let manifest = build_manifest(fleet, wallets, public_keys).await?;
+    let json = serde_json::to_string_pretty(&manifest)?;
+    match output {
+        Some(output) => {
+            fs::write(output, json + "\n")?;
+            println!(
+                "Wrote manifest of {} wallets signed by {} to {}",
+                manifest.body.wallets.len(),
+                manifest.signer,
+                output.display()
+            );
+        }
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+/// Checks that the manifest at `path` is signed by this fleet's main wallet and that
+/// every entry matches the address (and public key) derived from the current
+/// mnemonic and derivation path.
+pub async fn verify_manifest(fleet: &Fleet, path: &Path) -> Result<(), Box<dyn Error>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read manifest {}: {}", path.display(), e))?;
+    let manifest: FleetManifest = serde_json::from_str(&contents)
+        .map_err(|e| format!("Invalid manifest {}: {}", path.display(), e))?;
+    let mismatches = check_manifest(fleet, &manifest).await?;
+    if !mismatches.is_empty() {
+        for mismatch in &mismatches {
+            println!("{}", mismatch);
+        }
+        return Err(format!(
+            "Manifest {} does not match the current configuration ({} problems)",
+            path.display(),
+            mismatches.len()
+        )
+        .into());
+    }
+    println!(
+        "Manifest {} matches: {} wallets, signed by {}",
+        path.display(),
+        manifest.body.wallets.len(),
+        manifest.signer
+    );
+    Ok(())
+}
+
+/// Everything in `manifest` that does not match `fleet`.
+async fn check_manifest(
+    fleet: &Fleet,
+    manifest: &FleetManifest,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let body = &manifest.body;
+    if body.version != MANIFEST_VERSION {
+        return Err(format!("Unsupported manifest version {}", body.version).into());
+    }
+    let mut mismatches = Vec::new();
+
+    let main_wallet = fleet.wallet(0, None)?;
+    let signature = Signature::from_str(&manifest.signature)
+        .map_err(|e| format!("Invalid manifest signature: {}", e))?;
+    match signature.recover(&body.message()?) {
+        Ok(key) if Bech32Address::new(FUEL_BECH32_HRP, key.hash()) == *main_wallet.address() => {}
+        _ => mismatches.push(format!(
+            "signature: not made by the main wallet {} over this manifest",
+            main_wallet.address()
+        )),
+    }
+    if manifest.signer != main_wallet.address().to_string() {
+        mismatches.push(format!(
+            "signer: manifest has {}, main wallet is {}",
+            manifest.signer,
+            main_wallet.address()
+        ));
+    }
+    if body.derivation != fleet.derivation.to_string() {
+        mismatches.push(format!(
+            "derivation: manifest has {}, configured {}",
+            body.derivation, fleet.derivation
+        ));
+    }
+
+    for entry in &body.wallets {
+        let index = entry.address.index;
+        let derived = derive_address(fleet, index)?;
+        if entry.address != derived {
+            mismatches.push(format!(
+                "HD wallet {}: manifest has {} ({}), derived {} ({})",
+                index, entry.address.bech32, entry.address.path, derived.bech32, derived.path
+            ));
+        }
+        if let Some(expected) = &entry.public_key {
+            let derived = public_key(&fleet.wallet(index, None)?).await?;
+            if PublicKey::from_str(expected).ok() != Some(derived) {
+                mismatches.push(format!(
+                    "HD wallet {}: manifest public key {} does not match {}",
+                    index, expected, derived
+                ));
+            }
+        }
+    }
+    Ok(mismatches)
+}
+
+/// `WalletUnlocked` does not expose its key, so the public key is recovered from a
+/// signature instead.
+async fn public_key(wallet: &WalletUnlocked) -> Result<PublicKey, Box<dyn Error>> {
+    let message = Message::new(wallet.address().hash().as_ref());
+    let signature = wallet.sign(message).await?;
+    signature
+        .recover(&message)
+        .map_err(|e| format!("Unrecoverable signature: {}", e).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chain::mock::TEST_MNEMONIC, wallets::Derivation};
+    use secrecy::SecretString;
+
+    fn test_fleet(derivation: Derivation) -> Fleet {
+        Fleet::new(SecretString::new(TEST_MNEMONIC.to_string()), derivation)
+    }
+
+    #[tokio::test]
+    async fn verifies_manifests_and_catches_tampering() {
+        let fleet = test_fleet(Derivation::default());
+        let manifest = build_manifest(&fleet, 0..3, true).await.unwrap();
+        let json = serde_json::to_string(&manifest).unwrap();
+        let parsed: FleetManifest = serde_json::from_str(&json).unwrap();
+        assert!(check_manifest(&fleet, &parsed).await.unwrap().is_empty());
+
+        // A swapped address breaks both the entry and the signature
+        let mut tampered: FleetManifest = serde_json::from_str(&json).unwrap();
+        tampered.body.wallets[2].address = derive_address(&fleet, 7).unwrap();
+        tampered.body.wallets[2].address.index = 2;
+        assert_eq!(check_manifest(&fleet, &tampered).await.unwrap().len(), 2);
+
+        // Another derivation path derives different wallets from the same mnemonic
+        let other = test_fleet(Derivation {
+            coin_type: 60,
+            ..Derivation::default()
+        });
+        let mismatches = check_manifest(&other, &parsed).await.unwrap();
+        assert!(mismatches.iter().any(|m| m.starts_with("derivation")));
+        assert!(mismatches.iter().any(|m| m.starts_with("HD wallet 1")));
+    }
+}
@@ -0,0 +1,207 @@
+//! Two-person rule for large plans: a plan moving more than `APPROVAL_LIMIT` of
+//! `ETH_ASSET_ID` only executes once a reviewer has approved it with `approve-plan`,
+//! signing it with a wallet listed in `PLAN_APPROVERS`. The executing host only holds
+//! the approvers' addresses, so it cannot approve its own plans.
+
+use crate::{
+    context::Context, plan::TransferPlan, recipients::parse_address, units, wallets::Fleet,
+};
+use fuels::{
+    core::traits::Signer,
+    crypto::{Message, Signature},
+    types::bech32::{Bech32Address, FUEL_BECH32_HRP},
+};
+use serde::{Deserialize, Serialize};
+use std::{env, error::Error, path::Path, str::FromStr};
+
+/// A reviewer's approval of a plan, over everything in it but the approvals.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Approval {
+    /// Bech32 address of the approving wallet.
+    pub approver: String,
+    pub signature: String,
+}
+
+/// Which plans need approval and whose approvals count.
+pub struct ApprovalPolicy {
+    /// Plans moving more than this need approval; none do without it.
+    pub limit: Option<u64>,
+    /// Wallets whose signature approves a plan.
+    pub approvers: Vec<Bech32Address>,
+}
+
+impl ApprovalPolicy {
+    /// Reads `APPROVAL_LIMIT` (an amount of `ETH_ASSET_ID` in `decimals`) and
+    /// `PLAN_APPROVERS`.
+    pub fn from_env(decimals: u32) -> Result<Self, Box<dyn Error>> {
+        let limit = env::var("APPROVAL_LIMIT")
+            .ok()
+            .map(|value| {
+                units::parse_setting(&value, decimals)
+                    .map_err(|e| format!("Failed to parse APPROVAL_LIMIT: {}", e))
+            })
+            .transpose()?;
+        let approvers = env::var("PLAN_APPROVERS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|approver| !approver.is_empty())
+            .map(|approver| {
+                parse_address(approver).map_err(|e| format!("Invalid PLAN_APPROVERS: {}", e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if limit.is_some() && approvers.is_empty() {
+            return Err("APPROVAL_LIMIT needs PLAN_APPROVERS".into());
+        }
+        Ok(Self { limit, approvers })
+    }
+
+    /// Whether a plan moving `total` needs approval.
+    pub fn requires_approval(&self, total: u128) -> bool {
+        self.limit.is_some_and(|limit| total > u128::from(limit))
+    }
+
+    /// Checks `plan` before `executor` (the main wallet) runs it: every approval must be
+    /// valid for the plan as it is now, and a plan over the limit needs one that is not
+    /// the executor's own.
+    pub fn check(
+        &self,
+        plan: &TransferPlan,
+        total: u128,
+        executor: &Bech32Address,
+    ) -> Result<(), Box<dyn Error>> {
+        let message = plan.message()?;
+        let mut approved = false;
+        for approval in &plan.approvals {
+            self.verify(approval, &message)?;
+            approved |= approval.approver != executor.to_string();
+        }
+        if approved || !self.requires_approval(total) {
+            return Ok(());
+        }
+        Err("The plan moves more than APPROVAL_LIMIT and has no approval from a reviewer; run approve-plan on it first".into())
+    }
+
+    fn verify(&self, approval: &Approval, message: &Message) -> Result<(), Box<dyn Error>> {
+        let approver = parse_address(&approval.approver)
+            .map_err(|e| format!("Invalid approver {}: {}", approval.approver, e))?;
+        if !self.approvers.contains(&approver) {
+            return Err(format!("{} is not one of PLAN_APPROVERS", approver).into());
+        }
+        let signature = Signature::from_str(&approval.signature)
+            .map_err(|e| format!("Invalid approval signature of {}: {}", approver, e))?;
+        match signature.recover(message) {
+            Ok(key) if Bech32Address::new(FUEL_BECH32_HRP, key.hash()) == approver => Ok(()),
+            _ => Err(format!(
+                "The approval of {} does not match the plan: was it modified after approval?",
+                approver
+            )
+            .into()),
+        }
+    }
+}
+
+/// Refuses `what`, about to move up to `total` of `ETH_ASSET_ID` outside a reviewed
+/// plan, when that is more than `APPROVAL_LIMIT`. Checked where transfers start, so
+/// the API, gRPC and single-wallet commands are held to the limit as well.
+pub fn check_unreviewed(ctx: &Context<'_>, what: &str, total: u128) -> Result<(), Box<dyn Error>> {
+    match ctx.approval_limit {
+        Some(limit) if total > u128::from(limit) => Err(format!(
+            "{} would move {}, more than APPROVAL_LIMIT ({}): write it with --plan-only --plan-output, have it approved with approve-plan and run it with --execute-plan",
+            what,
+            ctx.format_amount(total),
+            ctx.format_amount(limit)
+        )
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+/// Implements `approve-plan`: adds an approval of the plan at `path`, signed by the
+/// configured main wallet.
+pub async fn approve_plan(fleet: &Fleet, path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut plan = TransferPlan::load(path)?;
+    let message = plan.message()?;
+    let wallet = fleet.wallet(0, None)?;
+    let approval = Approval {
+        approver: wallet.address().to_string(),
+        signature: wallet.sign(message).await?.to_string(),
+    };
+    println!(
+        "Approved {} plan of {} transfers as {}",
+        plan.command,
+        plan.transfers.len(),
+        approval.approver
+    );
+    plan.approvals
+        .retain(|existing| existing.approver != approval.approver);
+    plan.approvals.push(approval);
+    plan.save(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{plan::PlanStep, signer::Funder, wallets::Derivation};
+    use secrecy::SecretString;
+
+    #[tokio::test]
+    async fn refuses_unapproved_or_modified_plans() {
+        let fleet = Fleet::new(
+            SecretString::new(crate::chain::mock::TEST_MNEMONIC.to_string()),
+            Derivation::default(),
+        );
+        let main_key = fleet.wallet(0, None).unwrap();
+        let main_wallet = Funder::from(main_key.clone());
+        let reviewer = fleet.wallet(1, None).unwrap();
+        let step = PlanStep {
+            wallet_index: 2,
+            from_index: None,
+            from: main_wallet.address().to_string(),
+            to: fleet.wallet(2, None).unwrap().address().to_string(),
+            asset_id: format!("{:#x}", fuels::types::AssetId::default()),
+            balance: 0,
+            amount: 5_000_000,
+            sweep: false,
+        };
+        let mut plan = TransferPlan::new("init-dist", &main_wallet, vec![step]);
+        let policy = ApprovalPolicy {
+            limit: Some(1_000_000),
+            approvers: vec![reviewer.address().clone(), main_wallet.address().clone()],
+        };
+        assert!(policy
+            .check(&plan, 5_000_000, main_wallet.address())
+            .is_err());
+        assert!(policy.check(&plan, 500_000, main_wallet.address()).is_ok());
+
+        // The executor cannot approve its own plan
+        plan.approvals.push(Approval {
+            approver: main_wallet.address().to_string(),
+            signature: main_key
+                .sign(plan.message().unwrap())
+                .await
+                .unwrap()
+                .to_string(),
+        });
+        assert!(policy
+            .check(&plan, 5_000_000, main_wallet.address())
+            .is_err());
+
+        plan.approvals.push(Approval {
+            approver: reviewer.address().to_string(),
+            signature: reviewer
+                .sign(plan.message().unwrap())
+                .await
+                .unwrap()
+                .to_string(),
+        });
+        assert!(policy
+            .check(&plan, 5_000_000, main_wallet.address())
+            .is_ok());
+
+        plan.transfers[0].amount = 50_000_000;
+        assert!(policy
+            .check(&plan, 50_000_000, main_wallet.address())
+            .is_err());
+    }
+}
@@ -0,0 +1,219 @@
+//! Symbols and decimals of the assets the tool handles, from the JSON file named by
+//! `ASSETS` or asked of `ASSET_METADATA_URL`, so logs and text reports read
+//! `0.005 ETH (5000000)` rather than bare base units. Machine output (JSON and CSV
+//! reports, history, webhooks and events) stays in base units.
+
+use crate::units::{self, DEFAULT_DECIMALS};
+use fuels::types::AssetId;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap, env, error::Error, fs, str::FromStr, sync::RwLock, time::Duration,
+};
+use tracing::{debug, warn};
+
+/// Symbol of the chain's base asset unless `ASSETS` names it otherwise.
+pub const BASE_SYMBOL: &str = "ETH";
+
+/// Most decimals an asset may have: amounts are parsed into `u64` base units.
+const MAX_DECIMALS: u32 = 19;
+
+/// Every asset whose symbol and decimals are known, for the rest of the run.
+static KNOWN: RwLock<BTreeMap<AssetId, AssetInfo>> = RwLock::new(BTreeMap::new());
+
+/// What an asset's amounts are shown in.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct AssetInfo {
+    pub symbol: String,
+    pub decimals: u32,
+}
+
+#[derive(Deserialize)]
+struct AssetEntry {
+    id: String,
+    #[serde(flatten)]
+    info: AssetInfo,
+}
+
+#[derive(Deserialize)]
+struct AssetsFile {
+    assets: Vec<AssetEntry>,
+}
+
+/// Loads the assets in the JSON file named by `ASSETS`, if set.
+pub fn load_from_env() -> Result<(), Box<dyn Error>> {
+    let Ok(path) = env::var("ASSETS") else {
+        return Ok(());
+    };
+    let contents =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read assets {}: {}", path, e))?;
+    let assets = parse(&contents).map_err(|e| format!("Assets {}: {}", path, e))?;
+    KNOWN.write().unwrap().extend(assets);
+    Ok(())
+}
+
+fn parse(contents: &str) -> Result<BTreeMap<AssetId, AssetInfo>, Box<dyn Error>> {
+    let file: AssetsFile = serde_json::from_str(contents)?;
+    let mut assets = BTreeMap::new();
+    for entry in file.assets {
+        let asset_id = AssetId::from_str(&entry.id)
+            .map_err(|_| format!("'{}' is not an asset id", entry.id))?;
+        check(&asset_id, &entry.info)?;
+        if assets.insert(asset_id, entry.info).is_some() {
+            return Err(format!("asset {} is listed twice", entry.id).into());
+        }
+    }
+    Ok(assets)
+}
+
+fn check(asset_id: &AssetId, info: &AssetInfo) -> Result<(), String> {
+    if info.symbol.trim().is_empty() {
+        return Err(format!("asset {} has no symbol", asset_id));
+    }
+    if info.decimals > MAX_DECIMALS {
+        return Err(format!(
+            "asset {} has {} decimals, more than {}",
+            asset_id, info.decimals, MAX_DECIMALS
+        ));
+    }
+    Ok(())
+}
+
+/// Registers the chain's base asset as ETH, unless `ASSETS` already describes it.
+pub fn register_base(asset_id: AssetId) {
+    KNOWN
+        .write()
+        .unwrap()
+        .entry(asset_id)
+        .or_insert_with(|| AssetInfo {
+            symbol: BASE_SYMBOL.to_string(),
+            decimals: DEFAULT_DECIMALS,
+        });
+}
+
+/// Every asset whose symbol and decimals are known.
+pub fn ids() -> Vec<AssetId> {
+    KNOWN.read().unwrap().keys().copied().collect()
+}
+
+/// Symbol and decimals of `asset_id`, if known.
+pub fn info(asset_id: &AssetId) -> Option<AssetInfo> {
+    KNOWN.read().unwrap().get(asset_id).cloned()
+}
+
+/// Decimals of `asset_id`, if known.
+pub fn decimals(asset_id: &AssetId) -> Option<u32> {
+    info(asset_id).map(|info| info.decimals)
+}
+
+/// Formats base units of `asset_id`, e.g. `0.005 ETH (5000000)`, or as base units
+/// alone when the asset is unknown.
+pub fn format(amount: u128, asset_id: &AssetId) -> String {
+    match info(asset_id) {
+        Some(info) => format!(
+            "{} {} ({})",
+            units::format_amount(amount, info.decimals),
+            info.symbol,
+            amount
+        ),
+        None => format!("{} base units", amount),
+    }
+}
+
+/// Asks `ASSET_METADATA_URL` for the symbol and decimals of each asset not known yet.
+/// `{asset_id}` in the URL is replaced by the asset id, and the response is a JSON
+/// object with `symbol` and `decimals`, as Fuel's indexer serves them. An asset that
+/// cannot be looked up is only warned about: its amounts are shown in base units.
+pub async fn discover(asset_ids: &[AssetId]) -> Result<(), Box<dyn Error>> {
+    let Ok(url) = env::var("ASSET_METADATA_URL") else {
+        return Ok(());
+    };
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()?;
+    for asset_id in asset_ids {
+        if info(asset_id).is_some() {
+            continue;
+        }
+        let asset_url = url.replace("{asset_id}", &format!("{:#x}", asset_id));
+        let fetched = async {
+            let info: AssetInfo = client
+                .get(&asset_url)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            check(asset_id, &info)?;
+            Ok::<_, Box<dyn Error>>(info)
+        };
+        match fetched.await {
+            Ok(info) => {
+                debug!(
+                    "Asset {} is {} with {} decimals",
+                    asset_id, info.symbol, info.decimals
+                );
+                KNOWN.write().unwrap().insert(*asset_id, info);
+            }
+            Err(e) => warn!(
+                "Could not look up asset {} at {}: {}; its amounts are shown in base units",
+                asset_id, asset_url, e
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Refuses to run with `decimals` for `asset_id` when the asset is known to have
+/// others: every amount setting would be off by a power of ten.
+pub fn check_decimals(asset_id: &AssetId, decimals: u32) -> Result<(), Box<dyn Error>> {
+    match info(asset_id) {
+        Some(info) if info.decimals != decimals => Err(format!(
+            "Amounts are read with {} decimals, but {} ({}) has {}: set ASSET_DECIMALS={}",
+            decimals, info.symbol, asset_id, info.decimals, info.decimals
+        )
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_assets() {
+        let usdc_id = "0x286c479da40dc953bddc3bb4c453b608bba2e0ac483b077bd475174115395e6b";
+        let assets = parse(&format!(
+            r#"{{"assets": [{{"id": "{}", "symbol": "USDC", "decimals": 6}}]}}"#,
+            usdc_id
+        ))
+        .unwrap();
+        let usdc = AssetId::from_str(usdc_id).unwrap();
+        assert_eq!(
+            assets.get(&usdc),
+            Some(&AssetInfo {
+                symbol: "USDC".into(),
+                decimals: 6
+            })
+        );
+
+        KNOWN.write().unwrap().extend(assets);
+        assert_eq!(format(1_500_000, &usdc), "1.5 USDC (1500000)");
+        assert!(check_decimals(&usdc, 6).is_ok());
+        assert!(check_decimals(&usdc, 9).is_err());
+        assert_eq!(format(7, &AssetId::zeroed()), "7 base units");
+
+        let twice = format!(
+            r#"{{"assets": [{{"id": "{0}", "symbol": "A", "decimals": 6}},
+                            {{"id": "{0}", "symbol": "B", "decimals": 6}}]}}"#,
+            usdc_id
+        );
+        assert!(parse(&twice).is_err());
+        assert!(parse(r#"{"assets": [{"id": "0x01", "symbol": "A", "decimals": 6}]}"#).is_err());
+        assert!(parse(&format!(
+            r#"{{"assets": [{{"id": "{}", "symbol": "A", "decimals": 20}}]}}"#,
+            usdc_id
+        ))
+        .is_err());
+    }
+}
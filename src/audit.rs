@@ -0,0 +1,329 @@
+//! Append-only audit log (`AUDIT_LOG`): every run with its parameters, every plan,
+//! transfer and alert, and every result, as one JSON line each. Each entry carries
+//! the hash of the one before, and an HMAC of its own hash under `AUDIT_HMAC_KEY`
+//! if set, so `audit verify` detects entries edited, removed or reordered.
+
+use crate::daemon::unix_now;
+use fuels::{crypto::Hasher, types::Bytes32};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    env,
+    error::Error,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+    thread,
+    time::Duration,
+};
+use tracing::error;
+
+/// `prev_hash` of the first entry.
+const GENESIS: &str = "0x0000000000000000000000000000000000000000000000000000000000000000";
+
+/// How long an append waits for another process appending to the same log.
+const LOCK_WAIT: Duration = Duration::from_secs(5);
+
+/// The log this process appends to, once a run has opened it.
+static LOG: OnceLock<AuditLog> = OnceLock::new();
+
+/// What an entry's hash covers.
+#[derive(Debug, Serialize, Deserialize)]
+struct Body {
+    seq: u64,
+    at: u64,
+    /// The process that wrote the entry, `<start time>-<pid>`.
+    run: String,
+    event: String,
+    details: Value,
+    prev_hash: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    #[serde(flatten)]
+    body: Body,
+    hash: String,
+    /// Hex HMAC-SHA256 of `hash` under `AUDIT_HMAC_KEY`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<String>,
+}
+
+impl Entry {
+    fn seal(body: Body, key: Option<&SecretString>) -> Result<Self, Box<dyn Error>> {
+        let hash = Hasher::hash(serde_json::to_vec(&body)?).to_string();
+        let signature = key.map(|key| sign(key, &hash));
+        Ok(Self {
+            body,
+            hash,
+            signature,
+        })
+    }
+}
+
+fn sign(key: &SecretString, hash: &str) -> String {
+    hmac_sha256(key.expose_secret().as_bytes(), hash.as_bytes()).to_string()
+}
+
+/// HMAC-SHA256 (RFC 2104) of `message` under `key`.
+fn hmac_sha256(key: &[u8], message: &[u8]) -> Bytes32 {
+    const BLOCK_SIZE: usize = 64;
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(Hasher::hash(key).as_ref());
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let inner = Hasher::default()
+        .chain(block.map(|byte| byte ^ 0x36))
+        .chain(message)
+        .finalize();
+    Hasher::default()
+        .chain(block.map(|byte| byte ^ 0x5c))
+        .chain(inner)
+        .finalize()
+}
+
+/// An audit log file and this process's place in it.
+pub struct AuditLog {
+    path: PathBuf,
+    key: Option<SecretString>,
+    run: String,
+    /// Serializes this process's appends; other processes wait on the lock file.
+    append: Mutex<()>,
+}
+
+impl AuditLog {
+    pub fn new(path: PathBuf, key: Option<SecretString>) -> Self {
+        Self {
+            path,
+            key,
+            run: format!("{}-{}", unix_now(), std::process::id()),
+            append: Mutex::new(()),
+        }
+    }
+
+    /// Appends `event` with `details`, chained to the last entry in the file.
+    pub fn append<T: Serialize>(&self, event: &str, details: &T) -> Result<(), Box<dyn Error>> {
+        let _guard = self.append.lock().unwrap_or_else(|e| e.into_inner());
+        let _lock = AppendLock::acquire(&self.path)?;
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&self.path)?;
+        let (seq, prev_hash) = match last_line(&mut file)? {
+            Some(line) => {
+                let last: Entry = serde_json::from_str(&line).map_err(|e| {
+                    format!(
+                        "the last entry of {} is invalid: {}",
+                        self.path.display(),
+                        e
+                    )
+                })?;
+                (last.body.seq + 1, last.hash)
+            }
+            None => (1, GENESIS.to_string()),
+        };
+        let entry = Entry::seal(
+            Body {
+                seq,
+                at: unix_now(),
+                run: self.run.clone(),
+                event: event.to_string(),
+                details: serde_json::to_value(details)?,
+                prev_hash,
+            },
+            self.key.as_ref(),
+        )?;
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        file.write_all(&line)?;
+        file.sync_data()?;
+        Ok(())
+    }
+}
+
+/// Opens `AUDIT_LOG` for the rest of the process, if set.
+pub fn open_from_env() {
+    if let Ok(path) = env::var("AUDIT_LOG") {
+        let key = env::var("AUDIT_HMAC_KEY").ok().map(SecretString::new);
+        let _ = LOG.set(AuditLog::new(path.into(), key));
+    }
+}
+
+/// Appends `event` to the audit log, if one is open. A failed append is logged as an
+/// error; it never stops the operation being audited.
+pub fn record<T: Serialize>(event: &str, details: &T) {
+    if let Some(log) = LOG.get() {
+        if let Err(e) = log.append(event, details) {
+            error!(
+                "Failed to append {} to the audit log {}: {}",
+                event,
+                log.path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Records how the run ended: its exit code and error, if any.
+pub fn finished(exit_code: i32, error: Option<&str>) {
+    record(
+        "run_finished",
+        &json!({ "exit_code": exit_code, "error": error }),
+    );
+}
+
+/// Checks every entry of the log at `path`: its sequence number, the hash of the
+/// entry before, its own hash and, with `key`, its signature. Returns the number of
+/// entries and the last hash, or the first problem found.
+pub fn verify(path: &Path, key: Option<&SecretString>) -> Result<(u64, String), Box<dyn Error>> {
+    let file = File::open(path)
+        .map_err(|e| format!("Failed to read audit log {}: {}", path.display(), e))?;
+    let mut expected_seq = 1;
+    let mut prev_hash = GENESIS.to_string();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        let invalid = |what: String| format!("line {}: {}", number + 1, what);
+        let entry: Entry = serde_json::from_str(&line).map_err(|e| invalid(e.to_string()))?;
+        if entry.body.seq != expected_seq {
+            return Err(invalid(format!(
+                "entry {} where {} was expected; entries were removed or reordered",
+                entry.body.seq, expected_seq
+            ))
+            .into());
+        }
+        if entry.body.prev_hash != prev_hash {
+            return Err(invalid("does not follow the entry before it".into()).into());
+        }
+        let hash = Hasher::hash(serde_json::to_vec(&entry.body)?).to_string();
+        if entry.hash != hash {
+            return Err(invalid("was modified (hash mismatch)".into()).into());
+        }
+        if let Some(key) = key {
+            if entry.signature.as_deref() != Some(sign(key, &hash).as_str()) {
+                return Err(invalid("is not signed with AUDIT_HMAC_KEY".into()).into());
+            }
+        }
+        expected_seq += 1;
+        prev_hash = hash;
+    }
+    Ok((expected_seq - 1, prev_hash))
+}
+
+/// The last line of `file`, without its newline, reading back from the end.
+fn last_line(file: &mut File) -> io::Result<Option<String>> {
+    let mut position = file.seek(SeekFrom::End(0))?;
+    let mut tail: Vec<u8> = Vec::new();
+    while position > 0 {
+        let step = position.min(4096);
+        position -= step;
+        let mut chunk = vec![0; step as usize];
+        file.seek(SeekFrom::Start(position))?;
+        file.read_exact(&mut chunk)?;
+        chunk.extend_from_slice(&tail);
+        tail = chunk;
+        let line = tail.strip_suffix(b"\n").unwrap_or(&tail);
+        if let Some(start) = line.iter().rposition(|byte| *byte == b'\n') {
+            return Ok(Some(
+                String::from_utf8_lossy(&line[start + 1..]).into_owned(),
+            ));
+        }
+    }
+    let line = tail.strip_suffix(b"\n").unwrap_or(&tail);
+    Ok((!line.is_empty()).then(|| String::from_utf8_lossy(line).into_owned()))
+}
+
+/// `<log>.lock`, held while appending so runs sharing a log do not fork its chain.
+struct AppendLock {
+    path: PathBuf,
+}
+
+impl AppendLock {
+    fn acquire(log: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut path = log.as_os_str().to_owned();
+        path.push(".lock");
+        let path = PathBuf::from(path);
+        let mut waited = Duration::ZERO;
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(Self { path }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && waited < LOCK_WAIT => {
+                    thread::sleep(Duration::from_millis(10));
+                    waited += Duration::from_millis(10);
+                }
+                Err(e) => {
+                    return Err(format!(
+                        "Failed to lock {} ({}); remove it if no run is appending",
+                        path.display(),
+                        e
+                    )
+                    .into())
+                }
+            }
+        }
+    }
+}
+
+impl Drop for AppendLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computes_hmac_sha256() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            hex(hmac_sha256(b"Jefe", b"what do ya want for nothing?").as_ref()),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn detects_tampering() {
+        let path = env::temp_dir().join(format!("audit-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let key = SecretString::new("treasury".into());
+        let log = AuditLog::new(path.clone(), Some(SecretString::new("treasury".into())));
+        log.append("run_started", &json!({ "strategy": "reclaim" }))
+            .unwrap();
+        log.append("transfer_confirmed", &json!({ "amount": 5_000_000 }))
+            .unwrap();
+        log.append("run_finished", &json!({ "exit_code": 0 }))
+            .unwrap();
+
+        let (entries, last_hash) = verify(&path, Some(&key)).unwrap();
+        assert_eq!(entries, 3);
+        assert!(verify(&path, Some(&SecretString::new("other".into()))).is_err());
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert!(lines[2].contains(&last_hash));
+
+        // An edited amount
+        fs::write(&path, contents.replace("5000000", "500000")).unwrap();
+        assert!(verify(&path, None).is_err());
+        // A removed entry
+        fs::write(&path, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        assert!(verify(&path, None).is_err());
+
+        // Appends continue the chain of what is in the file
+        fs::write(&path, format!("{}\n", lines[0])).unwrap();
+        log.append("run_finished", &json!({ "exit_code": 1 }))
+            .unwrap();
+        assert_eq!(verify(&path, Some(&key)).unwrap().0, 2);
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,98 @@
+//! Optional publishing of funding lifecycle events to a message bus (NATS or
+//! Kafka), for consumers that already correlate other systems' events there.
+
+use serde::Serialize;
+use std::error::Error;
+use tracing::warn;
+
+/// Subject (NATS) or topic (Kafka) events go to unless `EVENT_BUS_TOPIC` is set.
+pub const DEFAULT_TOPIC: &str = "fund_distributor.events";
+
+/// A connected message bus. Which variants exist depends on the `nats` and
+/// `kafka` cargo features.
+pub enum EventBus {
+    #[cfg(feature = "nats")]
+    Nats {
+        client: async_nats::Client,
+        subject: String,
+    },
+    #[cfg(feature = "kafka")]
+    Kafka {
+        producer: rdkafka::producer::FutureProducer,
+        topic: String,
+    },
+}
+
+impl EventBus {
+    /// Connects to `nats://host:port` or `kafka://broker[,broker...]`.
+    pub async fn connect(url: &str, topic: &str) -> Result<Self, Box<dyn Error>> {
+        let _ = topic;
+        match url.split_once("://") {
+            #[cfg(feature = "nats")]
+            Some(("nats", _)) => Ok(EventBus::Nats {
+                client: async_nats::connect(url).await?,
+                subject: topic.to_string(),
+            }),
+            #[cfg(feature = "kafka")]
+            Some(("kafka", brokers)) => {
+                let producer = rdkafka::ClientConfig::new()
+                    .set("bootstrap.servers", brokers)
+                    .set("message.timeout.ms", "5000")
+                    .create()?;
+                Ok(EventBus::Kafka {
+                    producer,
+                    topic: topic.to_string(),
+                })
+            }
+            Some((scheme @ ("nats" | "kafka"), _)) => Err(format!(
+                "EVENT_BUS_URL uses {}://, which needs a build with the `{}` feature",
+                scheme, scheme
+            )
+            .into()),
+            _ => Err(format!(
+                "Unsupported EVENT_BUS_URL '{}': expected nats://host:port or kafka://broker[,broker...]",
+                url
+            )
+            .into()),
+        }
+    }
+
+    /// Publishes `event` as JSON under `key` (the Kafka message key, which keeps
+    /// one wallet's events in order). Failures are logged and never interrupt funding.
+    pub async fn publish<T: Serialize>(&self, key: &str, event: &T) {
+        let payload = match serde_json::to_vec(event) {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("Failed to encode event for the message bus: {}", e);
+                return;
+            }
+        };
+        let _ = (key, &payload);
+        match *self {
+            #[cfg(feature = "nats")]
+            EventBus::Nats {
+                ref client,
+                ref subject,
+            } => {
+                if let Err(e) = client.publish(subject.clone(), payload.into()).await {
+                    warn!("Publishing event to NATS subject {} failed: {}", subject, e);
+                }
+            }
+            #[cfg(feature = "kafka")]
+            EventBus::Kafka {
+                ref producer,
+                ref topic,
+            } => {
+                let record = rdkafka::producer::FutureRecord::to(topic)
+                    .key(key)
+                    .payload(&payload);
+                if let Err((e, _)) = producer
+                    .send(record, std::time::Duration::from_secs(5))
+                    .await
+                {
+                    warn!("Publishing event to Kafka topic {} failed: {}", topic, e);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,275 @@
+//! Optional provider features, detected once at startup so one binary behaves
+//! sensibly across fuel-core versions: where the node lacks a feature, the
+//! [`ChainClient`] implementation of [`Provider`] takes the fallback path below.
+
+use crate::{
+    assets,
+    chain::ChainClient,
+    signer::Funder,
+    transfer::{self, TransferOutcome},
+};
+use fuels::{
+    accounts::provider::Provider,
+    client::{PageDirection, PaginationRequest},
+    prelude::TxPolicies,
+    types::{bech32::Bech32Address, AssetId},
+};
+use std::{error::Error, sync::RwLock};
+use tracing::{info, warn};
+
+/// Oldest fuel-core release whose GraphQL API streams transaction status updates.
+const MIN_SUBSCRIPTION_VERSION: (u64, u64) = (0, 20);
+
+/// Fee assumed per transfer where the node cannot estimate one and `--max-fee` does
+/// not bound it, in base units of the base asset. Generous, so balance checks ask
+/// for more rather than less.
+pub const DEFAULT_TRANSFER_FEE: u64 = 10_000;
+
+/// What [`register`] recorded for the rest of the run.
+static DETECTED: RwLock<Option<Capabilities>> = RwLock::new(None);
+
+/// Optional provider features detected at startup.
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    pub node_version: String,
+    /// Transaction status subscriptions are supported.
+    pub subscriptions: bool,
+    /// All balances of an owner can be fetched in a single query.
+    pub batch_balances: bool,
+    /// The gas price / transaction cost estimation endpoints are available.
+    pub fee_estimation: bool,
+    /// Transactions can be listed by owner, which inbound checks rely on.
+    pub owner_transactions: bool,
+    /// Assets queried one at a time where batch balances are unavailable.
+    pub assets: Vec<AssetId>,
+}
+
+impl Capabilities {
+    /// Probes the provider, using `probe` as the owner for balance and transaction
+    /// queries. `assets` are the assets the run handles besides the base asset and
+    /// those `ASSETS` describes.
+    pub async fn detect(provider: &Provider, probe: &Bech32Address, assets: &[AssetId]) -> Self {
+        let node_version = match provider.node_info().await {
+            Ok(info) => info.node_version,
+            Err(e) => {
+                warn!("Could not query node info: {}", e);
+                "unknown".to_string()
+            }
+        };
+
+        let subscriptions = parse_version(&node_version)
+            .map_or(false, |version| version >= MIN_SUBSCRIPTION_VERSION);
+        let batch_balances = provider.get_balances(probe).await.is_ok();
+        let fee_estimation = provider.estimate_gas_price(1).await.is_ok();
+        let owner_transactions = provider
+            .get_transactions_by_owner(
+                probe,
+                PaginationRequest {
+                    cursor: None,
+                    results: 1,
+                    direction: PageDirection::Backward,
+                },
+            )
+            .await
+            .is_ok();
+
+        let mut known = vec![*provider.base_asset_id()];
+        known.extend(assets);
+        known.extend(assets::ids());
+        known.sort();
+        known.dedup();
+
+        Self {
+            node_version,
+            subscriptions,
+            batch_balances,
+            fee_estimation,
+            owner_transactions,
+            assets: known,
+        }
+    }
+
+    /// What a run assumes of a node it has not probed: every feature above.
+    fn assumed() -> Self {
+        Self {
+            node_version: "unknown".to_string(),
+            subscriptions: true,
+            batch_balances: true,
+            fee_estimation: true,
+            owner_transactions: true,
+            assets: Vec::new(),
+        }
+    }
+
+    pub fn log(&self) {
+        info!("Provider fuel-core version: {}", self.node_version);
+        info!(
+            "  subscriptions:   {}",
+            if self.subscriptions {
+                "enabled"
+            } else {
+                "unavailable, polling for confirmations"
+            }
+        );
+        info!(
+            "  batch balances:  {}",
+            if self.batch_balances {
+                "enabled"
+            } else {
+                "unavailable, querying per asset"
+            }
+        );
+        if self.fee_estimation {
+            info!("  fee estimation:  enabled");
+        } else {
+            info!(
+                "  fee estimation:  unavailable, assuming --max-fee or {} per transfer",
+                DEFAULT_TRANSFER_FEE
+            );
+        }
+        info!(
+            "  owner transactions: {}",
+            if self.owner_transactions {
+                "enabled"
+            } else {
+                "unavailable, inbound checks skipped"
+            }
+        );
+    }
+}
+
+/// Records `capabilities` for the rest of the run.
+pub fn register(capabilities: Capabilities) {
+    *DETECTED.write().unwrap() = Some(capabilities);
+}
+
+/// The capabilities [`register`] recorded, or every feature if none were.
+pub fn current() -> Capabilities {
+    DETECTED
+        .read()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(Capabilities::assumed)
+}
+
+/// Non-zero balances of `address` among `assets`, one query each, for nodes
+/// without batch balance queries.
+pub async fn balances_per_asset(
+    client: &dyn ChainClient,
+    address: &Bech32Address,
+    assets: &[AssetId],
+) -> Result<Vec<(AssetId, u64)>, Box<dyn Error>> {
+    let mut balances = Vec::new();
+    for asset_id in assets {
+        let balance = client.balance(address, asset_id).await?;
+        if balance > 0 {
+            balances.push((*asset_id, balance));
+        }
+    }
+    Ok(balances)
+}
+
+/// Fee assumed for a transfer where the node cannot estimate one.
+pub fn assumed_transfer_fee(tx_policies: TxPolicies) -> u64 {
+    tx_policies.max_fee().unwrap_or(DEFAULT_TRANSFER_FEE)
+}
+
+/// Submits a transfer and polls until it is included, for nodes that cannot stream
+/// its status.
+pub async fn transfer_by_polling(
+    client: &dyn ChainClient,
+    from_wallet: &Funder,
+    to_address: &Bech32Address,
+    amount: u64,
+    asset_id: &AssetId,
+    tx_policies: TxPolicies,
+) -> Result<TransferOutcome, Box<dyn Error>> {
+    let tx_id = client
+        .submit_transfer(from_wallet, to_address, amount, asset_id, tx_policies)
+        .await?;
+    match transfer::await_confirmation(client, &tx_id).await? {
+        Some(fee) => Ok(TransferOutcome { tx_id, fee }),
+        None => Err(format!("Transfer {} did not confirm in time", tx_id).into()),
+    }
+}
+
+/// Parses the major and minor version out of strings like `0.40.0`.
+fn parse_version(version: &str) -> Option<(u64, u64)> {
+    let mut parts = version.trim_start_matches('v').split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::mock::{address, base_asset, other_asset, test_context, MockChain, MOCK_FEE};
+
+    #[tokio::test]
+    async fn falls_back_where_the_node_lacks_features() {
+        let ctx = test_context(2, base_asset());
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(main_wallet.address(), base_asset(), 100_000);
+        chain.set_balance(main_wallet.address(), other_asset(), 5_000);
+
+        // Without batch queries, only the listed assets are asked about
+        let third = AssetId::from([2u8; 32]);
+        let balances = balances_per_asset(
+            &chain,
+            main_wallet.address(),
+            &[base_asset(), other_asset(), third],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            balances,
+            vec![(base_asset(), 100_000), (other_asset(), 5_000)]
+        );
+        let balances = balances_per_asset(&chain, main_wallet.address(), &[other_asset()])
+            .await
+            .unwrap();
+        assert_eq!(balances, vec![(other_asset(), 5_000)]);
+
+        // Without fee estimation, --max-fee bounds the fee assumed
+        assert_eq!(
+            assumed_transfer_fee(TxPolicies::default()),
+            DEFAULT_TRANSFER_FEE
+        );
+        assert_eq!(
+            assumed_transfer_fee(TxPolicies::default().with_max_fee(500)),
+            500
+        );
+
+        // Without subscriptions, the transfer is submitted and polled for
+        let outcome = transfer_by_polling(
+            &chain,
+            &main_wallet,
+            &address(&ctx, 1),
+            20_000,
+            &base_asset(),
+            TxPolicies::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(outcome.fee, MOCK_FEE);
+        assert_eq!(chain.balance_of(&address(&ctx, 1), base_asset()), 20_000);
+    }
+
+    #[test]
+    fn assumes_every_feature_until_detected() {
+        let assumed = current();
+        assert!(assumed.subscriptions);
+        assert!(assumed.batch_balances);
+        assert!(assumed.fee_estimation);
+        assert!(assumed.owner_transactions);
+    }
+
+    #[test]
+    fn parses_node_versions() {
+        assert_eq!(parse_version("0.40.0"), Some((0, 40)));
+        assert_eq!(parse_version("v0.19.1"), Some((0, 19)));
+        assert_eq!(parse_version("unknown"), None);
+    }
+}
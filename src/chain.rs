@@ -0,0 +1,892 @@
+//! The chain operations the funding logic depends on, so it can run against
+//! a live node or an in-memory mock.
+
+use crate::{
+    capabilities,
+    inbound::{self, Inbound},
+    signer::Funder,
+    transfer::{self, SweepOutcome, TransferOutcome},
+};
+use async_trait::async_trait;
+use fuels::{
+    accounts::provider::Provider,
+    prelude::TxPolicies,
+    types::{bech32::Bech32Address, tx_status::TxStatus, AssetId, TxId},
+};
+use futures::{stream, StreamExt};
+use std::{error::Error, str::FromStr, time::Duration};
+use tracing::{debug_span, Instrument};
+
+/// State of a submitted transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Confirmation {
+    Pending,
+    /// Included successfully, paying `fee` in base units of the base asset.
+    Confirmed {
+        fee: u64,
+    },
+    /// Included or dropped without the transfer taking effect.
+    Failed(String),
+}
+
+/// Balance queries and transfers used by distribution, funding and reclaim.
+#[async_trait]
+pub trait ChainClient: Send + Sync {
+    /// Provider to attach to derived wallets, if the client is backed by one.
+    fn provider(&self) -> Option<Provider>;
+
+    fn base_asset(&self) -> AssetId;
+
+    async fn balance(
+        &self,
+        address: &Bech32Address,
+        asset_id: &AssetId,
+    ) -> Result<u64, Box<dyn Error>>;
+
+    /// Every asset `address` holds a non-zero balance of.
+    async fn balances(
+        &self,
+        address: &Bech32Address,
+    ) -> Result<Vec<(AssetId, u64)>, Box<dyn Error>>;
+
+    /// Sends a transfer and waits for it to be included.
+    async fn transfer(
+        &self,
+        from_wallet: &Funder,
+        to_address: &Bech32Address,
+        amount: u64,
+        asset_id: &AssetId,
+        tx_policies: TxPolicies,
+    ) -> Result<TransferOutcome, Box<dyn Error>>;
+
+    /// Sends a transfer without waiting for it to be included.
+    async fn submit_transfer(
+        &self,
+        from_wallet: &Funder,
+        to_address: &Bech32Address,
+        amount: u64,
+        asset_id: &AssetId,
+        tx_policies: TxPolicies,
+    ) -> Result<TxId, Box<dyn Error>>;
+
+    /// See [`transfer::sweep`].
+    async fn sweep(
+        &self,
+        wallet: &Funder,
+        to_address: &Bech32Address,
+        asset_id: &AssetId,
+        tx_policies: TxPolicies,
+    ) -> Result<SweepOutcome, Box<dyn Error>>;
+
+    async fn confirmation(&self, tx_id: &TxId) -> Result<Confirmation, Box<dyn Error>>;
+
+    /// See [`transfer::credited`].
+    async fn credited(
+        &self,
+        tx_id: &TxId,
+        to_address: &Bech32Address,
+        asset_id: &AssetId,
+    ) -> Result<u64, Box<dyn Error>>;
+
+    /// Node-estimated fee of a transfer, in base units of the base asset.
+    async fn estimate_transfer_fee(
+        &self,
+        from_wallet: &Funder,
+        to_address: &Bech32Address,
+        amount: u64,
+        asset_id: &AssetId,
+        tx_policies: TxPolicies,
+    ) -> Result<u64, Box<dyn Error>>;
+
+    /// Amounts of the separate spendable coins of `asset_id` held by `address`.
+    async fn coins(
+        &self,
+        address: &Bech32Address,
+        asset_id: &AssetId,
+    ) -> Result<Vec<u64>, Box<dyn Error>>;
+
+    /// See [`transfer::split_coins`].
+    async fn split_coins(
+        &self,
+        wallet: &Funder,
+        amount: u64,
+        count: usize,
+        asset_id: &AssetId,
+        tx_policies: TxPolicies,
+    ) -> Result<TransferOutcome, Box<dyn Error>>;
+
+    /// See [`inbound::recent_inbound`].
+    async fn recent_inbound(
+        &self,
+        address: &Bech32Address,
+        asset_id: AssetId,
+        min_amount: u64,
+        lookback_blocks: u32,
+    ) -> Result<Option<Inbound>, Box<dyn Error>>;
+}
+
+/// Balances of `asset_id` held by each of `addresses`, in order, with up to
+/// `concurrency` queries in flight at once. Each query fails after `rpc_timeout`.
+pub async fn balances_of(
+    client: &dyn ChainClient,
+    addresses: &[Bech32Address],
+    asset_id: &AssetId,
+    concurrency: usize,
+    rpc_timeout: Option<Duration>,
+) -> Result<Vec<u64>, Box<dyn Error>> {
+    let results: Vec<Result<u64, String>> = stream::iter(addresses)
+        .map(|address| async move {
+            let query = client.balance(address, asset_id);
+            let balance = match rpc_timeout {
+                Some(rpc_timeout) => tokio::time::timeout(rpc_timeout, query)
+                    .await
+                    .map_err(|_| format!("balance query for {} timed out", address))?,
+                None => query.await,
+            };
+            // Errors are turned into strings so the stream stays `Send`
+            balance.map_err(|e| e.to_string())
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await;
+    results
+        .into_iter()
+        .collect::<Result<_, String>>()
+        .map_err(Into::into)
+}
+
+#[async_trait]
+impl ChainClient for Provider {
+    fn provider(&self) -> Option<Provider> {
+        Some(self.clone())
+    }
+
+    fn base_asset(&self) -> AssetId {
+        *self.base_asset_id()
+    }
+
+    async fn balance(
+        &self,
+        address: &Bech32Address,
+        asset_id: &AssetId,
+    ) -> Result<u64, Box<dyn Error>> {
+        Ok(self
+            .get_asset_balance(address, *asset_id)
+            .instrument(debug_span!("provider.balance", %address, %asset_id))
+            .await?)
+    }
+
+    async fn balances(
+        &self,
+        address: &Bech32Address,
+    ) -> Result<Vec<(AssetId, u64)>, Box<dyn Error>> {
+        let supported = capabilities::current();
+        if !supported.batch_balances {
+            return capabilities::balances_per_asset(self, address, &supported.assets).await;
+        }
+        self.get_balances(address)
+            .instrument(debug_span!("provider.balances", %address))
+            .await?
+            .into_iter()
+            .map(|(asset_id, balance)| {
+                AssetId::from_str(&asset_id)
+                    .map(|asset_id| (asset_id, balance))
+                    .map_err(|e| {
+                        Box::<dyn Error>::from(format!(
+                            "Invalid asset id '{}' from provider: {}",
+                            asset_id, e
+                        ))
+                    })
+            })
+            .collect()
+    }
+
+    async fn transfer(
+        &self,
+        from_wallet: &Funder,
+        to_address: &Bech32Address,
+        amount: u64,
+        asset_id: &AssetId,
+        tx_policies: TxPolicies,
+    ) -> Result<TransferOutcome, Box<dyn Error>> {
+        if !capabilities::current().subscriptions {
+            return capabilities::transfer_by_polling(
+                self,
+                from_wallet,
+                to_address,
+                amount,
+                asset_id,
+                tx_policies,
+            )
+            .await;
+        }
+        transfer::send_funds(from_wallet, to_address, amount, self, asset_id, tx_policies)
+            .instrument(debug_span!("provider.transfer", to = %to_address, amount, %asset_id))
+            .await
+    }
+
+    async fn submit_transfer(
+        &self,
+        from_wallet: &Funder,
+        to_address: &Bech32Address,
+        amount: u64,
+        asset_id: &AssetId,
+        tx_policies: TxPolicies,
+    ) -> Result<TxId, Box<dyn Error>> {
+        transfer::submit_transfer(from_wallet, to_address, amount, self, asset_id, tx_policies)
+            .instrument(
+                debug_span!("provider.submit_transfer", to = %to_address, amount, %asset_id),
+            )
+            .await
+    }
+
+    async fn sweep(
+        &self,
+        wallet: &Funder,
+        to_address: &Bech32Address,
+        asset_id: &AssetId,
+        tx_policies: TxPolicies,
+    ) -> Result<SweepOutcome, Box<dyn Error>> {
+        transfer::sweep(wallet, to_address, self, asset_id, tx_policies)
+            .instrument(debug_span!("provider.sweep", from = %wallet.address(), to = %to_address, %asset_id))
+            .await
+    }
+
+    async fn confirmation(&self, tx_id: &TxId) -> Result<Confirmation, Box<dyn Error>> {
+        let status = self
+            .tx_status(tx_id)
+            .instrument(debug_span!("provider.tx_status", %tx_id))
+            .await?;
+        Ok(match status {
+            TxStatus::Submitted => Confirmation::Pending,
+            TxStatus::Success { total_fee, .. } => Confirmation::Confirmed { fee: total_fee },
+            status => Confirmation::Failed(format!("{:?}", status)),
+        })
+    }
+
+    async fn credited(
+        &self,
+        tx_id: &TxId,
+        to_address: &Bech32Address,
+        asset_id: &AssetId,
+    ) -> Result<u64, Box<dyn Error>> {
+        transfer::credited(self, tx_id, to_address, asset_id)
+            .instrument(debug_span!("provider.credited", %tx_id))
+            .await
+    }
+
+    async fn estimate_transfer_fee(
+        &self,
+        from_wallet: &Funder,
+        to_address: &Bech32Address,
+        amount: u64,
+        asset_id: &AssetId,
+        tx_policies: TxPolicies,
+    ) -> Result<u64, Box<dyn Error>> {
+        if !capabilities::current().fee_estimation {
+            return Ok(capabilities::assumed_transfer_fee(tx_policies));
+        }
+        let cost = transfer::estimate_transfer_cost(
+            from_wallet,
+            &[(to_address.clone(), amount)],
+            self,
+            asset_id,
+            tx_policies,
+        )
+        .instrument(debug_span!("provider.estimate_fee", to = %to_address, amount))
+        .await?;
+        Ok(cost.total_fee)
+    }
+
+    async fn coins(
+        &self,
+        address: &Bech32Address,
+        asset_id: &AssetId,
+    ) -> Result<Vec<u64>, Box<dyn Error>> {
+        Ok(self
+            .get_coins(address, *asset_id)
+            .instrument(debug_span!("provider.coins", %address, %asset_id))
+            .await?
+            .into_iter()
+            .map(|coin| coin.amount)
+            .collect())
+    }
+
+    async fn split_coins(
+        &self,
+        wallet: &Funder,
+        amount: u64,
+        count: usize,
+        asset_id: &AssetId,
+        tx_policies: TxPolicies,
+    ) -> Result<TransferOutcome, Box<dyn Error>> {
+        transfer::split_coins(wallet, amount, count, self, asset_id, tx_policies)
+            .instrument(debug_span!("provider.split_coins", wallet = %wallet.address(), count))
+            .await
+    }
+
+    async fn recent_inbound(
+        &self,
+        address: &Bech32Address,
+        asset_id: AssetId,
+        min_amount: u64,
+        lookback_blocks: u32,
+    ) -> Result<Option<Inbound>, Box<dyn Error>> {
+        if !capabilities::current().owner_transactions {
+            return Ok(None);
+        }
+        inbound::recent_inbound(self, address, asset_id, min_amount, lookback_blocks)
+            .instrument(debug_span!("provider.recent_inbound", %address, lookback_blocks))
+            .await
+    }
+}
+
+/// A client whose queries and submissions fail after `rpc_timeout` instead of waiting
+/// on a hung connection (`--timeout`). Transfers that wait for inclusion take longer
+/// than a single request, and are bounded by the run's deadline instead.
+pub struct TimeoutClient<'a> {
+    inner: &'a dyn ChainClient,
+    rpc_timeout: Duration,
+}
+
+impl<'a> TimeoutClient<'a> {
+    pub fn new(inner: &'a dyn ChainClient, rpc_timeout: Duration) -> Self {
+        Self { inner, rpc_timeout }
+    }
+
+    async fn bounded<T>(
+        &self,
+        what: &str,
+        request: impl std::future::Future<Output = Result<T, Box<dyn Error>>>,
+    ) -> Result<T, Box<dyn Error>> {
+        tokio::time::timeout(self.rpc_timeout, request)
+            .await
+            .map_err(|_| {
+                format!(
+                    "{} timed out after {}s (PROVIDER_TIMEOUT_SECS)",
+                    what,
+                    self.rpc_timeout.as_secs()
+                )
+            })?
+    }
+}
+
+#[async_trait]
+impl ChainClient for TimeoutClient<'_> {
+    fn provider(&self) -> Option<Provider> {
+        self.inner.provider()
+    }
+
+    fn base_asset(&self) -> AssetId {
+        self.inner.base_asset()
+    }
+
+    async fn balance(
+        &self,
+        address: &Bech32Address,
+        asset_id: &AssetId,
+    ) -> Result<u64, Box<dyn Error>> {
+        self.bounded("Balance query", self.inner.balance(address, asset_id))
+            .await
+    }
+
+    async fn balances(
+        &self,
+        address: &Bech32Address,
+    ) -> Result<Vec<(AssetId, u64)>, Box<dyn Error>> {
+        self.bounded("Balances query", self.inner.balances(address))
+            .await
+    }
+
+    async fn transfer(
+        &self,
+        from_wallet: &Funder,
+        to_address: &Bech32Address,
+        amount: u64,
+        asset_id: &AssetId,
+        tx_policies: TxPolicies,
+    ) -> Result<TransferOutcome, Box<dyn Error>> {
+        self.inner
+            .transfer(from_wallet, to_address, amount, asset_id, tx_policies)
+            .await
+    }
+
+    async fn submit_transfer(
+        &self,
+        from_wallet: &Funder,
+        to_address: &Bech32Address,
+        amount: u64,
+        asset_id: &AssetId,
+        tx_policies: TxPolicies,
+    ) -> Result<TxId, Box<dyn Error>> {
+        self.bounded(
+            "Transfer submission",
+            self.inner
+                .submit_transfer(from_wallet, to_address, amount, asset_id, tx_policies),
+        )
+        .await
+    }
+
+    async fn sweep(
+        &self,
+        wallet: &Funder,
+        to_address: &Bech32Address,
+        asset_id: &AssetId,
+        tx_policies: TxPolicies,
+    ) -> Result<SweepOutcome, Box<dyn Error>> {
+        self.inner
+            .sweep(wallet, to_address, asset_id, tx_policies)
+            .await
+    }
+
+    async fn confirmation(&self, tx_id: &TxId) -> Result<Confirmation, Box<dyn Error>> {
+        self.bounded("Transaction status query", self.inner.confirmation(tx_id))
+            .await
+    }
+
+    async fn credited(
+        &self,
+        tx_id: &TxId,
+        to_address: &Bech32Address,
+        asset_id: &AssetId,
+    ) -> Result<u64, Box<dyn Error>> {
+        self.bounded(
+            "Transaction query",
+            self.inner.credited(tx_id, to_address, asset_id),
+        )
+        .await
+    }
+
+    async fn estimate_transfer_fee(
+        &self,
+        from_wallet: &Funder,
+        to_address: &Bech32Address,
+        amount: u64,
+        asset_id: &AssetId,
+        tx_policies: TxPolicies,
+    ) -> Result<u64, Box<dyn Error>> {
+        self.bounded(
+            "Fee estimate",
+            self.inner.estimate_transfer_fee(
+                from_wallet,
+                to_address,
+                amount,
+                asset_id,
+                tx_policies,
+            ),
+        )
+        .await
+    }
+
+    async fn coins(
+        &self,
+        address: &Bech32Address,
+        asset_id: &AssetId,
+    ) -> Result<Vec<u64>, Box<dyn Error>> {
+        self.bounded("Coins query", self.inner.coins(address, asset_id))
+            .await
+    }
+
+    async fn split_coins(
+        &self,
+        wallet: &Funder,
+        amount: u64,
+        count: usize,
+        asset_id: &AssetId,
+        tx_policies: TxPolicies,
+    ) -> Result<TransferOutcome, Box<dyn Error>> {
+        self.inner
+            .split_coins(wallet, amount, count, asset_id, tx_policies)
+            .await
+    }
+
+    async fn recent_inbound(
+        &self,
+        address: &Bech32Address,
+        asset_id: AssetId,
+        min_amount: u64,
+        lookback_blocks: u32,
+    ) -> Result<Option<Inbound>, Box<dyn Error>> {
+        self.bounded(
+            "Inbound transfers query",
+            self.inner
+                .recent_inbound(address, asset_id, min_amount, lookback_blocks),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+pub mod mock {
+    use super::*;
+    use crate::{
+        address_book::AddressBook,
+        context::Context,
+        exclusions::Exclusions,
+        funding_sources::FundingSources,
+        sinks::Sinks,
+        wallets::{Derivation, Fleet},
+    };
+    use secrecy::SecretString;
+    use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+    /// Well-known test mnemonic; never holds real funds.
+    pub const TEST_MNEMONIC: &str = "test test test test test test test test test test test junk";
+
+    /// Fee the mock charges per transfer, in base units of the base asset.
+    pub const MOCK_FEE: u64 = 1_000;
+
+    pub fn base_asset() -> AssetId {
+        AssetId::default()
+    }
+
+    pub fn other_asset() -> AssetId {
+        AssetId::from([1u8; 32])
+    }
+
+    /// A transfer applied by the mock.
+    #[derive(Debug, Clone)]
+    pub struct MockTransfer {
+        pub from: Bech32Address,
+        pub to: Bech32Address,
+        pub asset_id: AssetId,
+        pub amount: u64,
+    }
+
+    /// In-memory chain: transfers move balances immediately and charge [`MOCK_FEE`].
+    #[derive(Default)]
+    pub struct MockChain {
+        balances: Mutex<HashMap<(Bech32Address, AssetId), u64>>,
+        transfers: Mutex<Vec<MockTransfer>>,
+        inbound: Mutex<HashMap<Bech32Address, u64>>,
+        coins: Mutex<HashMap<(Bech32Address, AssetId), Vec<u64>>>,
+        pending: Mutex<Vec<TxId>>,
+        credited: Mutex<HashMap<TxId, u64>>,
+        failing: Mutex<HashMap<Bech32Address, usize>>,
+    }
+
+    impl MockChain {
+        pub fn set_balance(&self, address: &Bech32Address, asset_id: AssetId, amount: u64) {
+            self.balances
+                .lock()
+                .unwrap()
+                .insert((address.clone(), asset_id), amount);
+        }
+
+        pub fn balance_of(&self, address: &Bech32Address, asset_id: AssetId) -> u64 {
+            self.balances
+                .lock()
+                .unwrap()
+                .get(&(address.clone(), asset_id))
+                .copied()
+                .unwrap_or_default()
+        }
+
+        /// Makes `recent_inbound` report a transfer of `amount` to `address`.
+        pub fn set_inbound(&self, address: &Bech32Address, amount: u64) {
+            self.inbound.lock().unwrap().insert(address.clone(), amount);
+        }
+
+        /// Makes `coins` report these coins instead of one coin holding the whole balance.
+        pub fn set_coins(&self, address: &Bech32Address, asset_id: AssetId, coins: Vec<u64>) {
+            self.coins
+                .lock()
+                .unwrap()
+                .insert((address.clone(), asset_id), coins);
+        }
+
+        /// Makes `confirmation` report `tx_id` as pending until [`MockChain::confirm`].
+        pub fn set_pending(&self, tx_id: TxId) {
+            self.pending.lock().unwrap().push(tx_id);
+        }
+
+        pub fn confirm(&self, tx_id: &TxId) {
+            self.pending
+                .lock()
+                .unwrap()
+                .retain(|pending| pending != tx_id);
+        }
+
+        /// Makes `credited` report `amount` for `tx_id`, whatever it transferred.
+        pub fn set_credited(&self, tx_id: TxId, amount: u64) {
+            self.credited.lock().unwrap().insert(tx_id, amount);
+        }
+
+        /// Makes the next `times` transfers from `address` fail, as a node would reject them.
+        pub fn fail_transfers_from(&self, address: &Bech32Address, times: usize) {
+            self.failing.lock().unwrap().insert(address.clone(), times);
+        }
+
+        pub fn transfers(&self) -> Vec<MockTransfer> {
+            self.transfers.lock().unwrap().clone()
+        }
+
+        fn apply(
+            &self,
+            from: &Bech32Address,
+            to: &Bech32Address,
+            amount: u64,
+            asset_id: &AssetId,
+        ) -> Result<TxId, Box<dyn Error>> {
+            if let Some(times) = self
+                .failing
+                .lock()
+                .unwrap()
+                .get_mut(from)
+                .filter(|times| **times > 0)
+            {
+                *times -= 1;
+                return Err(format!("Transfer from {} rejected", from).into());
+            }
+            let base = base_asset();
+            let mut balances = self.balances.lock().unwrap();
+            let available = |key| balances.get(&key).copied().unwrap_or_default();
+
+            let base_needed = MOCK_FEE + if *asset_id == base { amount } else { 0 };
+            if available((from.clone(), base)) < base_needed
+                || available((from.clone(), *asset_id)) < amount
+            {
+                return Err(format!("Insufficient funds in {} to send {}", from, amount).into());
+            }
+
+            *balances.entry((from.clone(), *asset_id)).or_default() -= amount;
+            *balances.entry((from.clone(), base)).or_default() -= MOCK_FEE;
+            *balances.entry((to.clone(), *asset_id)).or_default() += amount;
+
+            let mut transfers = self.transfers.lock().unwrap();
+            transfers.push(MockTransfer {
+                from: from.clone(),
+                to: to.clone(),
+                asset_id: *asset_id,
+                amount,
+            });
+            let mut id = [0u8; 32];
+            id[24..].copy_from_slice(&(transfers.len() as u64).to_be_bytes());
+            Ok(TxId::from(id))
+        }
+    }
+
+    #[async_trait]
+    impl ChainClient for MockChain {
+        fn provider(&self) -> Option<Provider> {
+            None
+        }
+
+        fn base_asset(&self) -> AssetId {
+            base_asset()
+        }
+
+        async fn balance(
+            &self,
+            address: &Bech32Address,
+            asset_id: &AssetId,
+        ) -> Result<u64, Box<dyn Error>> {
+            Ok(self.balance_of(address, *asset_id))
+        }
+
+        async fn balances(
+            &self,
+            address: &Bech32Address,
+        ) -> Result<Vec<(AssetId, u64)>, Box<dyn Error>> {
+            Ok(self
+                .balances
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|((owner, _), balance)| owner == address && **balance > 0)
+                .map(|((_, asset_id), balance)| (*asset_id, *balance))
+                .collect())
+        }
+
+        async fn transfer(
+            &self,
+            from_wallet: &Funder,
+            to_address: &Bech32Address,
+            amount: u64,
+            asset_id: &AssetId,
+            _tx_policies: TxPolicies,
+        ) -> Result<TransferOutcome, Box<dyn Error>> {
+            let tx_id = self.apply(from_wallet.address(), to_address, amount, asset_id)?;
+            Ok(TransferOutcome {
+                tx_id,
+                fee: MOCK_FEE,
+            })
+        }
+
+        async fn submit_transfer(
+            &self,
+            from_wallet: &Funder,
+            to_address: &Bech32Address,
+            amount: u64,
+            asset_id: &AssetId,
+            _tx_policies: TxPolicies,
+        ) -> Result<TxId, Box<dyn Error>> {
+            self.apply(from_wallet.address(), to_address, amount, asset_id)
+        }
+
+        async fn sweep(
+            &self,
+            wallet: &Funder,
+            to_address: &Bech32Address,
+            asset_id: &AssetId,
+            _tx_policies: TxPolicies,
+        ) -> Result<SweepOutcome, Box<dyn Error>> {
+            let address = wallet.address();
+            let coins = self.coins(address, asset_id).await?;
+            let total: u64 = coins.iter().sum();
+            let amount = if *asset_id == base_asset() {
+                total.saturating_sub(MOCK_FEE)
+            } else {
+                total
+            };
+            let tx_id = self.apply(address, to_address, amount, asset_id)?;
+            self.coins
+                .lock()
+                .unwrap()
+                .remove(&(address.clone(), *asset_id));
+            Ok(SweepOutcome {
+                tx_id,
+                fee: MOCK_FEE,
+                amount,
+                coins: coins.len(),
+                remaining: 0,
+            })
+        }
+
+        async fn confirmation(&self, tx_id: &TxId) -> Result<Confirmation, Box<dyn Error>> {
+            if self.pending.lock().unwrap().contains(tx_id) {
+                return Ok(Confirmation::Pending);
+            }
+            Ok(Confirmation::Confirmed { fee: MOCK_FEE })
+        }
+
+        async fn credited(
+            &self,
+            tx_id: &TxId,
+            to_address: &Bech32Address,
+            asset_id: &AssetId,
+        ) -> Result<u64, Box<dyn Error>> {
+            if let Some(amount) = self.credited.lock().unwrap().get(tx_id) {
+                return Ok(*amount);
+            }
+            let transfers = self.transfers.lock().unwrap();
+            let index = u64::from_be_bytes(tx_id[24..].try_into().unwrap()) as usize;
+            let transfer = index
+                .checked_sub(1)
+                .and_then(|i| transfers.get(i))
+                .ok_or_else(|| format!("Unknown transaction {}", tx_id))?;
+            Ok(
+                if transfer.to == *to_address && transfer.asset_id == *asset_id {
+                    transfer.amount
+                } else {
+                    0
+                },
+            )
+        }
+
+        async fn estimate_transfer_fee(
+            &self,
+            _from_wallet: &Funder,
+            _to_address: &Bech32Address,
+            _amount: u64,
+            _asset_id: &AssetId,
+            _tx_policies: TxPolicies,
+        ) -> Result<u64, Box<dyn Error>> {
+            Ok(MOCK_FEE)
+        }
+
+        async fn coins(
+            &self,
+            address: &Bech32Address,
+            asset_id: &AssetId,
+        ) -> Result<Vec<u64>, Box<dyn Error>> {
+            if let Some(coins) = self
+                .coins
+                .lock()
+                .unwrap()
+                .get(&(address.clone(), *asset_id))
+            {
+                return Ok(coins.clone());
+            }
+            let balance = self.balance_of(address, *asset_id);
+            Ok(if balance > 0 { vec![balance] } else { vec![] })
+        }
+
+        async fn split_coins(
+            &self,
+            wallet: &Funder,
+            amount: u64,
+            count: usize,
+            asset_id: &AssetId,
+            _tx_policies: TxPolicies,
+        ) -> Result<TransferOutcome, Box<dyn Error>> {
+            let address = wallet.address();
+            let total = amount * count as u64;
+            let tx_id = self.apply(address, address, total, asset_id)?;
+            let remainder = self.balance_of(address, *asset_id) - total;
+            let mut coins = vec![amount; count];
+            if remainder > 0 {
+                coins.push(remainder);
+            }
+            self.set_coins(address, *asset_id, coins);
+            Ok(TransferOutcome {
+                tx_id,
+                fee: MOCK_FEE,
+            })
+        }
+
+        async fn recent_inbound(
+            &self,
+            address: &Bech32Address,
+            _asset_id: AssetId,
+            min_amount: u64,
+            _lookback_blocks: u32,
+        ) -> Result<Option<Inbound>, Box<dyn Error>> {
+            Ok(self
+                .inbound
+                .lock()
+                .unwrap()
+                .get(address)
+                .filter(|amount| **amount >= min_amount)
+                .map(|amount| Inbound {
+                    amount: *amount,
+                    block_height: 1,
+                }))
+        }
+    }
+
+    /// A context over the test mnemonic with no storage, callbacks or funding sources.
+    pub fn test_context(number_of_wallets: usize, asset_id: AssetId) -> Context<'static> {
+        Context {
+            fleet: Fleet::new(
+                SecretString::new(TEST_MNEMONIC.to_string()),
+                Derivation::default(),
+            ),
+            asset_id,
+            decimals: crate::units::DEFAULT_DECIMALS,
+            number_of_wallets,
+            funding_sources: FundingSources::default(),
+            relay: false,
+            jitter: Jitter::default(),
+            approval_limit: None,
+            tx_policies: TxPolicies::default(),
+            max_in_flight: 1,
+            reclaim_concurrency: 1,
+            balance_concurrency: 8,
+            split_coins: false,
+            in_flight_timeout: Duration::from_secs(300),
+            resubmit_tip: None,
+            max_cycle_duration: Duration::from_secs(600),
+            deadman_intervals: 5,
+            address_book: AddressBook::default(),
+            exclusions: Exclusions::default(),
+            inbound_check: None,
+            sinks: Sinks::default(),
+        }
+    }
+
+    /// Address of the HD wallet with the given index in `ctx`.
+    pub fn address(ctx: &Context<'_>, index: usize) -> Bech32Address {
+        ctx.fleet.wallet(index, None).unwrap().address().clone()
+    }
+}
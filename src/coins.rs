@@ -0,0 +1,127 @@
+use crate::{chain::ChainClient, context::Context, signer::Funder};
+use fuels::types::AssetId;
+use std::error::Error;
+use tracing::{info, warn};
+
+/// Makes sure every funding wallet holds at least `ctx.max_in_flight` separate coins
+/// of `amount` or more, so pipelined transfers do not contend for the same coin
+/// mid-run. Wallets short of coins are pre-split when `ctx.split_coins` is set;
+/// otherwise this fails before anything is sent.
+pub async fn ensure_parallel_coins(
+    ctx: &Context<'_>,
+    main_wallet: &Funder,
+    client: &dyn ChainClient,
+    amount: u64,
+) -> Result<(), Box<dyn Error>> {
+    let needed = ctx.max_in_flight;
+    if needed <= 1 {
+        return Ok(());
+    }
+
+    // Only check the wallets that will actually be paying
+    let sources = ctx.funding_sources.derive(&ctx.fleet, client.provider())?;
+    let mut funders: Vec<(usize, &Funder)> = Vec::new();
+    for wallet_index in 0..ctx.number_of_wallets {
+        let source_index = ctx.funding_sources.source_for(wallet_index).unwrap_or(0);
+        if !funders.iter().any(|(index, _)| *index == source_index) {
+            let wallet = ctx
+                .funding_sources
+                .wallet_for(wallet_index, main_wallet, &sources);
+            funders.push((source_index, wallet));
+        }
+    }
+
+    for (source_index, wallet) in funders {
+        let usable = usable_coins(client, wallet, &ctx.asset_id, amount).await?;
+        if usable >= needed {
+            continue;
+        }
+
+        if !ctx.split_coins {
+            return Err(format!(
+                "Funding wallet {} holds {} coin(s) of at least {} {}, but --max-in-flight {} needs {}; \
+                 pass --split-coins to pre-split its balance, or lower --max-in-flight",
+                source_index, usable, amount, ctx.asset_id, needed, needed
+            )
+            .into());
+        }
+
+        warn!(
+            "Funding wallet {} holds {} usable coin(s), splitting into {} coins of {}.",
+            source_index, usable, needed, amount
+        );
+        let outcome = client
+            .split_coins(wallet, amount, needed, &ctx.asset_id, ctx.tx_policies)
+            .await?;
+        info!(
+            "Split coins of funding wallet {} in {} (fee {}).",
+            source_index, outcome.tx_id, outcome.fee
+        );
+
+        let usable = usable_coins(client, wallet, &ctx.asset_id, amount).await?;
+        if usable < needed {
+            return Err(format!(
+                "Funding wallet {} still holds only {} usable coin(s) after splitting",
+                source_index, usable
+            )
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Number of coins large enough to pay a transfer of `amount` on their own.
+async fn usable_coins(
+    client: &dyn ChainClient,
+    wallet: &Funder,
+    asset_id: &AssetId,
+    amount: u64,
+) -> Result<usize, Box<dyn Error>> {
+    Ok(client
+        .coins(wallet.address(), asset_id)
+        .await?
+        .into_iter()
+        .filter(|coin| *coin >= amount)
+        .count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::mock::{base_asset, test_context, MockChain};
+
+    #[tokio::test]
+    async fn fails_without_enough_coins() {
+        let mut ctx = test_context(3, base_asset());
+        ctx.max_in_flight = 3;
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(main_wallet.address(), base_asset(), 100_000_000);
+
+        assert!(ensure_parallel_coins(&ctx, &main_wallet, &chain, 5_000_000)
+            .await
+            .is_err());
+        assert!(chain.transfers().is_empty());
+    }
+
+    #[tokio::test]
+    async fn splits_coins_when_allowed() {
+        let mut ctx = test_context(3, base_asset());
+        ctx.max_in_flight = 3;
+        ctx.split_coins = true;
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(main_wallet.address(), base_asset(), 100_000_000);
+
+        ensure_parallel_coins(&ctx, &main_wallet, &chain, 5_000_000)
+            .await
+            .unwrap();
+
+        let coins = chain
+            .coins(main_wallet.address(), &base_asset())
+            .await
+            .unwrap();
+        assert_eq!(coins.iter().filter(|c| **c >= 5_000_000).count(), 4);
+    }
+}
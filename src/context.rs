@@ -0,0 +1,84 @@
+use crate::{
+    address_book::AddressBook, assets, exclusions::Exclusions, funding_sources::FundingSources,
+    inbound::InboundCheck, jitter::Jitter, sinks::Sinks, units, wallets::Fleet,
+};
+use fuels::{prelude::TxPolicies, types::AssetId};
+use std::{error::Error, time::Duration};
+
+/// Configuration and services shared by the funding commands for one run.
+pub struct Context<'a> {
+    pub fleet: Fleet,
+    pub asset_id: AssetId,
+    /// Decimals of `asset_id` (`ASSET_DECIMALS`), used to display amounts.
+    pub decimals: u32,
+    pub number_of_wallets: usize,
+    pub funding_sources: FundingSources,
+    /// Have the main wallet fund the funding sources, as hubs, with what they are
+    /// about to send before they fund their HD wallets (`--relay`).
+    pub relay: bool,
+    /// Random spread of funded amounts and of the time before each transfer
+    /// (`--jitter`, `--jitter-delay`).
+    pub jitter: Jitter,
+    /// Most of `asset_id` a run, cycle or API job may move without a reviewed plan
+    /// (`APPROVAL_LIMIT`).
+    pub approval_limit: Option<u64>,
+    pub tx_policies: TxPolicies,
+    /// Maximum number of submitted transfers awaiting confirmation at once.
+    pub max_in_flight: usize,
+    /// Maximum number of HD wallets reclaimed at once.
+    pub reclaim_concurrency: usize,
+    /// Maximum number of balance queries sent to the node at once.
+    pub balance_concurrency: usize,
+    /// Pre-split funding wallets that hold too few coins for `max_in_flight`.
+    pub split_coins: bool,
+    /// How long continual funding skips a wallet whose top-up has not confirmed.
+    pub in_flight_timeout: Duration,
+    /// Tip to resubmit top-ups stuck past `in_flight_timeout` with, if at all.
+    pub resubmit_tip: Option<u64>,
+    /// How long a continual funding cycle may run before the watchdog cancels it.
+    pub max_cycle_duration: Duration,
+    /// Schedule intervals continual funding may go without checking in before the
+    /// deadman switch restarts it; 0 disables the switch.
+    pub deadman_intervals: u32,
+    /// Destination profiles every transfer is validated against before submission.
+    pub address_book: AddressBook,
+    /// HD wallets no command funds, checks or reclaims.
+    pub exclusions: Exclusions,
+    /// Skip top-ups of wallets that recently received funds from elsewhere.
+    pub inbound_check: Option<InboundCheck>,
+    pub sinks: Sinks<'a>,
+}
+
+impl Context<'_> {
+    /// Whether HD wallet `index` is on the denylist (`EXCLUDED_INDICES`, `EXCLUDED_ADDRESSES`).
+    pub fn is_excluded(&self, index: usize) -> Result<bool, Box<dyn Error>> {
+        self.exclusions.excludes(&self.fleet, index)
+    }
+
+    /// Key `key` of the saved state, namespaced by fleet when several share the storage.
+    pub fn state_key(&self, key: &str) -> String {
+        match &self.fleet.name {
+            Some(name) => format!("fleet.{}.{}", name, key),
+            None => key.to_string(),
+        }
+    }
+
+    /// Formats base units of `asset_id` for logs and reports, e.g. `0.005 ETH (5000000)`,
+    /// leaving out the symbol while the asset is unknown (see [`assets`]).
+    pub fn format_amount(&self, amount: impl Into<u128>) -> String {
+        let amount = amount.into();
+        match assets::info(&self.asset_id) {
+            Some(info) => format!(
+                "{} {} ({})",
+                units::format_amount(amount, self.decimals),
+                info.symbol,
+                amount
+            ),
+            None => format!(
+                "{} ({})",
+                units::format_amount(amount, self.decimals),
+                amount
+            ),
+        }
+    }
+}
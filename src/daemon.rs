@@ -0,0 +1,366 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tracing::{error, warn};
+
+/// Exit code when the deadman switch finds the funding loop blocked beyond recovery.
+pub const EXIT_DEADMAN: i32 = 4;
+
+/// PID file that is written on creation and removed again when dropped.
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        if let Ok(existing) = fs::read_to_string(path) {
+            warn!(
+                "PID file {} already exists (pid {}), overwriting",
+                path.display(),
+                existing.trim()
+            );
+        }
+        fs::write(path, format!("{}\n", std::process::id()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Liveness information shared between the funding loop and the health endpoint.
+#[derive(Clone, Default)]
+pub struct HealthState {
+    last_success: Arc<AtomicU64>,
+    cycles: Arc<AtomicU64>,
+    watchdog_trips: Arc<AtomicU64>,
+    invariant_violations: Arc<AtomicU64>,
+    stuck_transactions: Arc<AtomicU64>,
+    /// Unix time by which the funding loop must check in again, or 0 while disarmed.
+    deadman_deadline: Arc<AtomicU64>,
+    deadman_trips: Arc<AtomicU64>,
+    /// HD wallets that spent nothing for `IDLE_AFTER` as of the last cycle.
+    idle_wallets: Arc<RwLock<Vec<usize>>>,
+}
+
+impl HealthState {
+    /// Records a successfully completed funding cycle.
+    pub fn record_cycle(&self) {
+        self.last_success.store(unix_now(), Ordering::Relaxed);
+        self.cycles.fetch_add(1, Ordering::Relaxed);
+        notify_systemd("WATCHDOG=1");
+    }
+
+    /// Records a cycle the watchdog cancelled for running too long.
+    pub fn record_watchdog_trip(&self) {
+        self.watchdog_trips.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records an invariant that failed after a cycle.
+    pub fn record_invariant_violation(&self) {
+        self.invariant_violations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records top-ups found unconfirmed past the in-flight timeout.
+    pub fn record_stuck_transactions(&self, count: usize) {
+        self.stuck_transactions
+            .fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    /// Records the wallets found idle after a cycle.
+    pub fn set_idle_wallets(&self, wallets: Vec<usize>) {
+        *self
+            .idle_wallets
+            .write()
+            .expect("idle wallets lock poisoned") = wallets;
+    }
+
+    /// HD wallets idle as of the last cycle.
+    pub fn idle_wallets(&self) -> Vec<usize> {
+        self.idle_wallets
+            .read()
+            .expect("idle wallets lock poisoned")
+            .clone()
+    }
+
+    /// Arms the deadman switch: the funding loop must arm it again by `deadline`
+    /// (unix time), or it trips.
+    pub fn arm_deadman(&self, deadline: u64) {
+        self.deadman_deadline.store(deadline, Ordering::Relaxed);
+    }
+
+    pub fn disarm_deadman(&self) {
+        self.deadman_deadline.store(0, Ordering::Relaxed);
+    }
+
+    /// Seconds past the deadman deadline, or `None` while it is disarmed or not due.
+    pub fn deadman_overdue(&self) -> Option<u64> {
+        match self.deadman_deadline.load(Ordering::Relaxed) {
+            0 => None,
+            deadline => unix_now().checked_sub(deadline).filter(|late| *late > 0),
+        }
+    }
+
+    /// Resolves once the deadman switch trips, with the seconds the loop is overdue.
+    pub async fn deadman(&self) -> u64 {
+        loop {
+            if let Some(overdue) = self.deadman_overdue() {
+                self.deadman_trips.fetch_add(1, Ordering::Relaxed);
+                return overdue;
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    /// Watches the deadman switch from a thread of its own, so that a loop blocking
+    /// the runtime's threads, which [`HealthState::deadman`] cannot interrupt, still
+    /// ends the process: once overdue by more than `grace`, it runs `before_exit`,
+    /// which must release the run locks for the restart to take them, and exits with
+    /// [`EXIT_DEADMAN`] for the supervisor to restart it.
+    pub fn spawn_deadman_backstop(
+        &self,
+        grace: Duration,
+        before_exit: impl FnOnce() + Send + 'static,
+    ) {
+        let health = self.clone();
+        // Keeps the profile on its log line
+        let span = tracing::Span::current();
+        thread::spawn(move || loop {
+            let _entered = span.enter();
+            if let Some(overdue) = health.deadman_overdue().filter(|o| *o > grace.as_secs()) {
+                error!(
+                    deadman = true,
+                    "Deadman switch: the funding loop is {}s overdue and could not be restarted, exiting.",
+                    overdue
+                );
+                before_exit();
+                std::process::exit(EXIT_DEADMAN);
+            }
+            thread::sleep(Duration::from_secs(1));
+        });
+    }
+
+    /// Unix time of the last successful cycle, or `None` if none completed yet.
+    pub fn last_cycle(&self) -> Option<u64> {
+        match self.last_success.load(Ordering::Relaxed) {
+            0 => None,
+            last => Some(last),
+        }
+    }
+
+    /// Seconds since the last successful cycle, or `None` if none completed yet.
+    pub fn seconds_since_last_cycle(&self) -> Option<u64> {
+        match self.last_success.load(Ordering::Relaxed) {
+            0 => None,
+            last => Some(unix_now().saturating_sub(last)),
+        }
+    }
+
+    fn to_json(&self, max_age: u64) -> (bool, String) {
+        let last = self.last_success.load(Ordering::Relaxed);
+        let cycles = self.cycles.load(Ordering::Relaxed);
+        let healthy = matches!(self.seconds_since_last_cycle(), Some(age) if age <= max_age);
+        let body = format!(
+            "{{\"status\":\"{}\",\"last_successful_cycle\":{},\"seconds_since_last_cycle\":{},\"cycles\":{},\"watchdog_trips\":{},\"invariant_violations\":{},\"stuck_transactions\":{},\"deadman_trips\":{},\"idle_wallets\":{}}}",
+            if healthy { "ok" } else { "stale" },
+            if last == 0 { "null".to_string() } else { last.to_string() },
+            self.seconds_since_last_cycle()
+                .map_or("null".to_string(), |age| age.to_string()),
+            cycles,
+            self.watchdog_trips.load(Ordering::Relaxed),
+            self.invariant_violations.load(Ordering::Relaxed),
+            self.stuck_transactions.load(Ordering::Relaxed),
+            self.deadman_trips.load(Ordering::Relaxed),
+            serde_json::to_string(&self.idle_wallets()).unwrap_or_default()
+        );
+        (healthy, body)
+    }
+}
+
+/// Health of several fleets: healthy only while every one of them is.
+fn fleets_to_json(fleets: &[(String, HealthState)], max_age: u64) -> (bool, String) {
+    let mut healthy = true;
+    let entries: Vec<String> = fleets
+        .iter()
+        .map(|(name, health)| {
+            let (fleet_healthy, body) = health.to_json(max_age);
+            healthy &= fleet_healthy;
+            format!(
+                "{}:{}",
+                serde_json::to_string(name).unwrap_or_default(),
+                body
+            )
+        })
+        .collect();
+    let body = format!(
+        "{{\"status\":\"{}\",\"fleets\":{{{}}}}}",
+        if healthy { "ok" } else { "stale" },
+        entries.join(",")
+    );
+    (healthy, body)
+}
+
+/// Serves `GET /healthz`, answering 200 while the last successful cycle is
+/// younger than `max_age` seconds and 503 otherwise. `health` holds one unnamed
+/// entry, or one per fleet (`FLEETS`), each also served at `/healthz/<fleet>`.
+pub async fn serve_health(
+    listener: TcpListener,
+    health: Vec<(String, HealthState)>,
+    max_age: u64,
+) -> io::Result<()> {
+    let health: Arc<[(String, HealthState)]> = health.into();
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let health = health.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_health_request(stream, &health, max_age).await {
+                warn!("Health endpoint error: {}", e);
+            }
+        });
+    }
+}
+
+async fn handle_health_request(
+    mut stream: TcpStream,
+    health: &[(String, HealthState)],
+    max_age: u64,
+) -> io::Result<()> {
+    let mut buf = [0u8; 1024];
+    let n = stream.read(&mut buf).await?;
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let request_line = request.lines().next().unwrap_or_default();
+
+    let path = request_line
+        .strip_prefix("GET ")
+        .and_then(|rest| rest.split_whitespace().next())
+        .unwrap_or_default();
+    let report = match (path, health) {
+        ("/healthz", [(name, single)]) if name.is_empty() => Some(single.to_json(max_age)),
+        ("/healthz", fleets) => Some(fleets_to_json(fleets, max_age)),
+        (path, fleets) => path.strip_prefix("/healthz/").and_then(|fleet| {
+            fleets
+                .iter()
+                .find(|(name, _)| !name.is_empty() && name == fleet)
+                .map(|(_, health)| health.to_json(max_age))
+        }),
+    };
+    let (status, body) = match report {
+        Some((true, body)) => ("200 OK", body),
+        Some((false, body)) => ("503 Service Unavailable", body),
+        None => ("404 Not Found", "{\"error\":\"not found\"}".to_string()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Resolves once the process is asked to stop (Ctrl-C, SIGTERM on unix, or the
+/// Windows service manager).
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = sigterm.recv() => {}
+                }
+            }
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = crate::service::stop_requested() => {}
+    }
+}
+
+/// Resolves each time the process receives SIGHUP, the conventional request to
+/// reload configuration. Never resolves on platforms without signals.
+pub struct ReloadSignal {
+    #[cfg(unix)]
+    hangup: tokio::signal::unix::Signal,
+}
+
+impl ReloadSignal {
+    pub fn new() -> io::Result<Self> {
+        Ok(Self {
+            #[cfg(unix)]
+            hangup: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?,
+        })
+    }
+
+    pub async fn recv(&mut self) {
+        #[cfg(unix)]
+        self.hangup.recv().await;
+        #[cfg(not(unix))]
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Sends a state notification to systemd if running under a `Type=notify` unit.
+pub fn notify_systemd(state: &str) {
+    #[cfg(unix)]
+    if let Some(socket) = std::env::var_os("NOTIFY_SOCKET") {
+        use std::os::unix::net::UnixDatagram;
+        if let Ok(sock) = UnixDatagram::unbound() {
+            let _ = sock.send_to(state.as_bytes(), socket);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = state;
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn deadman_trips_once_past_its_deadline() {
+        let health = HealthState::default();
+        assert_eq!(health.deadman_overdue(), None);
+        health.arm_deadman(unix_now() + 60);
+        assert_eq!(health.deadman_overdue(), None);
+
+        health.arm_deadman(unix_now() - 5);
+        assert!(health.deadman_overdue() >= Some(5));
+        assert!(health.deadman().await >= 5);
+        assert!(health.to_json(120).1.contains("\"deadman_trips\":1"));
+
+        health.disarm_deadman();
+        assert_eq!(health.deadman_overdue(), None);
+    }
+}
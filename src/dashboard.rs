@@ -0,0 +1,108 @@
+use crate::{chain::ChainClient, context::Context, storage::TransferFilter};
+use fuels::accounts::provider::Provider;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Embedded single-page dashboard, served at `/` by `serve`.
+#[cfg(feature = "dashboard")]
+pub const DASHBOARD_HTML: &str = include_str!("dashboard.html");
+
+/// Transfers per wallet shown in the dashboard.
+const RECENT_TRANSFERS: usize = 5;
+
+/// How far back stored transfers are looked up for the dashboard.
+const LOOKBACK_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// One row of the dashboard.
+#[derive(Debug, Serialize)]
+pub struct WalletStatus {
+    pub wallet_index: usize,
+    pub address: String,
+    /// Balance of every asset the wallet holds, keyed by asset id, in base units.
+    pub balances: BTreeMap<String, u128>,
+    /// When the wallet last received a transfer from this tool (unix seconds).
+    pub last_funded: Option<u64>,
+    pub recent_transfers: Vec<RecentTransfer>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecentTransfer {
+    pub timestamp: u64,
+    pub command: String,
+    /// `in` if the wallet received the transfer, `out` if it sent it.
+    pub direction: &'static str,
+    pub asset_id: String,
+    pub amount: u64,
+    pub tx_id: String,
+}
+
+/// Collects the current state of every HD wallet: balances from the chain, and
+/// funding times and recent transfers from the history storage, if configured.
+pub async fn fleet_status(
+    ctx: &Context<'_>,
+    provider: &Provider,
+) -> Result<Vec<WalletStatus>, Box<dyn Error>> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let transfers = match ctx.sinks.storage {
+        Some(storage) => {
+            storage
+                .transfers(&TransferFilter {
+                    since: Some(now.saturating_sub(LOOKBACK_SECS)),
+                    ..TransferFilter::default()
+                })
+                .await?
+        }
+        None => Vec::new(),
+    };
+
+    let mut fleet = Vec::with_capacity(ctx.number_of_wallets);
+    for wallet_index in 0..ctx.number_of_wallets {
+        let wallet = ctx.fleet.wallet(wallet_index, None)?;
+        let address = wallet.address().to_string();
+        let balances = ChainClient::balances(provider, wallet.address())
+            .await?
+            .into_iter()
+            .map(|(asset_id, balance)| (asset_id.to_string(), u128::from(balance)))
+            .collect();
+
+        // Transfers are stored oldest first
+        let involving: Vec<_> = transfers
+            .iter()
+            .filter(|t| t.to_address == address || t.from_address == address)
+            .collect();
+        let last_funded = involving
+            .iter()
+            .rev()
+            .find(|t| t.to_address == address)
+            .map(|t| t.timestamp);
+        let recent_transfers = involving
+            .iter()
+            .rev()
+            .take(RECENT_TRANSFERS)
+            .map(|t| RecentTransfer {
+                timestamp: t.timestamp,
+                command: t.command.clone(),
+                direction: if t.to_address == address { "in" } else { "out" },
+                asset_id: t.asset_id.clone(),
+                amount: t.amount,
+                tx_id: t.tx_id.clone(),
+            })
+            .collect();
+
+        fleet.push(WalletStatus {
+            wallet_index,
+            address,
+            balances,
+            last_funded,
+            recent_transfers,
+        });
+    }
+    Ok(fleet)
+}
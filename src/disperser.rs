@@ -0,0 +1,272 @@
+//! Batch distribution through a deployed disperser contract (`contracts/disperser`):
+//! one call pays up to `DISPERSER_BATCH_SIZE` wallets, the cheapest way to fund
+//! thousands of them. Every transfer is paid from the main wallet.
+
+use crate::{
+    chain::{ChainClient, Confirmation},
+    context::Context,
+    signer::Funder,
+    storage::TransferRecord,
+    transfer::verify_credit,
+    units,
+};
+use fuels::{
+    prelude::{
+        abigen, CallParameters, Contract, LoadConfiguration, TxPolicies, VariableOutputPolicy,
+    },
+    programs::calls::{CallHandler, ContractCall},
+    types::{
+        bech32::{Bech32Address, Bech32ContractId, FUEL_BECH32_HRP},
+        Address, ContractId,
+    },
+};
+use std::{env, error::Error, path::Path, str::FromStr};
+use tracing::info;
+
+abigen!(Contract(
+    name = "DisperserContract",
+    abi = "contracts/disperser/disperser-abi.json"
+));
+
+/// Where `forc build` writes the contract's bytecode.
+pub const DEFAULT_BIN: &str = "contracts/disperser/out/release/disperser.bin";
+
+/// Most transfers in one call: a transaction has at most 255 outputs, and each
+/// transfer takes one besides the contract, change and gas outputs.
+pub const MAX_BATCH_SIZE: usize = 250;
+
+/// One transfer made by the disperser.
+pub struct Payment {
+    /// HD wallet paid, or none for an external recipient.
+    pub wallet_index: Option<usize>,
+    /// Checkpointed once the payment is made: the HD wallet index, or the line of
+    /// the recipients file.
+    pub position: usize,
+    pub to: Bech32Address,
+    pub amount: u64,
+    pub memo: Option<String>,
+}
+
+/// A deployed disperser, called by the main wallet.
+pub struct Disperser {
+    contract: DisperserContract<Funder>,
+    batch_size: usize,
+}
+
+impl Disperser {
+    /// The contract at `DISPERSER_CONTRACT` (hex or bech32 contract id), paying
+    /// `DISPERSER_BATCH_SIZE` transfers per call (default and at most [`MAX_BATCH_SIZE`]).
+    pub fn from_env(main_wallet: &Funder) -> Result<Self, Box<dyn Error>> {
+        let id = env::var("DISPERSER_CONTRACT")
+            .map_err(|_| "--disperser needs DISPERSER_CONTRACT, from deploy-disperser")?;
+        let batch_size = match env::var("DISPERSER_BATCH_SIZE") {
+            Ok(size) => size
+                .parse::<usize>()
+                .ok()
+                .filter(|size| (1..=MAX_BATCH_SIZE).contains(size))
+                .ok_or_else(|| {
+                    format!(
+                        "DISPERSER_BATCH_SIZE must be between 1 and {}, got '{}'",
+                        MAX_BATCH_SIZE, size
+                    )
+                })?,
+            Err(_) => MAX_BATCH_SIZE,
+        };
+        Ok(Self {
+            contract: DisperserContract::new(parse_contract_id(&id)?, main_wallet.clone()),
+            batch_size,
+        })
+    }
+
+    /// Pays every one of `payments` from the main wallet, a batch per call, after
+    /// checking the main wallet covers them and their estimated fees. With storage
+    /// configured, `checkpoint_key` holds the position of the last payment made
+    /// until all are.
+    pub async fn distribute(
+        &self,
+        ctx: &Context<'_>,
+        client: &dyn ChainClient,
+        command: &str,
+        payments: &[Payment],
+        checkpoint_key: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let Some(first_batch) = payments.chunks(self.batch_size).next() else {
+            info!("Nothing left to pay.");
+            return Ok(());
+        };
+        let batches = payments.len().div_ceil(self.batch_size);
+        let estimated_fees =
+            u128::from(self.estimate_fee(ctx, first_batch).await?) * batches as u128;
+        let total: u128 = payments.iter().map(|p| u128::from(p.amount)).sum();
+        let main_wallet = self.contract.account().address().clone();
+        let base_asset = client.base_asset();
+        let balance = u128::from(client.balance(&main_wallet, &ctx.asset_id).await?);
+        let gas = if ctx.asset_id == base_asset {
+            balance.saturating_sub(total)
+        } else {
+            u128::from(client.balance(&main_wallet, &base_asset).await?)
+        };
+        if balance < total || gas < estimated_fees {
+            return Err(format!(
+                "The disperser would run out of funds: the main wallet holds {} but needs {} plus {} of estimated fees",
+                ctx.format_amount(balance),
+                ctx.format_amount(total),
+                units::format_fee(estimated_fees)
+            )
+            .into());
+        }
+        info!(
+            "Pre-flight check passed: the main wallet covers {} transfers in {} disperser calls and {} of estimated fees.",
+            payments.len(),
+            batches,
+            units::format_fee(estimated_fees)
+        );
+
+        let mut fees = 0u128;
+        for batch in payments.chunks(self.batch_size) {
+            fees += u128::from(self.send(ctx, client, command, batch).await?);
+            if let (Some(storage), Some(last)) = (ctx.sinks.storage, batch.last()) {
+                storage
+                    .set_state(checkpoint_key, &last.position.to_string())
+                    .await?;
+            }
+        }
+
+        info!("Disperser distribution summary:");
+        info!("  Transfers:       {}", payments.len());
+        info!("  Calls:           {}", batches);
+        info!("  Sent:            {}", ctx.format_amount(total));
+        info!("  Fees estimated:  {}", units::format_fee(estimated_fees));
+        info!("  Fees paid:       {}", units::format_fee(fees));
+        if let Some(storage) = ctx.sinks.storage {
+            storage.delete_state(checkpoint_key).await?;
+        }
+        Ok(())
+    }
+
+    /// Node-estimated fee of the call paying `batch`, in base units of the base asset.
+    async fn estimate_fee(
+        &self,
+        ctx: &Context<'_>,
+        batch: &[Payment],
+    ) -> Result<u64, Box<dyn Error>> {
+        Ok(self
+            .call(ctx, batch)?
+            .estimate_transaction_cost(None, None)
+            .await?
+            .total_fee)
+    }
+
+    /// Pays `batch` in one call and waits for it to be included. Each transfer is
+    /// recorded under the call's transaction, with its share of the fee. Returns the fee.
+    async fn send(
+        &self,
+        ctx: &Context<'_>,
+        client: &dyn ChainClient,
+        command: &str,
+        batch: &[Payment],
+    ) -> Result<u64, Box<dyn Error>> {
+        let response = self.call(ctx, batch)?.call().await?;
+        let tx_id = response
+            .tx_id
+            .ok_or("The disperser call returned no transaction id")?;
+        let fee = match client.confirmation(&tx_id).await? {
+            Confirmation::Confirmed { fee } => fee,
+            Confirmation::Pending => 0,
+            Confirmation::Failed(reason) => {
+                return Err(format!("Disperser call {} failed: {}", tx_id, reason).into())
+            }
+        };
+        info!(
+            "Disperser call {:?} paid {} transfers (fee {}).",
+            tx_id,
+            batch.len(),
+            fee
+        );
+
+        let share = fee / batch.len() as u64;
+        for (i, payment) in batch.iter().enumerate() {
+            let mut record = TransferRecord::new(
+                command,
+                payment.wallet_index,
+                self.contract.account().address(),
+                &payment.to,
+                ctx.asset_id,
+                payment.amount,
+                tx_id,
+            );
+            record.label = Some("disperser".into());
+            record.memo = payment.memo.clone();
+            // The remainder of the fee goes to the first transfer
+            let fee_share = if i == 0 {
+                fee - share * (batch.len() as u64 - 1)
+            } else {
+                share
+            };
+            verify_credit(ctx, client, &record).await;
+            ctx.sinks.transfer_confirmed(record, fee_share).await;
+        }
+        Ok(fee)
+    }
+
+    fn call(
+        &self,
+        ctx: &Context<'_>,
+        batch: &[Payment],
+    ) -> Result<CallHandler<Funder, ContractCall, ()>, Box<dyn Error>> {
+        if batch.is_empty() || batch.len() > self.batch_size {
+            return Err(format!(
+                "A disperser call pays 1 to {} transfers, not {}",
+                self.batch_size,
+                batch.len()
+            )
+            .into());
+        }
+        let total = batch
+            .iter()
+            .try_fold(0u64, |total, payment| total.checked_add(payment.amount))
+            .ok_or("The batch total overflows")?;
+        let recipients = batch.iter().map(|p| Address::from(&p.to)).collect();
+        let amounts = batch.iter().map(|p| p.amount).collect();
+        Ok(self
+            .contract
+            .methods()
+            .disperse(recipients, amounts)
+            .call_params(
+                CallParameters::default()
+                    .with_amount(total)
+                    .with_asset_id(ctx.asset_id),
+            )?
+            .with_variable_output_policy(VariableOutputPolicy::Exactly(batch.len()))
+            .with_tx_policies(ctx.tx_policies))
+    }
+}
+
+/// Implements `deploy-disperser`: deploys the contract at `bin` from the main wallet
+/// and returns its id.
+pub async fn deploy(
+    main_wallet: &Funder,
+    bin: &Path,
+    tx_policies: TxPolicies,
+) -> Result<Bech32ContractId, Box<dyn Error>> {
+    let bin = bin
+        .to_str()
+        .ok_or_else(|| format!("{} is not a valid path", bin.display()))?;
+    let contract = Contract::load_from(bin, LoadConfiguration::default()).map_err(|e| {
+        format!(
+            "Failed to load {}: {} (build it with `forc build --release`)",
+            bin, e
+        )
+    })?;
+    Ok(contract.deploy(main_wallet, tx_policies).await?)
+}
+
+fn parse_contract_id(id: &str) -> Result<Bech32ContractId, String> {
+    if id.starts_with(FUEL_BECH32_HRP) {
+        Bech32ContractId::from_str(id).map_err(|e| format!("Invalid DISPERSER_CONTRACT: {}", e))
+    } else {
+        ContractId::from_str(id)
+            .map(Bech32ContractId::from)
+            .map_err(|_| format!("Invalid DISPERSER_CONTRACT '{}'", id))
+    }
+}
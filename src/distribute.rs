@@ -0,0 +1,413 @@
+#[cfg(feature = "disperser")]
+use crate::disperser::{Disperser, Payment};
+use crate::{
+    approval, chain::ChainClient, coins::ensure_parallel_coins, context::Context,
+    pipeline::Pipeline, progress::Progress, relay::Relay, signer::Funder, storage::TransferRecord,
+    units,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+};
+use tracing::{info, info_span, Instrument};
+
+/// Amount `--init-dist` sends to each HD wallet (0.005 ETH in base units).
+pub const INITIAL_AMOUNT: u64 = 5_000_000; // Adjust based on your asset's base units
+
+/// State key holding the index of the last wallet funded by an unfinished `--init-dist`.
+const INIT_DIST_CHECKPOINT: &str = "init-dist.checkpoint";
+
+pub async fn initial_distribution(
+    ctx: &Context<'_>,
+    main_wallet: &Funder,
+    client: &dyn ChainClient,
+) -> Result<(), Box<dyn Error>> {
+    let start = resume_point(ctx).await?;
+    // Drawn up front so the hubs are sent exactly what they relay
+    let amounts: Vec<u64> = (start..ctx.number_of_wallets)
+        .map(|_| ctx.jitter.amount(INITIAL_AMOUNT))
+        .collect();
+    let amount_for = |wallet_index: usize| amounts[wallet_index - start];
+    let mut total = 0u128;
+    for wallet_index in start..ctx.number_of_wallets {
+        if !ctx.is_excluded(wallet_index)? {
+            total += u128::from(amount_for(wallet_index));
+        }
+    }
+    approval::check_unreviewed(ctx, "The initial distribution", total)?;
+    // What the checks before the first transfer cover each wallet for
+    let amount = ctx.jitter.max_amount(INITIAL_AMOUNT);
+    let sources = ctx.funding_sources.derive(&ctx.fleet, client.provider())?;
+    let mut relay = ctx.relay.then(Relay::default);
+    if let Some(relay) = relay.as_mut() {
+        for wallet_index in start..ctx.number_of_wallets {
+            if !ctx.is_excluded(wallet_index)? {
+                relay.plan(ctx, wallet_index, amount_for(wallet_index));
+            }
+        }
+        relay.fund_hubs(ctx, main_wallet, client, &sources).await?;
+    }
+
+    // Fail before the first transfer rather than on a short balance or coin contention mid-run
+    let estimated_fees =
+        check_total_cost(ctx, main_wallet, client, &sources, start, amount).await?;
+    ensure_parallel_coins(ctx, main_wallet, client, amount).await?;
+    let mut pipeline = Pipeline::new(ctx, client).labelled("initial");
+    let mut progress = Progress::new("init-dist", ctx.number_of_wallets);
+    progress.set_position(start);
+    let mut transfers = 0u64;
+    let mut sent = 0u128;
+
+    for hd_wallet_number in start..ctx.number_of_wallets {
+        if ctx.is_excluded(hd_wallet_number)? {
+            info!("HD Wallet {} is excluded, skipping.", hd_wallet_number);
+            progress.done(hd_wallet_number, "excluded");
+            continue;
+        }
+        async {
+            // Derive the HD wallet
+            let wallet = ctx.fleet.wallet(hd_wallet_number, client.provider())?;
+            let source_wallet =
+                ctx.funding_sources
+                    .wallet_for(hd_wallet_number, main_wallet, &sources);
+
+            let wallet_address = wallet.address();
+            info!(
+                "HD Wallet {} address: {:?}",
+                hd_wallet_number, wallet_address
+            );
+
+            // Send the specified amount to the wallet
+            let amount = amount_for(hd_wallet_number);
+            let confirmed = pipeline
+                .submit(
+                    "init-dist",
+                    hd_wallet_number,
+                    source_wallet,
+                    wallet_address,
+                    amount,
+                    &ctx.asset_id,
+                )
+                .await?;
+            checkpoint(ctx, &confirmed).await?;
+            progress.done(hd_wallet_number, "submitted");
+            transfers += 1;
+            sent += u128::from(amount);
+            if let Some(relay) = relay.as_mut() {
+                relay.relayed(ctx, hd_wallet_number, amount);
+            }
+
+            Ok::<_, Box<dyn Error>>(())
+        }
+        .instrument(info_span!("wallet", index = hd_wallet_number))
+        .await?;
+    }
+
+    let confirmed = pipeline.finish().await?;
+    checkpoint(ctx, &confirmed).await?;
+
+    let unconfirmed = pipeline.take_unconfirmed();
+    progress.add_failures(unconfirmed.len());
+    progress.finish();
+
+    info!("Initial distribution summary:");
+    info!("  Transfers:       {}", transfers);
+    info!("  Sent:            {}", ctx.format_amount(sent));
+    info!("  Fees estimated:  {}", units::format_fee(estimated_fees));
+    info!(
+        "  Fees paid:       {}{}",
+        units::format_fee(pipeline.fees_paid().into()),
+        if unconfirmed.is_empty() {
+            ""
+        } else {
+            " (confirmed transfers only)"
+        }
+    );
+    if let Some(relay) = relay {
+        relay.finish(ctx).await;
+    }
+    if !unconfirmed.is_empty() {
+        return Err(format!(
+            "{} transfers are still unconfirmed; check them before re-running --init-dist",
+            unconfirmed.len()
+        )
+        .into());
+    }
+
+    if let Some(storage) = ctx.sinks.storage {
+        storage
+            .delete_state(&ctx.state_key(INIT_DIST_CHECKPOINT))
+            .await?;
+    }
+
+    info!("Initial distribution completed.");
+    Ok(())
+}
+
+/// `--init-dist --disperser`: pays every HD wallet through the disperser contract,
+/// a batch per call, from the main wallet. Resumes from the same checkpoint as
+/// [`initial_distribution`].
+#[cfg(feature = "disperser")]
+pub async fn disperse_initial_distribution(
+    ctx: &Context<'_>,
+    client: &dyn ChainClient,
+    disperser: &Disperser,
+) -> Result<(), Box<dyn Error>> {
+    let start = resume_point(ctx).await?;
+    let mut payments = Vec::new();
+    for wallet_index in start..ctx.number_of_wallets {
+        if ctx.is_excluded(wallet_index)? {
+            info!("HD Wallet {} is excluded, skipping.", wallet_index);
+            continue;
+        }
+        payments.push(Payment {
+            wallet_index: Some(wallet_index),
+            position: wallet_index,
+            to: ctx.fleet.wallet(wallet_index, None)?.address().clone(),
+            amount: ctx.jitter.amount(INITIAL_AMOUNT),
+            memo: None,
+        });
+    }
+    approval::check_unreviewed(
+        ctx,
+        "The initial distribution",
+        payments
+            .iter()
+            .map(|payment| u128::from(payment.amount))
+            .sum(),
+    )?;
+    disperser
+        .distribute(
+            ctx,
+            client,
+            "init-dist",
+            &payments,
+            &ctx.state_key(INIT_DIST_CHECKPOINT),
+        )
+        .await?;
+    info!("Initial distribution completed.");
+    Ok(())
+}
+
+/// The first HD wallet to fund: the one after the last funded wallet if a previous
+/// run was interrupted.
+async fn resume_point(ctx: &Context<'_>) -> Result<usize, Box<dyn Error>> {
+    let Some(storage) = ctx.sinks.storage else {
+        return Ok(0);
+    };
+    match storage
+        .get_state(&ctx.state_key(INIT_DIST_CHECKPOINT))
+        .await?
+    {
+        Some(last) => {
+            let next = last.parse::<usize>()? + 1;
+            info!(
+                "Resuming initial distribution from HD Wallet {} (checkpoint found).",
+                next
+            );
+            Ok(next)
+        }
+        None => Ok(0),
+    }
+}
+
+/// Checks that every paying wallet holds enough for the HD wallets from `start` on
+/// that it funds, plus their estimated fees, so a short balance is reported up
+/// front instead of leaving the fleet half-funded. Returns the estimated fees.
+async fn check_total_cost(
+    ctx: &Context<'_>,
+    main_wallet: &Funder,
+    client: &dyn ChainClient,
+    sources: &HashMap<usize, Funder>,
+    start: usize,
+    amount: u64,
+) -> Result<u128, Box<dyn Error>> {
+    // Paying wallet -> (first HD wallet it funds, number of HD wallets)
+    let mut payers: BTreeMap<usize, (usize, u64)> = BTreeMap::new();
+    for wallet_index in start..ctx.number_of_wallets {
+        if ctx.is_excluded(wallet_index)? {
+            continue;
+        }
+        let source_index = ctx.funding_sources.source_for(wallet_index).unwrap_or(0);
+        payers.entry(source_index).or_insert((wallet_index, 0)).1 += 1;
+    }
+
+    let base_asset = client.base_asset();
+    let mut shortfalls = Vec::new();
+    let mut estimated_fees = 0u128;
+    for (source_index, (first_wallet, count)) in payers {
+        let payer = sources.get(&source_index).unwrap_or(main_wallet);
+        let to = ctx.fleet.wallet(first_wallet, None)?;
+        let fee = client
+            .estimate_transfer_fee(payer, to.address(), amount, &ctx.asset_id, ctx.tx_policies)
+            .await?;
+        let total = u128::from(amount) * u128::from(count);
+        let fees = u128::from(fee) * u128::from(count);
+        estimated_fees += fees;
+
+        let balance = u128::from(client.balance(payer.address(), &ctx.asset_id).await?);
+        let needed = if ctx.asset_id == base_asset {
+            total + fees
+        } else {
+            total
+        };
+        if balance < needed {
+            shortfalls.push(format!(
+                "funding wallet {} holds {} but needs {} ({} wallets x {}{})",
+                source_index,
+                ctx.format_amount(balance),
+                ctx.format_amount(needed),
+                count,
+                ctx.format_amount(amount),
+                if ctx.asset_id == base_asset {
+                    format!(" plus {} estimated fees", ctx.format_amount(fees))
+                } else {
+                    String::new()
+                }
+            ));
+        }
+        if ctx.asset_id != base_asset {
+            let gas = u128::from(client.balance(payer.address(), &base_asset).await?);
+            if gas < fees {
+                shortfalls.push(format!(
+                    "funding wallet {} holds {} of the base asset but needs {} for estimated fees",
+                    source_index, gas, fees
+                ));
+            }
+        }
+    }
+
+    if !shortfalls.is_empty() {
+        return Err(format!(
+            "Initial distribution would run out of funds: {}",
+            shortfalls.join("; ")
+        )
+        .into());
+    }
+    info!(
+        "Pre-flight check passed: every funding wallet covers its transfers and {} of estimated fees.",
+        units::format_fee(estimated_fees)
+    );
+    Ok(estimated_fees)
+}
+
+/// Advances the checkpoint past transfers that have confirmed, in submission order.
+async fn checkpoint(ctx: &Context<'_>, confirmed: &[TransferRecord]) -> Result<(), Box<dyn Error>> {
+    if let (Some(storage), Some(last)) = (ctx.sinks.storage, confirmed.last()) {
+        if let Some(index) = last.wallet_index {
+            storage
+                .set_state(&ctx.state_key(INIT_DIST_CHECKPOINT), &index.to_string())
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::mock::{address, base_asset, test_context, MockChain, MOCK_FEE};
+    use fuels::types::TxId;
+
+    #[tokio::test]
+    async fn funds_every_wallet_once() {
+        let ctx = test_context(3, base_asset());
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(main_wallet.address(), base_asset(), 100_000_000);
+
+        initial_distribution(&ctx, &main_wallet, &chain)
+            .await
+            .unwrap();
+
+        assert_eq!(chain.transfers().len(), 3);
+        for index in 1..3 {
+            assert_eq!(
+                chain.balance_of(&address(&ctx, index), base_asset()),
+                5_000_000
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn holds_distributions_above_the_approval_limit() {
+        let mut ctx = test_context(3, base_asset());
+        ctx.approval_limit = Some(14_000_000);
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(main_wallet.address(), base_asset(), 100_000_000);
+
+        // Started from the API or gRPC as well as the CLI
+        let error = initial_distribution(&ctx, &main_wallet, &chain)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("APPROVAL_LIMIT"));
+        assert!(chain.transfers().is_empty());
+
+        ctx.approval_limit = Some(15_000_000);
+        initial_distribution(&ctx, &main_wallet, &chain)
+            .await
+            .unwrap();
+        assert_eq!(chain.transfers().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn reports_credit_discrepancies_without_stopping() {
+        let ctx = test_context(3, base_asset());
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(main_wallet.address(), base_asset(), 100_000_000);
+        // The first transfer's transaction shows less credited than was sent
+        let mut tx_id = [0u8; 32];
+        tx_id[31] = 1;
+        chain.set_credited(TxId::from(tx_id), 4_000_000);
+
+        initial_distribution(&ctx, &main_wallet, &chain)
+            .await
+            .unwrap();
+        assert_eq!(chain.transfers().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn fails_when_main_wallet_is_short() {
+        let ctx = test_context(3, base_asset());
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(main_wallet.address(), base_asset(), 6_000_000);
+
+        let error = initial_distribution(&ctx, &main_wallet, &chain)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("run out of funds"));
+        // Nothing is sent when the total cannot be covered
+        assert!(chain.transfers().is_empty());
+    }
+
+    #[tokio::test]
+    async fn counts_fees_against_the_total() {
+        let ctx = test_context(3, base_asset());
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        // Covers the three transfers but not their fees
+        chain.set_balance(main_wallet.address(), base_asset(), 15_000_000);
+
+        assert!(initial_distribution(&ctx, &main_wallet, &chain)
+            .await
+            .is_err());
+        assert!(chain.transfers().is_empty());
+    }
+
+    #[tokio::test]
+    async fn estimates_fees_for_the_remaining_wallets() {
+        let ctx = test_context(3, base_asset());
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(main_wallet.address(), base_asset(), 100_000_000);
+        let sources = HashMap::new();
+
+        let fees = check_total_cost(&ctx, &main_wallet, &chain, &sources, 1, 5_000_000)
+            .await
+            .unwrap();
+        assert_eq!(fees, u128::from(MOCK_FEE) * 2);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{recipients::parse_address, wallets::Fleet};
+use fuels::{accounts::ViewOnlyAccount, types::bech32::Bech32Address};
+use std::{
+    collections::{BTreeSet, HashSet},
+    env,
+    error::Error,
+    fmt,
+    ops::RangeInclusive,
+};
+
+/// HD wallets every command leaves alone, such as compromised or decommissioned
+/// ones, without renumbering the rest of the fleet.
+#[derive(Debug, Default)]
+pub struct Exclusions {
+    indices: BTreeSet<usize>,
+    addresses: HashSet<Bech32Address>,
+    /// `--group`: every wallet outside this range is excluded too.
+    only: Option<RangeInclusive<usize>>,
+}
+
+impl Exclusions {
+    /// Parses `EXCLUDED_INDICES` (e.g. `3,7,10-12`) and `EXCLUDED_ADDRESSES`
+    /// (comma-separated bech32 or hex addresses).
+    pub fn from_env() -> Result<Self, Box<dyn Error>> {
+        Self::parse(
+            &env::var("EXCLUDED_INDICES").unwrap_or_default(),
+            &env::var("EXCLUDED_ADDRESSES").unwrap_or_default(),
+        )
+    }
+
+    pub fn parse(indices: &str, addresses: &str) -> Result<Self, Box<dyn Error>> {
+        let mut exclusions = Self::default();
+        for entry in indices.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let invalid = || format!("Invalid EXCLUDED_INDICES entry '{}'", entry);
+            let (start, end) = entry.split_once('-').unwrap_or((entry, entry));
+            let start = start.trim().parse::<usize>().map_err(|_| invalid())?;
+            let end = end.trim().parse::<usize>().map_err(|_| invalid())?;
+            if start > end {
+                return Err(format!("{}: range start is after its end", invalid()).into());
+            }
+            exclusions.indices.extend(start..=end);
+        }
+        for entry in addresses
+            .split(',')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+        {
+            let address = parse_address(entry)
+                .map_err(|e| format!("Invalid EXCLUDED_ADDRESSES entry '{}': {}", entry, e))?;
+            exclusions.addresses.insert(address);
+        }
+        Ok(exclusions)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty() && self.addresses.is_empty()
+    }
+
+    /// Excludes every wallet outside `wallets` as well.
+    pub fn restrict_to(&mut self, wallets: RangeInclusive<usize>) {
+        self.only = Some(wallets);
+    }
+
+    /// Whether the HD wallet with the given index is excluded, by index or address.
+    pub fn excludes(&self, fleet: &Fleet, index: usize) -> Result<bool, Box<dyn Error>> {
+        if self.indices.contains(&index)
+            || self
+                .only
+                .as_ref()
+                .is_some_and(|only| !only.contains(&index))
+        {
+            return Ok(true);
+        }
+        if self.addresses.is_empty() {
+            return Ok(false);
+        }
+        Ok(self
+            .addresses
+            .contains(fleet.wallet(index, None)?.address()))
+    }
+}
+
+impl fmt::Display for Exclusions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut entries: Vec<String> = self.indices.iter().map(ToString::to_string).collect();
+        entries.extend(self.addresses.iter().map(ToString::to_string));
+        write!(f, "{}", entries.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chain::mock::TEST_MNEMONIC, wallets::Derivation};
+    use secrecy::SecretString;
+
+    #[test]
+    fn excludes_by_index_range_and_address() {
+        let fleet = Fleet::new(
+            SecretString::new(TEST_MNEMONIC.to_string()),
+            Derivation::default(),
+        );
+        let address = fleet.wallet(5, None).unwrap().address().to_string();
+        let exclusions = Exclusions::parse("1, 3-4", &address).unwrap();
+
+        let excluded: Vec<usize> = (0..7)
+            .filter(|index| exclusions.excludes(&fleet, *index).unwrap())
+            .collect();
+        assert_eq!(excluded, vec![1, 3, 4, 5]);
+
+        let mut group = Exclusions::parse("3", "").unwrap();
+        group.restrict_to(2..=4);
+        let excluded: Vec<usize> = (0..6)
+            .filter(|index| group.excludes(&fleet, *index).unwrap())
+            .collect();
+        assert_eq!(excluded, vec![0, 1, 3, 5]);
+
+        assert!(Exclusions::parse("4-2", "").is_err());
+        assert!(Exclusions::parse("", "not-an-address").is_err());
+        assert!(Exclusions::parse("", "").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,344 @@
+//! `export`: a snapshot of the fleet's balances and the transfer history, with fees,
+//! as CSV or Parquet files that finance can import into their reporting.
+
+use crate::{
+    addresses::ExportFormat,
+    chain::ChainClient,
+    context::Context,
+    daemon::unix_now,
+    report::ReportWriter,
+    storage::{Storage, TransferFilter, TransferRecord},
+};
+use clap::ValueEnum;
+use fuels::types::bech32::Bech32Address;
+use futures::{stream, StreamExt};
+use serde::Serialize;
+use std::{
+    error::Error,
+    fs::File,
+    path::{Path, PathBuf},
+};
+use tracing::{info, warn};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum AccountingFormat {
+    Csv,
+    /// Needs a build with the `parquet` feature.
+    Parquet,
+}
+
+impl AccountingFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            AccountingFormat::Csv => "csv",
+            AccountingFormat::Parquet => "parquet",
+        }
+    }
+}
+
+/// One wallet's balance of one asset at the time of the snapshot.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BalanceRow {
+    /// Unix timestamp (seconds) of the snapshot.
+    pub timestamp: u64,
+    /// HD wallet index, or none for the main wallet.
+    pub wallet_index: Option<u64>,
+    pub address: String,
+    pub asset_id: String,
+    /// In base units of the asset.
+    pub balance: u64,
+}
+
+/// Every asset held by the main wallet and the HD wallets not excluded.
+pub async fn balance_snapshot(
+    ctx: &Context<'_>,
+    main_wallet: &Bech32Address,
+    client: &dyn ChainClient,
+) -> Result<Vec<BalanceRow>, Box<dyn Error>> {
+    let mut wallets = vec![(None, main_wallet.clone())];
+    for index in 0..ctx.number_of_wallets {
+        if !ctx.is_excluded(index)? {
+            let address = ctx.fleet.wallet(index, None)?.address().clone();
+            if address != *main_wallet {
+                wallets.push((Some(index as u64), address));
+            }
+        }
+    }
+
+    let timestamp = unix_now();
+    let balances: Vec<Result<Vec<BalanceRow>, String>> = stream::iter(wallets)
+        .map(|(wallet_index, address)| async move {
+            let mut balances = client
+                .balances(&address)
+                .await
+                // Errors are turned into strings so the stream stays `Send`
+                .map_err(|e| e.to_string())?;
+            balances.sort();
+            Ok(balances
+                .into_iter()
+                .map(|(asset_id, balance)| BalanceRow {
+                    timestamp,
+                    wallet_index,
+                    address: address.to_string(),
+                    asset_id: format!("{:#x}", asset_id),
+                    balance,
+                })
+                .collect())
+        })
+        .buffered(ctx.balance_concurrency.max(1))
+        .collect()
+        .await;
+    let mut rows = Vec::new();
+    for wallet in balances {
+        rows.extend(wallet?);
+    }
+    Ok(rows)
+}
+
+/// Implements `export`: writes `balances-<time>` and, with storage configured,
+/// `transfers-<time>` files in `format` to `dir`. Returns the files written.
+pub async fn export(
+    ctx: &Context<'_>,
+    main_wallet: &Bech32Address,
+    client: &dyn ChainClient,
+    filter: &TransferFilter,
+    format: AccountingFormat,
+    dir: &Path,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let now = unix_now();
+    let path = |name: &str| dir.join(format!("{}-{}.{}", name, now, format.extension()));
+    let mut written = Vec::new();
+
+    let balances = balance_snapshot(ctx, main_wallet, client).await?;
+    let balances_path = path("balances");
+    match format {
+        AccountingFormat::Csv => write_csv(&balances_path, &balances)?,
+        AccountingFormat::Parquet => write_parquet(&balances_path, balance_columns(&balances))?,
+    }
+    info!(
+        "Wrote {} balances to {}",
+        balances.len(),
+        balances_path.display()
+    );
+    written.push(balances_path);
+
+    let Some(storage) = ctx.sinks.storage else {
+        warn!("STORAGE_URL is not set: exporting balances only, without transfer history.");
+        return Ok(written);
+    };
+    let transfers = transfer_history(storage, filter).await?;
+    let transfers_path = path("transfers");
+    match format {
+        AccountingFormat::Csv => write_csv(&transfers_path, &transfers)?,
+        AccountingFormat::Parquet => write_parquet(&transfers_path, transfer_columns(&transfers))?,
+    }
+    info!(
+        "Wrote {} transfers to {}",
+        transfers.len(),
+        transfers_path.display()
+    );
+    written.push(transfers_path);
+    Ok(written)
+}
+
+async fn transfer_history(
+    storage: &dyn Storage,
+    filter: &TransferFilter,
+) -> Result<Vec<TransferRecord>, Box<dyn Error>> {
+    let transfers = storage.transfers(filter).await?;
+    let unknown = transfers.iter().filter(|t| t.fee.is_none()).count();
+    if unknown > 0 {
+        warn!(
+            "{} transfers were recorded without their fee; their fee column is empty.",
+            unknown
+        );
+    }
+    Ok(transfers)
+}
+
+fn write_csv<T: Serialize>(path: &Path, rows: &[T]) -> Result<(), Box<dyn Error>> {
+    let mut report = ReportWriter::new(File::create(path)?, ExportFormat::Csv, None, "")?;
+    for row in rows {
+        report.write(row)?;
+    }
+    report.finish()?;
+    Ok(())
+}
+
+/// A column of a Parquet file; every column is nullable.
+#[cfg_attr(not(feature = "parquet"), allow(dead_code))]
+enum Column {
+    UInt64(Vec<Option<u64>>),
+    Utf8(Vec<Option<String>>),
+}
+
+fn balance_columns(rows: &[BalanceRow]) -> Vec<(&'static str, Column)> {
+    vec![
+        (
+            "timestamp",
+            Column::UInt64(rows.iter().map(|r| Some(r.timestamp)).collect()),
+        ),
+        (
+            "wallet_index",
+            Column::UInt64(rows.iter().map(|r| r.wallet_index).collect()),
+        ),
+        (
+            "address",
+            Column::Utf8(rows.iter().map(|r| Some(r.address.clone())).collect()),
+        ),
+        (
+            "asset_id",
+            Column::Utf8(rows.iter().map(|r| Some(r.asset_id.clone())).collect()),
+        ),
+        (
+            "balance",
+            Column::UInt64(rows.iter().map(|r| Some(r.balance)).collect()),
+        ),
+    ]
+}
+
+fn transfer_columns(rows: &[TransferRecord]) -> Vec<(&'static str, Column)> {
+    let text = |field: fn(&TransferRecord) -> Option<String>| {
+        Column::Utf8(rows.iter().map(field).collect())
+    };
+    let number = |field: fn(&TransferRecord) -> Option<u64>| {
+        Column::UInt64(rows.iter().map(field).collect())
+    };
+    vec![
+        ("timestamp", number(|t| Some(t.timestamp))),
+        ("sequence", number(|t| t.sequence)),
+        ("command", text(|t| Some(t.command.clone()))),
+        ("label", text(|t| t.label.clone())),
+        ("tag", text(|t| t.tag.clone())),
+        ("wallet_index", number(|t| t.wallet_index)),
+        ("from_address", text(|t| Some(t.from_address.clone()))),
+        ("to_address", text(|t| Some(t.to_address.clone()))),
+        ("asset_id", text(|t| Some(t.asset_id.clone()))),
+        ("amount", number(|t| Some(t.amount))),
+        ("fee", number(|t| t.fee)),
+        ("tx_id", text(|t| Some(t.tx_id.clone()))),
+        ("memo", text(|t| t.memo.clone())),
+    ]
+}
+
+/// Writes `columns` as a single row group; unsigned integers keep their full range
+/// as `INT64` annotated unsigned.
+#[cfg(feature = "parquet")]
+fn write_parquet(path: &Path, columns: Vec<(&str, Column)>) -> Result<(), Box<dyn Error>> {
+    use parquet::{
+        data_type::{ByteArray, ByteArrayType, Int64Type},
+        file::{properties::WriterProperties, writer::SerializedFileWriter},
+        schema::parser::parse_message_type,
+    };
+    use std::sync::Arc;
+
+    let fields: Vec<String> = columns
+        .iter()
+        .map(|(name, column)| match column {
+            Column::UInt64(_) => format!("OPTIONAL INT64 {} (INTEGER(64,false));", name),
+            Column::Utf8(_) => format!("OPTIONAL BYTE_ARRAY {} (UTF8);", name),
+        })
+        .collect();
+    let schema = parse_message_type(&format!("message export {{ {} }}", fields.join(" ")))?;
+    let properties = WriterProperties::builder().build();
+    let mut writer =
+        SerializedFileWriter::new(File::create(path)?, Arc::new(schema), Arc::new(properties))?;
+
+    let mut row_group = writer.next_row_group()?;
+    for (_, column) in columns {
+        let mut column_writer = row_group
+            .next_column()?
+            .ok_or("Parquet schema has fewer columns than the export")?;
+        match column {
+            Column::UInt64(values) => {
+                let levels: Vec<i16> = values.iter().map(|v| i16::from(v.is_some())).collect();
+                // Parquet stores unsigned 64-bit integers in INT64 bit for bit
+                let present: Vec<i64> = values.into_iter().flatten().map(|v| v as i64).collect();
+                column_writer
+                    .typed::<Int64Type>()
+                    .write_batch(&present, Some(&levels), None)?;
+            }
+            Column::Utf8(values) => {
+                let levels: Vec<i16> = values.iter().map(|v| i16::from(v.is_some())).collect();
+                let present: Vec<ByteArray> = values
+                    .into_iter()
+                    .flatten()
+                    .map(|v| ByteArray::from(v.into_bytes()))
+                    .collect();
+                column_writer.typed::<ByteArrayType>().write_batch(
+                    &present,
+                    Some(&levels),
+                    None,
+                )?;
+            }
+        }
+        column_writer.close()?;
+    }
+    row_group.close()?;
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "parquet"))]
+fn write_parquet(_path: &Path, _columns: Vec<(&str, Column)>) -> Result<(), Box<dyn Error>> {
+    Err("--format parquet needs a build with the `parquet` feature".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chain::mock::{address, base_asset, other_asset, test_context, MockChain},
+        storage::mock::MemoryStorage,
+    };
+
+    #[tokio::test]
+    async fn exports_balances_and_transfers_as_csv() {
+        let storage: &'static MemoryStorage = Box::leak(Box::default());
+        let mut ctx = test_context(3, base_asset());
+        let mut record = TransferRecord::new(
+            "cont-fund",
+            Some(2),
+            address(&ctx, 0),
+            address(&ctx, 2),
+            base_asset(),
+            5_000,
+            "0xabc",
+        );
+        record.fee = Some(1_000);
+        storage.record_transfer(&record).await.unwrap();
+        ctx.sinks.storage = Some(storage);
+
+        let chain = MockChain::default();
+        let main_wallet = address(&ctx, 0);
+        chain.set_balance(&main_wallet, base_asset(), 90_000);
+        chain.set_balance(&address(&ctx, 2), base_asset(), 5_000);
+        chain.set_balance(&address(&ctx, 2), other_asset(), 7);
+
+        let dir =
+            std::env::temp_dir().join(format!("fund_distributor-export-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = export(
+            &ctx,
+            &main_wallet,
+            &chain,
+            &TransferFilter::default(),
+            AccountingFormat::Csv,
+            &dir,
+        )
+        .await
+        .unwrap();
+        let balances = std::fs::read_to_string(&files[0]).unwrap();
+        let transfers = std::fs::read_to_string(&files[1]).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // The main wallet is wallet 0 here, so it is only listed once
+        let lines: Vec<&str> = balances.lines().collect();
+        assert_eq!(lines[0], "timestamp,wallet_index,address,asset_id,balance");
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].ends_with(&format!(",{},{:#x},90000", main_wallet, base_asset())));
+        assert!(lines[3].ends_with(&format!("{:#x},7", other_asset())));
+        assert!(transfers.lines().nth(1).unwrap().contains(",5000,0xabc,"));
+        assert!(transfers.lines().next().unwrap().ends_with(",fee"));
+    }
+}
@@ -0,0 +1,161 @@
+use std::{error::Error, fmt};
+use tracing::error;
+
+/// Broad cause of a failed transfer or cycle, derived from the SDK/node error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    InsufficientFee,
+    InsufficientBalance,
+    /// A coin the transaction spends no longer exists or is already being spent.
+    InvalidUtxo,
+    ProviderTimeout,
+    /// Dropped from the txpool in favour of other transactions.
+    SqueezedOut,
+    Other,
+}
+
+impl FailureKind {
+    /// Classifies an error by the messages the SDK and node produce for each case.
+    pub fn classify(error: &dyn Error) -> Self {
+        let message = format!("{} {:?}", error, error).to_lowercase();
+        let has = |patterns: &[&str]| patterns.iter().any(|p| message.contains(p));
+
+        if has(&["squeezedout", "squeezed out"]) {
+            FailureKind::SqueezedOut
+        } else if has(&[
+            "insufficientfee",
+            "insufficient fee",
+            "insufficientmaxfee",
+            "max fee",
+        ]) {
+            FailureKind::InsufficientFee
+        } else if has(&[
+            "insufficient funds",
+            "not enough coins",
+            "not enough resources",
+            "insufficientcoins",
+            "insufficient balance",
+        ]) {
+            FailureKind::InsufficientBalance
+        } else if has(&[
+            "utxodoesnotexist",
+            "utxo does not exist",
+            "coinalreadyspent",
+            "already spent",
+            "inputcoinnotfound",
+        ]) {
+            FailureKind::InvalidUtxo
+        } else if has(&[
+            "deadline has elapsed",
+            "timed out",
+            "timeout",
+            "connection refused",
+            "connection reset",
+            "connection closed",
+            "error sending request",
+            "broken pipe",
+            "dns error",
+            "no healthy provider",
+        ]) {
+            FailureKind::ProviderTimeout
+        } else {
+            FailureKind::Other
+        }
+    }
+
+    /// Whether the provider itself failed, rather than the transfers it was asked to
+    /// make: only then does another provider stand a better chance.
+    pub fn is_transport(self) -> bool {
+        self == FailureKind::ProviderTimeout
+    }
+
+    /// What an operator can do about it.
+    pub fn hint(self) -> &'static str {
+        match self {
+            FailureKind::InsufficientFee => {
+                "raise --max-fee (TX_MAX_FEE) or add a --tip; fees rise during congestion"
+            }
+            FailureKind::InsufficientBalance => {
+                "top up the funding wallet, or check that ETH_ASSET_ID and the amounts are right"
+            }
+            FailureKind::InvalidUtxo => {
+                "another process is spending the same wallet's coins, or a previous transfer is still pending; \
+                 make sure only one instance runs, lower --max-in-flight, or retry once pending transfers settle"
+            }
+            FailureKind::ProviderTimeout => {
+                "the node is slow or unreachable; check PROVIDER, add failover endpoints, or raise PROVIDER_TIMEOUT_SECS"
+            }
+            FailureKind::SqueezedOut => {
+                "the txpool dropped the transaction for better-paying ones; retry with a --tip"
+            }
+            FailureKind::Other => "see the error message above",
+        }
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FailureKind::InsufficientFee => "insufficient fee",
+            FailureKind::InsufficientBalance => "insufficient balance",
+            FailureKind::InvalidUtxo => "invalid UTXO state",
+            FailureKind::ProviderTimeout => "provider timeout",
+            FailureKind::SqueezedOut => "squeezed out",
+            FailureKind::Other => "unclassified",
+        })
+    }
+}
+
+/// Logs a failure of `what` with its category and remediation hint.
+pub fn report(what: &str, error: &dyn Error) -> FailureKind {
+    let kind = FailureKind::classify(error);
+    error!(failure = %kind, "{} failed ({}): {}", what, kind, error);
+    error!("  Hint: {}", kind.hint());
+    kind
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(message: &str) -> FailureKind {
+        let error: Box<dyn Error> = message.into();
+        FailureKind::classify(error.as_ref())
+    }
+
+    #[test]
+    fn classifies_common_failures() {
+        assert_eq!(
+            classify("Insufficient funds: attempted to send 5, but balance is 1"),
+            FailureKind::InsufficientBalance
+        );
+        assert_eq!(
+            classify("Transaction 0xab failed: SqueezedOut { reason: \"...\" }"),
+            FailureKind::SqueezedOut
+        );
+        assert_eq!(
+            classify("deadline has elapsed"),
+            FailureKind::ProviderTimeout
+        );
+        assert_eq!(
+            classify("Validity(InsufficientFeeAmount { expected: 10, provided: 1 })"),
+            FailureKind::InsufficientFee
+        );
+        assert_eq!(
+            classify("Validity(UtxoDoesNotExist(0x12))"),
+            FailureKind::InvalidUtxo
+        );
+        assert_eq!(
+            classify("io: error sending request for url (http://node:4000/v1/graphql)"),
+            FailureKind::ProviderTimeout
+        );
+        assert_eq!(classify("something else"), FailureKind::Other);
+
+        assert!(classify("connection reset by peer").is_transport());
+        // A local I/O error is not the provider's fault
+        assert!(!classify("io: Permission denied (os error 13)").is_transport());
+        assert!(
+            !classify("Insufficient funds: attempted to send 5, but balance is 1").is_transport()
+        );
+    }
+}
@@ -0,0 +1,414 @@
+//! High-frequency funding (`--fast`): follows the chain block by block with every
+//! wallet's balance cached in memory, and tops up a wallet within a block or two of
+//! a transaction spending it below the threshold, instead of on the next cycle.
+
+use crate::{
+    approval,
+    chain::{balances_of, ChainClient},
+    coins::ensure_parallel_coins,
+    context::Context,
+    daemon::{unix_now, HealthState},
+    failure,
+    fund::{self, FundingSettings},
+    in_flight::InFlight,
+    pipeline::Pipeline,
+    provider_pool::ProviderPool,
+    signer::Funder,
+    velocity::Velocity,
+};
+use fuels::{
+    accounts::provider::Provider,
+    types::{
+        bech32::Bech32Address,
+        transaction::{Transaction, TransactionType},
+        Address,
+    },
+};
+use std::{
+    collections::{BTreeSet, HashMap},
+    error::Error,
+    time::Duration,
+};
+use tokio::time::sleep;
+use tracing::{debug, info, warn};
+
+/// How often the chain is polled for new blocks.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Most blocks caught up on one by one; further behind, every balance is read again.
+const MAX_CATCH_UP: u32 = 20;
+
+/// Balances read from the chain, and the block height each was read at.
+#[derive(Default)]
+pub struct BalanceCache {
+    balances: HashMap<usize, (u64, u32)>,
+}
+
+impl BalanceCache {
+    pub fn update(&mut self, wallet_index: usize, balance: u64, height: u32) {
+        self.balances.insert(wallet_index, (balance, height));
+    }
+
+    /// Forgets the balance of `wallet_index`, e.g. once a top-up changed it.
+    pub fn invalidate(&mut self, wallet_index: usize) {
+        self.balances.remove(&wallet_index);
+    }
+
+    pub fn clear(&mut self) {
+        self.balances.clear();
+    }
+
+    pub fn get(&self, wallet_index: usize) -> Option<(u64, u32)> {
+        self.balances.get(&wallet_index).copied()
+    }
+
+    /// Wallets last seen below `threshold`, in index order.
+    pub fn below(&self, threshold: u64) -> BTreeSet<usize> {
+        self.balances
+            .iter()
+            .filter(|(_, (balance, _))| *balance < threshold)
+            .map(|(index, _)| *index)
+            .collect()
+    }
+}
+
+/// The wallets `--fast` watches: every HD wallet not excluded.
+struct Watched {
+    addresses: HashMap<usize, Bech32Address>,
+    /// Wallet index of each watched owner, to spot their transactions in a block.
+    owners: HashMap<Address, usize>,
+}
+
+impl Watched {
+    fn new(ctx: &Context<'_>, number_of_wallets: usize) -> Result<Self, Box<dyn Error>> {
+        let mut addresses = HashMap::new();
+        let mut owners = HashMap::new();
+        for index in 0..number_of_wallets {
+            if ctx.is_excluded(index)? {
+                continue;
+            }
+            let address = ctx.fleet.wallet(index, None)?.address().clone();
+            owners.insert(Address::from(&address), index);
+            addresses.insert(index, address);
+        }
+        Ok(Self { addresses, owners })
+    }
+}
+
+/// State kept across blocks.
+struct FastFunding {
+    watched: Watched,
+    cache: BalanceCache,
+    in_flight: InFlight,
+    velocity: Velocity,
+    /// Last block looked at; `None` until every balance has been read.
+    last_height: Option<u32>,
+    /// Unix time of the next full refresh of the cache.
+    next_refresh: u64,
+}
+
+impl FastFunding {
+    /// Looks at the blocks since the last step and funds the wallets that need it.
+    async fn step(
+        &mut self,
+        ctx: &Context<'_>,
+        main_wallet: &Funder,
+        provider: &Provider,
+        rpc_timeout: Duration,
+        settings: &FundingSettings,
+    ) -> Result<usize, Box<dyn Error>> {
+        let height = provider.latest_block_height().await?;
+        let candidates = match self.last_height {
+            Some(last) if height <= last => return Ok(0),
+            Some(last) if height - last <= MAX_CATCH_UP && unix_now() < self.next_refresh => {
+                let mut candidates = self.cache.below(settings.threshold);
+                for block in last + 1..=height {
+                    candidates.extend(spenders(provider, block, &self.watched.owners).await?);
+                }
+                candidates
+            }
+            last => {
+                if let Some(last) = last.filter(|last| height - last > MAX_CATCH_UP) {
+                    warn!(
+                        "Fell {} blocks behind the chain, reading every balance again.",
+                        height - last
+                    );
+                }
+                self.refresh(ctx, provider, rpc_timeout, height).await?;
+                self.next_refresh = settings.schedule.next_after(unix_now());
+                self.cache.below(settings.threshold)
+            }
+        };
+        self.last_height = Some(height);
+        self.fund(ctx, main_wallet, provider, settings, &candidates, height)
+            .await
+    }
+
+    /// Reads every watched balance into a cleared cache.
+    async fn refresh(
+        &mut self,
+        ctx: &Context<'_>,
+        client: &dyn ChainClient,
+        rpc_timeout: Duration,
+        height: u32,
+    ) -> Result<(), Box<dyn Error>> {
+        self.cache.clear();
+        let mut indices: Vec<usize> = self.watched.addresses.keys().copied().collect();
+        indices.sort_unstable();
+        let addresses: Vec<Bech32Address> = indices
+            .iter()
+            .map(|index| self.watched.addresses[index].clone())
+            .collect();
+        let balances = balances_of(
+            client,
+            &addresses,
+            &ctx.asset_id,
+            ctx.balance_concurrency,
+            Some(rpc_timeout),
+        )
+        .await?;
+        for (index, balance) in indices.into_iter().zip(balances) {
+            self.cache.update(index, balance, height);
+        }
+        debug!(
+            "Read {} balances at block {}.",
+            self.watched.addresses.len(),
+            height
+        );
+        Ok(())
+    }
+
+    /// Tops up those of `candidates` below the threshold. The cache only picks the
+    /// candidates: each balance is read again before anything is sent, and a wallet
+    /// with a top-up still in flight is left alone, so a stale balance never causes
+    /// a top-up.
+    async fn fund(
+        &mut self,
+        ctx: &Context<'_>,
+        main_wallet: &Funder,
+        client: &dyn ChainClient,
+        settings: &FundingSettings,
+        candidates: &BTreeSet<usize>,
+        height: u32,
+    ) -> Result<usize, Box<dyn Error>> {
+        if candidates.is_empty() {
+            return Ok(0);
+        }
+        let sources = ctx.funding_sources.derive(&ctx.fleet, client.provider())?;
+        let mut pipeline = Pipeline::new(ctx, client).labelled(format!("fast-{}", height));
+        let mut funded = 0;
+        let mut sent = 0u128;
+        for &index in candidates {
+            let Some(address) = self.watched.addresses.get(&index) else {
+                continue;
+            };
+            let source = ctx.funding_sources.wallet_for(index, main_wallet, &sources);
+            if self.in_flight.blocks(ctx, client, index, source).await? {
+                continue;
+            }
+            let balance = client.balance(address, &ctx.asset_id).await?;
+            let cached = self.cache.get(index);
+            self.cache.update(index, balance, height);
+            if balance >= settings.threshold {
+                if let Some((cached, read_at)) = cached.filter(|(b, _)| *b < settings.threshold) {
+                    debug!(
+                        "HD Wallet {} had {} at block {} but holds {} now, not funding it.",
+                        index,
+                        ctx.format_amount(cached),
+                        read_at,
+                        ctx.format_amount(balance)
+                    );
+                }
+                continue;
+            }
+            let amount = ctx
+                .jitter
+                .top_up(settings.top_up_amount, settings.threshold - balance);
+            let amount = settings.capped(balance, amount);
+            let now = unix_now();
+            if let Err(limit) = self.velocity.check(&settings.limits, index, amount, now) {
+                debug!(
+                    "HD Wallet {} is below threshold but limited ({:?}), skipping.",
+                    index, limit
+                );
+                continue;
+            }
+            // A block's top-ups stop at APPROVAL_LIMIT; the rest wait for a later block
+            if let Err(e) =
+                approval::check_unreviewed(ctx, "The block's top-ups", sent + u128::from(amount))
+            {
+                warn!("HD Wallet {} is below threshold, skipping: {}", index, e);
+                continue;
+            }
+            info!(
+                "HD Wallet {} is down to {} at block {}, sending {}...",
+                index,
+                ctx.format_amount(balance),
+                height,
+                ctx.format_amount(amount)
+            );
+            let confirmed = pipeline
+                .submit("cont-fund", index, source, address, amount, &ctx.asset_id)
+                .await?;
+            self.velocity.record(index, amount, now);
+            sent += u128::from(amount);
+            self.in_flight.settled(ctx, &confirmed).await;
+            if let Some(record) = pipeline.last_submitted() {
+                self.in_flight.sent(ctx, record.clone()).await;
+            }
+            // The cached balance does not have the top-up: it is read again next time
+            self.cache.invalidate(index);
+            funded += 1;
+        }
+        let confirmed = pipeline.finish().await?;
+        self.in_flight.settled(ctx, &confirmed).await;
+        self.in_flight.track(pipeline.take_unconfirmed());
+        Ok(funded)
+    }
+}
+
+/// Watched wallets that spent coins in the block at `height`.
+async fn spenders(
+    provider: &Provider,
+    height: u32,
+    owners: &HashMap<Address, usize>,
+) -> Result<BTreeSet<usize>, Box<dyn Error>> {
+    let block = provider
+        .block_by_height(height.into())
+        .await?
+        .ok_or_else(|| format!("Block {} is not available from the provider", height))?;
+    let mut spenders = BTreeSet::new();
+    for tx_id in &block.transactions {
+        let Some(response) = provider.get_transaction_by_id(tx_id).await? else {
+            continue;
+        };
+        let inputs = match &response.transaction {
+            TransactionType::Script(tx) => tx.inputs(),
+            TransactionType::Create(tx) => tx.inputs(),
+            _ => continue,
+        };
+        spenders.extend(
+            inputs
+                .iter()
+                .filter_map(|input| input.input_owner())
+                .filter_map(|owner| owners.get(owner).copied()),
+        );
+    }
+    Ok(spenders)
+}
+
+/// Runs high-frequency funding until an error that failover cannot fix. Every
+/// balance is read on start, on `settings.schedule` and after falling behind the
+/// chain; in between, only the wallets that spent in a new block are read again.
+pub async fn fast_funding(
+    ctx: &Context<'_>,
+    main_wallet: &mut Funder,
+    provider_pool: &mut ProviderPool,
+    mut provider: Provider,
+    health: &HealthState,
+    settings: &FundingSettings,
+) -> Result<(), Box<dyn Error>> {
+    if ctx.asset_id != *provider.base_asset_id() {
+        return Err("--fast only funds the base asset, which pays its own fees".into());
+    }
+    ensure_parallel_coins(ctx, main_wallet, &provider, settings.top_up_amount).await?;
+    let mut in_flight = InFlight::new(ctx.in_flight_timeout);
+    in_flight.restore(ctx).await;
+    let mut state = FastFunding {
+        watched: Watched::new(ctx, settings.number_of_wallets)?,
+        cache: BalanceCache::default(),
+        in_flight,
+        velocity: Velocity::from_history(ctx, unix_now()).await,
+        last_height: None,
+        next_refresh: unix_now(),
+    };
+    info!(
+        "Fast funding {} HD wallets block by block, reading every balance {}.",
+        state.watched.addresses.len(),
+        settings.schedule
+    );
+
+    loop {
+        match state
+            .step(
+                ctx,
+                main_wallet,
+                &provider,
+                provider_pool.timeout(),
+                settings,
+            )
+            .await
+        {
+            Ok(_) => {
+                health.record_cycle();
+                health.record_stuck_transactions(state.in_flight.take_stuck());
+            }
+            Err(e) => {
+                let kind = failure::report(
+                    &format!("Fast funding on provider {}", provider_pool.current_url()),
+                    e.as_ref(),
+                );
+                // Whatever was missed meanwhile is caught by reading every balance again
+                state.last_height = None;
+                if kind.is_transport() {
+                    fund::fail_over(provider_pool, &mut provider, main_wallet).await?;
+                }
+            }
+        }
+        sleep(POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chain::mock::{address, base_asset, test_context, MockChain},
+        fund::DEFAULT_THRESHOLD,
+    };
+
+    #[tokio::test]
+    async fn funds_only_on_a_fresh_balance() {
+        let ctx = test_context(3, base_asset());
+        let chain = MockChain::default();
+        chain.set_balance(&address(&ctx, 0), base_asset(), 100_000_000);
+        chain.set_balance(&address(&ctx, 1), base_asset(), 10_000_000);
+        chain.set_balance(&address(&ctx, 2), base_asset(), 1_000);
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        let settings = FundingSettings::new(DEFAULT_THRESHOLD, DEFAULT_THRESHOLD, 3);
+        let mut state = FastFunding {
+            watched: Watched::new(&ctx, 3).unwrap(),
+            cache: BalanceCache::default(),
+            in_flight: InFlight::new(ctx.in_flight_timeout),
+            velocity: Velocity::default(),
+            last_height: Some(10),
+            next_refresh: u64::MAX,
+        };
+        state
+            .refresh(&ctx, &chain, Duration::from_secs(1), 10)
+            .await
+            .unwrap();
+        assert_eq!(state.cache.below(DEFAULT_THRESHOLD), BTreeSet::from([2]));
+
+        // Wallet 1 was topped up elsewhere after its balance was cached low
+        state.cache.update(1, 1_000, 10);
+        let candidates = state.cache.below(DEFAULT_THRESHOLD);
+        let funded = state
+            .fund(&ctx, &main_wallet, &chain, &settings, &candidates, 11)
+            .await
+            .unwrap();
+
+        assert_eq!(funded, 1);
+        assert_eq!(
+            chain.balance_of(&address(&ctx, 1), base_asset()),
+            10_000_000
+        );
+        assert_eq!(
+            chain.balance_of(&address(&ctx, 2), base_asset()),
+            1_000 + DEFAULT_THRESHOLD
+        );
+        assert_eq!(state.cache.get(1), Some((10_000_000, 11)));
+        // Read again before it is trusted
+        assert_eq!(state.cache.get(2), None);
+    }
+}
@@ -0,0 +1,196 @@
+//! `faucet`: asks a testnet faucet for funds for the main wallet and, with
+//! `--wallets`, for HD wallets directly, so a new testnet fleet is bootstrapped with
+//! one command. Each wallet is one POST of `{"address", "captcha"}` to `FAUCET_URL`
+//! (Fuel's faucet for `--network`, testnet by default), with the captcha response
+//! token the faucet asks for and `FAUCET_TOKEN` as a bearer token, if set. Refused
+//! on mainnet.
+
+use crate::{chain::ChainClient, network::Network};
+use fuels::types::{bech32::Bech32Address, Address, AssetId};
+use secrecy::{ExposeSecret, SecretString};
+use serde_json::{json, Value};
+use std::{
+    env,
+    error::Error,
+    time::{Duration, Instant},
+};
+use tokio::time::sleep;
+use tracing::{debug, info};
+
+/// Fuel's testnet faucet.
+pub const TESTNET_FAUCET_URL: &str = "https://faucet-testnet.fuel.network/dispense";
+
+/// Fuel's devnet faucet.
+pub const DEVNET_FAUCET_URL: &str = "https://faucet-devnet.fuel.network/dispense";
+
+/// How long `--wait` waits for the requested funds to arrive.
+const WAIT_TIMEOUT: Duration = Duration::from_secs(120);
+
+pub struct Faucet {
+    url: String,
+    /// Captcha response token (`--captcha`, `FAUCET_CAPTCHA`).
+    captcha: Option<SecretString>,
+    /// Bearer token of faucets behind an API key (`FAUCET_TOKEN`).
+    token: Option<SecretString>,
+    client: reqwest::Client,
+}
+
+impl Faucet {
+    /// The faucet of `network` unless `FAUCET_URL` names another. There is none on
+    /// mainnet.
+    pub fn from_env(
+        network: Option<Network>,
+        captcha: Option<String>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let url = match (env::var("FAUCET_URL"), network) {
+            (_, Some(Network::Mainnet)) => return Err("There is no faucet on mainnet".into()),
+            (Ok(url), _) => url,
+            (Err(_), Some(Network::Devnet)) => DEVNET_FAUCET_URL.to_string(),
+            (Err(_), _) => TESTNET_FAUCET_URL.to_string(),
+        };
+        Ok(Self {
+            url,
+            captcha: captcha.map(SecretString::new),
+            token: env::var("FAUCET_TOKEN").ok().map(SecretString::new),
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()?,
+        })
+    }
+
+    /// Asks the faucet to send funds to `address`. Returns the amount dispensed, if
+    /// the faucet says.
+    pub async fn request(&self, address: &Bech32Address) -> Result<Option<u64>, Box<dyn Error>> {
+        let captcha = self
+            .captcha
+            .as_ref()
+            .map_or("", |captcha| captcha.expose_secret().as_str());
+        let body = json!({
+            "address": format!("{:#x}", Address::from(address)),
+            "captcha": captcha,
+        });
+        let mut request = self.client.post(&self.url).json(&body);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token.expose_secret());
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        debug!("Faucet {} answered {}: {}", self.url, status, text);
+        let body: Value = serde_json::from_str(&text).unwrap_or(Value::Null);
+        dispensed(status, &body, &text)
+    }
+}
+
+/// What the faucet's answer means: the amount dispensed (`tokens`, if given) or why
+/// it refused.
+fn dispensed(
+    status: reqwest::StatusCode,
+    body: &Value,
+    text: &str,
+) -> Result<Option<u64>, Box<dyn Error>> {
+    let error = body
+        .get("error")
+        .and_then(Value::as_str)
+        .filter(|error| !error.is_empty());
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(format!("rate limited: {}", error.unwrap_or("try again later")).into());
+    }
+    if !status.is_success() || error.is_some() {
+        let reason = error.map_or_else(|| text.trim().to_string(), str::to_string);
+        return Err(format!("refused ({}): {}", status, reason).into());
+    }
+    Ok(body.get("tokens").and_then(Value::as_u64))
+}
+
+/// Parses `--wallets`: indices and ranges such as `0-9` or `0,3,5`, each below
+/// `number_of_wallets`, in order and without repeats.
+pub fn parse_wallets(spec: &str, number_of_wallets: usize) -> Result<Vec<usize>, Box<dyn Error>> {
+    let mut indices = Vec::new();
+    for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let invalid = || format!("Invalid --wallets entry '{}'", entry);
+        let (start, end) = entry.split_once('-').unwrap_or((entry, entry));
+        let start = start.trim().parse::<usize>().map_err(|_| invalid())?;
+        let end = end.trim().parse::<usize>().map_err(|_| invalid())?;
+        if start > end {
+            return Err(format!("{}: range start is after its end", invalid()).into());
+        }
+        if end >= number_of_wallets {
+            return Err(format!(
+                "{}: beyond NUMBER_OF_WALLETS ({})",
+                invalid(),
+                number_of_wallets
+            )
+            .into());
+        }
+        for index in start..=end {
+            if !indices.contains(&index) {
+                indices.push(index);
+            }
+        }
+    }
+    Ok(indices)
+}
+
+/// Waits until every one of `wallets` holds more of `asset_id` than it did before
+/// its request. Returns those still waiting when the time runs out.
+pub async fn wait_for_funds<'w>(
+    client: &dyn ChainClient,
+    asset_id: &AssetId,
+    wallets: &'w [(String, Bech32Address, u64)],
+) -> Result<Vec<&'w str>, Box<dyn Error>> {
+    let started = Instant::now();
+    let mut waiting: Vec<&(String, Bech32Address, u64)> = wallets.iter().collect();
+    loop {
+        let mut still_waiting = Vec::new();
+        for wallet in waiting {
+            let (label, address, before) = wallet;
+            let balance = client.balance(address, asset_id).await?;
+            if balance > *before {
+                info!("{} received {} base units.", label, balance - before);
+            } else {
+                still_waiting.push(wallet);
+            }
+        }
+        waiting = still_waiting;
+        if waiting.is_empty() || started.elapsed() > WAIT_TIMEOUT {
+            return Ok(waiting
+                .into_iter()
+                .map(|(label, ..)| label.as_str())
+                .collect());
+        }
+        sleep(Duration::from_secs(2)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn parses_wallets_and_faucet_answers() {
+        assert_eq!(parse_wallets("0-2, 5,1", 10).unwrap(), vec![0, 1, 2, 5]);
+        assert!(parse_wallets("3-1", 10).is_err());
+        assert!(parse_wallets("8-10", 10).is_err());
+        assert!(parse_wallets("x", 10).is_err());
+
+        let ok = json!({"status": "Success", "tokens": 500_000_000});
+        assert_eq!(
+            dispensed(StatusCode::OK, &ok, "").unwrap(),
+            Some(500_000_000)
+        );
+        let refused = json!({"error": "Invalid captcha"});
+        let error = dispensed(StatusCode::BAD_REQUEST, &refused, "")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Invalid captcha"));
+        assert!(dispensed(StatusCode::OK, &refused, "").is_err());
+        let limited = dispensed(StatusCode::TOO_MANY_REQUESTS, &Value::Null, "")
+            .unwrap_err()
+            .to_string();
+        assert!(limited.starts_with("rate limited"));
+
+        assert!(Faucet::from_env(Some(Network::Mainnet), None).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+use crate::{mnemonic, schedule::Schedule, wallets::Derivation};
+use fuels::types::AssetId;
+use secrecy::SecretString;
+use serde::Deserialize;
+use std::{env, error::Error, fs, path::PathBuf, str::FromStr};
+
+/// Where a fleet's mnemonic is read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MnemonicSource {
+    /// Environment variable, removed once read like `MNEMONIC`.
+    Env(String),
+    File(PathBuf),
+}
+
+/// One of several wallet fleets continual funding runs side by side (`FLEETS`),
+/// each with its own mnemonic, main wallet and funding policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FleetConfig {
+    pub name: String,
+    pub mnemonic: MnemonicSource,
+    /// `NUMBER_OF_WALLETS` for the fleet.
+    pub wallets: usize,
+    pub derivation: Derivation,
+    /// `ETH_ASSET_ID` for the fleet.
+    pub asset_id: Option<AssetId>,
+    /// `ASSET_DECIMALS` for the fleet.
+    pub decimals: Option<u32>,
+    /// `FUNDING_THRESHOLD` for the fleet, in its decimals or base units.
+    pub threshold: Option<String>,
+    /// `TOP_UP_AMOUNT` for the fleet.
+    pub top_up_amount: Option<String>,
+    /// `MAX_WALLET_BALANCE` for the fleet.
+    pub max_balance: Option<String>,
+    /// `FUND_SCHEDULE` for the fleet.
+    pub schedule: Option<Schedule>,
+    /// `EXCLUDED_INDICES` for the fleet.
+    pub excluded: String,
+}
+
+impl FleetConfig {
+    /// Reads the fleet's mnemonic from its source.
+    pub fn read_mnemonic(&self) -> Result<SecretString, Box<dyn Error>> {
+        match &self.mnemonic {
+            MnemonicSource::Env(name) => {
+                let mnemonic = env::var(name).map_err(|_| {
+                    format!("Fleet '{}': {} not set in the environment", self.name, name)
+                })?;
+                env::remove_var(name);
+                Ok(SecretString::new(mnemonic))
+            }
+            MnemonicSource::File(path) => mnemonic::read_file(path),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct FleetEntry {
+    name: String,
+    mnemonic_env: Option<String>,
+    mnemonic_file: Option<PathBuf>,
+    wallets: usize,
+    /// Path template such as `m/44'/1179993420'/<index>'/0/0`.
+    path: Option<String>,
+    asset_id: Option<String>,
+    decimals: Option<u32>,
+    threshold: Option<String>,
+    top_up_amount: Option<String>,
+    max_balance: Option<String>,
+    schedule: Option<String>,
+    #[serde(default)]
+    excluded: String,
+}
+
+#[derive(Deserialize)]
+struct FleetsFile {
+    fleets: Vec<FleetEntry>,
+}
+
+/// Every fleet in the JSON file named by `FLEETS`, or `None` if it is not set.
+pub fn load_all() -> Result<Option<Vec<FleetConfig>>, Box<dyn Error>> {
+    let Ok(path) = env::var("FLEETS") else {
+        return Ok(None);
+    };
+    let contents =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read fleets {}: {}", path, e))?;
+    let fleets = parse(&contents).map_err(|e| format!("Fleets {}: {}", path, e))?;
+    Ok(Some(fleets))
+}
+
+/// The fleet called `name` from `FLEETS`.
+pub fn load(name: &str) -> Result<FleetConfig, Box<dyn Error>> {
+    load_all()?
+        .into_iter()
+        .flatten()
+        .find(|fleet| fleet.name == name)
+        .ok_or_else(|| format!("No fleet '{}' in FLEETS", name).into())
+}
+
+/// Environment variables fleets read their mnemonics from, which a `.env` reload
+/// must not put back.
+pub fn mnemonic_vars() -> Vec<String> {
+    load_all()
+        .ok()
+        .flatten()
+        .into_iter()
+        .flatten()
+        .filter_map(|fleet| match fleet.mnemonic {
+            MnemonicSource::Env(name) => Some(name),
+            MnemonicSource::File(_) => None,
+        })
+        .collect()
+}
+
+fn parse(contents: &str) -> Result<Vec<FleetConfig>, Box<dyn Error>> {
+    let file: FleetsFile = serde_json::from_str(contents)?;
+    if file.fleets.is_empty() {
+        return Err("no fleets configured".into());
+    }
+    let mut fleets: Vec<FleetConfig> = Vec::new();
+    for entry in file.fleets {
+        let invalid = |what: String| format!("fleet '{}': {}", entry.name, what);
+
+        if fleets.iter().any(|other| other.name == entry.name) {
+            return Err(invalid("duplicate name".into()).into());
+        }
+        let mnemonic = match (entry.mnemonic_env, entry.mnemonic_file) {
+            (Some(name), None) => MnemonicSource::Env(name),
+            (None, Some(path)) => MnemonicSource::File(path),
+            _ => {
+                return Err(
+                    invalid("set exactly one of mnemonic_env and mnemonic_file".into()).into(),
+                )
+            }
+        };
+        if entry.wallets == 0 {
+            return Err(invalid("wallets must be greater than 0".into()).into());
+        }
+        let derivation = match &entry.path {
+            Some(path) => path.parse().map_err(invalid)?,
+            None => Derivation::default(),
+        };
+        let asset_id = entry
+            .asset_id
+            .as_deref()
+            .map(|asset| {
+                AssetId::from_str(asset)
+                    .map_err(|_| invalid(format!("invalid asset id '{}'", asset)))
+            })
+            .transpose()?;
+        let schedule = entry
+            .schedule
+            .as_deref()
+            .map(str::parse::<Schedule>)
+            .transpose()
+            .map_err(invalid)?;
+
+        fleets.push(FleetConfig {
+            name: entry.name.clone(),
+            mnemonic,
+            wallets: entry.wallets,
+            derivation,
+            asset_id,
+            decimals: entry.decimals,
+            threshold: entry.threshold,
+            top_up_amount: entry.top_up_amount,
+            max_balance: entry.max_balance,
+            schedule,
+            excluded: entry.excluded,
+        });
+    }
+    Ok(fleets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_fleets() {
+        let fleets = parse(
+            r#"{"fleets": [
+                {"name": "market_makers", "mnemonic_env": "MM_MNEMONIC", "wallets": 50,
+                 "threshold": "0.01", "schedule": "every 10s", "excluded": "3"},
+                {"name": "arbitrage", "mnemonic_file": "/run/secrets/arb", "wallets": 200,
+                 "path": "m/44'/60'/<index>'/0/0",
+                 "asset_id": "0x0101010101010101010101010101010101010101010101010101010101010101"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            fleets[0].mnemonic,
+            MnemonicSource::Env("MM_MNEMONIC".into())
+        );
+        assert_eq!(fleets[0].wallets, 50);
+        assert_eq!(
+            fleets[0].derivation.coin_type,
+            Derivation::default().coin_type
+        );
+        assert_eq!(fleets[1].derivation.coin_type, 60);
+        assert_eq!(fleets[1].asset_id, Some(AssetId::from([1u8; 32])));
+
+        assert!(parse(
+            r#"{"fleets": [{"name": "a", "mnemonic_env": "A", "wallets": 1},
+                           {"name": "a", "mnemonic_env": "B", "wallets": 1}]}"#
+        )
+        .is_err());
+        assert!(parse(r#"{"fleets": [{"name": "a", "wallets": 1}]}"#).is_err());
+        assert!(
+            parse(r#"{"fleets": [{"name": "a", "mnemonic_env": "A", "wallets": 0}]}"#).is_err()
+        );
+    }
+}
@@ -0,0 +1,2029 @@
+use crate::{
+    activity::{Activity, Runway, MAX_RUNWAY},
+    approval,
+    chain::{balances_of, ChainClient},
+    coins::ensure_parallel_coins,
+    context::Context,
+    daemon::{unix_now, HealthState, ReloadSignal},
+    failure, fleets,
+    funding_sources::FundingSources,
+    gas::GasTopUps,
+    groups,
+    in_flight::InFlight,
+    invariants::{self, Invariants, Snapshot, Violation},
+    pipeline::Pipeline,
+    price::{PriceOracle, Usd},
+    profiles,
+    provider_pool::ProviderPool,
+    reclaim::{reclaim_funds, ReclaimOptions, DEFAULT_GAS_RESERVE},
+    refill::{self, RefillNeeded},
+    relay::Relay,
+    schedule::{self, Schedule},
+    signer::Funder,
+    storage::TransferRecord,
+    transfer::verify_credit,
+    units, vault,
+    velocity::{LimitHit, Velocity, VelocityLimits},
+};
+use fuels::{accounts::provider::Provider, types::bech32::Bech32Address};
+use std::{
+    env,
+    error::Error,
+    time::{Duration, Instant},
+};
+use tokio::time::{sleep, timeout};
+use tracing::{debug, error, info, info_span, warn, Instrument};
+
+/// How often continual funding checks the wallets unless `FUND_SCHEDULE` is set.
+pub const DEFAULT_SCHEDULE: Schedule = Schedule::Every(Duration::from_secs(20));
+
+/// How long past its watchdog a cycle may run before the deadman switch trips.
+const DEADMAN_SLACK_SECS: u64 = 60;
+
+/// How long past its deadline the funding loop may stay unrestarted before the
+/// deadman backstop ends the process.
+pub const DEADMAN_BACKSTOP_GRACE: Duration = Duration::from_secs(120);
+
+/// Balance below which continual funding tops a wallet up (0.005 ETH in base units).
+pub const DEFAULT_THRESHOLD: u64 = 5_000_000; // Adjust based on your asset's base units
+
+/// Continual funding settings that can be changed without a restart: they are
+/// re-read from the environment and `.env` on SIGHUP and applied from the next cycle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FundingSettings {
+    /// Balance below which a wallet is topped up (`FUNDING_THRESHOLD`).
+    pub threshold: u64,
+    /// Amount sent per top-up (`TOP_UP_AMOUNT`, defaults to the threshold).
+    pub top_up_amount: u64,
+    pub number_of_wallets: usize,
+    /// When funding cycles run (`FUND_SCHEDULE`).
+    pub schedule: Schedule,
+    /// When a full reclaim runs between cycles, if at all (`RECLAIM_SCHEDULE`).
+    pub reclaim_schedule: Option<Schedule>,
+    /// Hourly caps on top-ups per wallet and total outflow.
+    pub limits: VelocityLimits,
+    /// How long a wallet may spend nothing before it is no longer topped up
+    /// (`IDLE_AFTER`); `None` tops up idle wallets like any other.
+    pub idle_after: Option<Duration>,
+    /// Top-ups sized to each wallet's spending rather than `top_up_amount`, if set.
+    pub runway: Option<Runway>,
+    /// Main wallet balance below which a refill is requested (`MAIN_WALLET_RESERVE`,
+    /// defaults to one top-up).
+    pub main_reserve: u64,
+    /// Checks run after every cycle.
+    pub invariants: Invariants,
+    /// Amounts given in dollars, converted into `threshold`, `top_up_amount`,
+    /// `main_reserve` and `max_balance` by [`FundingSettings::priced`].
+    pub usd: UsdAmounts,
+    /// Most a wallet may hold (`MAX_WALLET_BALANCE`): top-ups stop short of it and any
+    /// excess is swept back to the wallet that funds it. `None` sets no cap.
+    pub max_balance: Option<u64>,
+    /// Base asset that senders and receivers of a non-base `ETH_ASSET_ID` are kept
+    /// topped up with for gas (`GAS_RESERVE`); `None` with `AUTO_GAS_TOP_UP=false`.
+    pub gas_reserve: Option<u64>,
+    /// Confirmation callback (`--callback-url`, else `CALLBACK_URL`).
+    pub callback_url: Option<String>,
+    /// `--callback-url`, which keeps precedence over `CALLBACK_URL` across reloads.
+    callback_flag: Option<String>,
+    /// `--group`, whose policy is re-read from `WALLET_GROUPS` on reload.
+    group: Option<String>,
+    /// Fleet whose policy is re-read from `FLEETS` on reload.
+    fleet: Option<String>,
+}
+
+impl FundingSettings {
+    /// Settings without a confirmation callback.
+    pub fn new(threshold: u64, top_up_amount: u64, number_of_wallets: usize) -> Self {
+        Self {
+            threshold,
+            top_up_amount,
+            number_of_wallets,
+            schedule: DEFAULT_SCHEDULE,
+            reclaim_schedule: None,
+            main_reserve: top_up_amount,
+            limits: VelocityLimits::default(),
+            idle_after: None,
+            runway: None,
+            invariants: Invariants::default(),
+            usd: UsdAmounts::default(),
+            max_balance: None,
+            gas_reserve: Some(DEFAULT_GAS_RESERVE),
+            callback_url: None,
+            callback_flag: None,
+            group: None,
+            fleet: None,
+        }
+    }
+
+    /// Reads the settings from the environment. With `group` (`--group`), that
+    /// wallet group's threshold, top-up amount and schedule take precedence.
+    pub fn from_env(
+        callback_flag: Option<String>,
+        group: Option<String>,
+    ) -> Result<Self, Box<dyn Error>> {
+        Self::read(callback_flag, group, None)
+    }
+
+    /// Settings of the fleet called `fleet` in `FLEETS`: its wallet count, decimals,
+    /// threshold, top-up amount and schedule take precedence over the environment.
+    pub fn for_fleet(callback_flag: Option<String>, fleet: &str) -> Result<Self, Box<dyn Error>> {
+        Self::read(callback_flag, None, Some(fleet.to_string()))
+    }
+
+    fn read(
+        callback_flag: Option<String>,
+        group: Option<String>,
+        fleet: Option<String>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let fleet_config = fleet.as_deref().map(fleets::load).transpose()?;
+        let number_of_wallets = match &fleet_config {
+            Some(config) => config.wallets,
+            None => number_of_wallets_from_env()?,
+        };
+        let decimals = match fleet_config.as_ref().and_then(|config| config.decimals) {
+            Some(decimals) => decimals,
+            None => units::decimals_from_env()?,
+        };
+        // A fleet or group's own policy takes precedence over the environment
+        let (policy, policy_threshold, policy_amount, policy_max_balance, policy_schedule) = match (
+            &fleet_config,
+            group.as_deref().map(groups::load).transpose()?,
+        ) {
+            (Some(config), _) => (
+                format!("fleet '{}'", config.name),
+                config.threshold.clone(),
+                config.top_up_amount.clone(),
+                config.max_balance.clone(),
+                config.schedule.clone(),
+            ),
+            (None, Some(group)) => (
+                format!("group '{}'", group.name),
+                group.threshold,
+                group.top_up_amount,
+                group.max_balance,
+                group.schedule,
+            ),
+            (None, None) => Default::default(),
+        };
+        let policy_amount_of = |value: Option<&String>, name: &str| {
+            value
+                .map(|value| {
+                    parse_amount_setting(value, decimals)
+                        .map_err(|e| format!("Failed to parse the {} of {}: {}", name, policy, e))
+                })
+                .transpose()
+        };
+        let threshold = match policy_amount_of(policy_threshold.as_ref(), "threshold")? {
+            Some(threshold) => threshold,
+            None => parse_env_setting("FUNDING_THRESHOLD", decimals)?
+                .unwrap_or(AmountSetting::Units(DEFAULT_THRESHOLD)),
+        };
+        let top_up_amount = match policy_amount_of(policy_amount.as_ref(), "top_up_amount")? {
+            Some(amount) => amount,
+            None => parse_env_setting("TOP_UP_AMOUNT", decimals)?.unwrap_or(threshold),
+        };
+        if threshold.is_zero() || top_up_amount.is_zero() {
+            return Err("FUNDING_THRESHOLD and TOP_UP_AMOUNT must be greater than 0".into());
+        }
+        // Funding source ranges are only valid for a given wallet count; fleets have none
+        if fleet.is_none() {
+            FundingSources::from_env(number_of_wallets)?;
+        }
+        let schedule = match policy_schedule {
+            Some(schedule) => schedule,
+            None => parse_env_schedule("FUND_SCHEDULE")?.unwrap_or(DEFAULT_SCHEDULE),
+        };
+        let reclaim_schedule = parse_env_schedule("RECLAIM_SCHEDULE")?;
+        let main_reserve =
+            parse_env_setting("MAIN_WALLET_RESERVE", decimals)?.unwrap_or(top_up_amount);
+        let max_balance = match policy_amount_of(policy_max_balance.as_ref(), "max_balance")? {
+            Some(max_balance) => Some(max_balance),
+            None => parse_env_setting("MAX_WALLET_BALANCE", decimals)?,
+        };
+        // Dollar amounts stay at zero until priced
+        let usd = UsdAmounts {
+            threshold: threshold.usd(),
+            top_up_amount: top_up_amount.usd(),
+            main_reserve: main_reserve.usd(),
+            max_balance: max_balance.and_then(AmountSetting::usd),
+        };
+        let max_balance = max_balance.map(AmountSetting::units);
+        let (threshold, top_up_amount, main_reserve) = (
+            threshold.units(),
+            top_up_amount.units(),
+            main_reserve.units(),
+        );
+        let limits = VelocityLimits {
+            max_top_ups_per_wallet: env::var("MAX_TOP_UPS_PER_WALLET_HOUR")
+                .ok()
+                .map(|value| {
+                    value
+                        .parse::<u32>()
+                        .map_err(|e| format!("Failed to parse MAX_TOP_UPS_PER_WALLET_HOUR: {}", e))
+                })
+                .transpose()?,
+            max_outflow: parse_env_amount("MAX_OUTFLOW_PER_HOUR", decimals)?,
+        };
+        let idle_after = env::var("IDLE_AFTER")
+            .ok()
+            .map(|value| {
+                schedule::parse_interval(value.trim())
+                    .map_err(|e| format!("Failed to parse IDLE_AFTER: {}", e))
+            })
+            .transpose()?;
+        let runway = match env::var("FUNDING_RUNWAY") {
+            Ok(value) => {
+                let duration = schedule::parse_interval(value.trim())
+                    .map_err(|e| format!("Failed to parse FUNDING_RUNWAY: {}", e))?;
+                if duration > MAX_RUNWAY {
+                    return Err("FUNDING_RUNWAY may be at most 7d".into());
+                }
+                let min_amount = parse_env_amount("MIN_TOP_UP_AMOUNT", decimals)?.unwrap_or(0);
+                let max_amount = parse_env_amount("MAX_TOP_UP_AMOUNT", decimals)?
+                    .ok_or("FUNDING_RUNWAY needs MAX_TOP_UP_AMOUNT")?;
+                if max_amount == 0 || min_amount > max_amount {
+                    return Err(
+                        "MAX_TOP_UP_AMOUNT must be greater than 0 and at least MIN_TOP_UP_AMOUNT"
+                            .into(),
+                    );
+                }
+                Some(Runway {
+                    duration,
+                    min_amount,
+                    max_amount,
+                })
+            }
+            Err(_) => None,
+        };
+        let invariants = Invariants {
+            max_drift: parse_env_amount("INVARIANT_MAX_DRIFT", decimals)?,
+            wallet_cap: parse_env_amount("INVARIANT_WALLET_CAP", decimals)?,
+            pause: env::var("INVARIANT_PAUSE")
+                .ok()
+                .map(|value| {
+                    value
+                        .parse::<bool>()
+                        .map_err(|e| format!("Failed to parse INVARIANT_PAUSE: {}", e))
+                })
+                .transpose()?
+                .unwrap_or(false),
+        };
+        let auto_gas = env::var("AUTO_GAS_TOP_UP")
+            .ok()
+            .map(|value| {
+                value
+                    .parse::<bool>()
+                    .map_err(|e| format!("Failed to parse AUTO_GAS_TOP_UP: {}", e))
+            })
+            .transpose()?
+            .unwrap_or(true);
+        let gas_reserve = if auto_gas {
+            Some(
+                parse_env_amount("GAS_RESERVE", units::DEFAULT_DECIMALS)?
+                    .unwrap_or(DEFAULT_GAS_RESERVE),
+            )
+        } else {
+            None
+        };
+
+        let settings = Self {
+            threshold,
+            top_up_amount,
+            number_of_wallets,
+            schedule,
+            reclaim_schedule,
+            main_reserve,
+            limits,
+            idle_after,
+            runway,
+            invariants,
+            usd,
+            max_balance,
+            gas_reserve,
+            callback_url: callback_flag
+                .clone()
+                .or_else(|| env::var("CALLBACK_URL").ok()),
+            callback_flag,
+            group,
+            fleet,
+        };
+        // Dollar amounts are checked once priced
+        if settings.usd == UsdAmounts::default() {
+            settings.check_max_balance()?;
+        }
+        Ok(settings)
+    }
+
+    /// Checks that `MAX_WALLET_BALANCE` leaves room above the threshold, or wallets
+    /// below it would be topped up every cycle without ever reaching it.
+    fn check_max_balance(&self) -> Result<(), Box<dyn Error>> {
+        match self.max_balance {
+            Some(max_balance) if max_balance <= self.threshold => {
+                Err("MAX_WALLET_BALANCE must be above FUNDING_THRESHOLD".into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// `amount` reduced so that a wallet holding `balance` ends up with at most
+    /// `max_balance`.
+    pub fn capped(&self, balance: u64, amount: u64) -> u64 {
+        match self.max_balance {
+            Some(max_balance) => amount.min(max_balance.saturating_sub(balance)),
+            None => amount,
+        }
+    }
+
+    /// Re-reads `.env` (without restoring the removed `MNEMONIC` or overriding
+    /// values from Vault) and the environment, returning the new settings if they are valid.
+    pub fn reload(&self) -> Result<Self, Box<dyn Error>> {
+        let fleet_mnemonics = fleets::mnemonic_vars();
+        if let Ok(entries) = dotenv::dotenv_iter() {
+            for entry in entries {
+                let (key, value) = entry?;
+                if key != "MNEMONIC" && !fleet_mnemonics.contains(&key) && !vault::manages(&key) {
+                    env::set_var(key, value);
+                }
+            }
+        }
+        profiles::reapply()?;
+        Self::read(
+            self.callback_flag.clone(),
+            self.group.clone(),
+            self.fleet.clone(),
+        )
+    }
+
+    /// These settings with their dollar amounts converted into the asset (with
+    /// `decimals`) at the oracle's current price.
+    pub async fn priced(
+        &self,
+        oracle: &mut PriceOracle,
+        decimals: u32,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut settings = self.clone();
+        if self.usd == UsdAmounts::default() {
+            return Ok(settings);
+        }
+        let price = oracle.price().await?;
+        let convert = |usd: Option<Usd>, units: &mut u64, name: &str| {
+            if let Some(usd) = usd {
+                *units = usd
+                    .to_units(price, decimals)
+                    .map_err(|e| format!("Failed to price {} of {}: {}", name, usd, e))?;
+            }
+            Ok::<_, String>(())
+        };
+        convert(
+            self.usd.threshold,
+            &mut settings.threshold,
+            "FUNDING_THRESHOLD",
+        )?;
+        convert(
+            self.usd.top_up_amount,
+            &mut settings.top_up_amount,
+            "TOP_UP_AMOUNT",
+        )?;
+        convert(
+            self.usd.main_reserve,
+            &mut settings.main_reserve,
+            "MAIN_WALLET_RESERVE",
+        )?;
+        if let Some(max_balance) = settings.max_balance.as_mut() {
+            convert(self.usd.max_balance, max_balance, "MAX_WALLET_BALANCE")?;
+        }
+        if settings.threshold == 0 || settings.top_up_amount == 0 {
+            return Err(format!(
+                "FUNDING_THRESHOLD and TOP_UP_AMOUNT are less than one base unit at ${}",
+                price
+            )
+            .into());
+        }
+        settings.check_max_balance()?;
+        debug!(
+            "At ${}: threshold {}, top-up {}, main wallet reserve {}",
+            price,
+            units::format_amount(settings.threshold.into(), decimals),
+            units::format_amount(settings.top_up_amount.into(), decimals),
+            units::format_amount(settings.main_reserve.into(), decimals)
+        );
+        Ok(settings)
+    }
+}
+
+/// Funding amounts configured in dollars rather than in the asset.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UsdAmounts {
+    pub threshold: Option<Usd>,
+    pub top_up_amount: Option<Usd>,
+    pub main_reserve: Option<Usd>,
+    pub max_balance: Option<Usd>,
+}
+
+/// A funding amount as configured: in the asset, or in dollars.
+#[derive(Debug, Clone, Copy)]
+enum AmountSetting {
+    Units(u64),
+    Usd(Usd),
+}
+
+impl AmountSetting {
+    fn is_zero(self) -> bool {
+        matches!(self, AmountSetting::Units(0) | AmountSetting::Usd(Usd(0)))
+    }
+
+    fn units(self) -> u64 {
+        match self {
+            AmountSetting::Units(units) => units,
+            AmountSetting::Usd(_) => 0,
+        }
+    }
+
+    fn usd(self) -> Option<Usd> {
+        match self {
+            AmountSetting::Units(_) => None,
+            AmountSetting::Usd(usd) => Some(usd),
+        }
+    }
+}
+
+/// Parses an amount in dollars (`$15`, `15 USD`), or else like [`units::parse_setting`].
+fn parse_amount_setting(value: &str, decimals: u32) -> Result<AmountSetting, String> {
+    match Usd::parse(value) {
+        Some(usd) => usd.map(AmountSetting::Usd),
+        None => units::parse_setting(value, decimals).map(AmountSetting::Units),
+    }
+}
+
+/// Reads and validates `NUMBER_OF_WALLETS`.
+pub fn number_of_wallets_from_env() -> Result<usize, Box<dyn Error>> {
+    let number_of_wallets_str = env::var("NUMBER_OF_WALLETS")
+        .map_err(|_| "NUMBER_OF_WALLETS not set in the environment".to_string())?;
+
+    // Parse NUMBER_OF_WALLETS
+    let number_of_wallets = number_of_wallets_str.parse::<usize>().map_err(|e| {
+        format!(
+            "Failed to parse NUMBER_OF_WALLETS ('{}') as a positive integer: {}",
+            number_of_wallets_str, e
+        )
+    })?;
+
+    if number_of_wallets == 0 {
+        return Err("NUMBER_OF_WALLETS must be greater than 0".into());
+    }
+    Ok(number_of_wallets)
+}
+
+/// Reads an amount that is either decimal (`0.005`) or in base units (`5000000`).
+fn parse_env_amount(name: &str, decimals: u32) -> Result<Option<u64>, Box<dyn Error>> {
+    match env::var(name) {
+        Ok(value) => Ok(Some(
+            units::parse_setting(&value, decimals)
+                .map_err(|e| format!("Failed to parse {}: {}", name, e))?,
+        )),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Reads an amount that may also be in dollars.
+fn parse_env_setting(name: &str, decimals: u32) -> Result<Option<AmountSetting>, Box<dyn Error>> {
+    match env::var(name) {
+        Ok(value) => Ok(Some(
+            parse_amount_setting(&value, decimals)
+                .map_err(|e| format!("Failed to parse {}: {}", name, e))?,
+        )),
+        Err(_) => Ok(None),
+    }
+}
+
+fn parse_env_schedule(name: &str) -> Result<Option<Schedule>, Box<dyn Error>> {
+    match env::var(name) {
+        Ok(value) => Ok(Some(
+            value
+                .parse()
+                .map_err(|e| format!("Failed to parse {}: {}", name, e))?,
+        )),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Sends a refill notice when the main wallet drops below `reserve`, once until it
+/// has recovered. Returns whether a notice is outstanding.
+async fn check_refill(
+    ctx: &Context<'_>,
+    main_wallet: &Bech32Address,
+    balance: u64,
+    reserve: u64,
+    requested: bool,
+) -> bool {
+    let Some(refill) =
+        RefillNeeded::check(main_wallet, ctx.asset_id, ctx.decimals, balance, reserve)
+    else {
+        return false;
+    };
+    if !requested {
+        warn!(
+            "Main wallet holds {}, below its reserve of {}: send {} to {}.",
+            ctx.format_amount(balance),
+            ctx.format_amount(reserve),
+            ctx.format_amount(refill.shortfall),
+            refill.main_wallet
+        );
+        ctx.sinks.refill_needed(&refill).await;
+    }
+    true
+}
+
+/// A recurring task of continual funding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Task {
+    Fund,
+    Reclaim,
+}
+
+/// Runs funding cycles on `settings.schedule` until an error that failover cannot
+/// fix, with a full reclaim in between whenever `settings.reclaim_schedule` is due.
+/// Dollar amounts in `settings` are priced again before every cycle. When the loop
+/// stops checking in (see [`Context::deadman_intervals`]), the deadman switch
+/// alerts and restarts it on the next provider.
+pub async fn continual_funding(
+    ctx: &Context<'_>,
+    main_wallet: &mut Funder,
+    provider_pool: &mut ProviderPool,
+    mut provider: Provider,
+    health: &HealthState,
+    mut settings: FundingSettings,
+    reclaim: &ReclaimOptions,
+) -> Result<(), Box<dyn Error>> {
+    loop {
+        // Getting to the first cycle has the same budget as a turn of the loop
+        arm_deadman(ctx, health, &settings.schedule, unix_now());
+        let overdue = tokio::select! {
+            result = funding_loop(
+                ctx,
+                main_wallet,
+                provider_pool,
+                provider.clone(),
+                health,
+                &mut settings,
+                reclaim,
+            ) => {
+                health.disarm_deadman();
+                return result;
+            }
+            overdue = health.deadman() => overdue,
+        };
+        error!(
+            deadman = true,
+            "Deadman switch: continual funding is {}s overdue on provider {}, restarting it.",
+            overdue,
+            provider_pool.current_url()
+        );
+        ctx.sinks.deadman_tripped(overdue).await;
+        fail_over(provider_pool, &mut provider, main_wallet).await?;
+    }
+}
+
+/// Moves `provider` and `main_wallet` to the next healthy provider. With none healthy,
+/// they stay where they are and the next cycle tries again, rather than the loop
+/// ending; only a provider serving the wrong network ends it.
+pub async fn fail_over(
+    provider_pool: &mut ProviderPool,
+    provider: &mut Provider,
+    main_wallet: &mut Funder,
+) -> Result<(), Box<dyn Error>> {
+    match provider_pool.failover().await? {
+        Some(next) => {
+            main_wallet.set_provider(next.clone());
+            *provider = next;
+        }
+        None => warn!(
+            "No healthy provider, staying on {} until the next cycle.",
+            provider_pool.current_url()
+        ),
+    }
+    Ok(())
+}
+
+/// Exit code of `--once` when the cycle topped up at least one wallet.
+pub const EXIT_FUNDED: i32 = 2;
+
+/// How a `--once` cycle went, as reported by its exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnceOutcome {
+    /// No wallet needed a top-up.
+    NothingFunded,
+    /// At least one wallet was topped up.
+    Funded,
+    /// The cycle completed, but left the main wallet below its reserve.
+    RefillNeeded,
+    /// The cycle failed, was cancelled by the watchdog, or broke an invariant.
+    Failed,
+}
+
+impl OnceOutcome {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            OnceOutcome::NothingFunded => 0,
+            OnceOutcome::Funded => EXIT_FUNDED,
+            OnceOutcome::RefillNeeded => refill::EXIT_REFILL_NEEDED,
+            OnceOutcome::Failed => 1,
+        }
+    }
+}
+
+/// Runs a single continual funding cycle (`--once`), for schedulers that start the
+/// process for each cycle, such as Kubernetes CronJobs. Top-ups a previous run left
+/// in flight are picked up from storage; idle wallets and `FUNDING_RUNWAY` need
+/// spending observed across cycles and are not applied.
+pub async fn funding_once(
+    ctx: &Context<'_>,
+    main_wallet: &Funder,
+    provider: &Provider,
+    rpc_timeout: Duration,
+    settings: &FundingSettings,
+) -> Result<OnceOutcome, Box<dyn Error>> {
+    ensure_parallel_coins(ctx, main_wallet, provider, settings.top_up_amount).await?;
+    let mut in_flight = InFlight::new(ctx.in_flight_timeout);
+    in_flight.restore(ctx).await;
+    let mut velocity = Velocity::from_history(ctx, unix_now()).await;
+    let health = HealthState::default();
+    let cycle_start = Instant::now();
+
+    let result = timeout(
+        ctx.max_cycle_duration,
+        funding_cycle(
+            ctx,
+            main_wallet,
+            provider,
+            rpc_timeout,
+            settings,
+            &mut in_flight,
+            &mut velocity,
+            &mut Activity::default(),
+            1,
+        )
+        .instrument(info_span!("cycle", number = 1)),
+    )
+    .await;
+    let stats = match result {
+        Err(_) => {
+            error!(
+                watchdog = true,
+                "Watchdog: the cycle exceeded {}s, cancelled it.",
+                ctx.max_cycle_duration.as_secs()
+            );
+            ctx.sinks.watchdog_tripped(1, ctx.max_cycle_duration).await;
+            return Ok(OnceOutcome::Failed);
+        }
+        Ok(Err(e)) => {
+            failure::report("Funding cycle", e.as_ref());
+            return Ok(OnceOutcome::Failed);
+        }
+        Ok(Ok(stats)) => stats,
+    };
+
+    let main_balance = provider
+        .get_asset_balance(main_wallet.address(), ctx.asset_id)
+        .await;
+    log_cycle_summary(
+        ctx,
+        1,
+        &stats,
+        &[],
+        in_flight.pending(),
+        (stats.amount_sent.into(), stats.fees_paid.into()),
+        &main_balance,
+        cycle_start.elapsed(),
+    );
+    if settings.invariants.is_enabled() {
+        let checked = check_invariants(
+            ctx,
+            main_wallet,
+            provider,
+            settings,
+            None,
+            stats.fees_paid,
+            1,
+            &health,
+            false,
+        )
+        .await;
+        if checked.is_some_and(|(_, violations)| !violations.is_empty()) {
+            return Ok(OnceOutcome::Failed);
+        }
+    }
+    let refill = match main_balance {
+        Ok(balance) => {
+            check_refill(
+                ctx,
+                main_wallet.address(),
+                balance,
+                settings.main_reserve,
+                false,
+            )
+            .await
+        }
+        Err(_) => false,
+    };
+    Ok(if refill {
+        OnceOutcome::RefillNeeded
+    } else if stats.wallets_funded > 0 {
+        OnceOutcome::Funded
+    } else {
+        OnceOutcome::NothingFunded
+    })
+}
+
+/// Arms the deadman switch for a turn of the funding loop waiting for a task due at
+/// `due`: the loop must be back within the deadman intervals of `schedule`, and is
+/// always given long enough for the cycle watchdog to act first.
+fn arm_deadman(ctx: &Context<'_>, health: &HealthState, schedule: &Schedule, due: u64) {
+    if ctx.deadman_intervals == 0 {
+        return;
+    }
+    let interval = schedule.next_after(due).saturating_sub(due);
+    let budget = interval
+        .saturating_mul(ctx.deadman_intervals.into())
+        .max(ctx.max_cycle_duration.as_secs() + DEADMAN_SLACK_SECS);
+    health.arm_deadman(due.saturating_add(budget));
+}
+
+async fn funding_loop(
+    ctx: &Context<'_>,
+    main_wallet: &mut Funder,
+    provider_pool: &mut ProviderPool,
+    mut provider: Provider,
+    health: &HealthState,
+    settings: &mut FundingSettings,
+    reclaim: &ReclaimOptions,
+) -> Result<(), Box<dyn Error>> {
+    ensure_parallel_coins(ctx, main_wallet, &provider, settings.top_up_amount).await?;
+
+    let mut reload = ReloadSignal::new()?;
+    // Prices dollar amounts before every cycle
+    let mut oracle = PriceOracle::from_env()?;
+
+    let mut in_flight = InFlight::new(ctx.in_flight_timeout);
+    // Top-ups a previous run sent but did not see confirm
+    in_flight.restore(ctx).await;
+    let mut velocity = Velocity::from_history(ctx, unix_now()).await;
+    // What each wallet spent between cycles, to tell idle wallets apart
+    let mut activity = Activity::default();
+    // Balances after the last checked cycle, and the violation that paused funding
+    let mut last_snapshot: Option<Snapshot> = None;
+    let mut paused: Option<String> = None;
+    // Height at the last cycle, for block schedules
+    let mut last_height: Option<u32> = None;
+    let mut cycle = 0u64;
+    let mut total_sent = 0u128;
+    let mut total_fees = 0u128;
+    // Whether a refill notice went out since the main wallet was last above its reserve
+    let mut refill_requested = false;
+    // The first cycle runs straight away, later ones follow the schedules
+    let mut next_cycle = unix_now();
+    let mut next_reclaim = settings
+        .reclaim_schedule
+        .as_ref()
+        .map(|schedule| schedule.next_after(unix_now()));
+    loop {
+        let (due, task) = match next_reclaim {
+            Some(at) if at < next_cycle => (at, Task::Reclaim),
+            _ => (next_cycle, Task::Fund),
+        };
+        arm_deadman(ctx, health, &settings.schedule, due);
+
+        // Wait for the next task, or reload the settings on SIGHUP
+        let now = unix_now();
+        if due > now {
+            match task {
+                // Block schedules poll the height, too often to log each wait
+                Task::Fund if matches!(settings.schedule, Schedule::Blocks(_)) => {}
+                Task::Fund => info!("Next check in {} seconds...", due - now),
+                Task::Reclaim => info!("Next scheduled reclaim in {} seconds...", due - now),
+            }
+            let reloaded = tokio::select! {
+                _ = sleep(Duration::from_secs(due - now)) => false,
+                _ = reload.recv() => true,
+            };
+            if reloaded {
+                if paused.take().is_some() {
+                    info!("Reload received, resuming funding paused by an invariant violation.");
+                    // Whatever caused the violation is the new baseline
+                    last_snapshot = None;
+                }
+                // A new oracle forgets the last price, so a move beyond PRICE_MAX_CHANGE
+                // is accepted on reload
+                let reloaded = match (settings.reload(), PriceOracle::from_env()) {
+                    (Ok(new_settings), Ok(mut new_oracle)) => {
+                        let priced = new_settings.priced(&mut new_oracle, ctx.decimals).await;
+                        if priced.is_ok() {
+                            oracle = new_oracle;
+                        }
+                        priced
+                    }
+                    (Err(e), _) | (_, Err(e)) => Err(e),
+                };
+                match reloaded {
+                    Ok(new_settings) if new_settings == *settings => {
+                        info!("Configuration reloaded, no changes.");
+                    }
+                    Ok(new_settings) => {
+                        info!(
+                            "Configuration reloaded: {:?} -> {:?}",
+                            settings, new_settings
+                        );
+                        ctx.sinks.set_webhook(new_settings.callback_url.clone());
+                        let now = unix_now();
+                        if new_settings.schedule != settings.schedule {
+                            next_cycle = new_settings.schedule.next_after(now);
+                        }
+                        if new_settings.reclaim_schedule != settings.reclaim_schedule {
+                            next_reclaim = new_settings
+                                .reclaim_schedule
+                                .as_ref()
+                                .map(|schedule| schedule.next_after(now));
+                        }
+                        *settings = new_settings;
+                    }
+                    Err(e) => warn!(
+                        "Ignoring invalid configuration on reload, keeping the current one: {}",
+                        e
+                    ),
+                }
+                continue;
+            }
+        }
+
+        if task == Task::Reclaim {
+            info!("Starting scheduled reclaim...");
+            if let Err(e) = reclaim_funds(ctx, main_wallet, &provider, reclaim, None)
+                .instrument(info_span!("scheduled_reclaim"))
+                .await
+            {
+                failure::report("Scheduled reclaim", e.as_ref());
+            }
+            next_reclaim = settings
+                .reclaim_schedule
+                .as_ref()
+                .map(|schedule| schedule.next_after(unix_now()));
+            continue;
+        }
+
+        if let Some(violation) = &paused {
+            warn!(
+                "Funding paused after invariant violation ({}); send SIGHUP to resume.",
+                violation
+            );
+            next_cycle = settings.schedule.next_after(unix_now());
+            continue;
+        }
+
+        // Block schedules only run a cycle once the chain has advanced far enough
+        if let Schedule::Blocks(blocks) = settings.schedule {
+            match provider.latest_block_height().await {
+                Ok(height) if last_height.is_some_and(|last| height < last + blocks) => {
+                    // Read the height again once the missing blocks should be there
+                    let missing = last_height.unwrap_or_default() + blocks - height;
+                    next_cycle = unix_now() + u64::from(missing) * schedule::BLOCK_TIME_SECS;
+                    continue;
+                }
+                Ok(height) => {
+                    debug!("Block {} reached, checking the wallets.", height);
+                    last_height = Some(height);
+                }
+                Err(e) => warn!("Could not read the block height, checking anyway: {}", e),
+            }
+        }
+
+        match settings.priced(&mut oracle, ctx.decimals).await {
+            Ok(priced) => *settings = priced,
+            Err(e) => {
+                warn!(
+                    "Skipping the cycle, could not price the dollar amounts: {}",
+                    e
+                );
+                next_cycle = settings.schedule.next_after(unix_now());
+                continue;
+            }
+        }
+
+        cycle += 1;
+        let cycle_start = Instant::now();
+
+        // The watchdog drops a cycle that outlives its budget, cancelling whatever it awaits
+        let result = timeout(
+            ctx.max_cycle_duration,
+            funding_cycle(
+                ctx,
+                main_wallet,
+                &provider,
+                provider_pool.timeout(),
+                settings,
+                &mut in_flight,
+                &mut velocity,
+                &mut activity,
+                cycle,
+            )
+            .instrument(info_span!("cycle", number = cycle)),
+        )
+        .await;
+
+        match result {
+            Err(_) => {
+                error!(
+                    watchdog = true,
+                    "Watchdog: cycle {} exceeded {}s on provider {}, cancelled it.",
+                    cycle,
+                    ctx.max_cycle_duration.as_secs(),
+                    provider_pool.current_url()
+                );
+                health.record_watchdog_trip();
+                // Top-ups the cancelled cycle submitted may still land
+                in_flight.restore(ctx).await;
+                ctx.sinks
+                    .watchdog_tripped(cycle, ctx.max_cycle_duration)
+                    .await;
+                fail_over(provider_pool, &mut provider, main_wallet).await?;
+            }
+            Ok(Ok(stats)) => {
+                health.record_cycle();
+                health.record_stuck_transactions(stats.stuck_transactions);
+                let idle = settings
+                    .idle_after
+                    .map(|idle_after| activity.idle(idle_after, unix_now()))
+                    .unwrap_or_default();
+                health.set_idle_wallets(idle.clone());
+                total_sent += u128::from(stats.amount_sent);
+                total_fees += u128::from(stats.fees_paid);
+
+                let main_balance = provider
+                    .get_asset_balance(main_wallet.address(), ctx.asset_id)
+                    .await;
+                log_cycle_summary(
+                    ctx,
+                    cycle,
+                    &stats,
+                    &idle,
+                    in_flight.pending(),
+                    (total_sent, total_fees),
+                    &main_balance,
+                    cycle_start.elapsed(),
+                );
+
+                if let Ok(balance) = main_balance {
+                    refill_requested = check_refill(
+                        ctx,
+                        main_wallet.address(),
+                        balance,
+                        settings.main_reserve,
+                        refill_requested,
+                    )
+                    .await;
+                }
+
+                if settings.invariants.is_enabled() {
+                    let pause = settings.invariants.pause;
+                    if let Some((snapshot, violations)) = check_invariants(
+                        ctx,
+                        main_wallet,
+                        &provider,
+                        settings,
+                        last_snapshot.as_ref(),
+                        stats.fees_paid,
+                        cycle,
+                        health,
+                        pause,
+                    )
+                    .await
+                    {
+                        if pause && !violations.is_empty() {
+                            paused = Some(violations[0].detail.clone());
+                        }
+                        last_snapshot = Some(snapshot);
+                    }
+                }
+            }
+            Ok(Err(e)) => {
+                let kind = failure::report(
+                    &format!(
+                        "Cycle {} on provider {}",
+                        cycle,
+                        provider_pool.current_url()
+                    ),
+                    e.as_ref(),
+                );
+                // Another provider would not fix a transfer the node rejected
+                if kind.is_transport() {
+                    fail_over(provider_pool, &mut provider, main_wallet).await?;
+                }
+            }
+        }
+
+        next_cycle = settings.schedule.next_after(unix_now());
+    }
+}
+
+/// Logs what `cycle` did; `totals` are the amount sent and fees paid since start.
+#[allow(clippy::too_many_arguments)]
+fn log_cycle_summary<E: std::fmt::Display>(
+    ctx: &Context<'_>,
+    cycle: u64,
+    stats: &CycleStats,
+    idle: &[usize],
+    pending: usize,
+    (total_sent, total_fees): (u128, u128),
+    main_balance: &Result<u64, E>,
+    duration: Duration,
+) {
+    info!("Cycle {} summary:", cycle);
+    info!("  Wallets checked:        {}", stats.wallets_checked);
+    info!("  Wallets funded:         {}", stats.wallets_funded);
+    info!("  Skipped (inbound):      {}", stats.wallets_skipped);
+    info!("  Skipped (in flight):    {}", stats.wallets_pending);
+    info!("  Skipped (limits):       {}", stats.wallets_limited);
+    info!("  Held (approval limit):  {}", stats.wallets_held);
+    info!("  Skipped (no gas):       {}", stats.wallets_without_gas);
+    info!("  Skipped (idle):         {}", stats.wallets_idle);
+    info!("  Gas top-ups:            {}", stats.gas_top_ups);
+    info!("  Excluded:               {}", stats.wallets_excluded);
+    info!("  Inbound received:       {}", stats.inbound_transfers);
+    if stats.wallets_swept > 0 {
+        info!(
+            "  Swept back:             {} from {} wallets",
+            ctx.format_amount(stats.amount_swept),
+            stats.wallets_swept
+        );
+    }
+    if !idle.is_empty() {
+        info!("  Idle wallets:           {:?}", idle);
+    }
+    info!("  Awaiting confirmation:  {}", pending);
+    info!("  Stuck transactions:     {}", stats.stuck_transactions);
+    info!(
+        "  Sent this cycle:        {}",
+        ctx.format_amount(stats.amount_sent)
+    );
+    info!(
+        "  Sent since start:       {}",
+        ctx.format_amount(total_sent)
+    );
+    info!(
+        "  Fees this cycle:        {}",
+        units::format_fee(stats.fees_paid.into())
+    );
+    info!(
+        "  Fees since start:       {}",
+        units::format_fee(total_fees)
+    );
+    info!(
+        "  Main wallet balance:    {}",
+        main_balance.as_ref().map_or_else(
+            |e| format!("unavailable ({})", e),
+            |b| ctx.format_amount(*b)
+        )
+    );
+    info!("  Cycle duration:         {:.1}s", duration.as_secs_f64());
+}
+
+/// Checks the invariants after `cycle` against `last_snapshot`, the balances after
+/// the last checked cycle, and reports each violation, with funding `paused` or not.
+/// Returns the balances now and the violations, or `None` if they could not be read.
+#[allow(clippy::too_many_arguments)]
+async fn check_invariants(
+    ctx: &Context<'_>,
+    main_wallet: &Funder,
+    provider: &Provider,
+    settings: &FundingSettings,
+    last_snapshot: Option<&Snapshot>,
+    fees_paid: u64,
+    cycle: u64,
+    health: &HealthState,
+    paused: bool,
+) -> Option<(Snapshot, Vec<Violation>)> {
+    let snapshot =
+        match invariants::snapshot(ctx, main_wallet, provider, settings.number_of_wallets).await {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                warn!("Could not check invariants after cycle {}: {}", cycle, e);
+                return None;
+            }
+        };
+    // Fees are paid in the base asset
+    let fees = if ctx.asset_id == *provider.base_asset_id() {
+        fees_paid
+    } else {
+        0
+    };
+    let violations = invariants::evaluate(
+        &settings.invariants,
+        last_snapshot,
+        &snapshot,
+        fees,
+        ctx.decimals,
+    );
+    let paused = paused && !violations.is_empty();
+    for violation in &violations {
+        error!(
+            invariant = violation.invariant,
+            "Invariant violated after cycle {}: {}", cycle, violation.detail
+        );
+        health.record_invariant_violation();
+        ctx.sinks.invariant_violated(cycle, violation, paused).await;
+    }
+    Some((snapshot, violations))
+}
+
+/// What a single continual funding cycle did.
+#[derive(Debug, Default)]
+struct CycleStats {
+    wallets_checked: usize,
+    wallets_funded: usize,
+    /// Wallets below threshold left alone because another source recently funded them.
+    wallets_skipped: usize,
+    /// Wallets left alone because an earlier top-up has not confirmed yet.
+    wallets_pending: usize,
+    /// Wallets below threshold left alone because a velocity limit was reached.
+    wallets_limited: usize,
+    /// Wallets below threshold held back because the cycle reached `APPROVAL_LIMIT`.
+    wallets_held: usize,
+    /// Wallets below threshold left alone because their top-up could not be paid for in gas.
+    wallets_without_gas: usize,
+    /// Wallets below threshold left alone because they spent nothing for `IDLE_AFTER`.
+    wallets_idle: usize,
+    /// Base asset transfers sent ahead of top-ups of a non-base asset.
+    gas_top_ups: usize,
+    /// Wallets on the denylist, not checked at all.
+    wallets_excluded: usize,
+    /// Wallets holding more than they were left with and sent by continual funding.
+    inbound_transfers: usize,
+    /// Wallets above `MAX_WALLET_BALANCE` whose excess was swept back.
+    wallets_swept: usize,
+    /// Total swept back this cycle, in base units.
+    amount_swept: u64,
+    /// Earlier top-ups found still unconfirmed after the in-flight timeout.
+    stuck_transactions: usize,
+    /// Total amount sent this cycle, in base units.
+    amount_sent: u64,
+    /// Fees of the top-ups confirmed this cycle, in base units of the base asset.
+    fees_paid: u64,
+}
+
+/// Checks every HD wallet once and tops up those below the threshold.
+#[allow(clippy::too_many_arguments)]
+async fn funding_cycle(
+    ctx: &Context<'_>,
+    main_wallet: &Funder,
+    client: &dyn ChainClient,
+    rpc_timeout: Duration,
+    settings: &FundingSettings,
+    in_flight: &mut InFlight,
+    velocity: &mut Velocity,
+    activity: &mut Activity,
+    cycle: u64,
+) -> Result<CycleStats, Box<dyn Error>> {
+    let threshold = settings.threshold;
+    let mut stats = CycleStats::default();
+    let sources = ctx.funding_sources.derive(&ctx.fleet, client.provider())?;
+    let mut pipeline = Pipeline::new(ctx, client).labelled(format!("topup-cycle-{}", cycle));
+    // The base asset pays its own fees; other assets need gas on both ends
+    let mut gas = settings
+        .gas_reserve
+        .filter(|_| ctx.asset_id != client.base_asset())
+        .map(|reserve| GasTopUps::new(client, reserve));
+
+    // Query every balance up front, concurrently, rather than one RPC round trip per wallet
+    let mut indices = Vec::with_capacity(settings.number_of_wallets);
+    let mut addresses = Vec::with_capacity(settings.number_of_wallets);
+    for index in 0..settings.number_of_wallets {
+        if ctx.is_excluded(index)? {
+            stats.wallets_excluded += 1;
+            continue;
+        }
+        indices.push(index);
+        addresses.push(ctx.fleet.wallet(index, None)?.address().clone());
+    }
+    let balances = balances_of(
+        client,
+        &addresses,
+        &ctx.asset_id,
+        ctx.balance_concurrency,
+        Some(rpc_timeout),
+    )
+    .await?;
+
+    // Top-ups decided on below, sent once the hubs have been funded for them
+    let mut scheduled: Vec<(usize, u64)> = Vec::new();
+    let mut scheduled_total = 0u128;
+
+    for (hd_wallet_number, balance) in indices.into_iter().zip(balances) {
+        async {
+            // Derive the HD wallet
+            let wallet = ctx.fleet.wallet(hd_wallet_number, client.provider())?;
+            let source_wallet =
+                ctx.funding_sources
+                    .wallet_for(hd_wallet_number, main_wallet, &sources);
+
+            let wallet_address = wallet.address();
+
+            // An earlier top-up that has not landed yet is not reflected in the balance
+            if timeout(
+                rpc_timeout,
+                in_flight.blocks(ctx, client, hd_wallet_number, source_wallet),
+            )
+            .await??
+            {
+                stats.wallets_pending += 1;
+                return Ok(());
+            }
+
+            stats.wallets_checked += 1;
+
+            info!(
+                "HD Wallet {} balance: {}",
+                hd_wallet_number,
+                ctx.format_amount(balance)
+            );
+            let now = unix_now();
+            let received = activity.received(hd_wallet_number, balance);
+            let spent = activity.observe(hd_wallet_number, balance, now);
+            if spent > 0 {
+                debug!(
+                    "HD Wallet {} spent {} since the last check ({} per hour on average).",
+                    hd_wallet_number,
+                    ctx.format_amount(spent),
+                    activity
+                        .hourly_spend(hd_wallet_number, now)
+                        .map_or_else(|| "not yet known".to_string(), |rate| ctx.format_amount(rate))
+                );
+            }
+            if received > 0 {
+                warn!(
+                    "HD Wallet {} received {} that continual funding did not send.",
+                    hd_wallet_number,
+                    ctx.format_amount(received)
+                );
+                stats.inbound_transfers += 1;
+                ctx.sinks
+                    .inbound_received(
+                        hd_wallet_number,
+                        &wallet_address.to_string(),
+                        &ctx.asset_id.to_string(),
+                        received,
+                        balance,
+                    )
+                    .await;
+            }
+
+            // Whatever a wallet holds beyond MAX_WALLET_BALANCE goes back to its source
+            if let Some(max_balance) = settings.max_balance.filter(|max| balance > *max) {
+                let excess = balance - max_balance;
+                match sweep_excess(
+                    ctx,
+                    client,
+                    &Funder::from(wallet.clone()),
+                    hd_wallet_number,
+                    source_wallet.address(),
+                    excess,
+                )
+                .await
+                {
+                    Ok(fee) => {
+                        // The base asset also paid the fee out of the wallet
+                        let fee = if ctx.asset_id == client.base_asset() {
+                            fee
+                        } else {
+                            0
+                        };
+                        activity.withdrawn(hd_wallet_number, excess.saturating_add(fee));
+                        stats.wallets_swept += 1;
+                        stats.amount_swept += excess;
+                    }
+                    Err(e) => warn!(
+                        "Failed to sweep {} from HD Wallet {}: {}",
+                        ctx.format_amount(excess),
+                        hd_wallet_number,
+                        e
+                    ),
+                }
+                return Ok(());
+            }
+
+            // Check if balance is less than threshold
+            if balance < threshold {
+                // A wallet that stopped spending has no use for more
+                if let Some(idle_after) = settings
+                    .idle_after
+                    .filter(|idle_after| activity.is_idle(hd_wallet_number, *idle_after, now))
+                {
+                    info!(
+                        "HD Wallet {} is below threshold but spent nothing in {}s, skipping it as idle.",
+                        hd_wallet_number,
+                        idle_after.as_secs()
+                    );
+                    stats.wallets_idle += 1;
+                    return Ok(());
+                }
+
+                // Another system may already be topping this wallet up
+                if let Some(check) = ctx.inbound_check {
+                    let inbound = timeout(
+                        rpc_timeout,
+                        client.recent_inbound(
+                            wallet_address,
+                            ctx.asset_id,
+                            check.min_amount.unwrap_or(settings.top_up_amount),
+                            check.lookback_blocks,
+                        ),
+                    )
+                    .await??;
+                    if let Some(inbound) = inbound {
+                        info!(
+                            "HD Wallet {} received {} at block {}, skipping top-up.",
+                            hd_wallet_number,
+                            ctx.format_amount(inbound.amount),
+                            inbound.block_height
+                        );
+                        stats.wallets_skipped += 1;
+                        return Ok(());
+                    }
+                }
+
+                // Enough for the runway at the wallet's recent spending, once that is known
+                let amount = settings
+                    .runway
+                    .and_then(|runway| {
+                        runway.top_up(activity, hd_wallet_number, balance, threshold, now)
+                    })
+                    .unwrap_or(settings.top_up_amount);
+                // Varied by --jitter, but never more than MAX_WALLET_BALANCE in a hot wallet
+                let amount = ctx
+                    .jitter
+                    .top_up(amount, threshold.saturating_sub(balance));
+                let amount = settings.capped(balance, amount);
+                if let Err(limit) = velocity.check(&settings.limits, hd_wallet_number, amount, now)
+                {
+                    match limit {
+                        LimitHit::WalletTopUps { count } => warn!(
+                            "HD Wallet {} is below threshold but was already topped up {} times in the last hour, skipping.",
+                            hd_wallet_number, count
+                        ),
+                        LimitHit::Outflow { sent } => warn!(
+                            "HD Wallet {} is below threshold but {} was already sent in the last hour, skipping.",
+                            hd_wallet_number,
+                            ctx.format_amount(sent)
+                        ),
+                    }
+                    stats.wallets_limited += 1;
+                    return Ok(());
+                }
+
+                // A cycle's top-ups stop at APPROVAL_LIMIT; the rest wait for a later cycle
+                if let Err(e) = approval::check_unreviewed(
+                    ctx,
+                    "The funding cycle",
+                    scheduled_total + u128::from(amount),
+                ) {
+                    warn!(
+                        "HD Wallet {} is below threshold, holding its top-up: {}",
+                        hd_wallet_number, e
+                    );
+                    stats.wallets_held += 1;
+                    return Ok(());
+                }
+                velocity.record(hd_wallet_number, amount, now);
+                scheduled_total += u128::from(amount);
+                scheduled.push((hd_wallet_number, amount));
+            }
+
+            Ok::<_, Box<dyn Error>>(())
+        }
+        .instrument(info_span!("wallet", index = hd_wallet_number))
+        .await?;
+    }
+
+    // The hubs are sent what the scheduled top-ups need, and nothing for the wallets
+    // skipped above
+    let mut relay = ctx.relay.then(Relay::default);
+    if let Some(relay) = relay.as_mut() {
+        for &(hd_wallet_number, amount) in &scheduled {
+            relay.plan(ctx, hd_wallet_number, amount);
+        }
+        relay.fund_hubs(ctx, main_wallet, client, &sources).await?;
+    }
+
+    for (hd_wallet_number, amount) in scheduled {
+        async {
+            let wallet = ctx.fleet.wallet(hd_wallet_number, client.provider())?;
+            let wallet_address = wallet.address();
+            let source_wallet =
+                ctx.funding_sources
+                    .wallet_for(hd_wallet_number, main_wallet, &sources);
+
+            if let Some(gas) = gas.as_mut() {
+                let funded = gas
+                    .prepare(
+                        &mut pipeline,
+                        main_wallet,
+                        source_wallet,
+                        ctx.funding_sources.source_for(hd_wallet_number),
+                        hd_wallet_number,
+                        wallet_address,
+                    )
+                    .await?;
+                in_flight.settled(ctx, &gas.take_confirmed()).await;
+                if !funded {
+                    stats.wallets_without_gas += 1;
+                    return Ok(());
+                }
+            }
+
+            info!(
+                "HD Wallet {} balance is below threshold, sending {}...",
+                hd_wallet_number,
+                ctx.format_amount(amount)
+            );
+
+            // Send the top-up amount to the wallet
+            let confirmed = pipeline
+                .submit(
+                    "cont-fund",
+                    hd_wallet_number,
+                    source_wallet,
+                    wallet_address,
+                    amount,
+                    &ctx.asset_id,
+                )
+                .await?;
+            activity.funded(hd_wallet_number, amount);
+            in_flight.settled(ctx, &confirmed).await;
+            // Journal the top-up before waiting for it, so a restart does not repeat it
+            if let Some(record) = pipeline.last_submitted() {
+                in_flight.sent(ctx, record.clone()).await;
+            }
+            stats.wallets_funded += 1;
+            stats.amount_sent += amount;
+            if let Some(relay) = relay.as_mut() {
+                relay.relayed(ctx, hd_wallet_number, amount);
+            }
+            Ok::<_, Box<dyn Error>>(())
+        }
+        .instrument(info_span!("wallet", index = hd_wallet_number))
+        .await?;
+    }
+
+    let confirmed = pipeline.finish().await?;
+    in_flight.settled(ctx, &confirmed).await;
+    stats.fees_paid = pipeline.fees_paid();
+    stats.gas_top_ups = gas.map_or(0, |gas| gas.sent);
+    in_flight.track(pipeline.take_unconfirmed());
+    stats.stuck_transactions = in_flight.take_stuck();
+    if let Some(relay) = relay {
+        relay.finish(ctx).await;
+    }
+
+    Ok(stats)
+}
+
+/// Sends `excess` of HD wallet `hd_wallet_number` back to `to_address`, the wallet
+/// that funds it. Returns the fee paid.
+async fn sweep_excess(
+    ctx: &Context<'_>,
+    client: &dyn ChainClient,
+    wallet: &Funder,
+    hd_wallet_number: usize,
+    to_address: &Bech32Address,
+    excess: u64,
+) -> Result<u64, Box<dyn Error>> {
+    info!(
+        "HD Wallet {} is above MAX_WALLET_BALANCE, sweeping {} back to {}...",
+        hd_wallet_number,
+        ctx.format_amount(excess),
+        to_address
+    );
+    ctx.address_book
+        .check(to_address, Some(&ctx.asset_id), excess, None)?;
+    let outcome = client
+        .transfer(wallet, to_address, excess, &ctx.asset_id, ctx.tx_policies)
+        .await?;
+    let record = TransferRecord::new(
+        "auto-sweep",
+        Some(hd_wallet_number),
+        wallet.address(),
+        to_address,
+        &ctx.asset_id,
+        excess,
+        outcome.tx_id,
+    )
+    .labelled("auto-sweep");
+    verify_credit(ctx, client, &record).await;
+    ctx.sinks.transfer_confirmed(record, outcome.fee).await;
+    Ok(outcome.fee)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chain::mock::{address, base_asset, other_asset, test_context, MockChain, MOCK_FEE},
+        exclusions::Exclusions,
+        inbound::InboundCheck,
+        storage::{mock::MemoryStorage, TransferRecord},
+    };
+    use fuels::types::TxId;
+
+    fn test_settings(number_of_wallets: usize) -> FundingSettings {
+        FundingSettings::new(DEFAULT_THRESHOLD, DEFAULT_THRESHOLD, number_of_wallets)
+    }
+
+    fn funded_chain(ctx: &Context<'_>) -> MockChain {
+        let chain = MockChain::default();
+        chain.set_balance(&address(ctx, 0), base_asset(), 100_000_000);
+        chain.set_balance(&address(ctx, 1), base_asset(), 10_000_000);
+        chain.set_balance(&address(ctx, 2), base_asset(), 1_000);
+        chain
+    }
+
+    #[tokio::test]
+    async fn tops_up_only_wallets_below_threshold() {
+        let ctx = test_context(3, base_asset());
+        let chain = funded_chain(&ctx);
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        let mut in_flight = InFlight::new(ctx.in_flight_timeout);
+
+        let stats = funding_cycle(
+            &ctx,
+            &main_wallet,
+            &chain,
+            Duration::from_secs(1),
+            &test_settings(3),
+            &mut in_flight,
+            &mut Velocity::default(),
+            &mut Activity::default(),
+            1,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(stats.wallets_checked, 3);
+        assert_eq!(stats.wallets_funded, 1);
+        assert_eq!(stats.amount_sent, DEFAULT_THRESHOLD);
+        assert_eq!(
+            chain.balance_of(&address(&ctx, 1), base_asset()),
+            10_000_000
+        );
+        assert_eq!(
+            chain.balance_of(&address(&ctx, 2), base_asset()),
+            1_000 + DEFAULT_THRESHOLD
+        );
+    }
+
+    #[tokio::test]
+    async fn funds_cycles_up_to_the_approval_limit() {
+        let mut ctx = test_context(4, base_asset());
+        ctx.approval_limit = Some(DEFAULT_THRESHOLD + DEFAULT_THRESHOLD / 2);
+        let chain = funded_chain(&ctx);
+        chain.set_balance(&address(&ctx, 3), base_asset(), 1_000);
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+
+        // One top-up fits under the limit; the other waits rather than failing the cycle
+        let stats = run_cycle(&ctx, &main_wallet, &chain, &test_settings(4)).await;
+        assert_eq!(stats.wallets_funded, 1);
+        assert_eq!(stats.wallets_held, 1);
+        assert_eq!(stats.amount_sent, DEFAULT_THRESHOLD);
+        assert_eq!(
+            chain.balance_of(&address(&ctx, 2), base_asset()),
+            1_000 + DEFAULT_THRESHOLD
+        );
+        assert_eq!(chain.balance_of(&address(&ctx, 3), base_asset()), 1_000);
+
+        // The next cycle funds the wallet held back
+        let stats = run_cycle(&ctx, &main_wallet, &chain, &test_settings(4)).await;
+        assert_eq!((stats.wallets_funded, stats.wallets_held), (1, 0));
+        assert_eq!(
+            chain.balance_of(&address(&ctx, 3), base_asset()),
+            1_000 + DEFAULT_THRESHOLD
+        );
+
+        // A limit below a single top-up holds everything, still without an error
+        ctx.approval_limit = Some(DEFAULT_THRESHOLD - 1);
+        chain.set_balance(&address(&ctx, 2), base_asset(), 1_000);
+        let stats = run_cycle(&ctx, &main_wallet, &chain, &test_settings(4)).await;
+        assert_eq!((stats.wallets_funded, stats.wallets_held), (0, 1));
+        assert_eq!(chain.transfers().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn leaves_excluded_wallets_alone() {
+        let mut ctx = test_context(3, base_asset());
+        ctx.exclusions = Exclusions::parse("2", "").unwrap();
+        let chain = funded_chain(&ctx);
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        let mut in_flight = InFlight::new(ctx.in_flight_timeout);
+
+        let stats = funding_cycle(
+            &ctx,
+            &main_wallet,
+            &chain,
+            Duration::from_secs(1),
+            &test_settings(3),
+            &mut in_flight,
+            &mut Velocity::default(),
+            &mut Activity::default(),
+            1,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(stats.wallets_checked, 2);
+        assert_eq!(stats.wallets_excluded, 1);
+        assert_eq!(stats.wallets_funded, 0);
+        assert_eq!(chain.balance_of(&address(&ctx, 2), base_asset()), 1_000);
+    }
+
+    #[tokio::test]
+    async fn skips_wallets_funded_elsewhere() {
+        let mut ctx = test_context(3, base_asset());
+        ctx.inbound_check = Some(InboundCheck {
+            lookback_blocks: 10,
+            min_amount: None,
+        });
+        let chain = funded_chain(&ctx);
+        chain.set_inbound(&address(&ctx, 2), DEFAULT_THRESHOLD);
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        let mut in_flight = InFlight::new(ctx.in_flight_timeout);
+
+        let stats = funding_cycle(
+            &ctx,
+            &main_wallet,
+            &chain,
+            Duration::from_secs(1),
+            &test_settings(3),
+            &mut in_flight,
+            &mut Velocity::default(),
+            &mut Activity::default(),
+            1,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(stats.wallets_funded, 0);
+        assert_eq!(stats.wallets_skipped, 1);
+        assert!(chain.transfers().is_empty());
+    }
+
+    #[tokio::test]
+    async fn funds_hubs_only_for_scheduled_top_ups() {
+        let mut ctx = test_context(3, base_asset());
+        ctx.funding_sources = crate::funding_sources::FundingSources::parse(
+            "1-2:1000",
+            3,
+            &crate::wallets::Derivation::default(),
+        )
+        .unwrap();
+        ctx.relay = true;
+        ctx.inbound_check = Some(InboundCheck {
+            lookback_blocks: 10,
+            min_amount: None,
+        });
+        let chain = funded_chain(&ctx);
+        chain.set_balance(&address(&ctx, 1), base_asset(), 1_000);
+        // Funded elsewhere, so skipped: its hub must not be sent its top-up either
+        chain.set_inbound(&address(&ctx, 2), DEFAULT_THRESHOLD);
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        let hub = ctx.fleet.wallet_at(1000, None).unwrap().address().clone();
+
+        let stats = run_cycle(&ctx, &main_wallet, &chain, &test_settings(3)).await;
+        assert_eq!((stats.wallets_funded, stats.wallets_skipped), (1, 1));
+
+        let transfers = chain.transfers();
+        assert_eq!(transfers.len(), 2);
+        assert_eq!(transfers[0].to, hub);
+        assert_eq!(transfers[0].amount, DEFAULT_THRESHOLD + MOCK_FEE);
+        assert_eq!(transfers[1].to, address(&ctx, 1));
+        assert_eq!(chain.balance_of(&hub, base_asset()), 0);
+    }
+
+    #[tokio::test]
+    async fn caps_top_ups_at_the_max_balance() {
+        let ctx = test_context(3, base_asset());
+        let chain = funded_chain(&ctx);
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        let mut in_flight = InFlight::new(ctx.in_flight_timeout);
+        let mut settings = FundingSettings::new(DEFAULT_THRESHOLD, 4 * DEFAULT_THRESHOLD, 3);
+        settings.max_balance = Some(2 * DEFAULT_THRESHOLD);
+
+        let stats = funding_cycle(
+            &ctx,
+            &main_wallet,
+            &chain,
+            Duration::from_secs(1),
+            &settings,
+            &mut in_flight,
+            &mut Velocity::default(),
+            &mut Activity::default(),
+            1,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(stats.wallets_funded, 1);
+        assert_eq!(stats.amount_sent, 2 * DEFAULT_THRESHOLD - 1_000);
+        assert_eq!(
+            chain.balance_of(&address(&ctx, 2), base_asset()),
+            2 * DEFAULT_THRESHOLD
+        );
+    }
+
+    #[tokio::test]
+    async fn sweeps_income_above_the_max_balance() {
+        let ctx = test_context(3, base_asset());
+        let chain = funded_chain(&ctx);
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        let mut in_flight = InFlight::new(ctx.in_flight_timeout);
+        let mut velocity = Velocity::default();
+        let mut activity = Activity::default();
+        let mut settings = test_settings(3);
+        settings.max_balance = Some(20_000_000);
+
+        let stats = funding_cycle(
+            &ctx,
+            &main_wallet,
+            &chain,
+            Duration::from_secs(1),
+            &settings,
+            &mut in_flight,
+            &mut velocity,
+            &mut activity,
+            1,
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.inbound_transfers, 0);
+
+        // Wallet 1 earns 20_000_000, of which 10_000_000 is more than it may hold
+        chain.set_balance(&address(&ctx, 1), base_asset(), 30_000_000);
+        let main_before = chain.balance_of(&address(&ctx, 0), base_asset());
+        let stats = funding_cycle(
+            &ctx,
+            &main_wallet,
+            &chain,
+            Duration::from_secs(1),
+            &settings,
+            &mut in_flight,
+            &mut velocity,
+            &mut activity,
+            2,
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.inbound_transfers, 1);
+        assert_eq!(stats.wallets_swept, 1);
+        assert_eq!(stats.amount_swept, 10_000_000);
+        assert_eq!(
+            chain.balance_of(&address(&ctx, 1), base_asset()),
+            20_000_000 - MOCK_FEE
+        );
+        assert_eq!(
+            chain.balance_of(&address(&ctx, 0), base_asset()),
+            main_before + 10_000_000
+        );
+
+        // The sweep is neither income nor spending
+        let stats = funding_cycle(
+            &ctx,
+            &main_wallet,
+            &chain,
+            Duration::from_secs(1),
+            &settings,
+            &mut in_flight,
+            &mut velocity,
+            &mut activity,
+            3,
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.inbound_transfers, 0);
+        assert_eq!(stats.wallets_swept, 0);
+    }
+
+    #[tokio::test]
+    async fn restored_top_up_is_not_sent_again() {
+        let storage: &'static MemoryStorage = Box::leak(Box::default());
+        let mut ctx = test_context(3, base_asset());
+        ctx.sinks.storage = Some(storage);
+        let chain = funded_chain(&ctx);
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+
+        // A previous run sent wallet 2 a top-up and stopped before it confirmed
+        let tx_id = TxId::from([9u8; 32]);
+        chain.set_pending(tx_id);
+        InFlight::new(ctx.in_flight_timeout)
+            .sent(
+                &ctx,
+                TransferRecord::new(
+                    "cont-fund",
+                    Some(2),
+                    address(&ctx, 0),
+                    address(&ctx, 2),
+                    base_asset(),
+                    DEFAULT_THRESHOLD,
+                    tx_id,
+                ),
+            )
+            .await;
+
+        let mut in_flight = InFlight::new(ctx.in_flight_timeout);
+        in_flight.restore(&ctx).await;
+        let stats = funding_cycle(
+            &ctx,
+            &main_wallet,
+            &chain,
+            Duration::from_secs(1),
+            &test_settings(3),
+            &mut in_flight,
+            &mut Velocity::default(),
+            &mut Activity::default(),
+            1,
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.wallets_pending, 1);
+        assert_eq!(stats.wallets_funded, 0);
+        assert!(chain.transfers().is_empty());
+
+        // Once it lands it is recorded and no longer journaled
+        chain.set_balance(&address(&ctx, 2), base_asset(), 1_000 + DEFAULT_THRESHOLD);
+        chain.confirm(&tx_id);
+        let stats = funding_cycle(
+            &ctx,
+            &main_wallet,
+            &chain,
+            Duration::from_secs(1),
+            &test_settings(3),
+            &mut in_flight,
+            &mut Velocity::default(),
+            &mut Activity::default(),
+            1,
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.wallets_funded, 0);
+        assert_eq!(in_flight.pending(), 0);
+        assert_eq!(storage.transfers.lock().unwrap().len(), 1);
+        assert!(storage.state.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn resubmits_stuck_top_ups() {
+        let mut ctx = test_context(3, base_asset());
+        ctx.in_flight_timeout = Duration::ZERO;
+        ctx.resubmit_tip = Some(500);
+        let chain = funded_chain(&ctx);
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+
+        // Wallet 2's top-up from an earlier cycle never made it into a block
+        let tx_id = TxId::from([9u8; 32]);
+        chain.set_pending(tx_id);
+        let mut in_flight = InFlight::new(ctx.in_flight_timeout);
+        in_flight.track(vec![(
+            tx_id,
+            TransferRecord::new(
+                "cont-fund",
+                Some(2),
+                address(&ctx, 0),
+                address(&ctx, 2),
+                base_asset(),
+                DEFAULT_THRESHOLD,
+                tx_id,
+            ),
+        )]);
+
+        let stats = funding_cycle(
+            &ctx,
+            &main_wallet,
+            &chain,
+            Duration::from_secs(1),
+            &test_settings(3),
+            &mut in_flight,
+            &mut Velocity::default(),
+            &mut Activity::default(),
+            1,
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.stuck_transactions, 1);
+        assert_eq!(stats.wallets_pending, 1);
+        assert_eq!(stats.wallets_funded, 0);
+        assert_eq!(in_flight.pending(), 1);
+        let transfers = chain.transfers();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].to, address(&ctx, 2));
+        assert_eq!(transfers[0].amount, DEFAULT_THRESHOLD);
+    }
+
+    #[tokio::test]
+    async fn labels_top_ups_with_the_cycle_unless_overridden() {
+        let storage: &'static MemoryStorage = Box::leak(Box::default());
+        let mut ctx = test_context(3, base_asset());
+        ctx.sinks.storage = Some(storage);
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+
+        for (cycle, label) in [(142, None), (143, Some("manual"))] {
+            ctx.sinks.label = label.map(str::to_string);
+            funding_cycle(
+                &ctx,
+                &main_wallet,
+                &funded_chain(&ctx),
+                Duration::from_secs(1),
+                &test_settings(3),
+                &mut InFlight::new(ctx.in_flight_timeout),
+                &mut Velocity::default(),
+                &mut Activity::default(),
+                cycle,
+            )
+            .await
+            .unwrap();
+        }
+
+        let labels: Vec<_> = storage
+            .transfers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|t| t.label.clone().unwrap())
+            .collect();
+        assert_eq!(labels, ["topup-cycle-142", "manual"]);
+    }
+
+    async fn run_cycle(
+        ctx: &Context<'_>,
+        main_wallet: &Funder,
+        chain: &MockChain,
+        settings: &FundingSettings,
+    ) -> CycleStats {
+        funding_cycle(
+            ctx,
+            main_wallet,
+            chain,
+            Duration::from_secs(1),
+            settings,
+            &mut InFlight::new(ctx.in_flight_timeout),
+            &mut Velocity::default(),
+            &mut Activity::default(),
+            1,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn sends_gas_ahead_of_other_asset_top_ups() {
+        let ctx = test_context(3, other_asset());
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        let chain_with_main_gas = |gas: u64| {
+            let chain = MockChain::default();
+            chain.set_balance(&address(&ctx, 0), base_asset(), gas);
+            chain.set_balance(&address(&ctx, 0), other_asset(), 100_000_000);
+            chain.set_balance(&address(&ctx, 1), other_asset(), 1_000);
+            chain.set_balance(&address(&ctx, 2), other_asset(), 1_000);
+            chain.set_balance(&address(&ctx, 2), base_asset(), DEFAULT_GAS_RESERVE);
+            chain
+        };
+
+        // Wallet 1 has no gas and gets the reserve first; wallet 2 already holds it
+        let chain = chain_with_main_gas(100_000_000);
+        let stats = run_cycle(&ctx, &main_wallet, &chain, &test_settings(3)).await;
+        assert_eq!(stats.wallets_funded, 2);
+        assert_eq!(stats.gas_top_ups, 1);
+        assert_eq!(
+            chain.balance_of(&address(&ctx, 1), base_asset()),
+            DEFAULT_GAS_RESERVE
+        );
+        assert_eq!(
+            chain.balance_of(&address(&ctx, 1), other_asset()),
+            1_000 + DEFAULT_THRESHOLD
+        );
+        assert_eq!(
+            chain.balance_of(&address(&ctx, 2), base_asset()),
+            DEFAULT_GAS_RESERVE
+        );
+
+        // A main wallet that cannot pay for gas sends nothing
+        let chain = chain_with_main_gas(50_000);
+        let stats = run_cycle(&ctx, &main_wallet, &chain, &test_settings(3)).await;
+        assert_eq!(stats.wallets_funded, 0);
+        assert_eq!(stats.wallets_without_gas, 2);
+        assert!(chain.transfers().is_empty());
+
+        // AUTO_GAS_TOP_UP=false leaves gas alone
+        let settings = FundingSettings {
+            gas_reserve: None,
+            ..test_settings(3)
+        };
+        let chain = chain_with_main_gas(100_000_000);
+        let stats = run_cycle(&ctx, &main_wallet, &chain, &settings).await;
+        assert_eq!(stats.wallets_funded, 2);
+        assert_eq!(chain.balance_of(&address(&ctx, 1), base_asset()), 0);
+    }
+}
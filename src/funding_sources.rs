@@ -0,0 +1,123 @@
+use crate::{
+    signer::Funder,
+    wallets::{Derivation, Fleet},
+};
+use fuels::accounts::provider::Provider;
+use std::{collections::HashMap, env, error::Error, ops::RangeInclusive};
+use tracing::debug;
+
+/// Assignment of HD wallet index ranges to the derivation index of the
+/// wallet that funds them. Wallets outside every range are funded by the
+/// main wallet (index 0).
+#[derive(Debug, Default)]
+pub struct FundingSources {
+    ranges: Vec<(RangeInclusive<usize>, usize)>,
+}
+
+impl FundingSources {
+    /// Parses `FUNDING_SOURCES`, e.g. `0-9:1000,10-49:1001`.
+    pub fn from_env(number_of_wallets: usize) -> Result<Self, Box<dyn Error>> {
+        match env::var("FUNDING_SOURCES") {
+            Ok(spec) => Self::parse(&spec, number_of_wallets, &Derivation::from_env()?),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Parses a `FUNDING_SOURCES` value.
+    pub fn parse(
+        spec: &str,
+        number_of_wallets: usize,
+        derivation: &Derivation,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut ranges: Vec<(RangeInclusive<usize>, usize)> = Vec::new();
+
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let invalid = || format!("Invalid FUNDING_SOURCES entry '{}'", entry);
+            let (range, source) = entry.split_once(':').ok_or_else(invalid)?;
+            let (start, end) = range.split_once('-').unwrap_or((range, range));
+            let start = start.trim().parse::<usize>().map_err(|_| invalid())?;
+            let end = end.trim().parse::<usize>().map_err(|_| invalid())?;
+            let source = source.trim().parse::<usize>().map_err(|_| invalid())?;
+
+            if start > end {
+                return Err(format!("{}: range start is after its end", invalid()).into());
+            }
+            if let Some(index) = derivation
+                .hd_index(source)
+                .filter(|index| *index < number_of_wallets)
+            {
+                return Err(format!(
+                    "{}: source index {} is itself HD wallet {}",
+                    invalid(),
+                    source,
+                    index
+                )
+                .into());
+            }
+            if let Some((other, _)) = ranges
+                .iter()
+                .find(|(other, _)| start <= *other.end() && *other.start() <= end)
+            {
+                return Err(format!(
+                    "{}: overlaps range {}-{}",
+                    invalid(),
+                    other.start(),
+                    other.end()
+                )
+                .into());
+            }
+
+            ranges.push((start..=end, source));
+        }
+
+        Ok(Self { ranges })
+    }
+
+    /// Whether no ranges are configured, so the main wallet funds every HD wallet.
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Derivation index funding the given HD wallet, if not the main wallet.
+    pub fn source_for(&self, wallet_index: usize) -> Option<usize> {
+        self.ranges
+            .iter()
+            .find(|(range, _)| range.contains(&wallet_index))
+            .map(|(_, source)| *source)
+    }
+
+    /// Derives every configured source wallet, keyed by derivation index.
+    pub fn derive(
+        &self,
+        fleet: &Fleet,
+        provider: Option<Provider>,
+    ) -> Result<HashMap<usize, Funder>, Box<dyn Error>> {
+        let mut wallets = HashMap::new();
+        for (range, source) in &self.ranges {
+            if !wallets.contains_key(source) {
+                let wallet = fleet.wallet_at(*source, provider.clone())?;
+                debug!(
+                    "Funding source for wallets {}-{}: index {} ({})",
+                    range.start(),
+                    range.end(),
+                    source,
+                    wallet.address()
+                );
+                wallets.insert(*source, Funder::from(wallet));
+            }
+        }
+        Ok(wallets)
+    }
+
+    /// Picks the wallet that funds `wallet_index`, defaulting to `main_wallet`.
+    pub fn wallet_for<'a>(
+        &self,
+        wallet_index: usize,
+        main_wallet: &'a Funder,
+        sources: &'a HashMap<usize, Funder>,
+    ) -> &'a Funder {
+        self.source_for(wallet_index)
+            .and_then(|source| sources.get(&source))
+            .unwrap_or(main_wallet)
+    }
+}
@@ -0,0 +1,148 @@
+//! Gas for continual funding of a non-base asset: before a top-up, the sending wallet
+//! must be able to pay its fee, and the receiving wallet must hold enough base asset to
+//! use what it receives. Missing gas is sent ahead of the top-up through the same
+//! pipeline, so the top-up waits for it like any transfer waits for its change.
+
+use crate::{
+    chain::ChainClient, pipeline::Pipeline, signer::Funder, storage::TransferRecord, units,
+};
+use fuels::types::{bech32::Bech32Address, AssetId};
+use std::{collections::HashMap, error::Error};
+use tracing::{info, warn};
+
+/// Command recorded for gas sent by continual funding.
+const COMMAND: &str = "cont-fund-gas";
+
+/// Gas top-ups of one funding cycle.
+pub struct GasTopUps<'c> {
+    client: &'c dyn ChainClient,
+    base_asset: AssetId,
+    /// Base asset every wallet, sender or receiver, keeps for fees (`GAS_RESERVE`).
+    reserve: u64,
+    /// Base asset of each sender as of its first use this cycle, less the gas it has
+    /// sent since and plus the gas it has been sent, which may not show yet.
+    available: HashMap<Bech32Address, u64>,
+    /// Earlier transfers the pipeline confirmed while gas was being submitted.
+    confirmed: Vec<TransferRecord>,
+    /// Gas transfers submitted this cycle.
+    pub sent: usize,
+}
+
+impl<'c> GasTopUps<'c> {
+    pub fn new(client: &'c dyn ChainClient, reserve: u64) -> Self {
+        Self {
+            client,
+            base_asset: client.base_asset(),
+            reserve,
+            available: HashMap::new(),
+            confirmed: Vec::new(),
+            sent: 0,
+        }
+    }
+
+    /// Makes sure `source` can pay for a top-up of HD wallet `hd_wallet_number` and
+    /// that the wallet keeps the reserve, sending gas from `source` to the wallet and,
+    /// for a funding source HD wallet (`source_index`), from `main_wallet` to `source`.
+    /// Returns false if the gas cannot be found, in which case the top-up is skipped.
+    pub async fn prepare(
+        &mut self,
+        pipeline: &mut Pipeline<'_, '_>,
+        main_wallet: &Funder,
+        source: &Funder,
+        source_index: Option<usize>,
+        hd_wallet_number: usize,
+        wallet_address: &Bech32Address,
+    ) -> Result<bool, Box<dyn Error>> {
+        let gas_balance = self
+            .client
+            .balance(wallet_address, &self.base_asset)
+            .await?;
+        let needed = self.reserve.saturating_sub(gas_balance);
+        // The source sends the missing gas and keeps the reserve for its own fees
+        let required = needed + self.reserve;
+
+        let available = self.available(source).await?;
+        if available < required {
+            let Some(source_index) = source_index else {
+                warn!(
+                    "Main wallet holds {}, not enough to pay for gas of HD Wallet {}; skipping.",
+                    units::format_fee(available.into()),
+                    hd_wallet_number
+                );
+                return Ok(false);
+            };
+            let shortfall = required - available;
+            if self.available(main_wallet).await? < shortfall + self.reserve {
+                warn!(
+                    "Neither funding source {} nor the main wallet can pay for gas of HD Wallet {}; skipping.",
+                    source_index, hd_wallet_number
+                );
+                return Ok(false);
+            }
+            info!(
+                "Sending {} for gas to funding source {}.",
+                units::format_fee(shortfall.into()),
+                source_index
+            );
+            self.send(
+                pipeline,
+                source_index,
+                main_wallet,
+                source.address(),
+                shortfall,
+            )
+            .await?;
+        }
+
+        if needed > 0 {
+            info!(
+                "Sending {} for gas to HD Wallet {}.",
+                units::format_fee(needed.into()),
+                hd_wallet_number
+            );
+            self.send(pipeline, hd_wallet_number, source, wallet_address, needed)
+                .await?;
+        }
+        Ok(true)
+    }
+
+    /// Records of transfers that confirmed while gas was submitted, as returned by
+    /// [`Pipeline::submit`].
+    pub fn take_confirmed(&mut self) -> Vec<TransferRecord> {
+        std::mem::take(&mut self.confirmed)
+    }
+
+    async fn available(&mut self, wallet: &Funder) -> Result<u64, Box<dyn Error>> {
+        if let Some(available) = self.available.get(wallet.address()) {
+            return Ok(*available);
+        }
+        let balance = self
+            .client
+            .balance(wallet.address(), &self.base_asset)
+            .await?;
+        self.available.insert(wallet.address().clone(), balance);
+        Ok(balance)
+    }
+
+    async fn send(
+        &mut self,
+        pipeline: &mut Pipeline<'_, '_>,
+        wallet_index: usize,
+        from: &Funder,
+        to: &Bech32Address,
+        amount: u64,
+    ) -> Result<(), Box<dyn Error>> {
+        let confirmed = pipeline
+            .submit(COMMAND, wallet_index, from, to, amount, &self.base_asset)
+            .await?;
+        self.confirmed.extend(confirmed);
+        if let Some(available) = self.available.get_mut(from.address()) {
+            *available = available.saturating_sub(amount);
+        }
+        if let Some(available) = self.available.get_mut(to) {
+            *available += amount;
+        }
+        self.sent += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+use crate::schedule::Schedule;
+use fuels::types::AssetId;
+use serde::Deserialize;
+use std::{env, error::Error, fs, ops::RangeInclusive, str::FromStr};
+
+/// A named range of HD wallets with its own funding policy, selected with `--group`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalletGroup {
+    pub name: String,
+    pub wallets: RangeInclusive<usize>,
+    /// The first asset is distributed by the funding commands; reclaim sweeps all of them.
+    pub assets: Vec<AssetId>,
+    /// `FUNDING_THRESHOLD` for the group, in `ASSET_DECIMALS` or base units.
+    pub threshold: Option<String>,
+    /// `TOP_UP_AMOUNT` for the group.
+    pub top_up_amount: Option<String>,
+    /// `MAX_WALLET_BALANCE` for the group.
+    pub max_balance: Option<String>,
+    /// `FUND_SCHEDULE` for the group.
+    pub schedule: Option<Schedule>,
+}
+
+#[derive(Deserialize)]
+struct GroupEntry {
+    name: String,
+    /// Index range such as `0-9`, or a single index.
+    wallets: String,
+    #[serde(default)]
+    assets: Vec<String>,
+    threshold: Option<String>,
+    top_up_amount: Option<String>,
+    max_balance: Option<String>,
+    schedule: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GroupsFile {
+    groups: Vec<GroupEntry>,
+}
+
+/// Loads the group called `name` from the JSON file named by `WALLET_GROUPS`.
+pub fn load(name: &str) -> Result<WalletGroup, Box<dyn Error>> {
+    let path = env::var("WALLET_GROUPS")
+        .map_err(|_| "--group needs WALLET_GROUPS to name a groups file")?;
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read wallet groups {}: {}", path, e))?;
+    let mut groups = parse(&contents).map_err(|e| format!("Wallet groups {}: {}", path, e))?;
+    match groups.iter().position(|group| group.name == name) {
+        Some(index) => Ok(groups.swap_remove(index)),
+        None => Err(format!(
+            "No wallet group '{}' in {} (groups: {})",
+            name,
+            path,
+            groups
+                .iter()
+                .map(|group| group.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+        .into()),
+    }
+}
+
+fn parse(contents: &str) -> Result<Vec<WalletGroup>, Box<dyn Error>> {
+    let file: GroupsFile = serde_json::from_str(contents)?;
+    let mut groups: Vec<WalletGroup> = Vec::new();
+    for entry in file.groups {
+        let invalid = |what: String| format!("group '{}': {}", entry.name, what);
+
+        let (start, end) = entry
+            .wallets
+            .split_once('-')
+            .unwrap_or((&entry.wallets, &entry.wallets));
+        let (start, end) = match (start.trim().parse::<usize>(), end.trim().parse::<usize>()) {
+            (Ok(start), Ok(end)) if start <= end => (start, end),
+            _ => return Err(invalid(format!("invalid wallet range '{}'", entry.wallets)).into()),
+        };
+        if let Some(other) = groups.iter().find(|other| {
+            other.name == entry.name
+                || (start <= *other.wallets.end() && *other.wallets.start() <= end)
+        }) {
+            return Err(invalid(format!("overlaps group '{}'", other.name)).into());
+        }
+
+        let assets = entry
+            .assets
+            .iter()
+            .map(|asset| {
+                AssetId::from_str(asset)
+                    .map_err(|_| invalid(format!("invalid asset id '{}'", asset)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let schedule = entry
+            .schedule
+            .as_deref()
+            .map(str::parse::<Schedule>)
+            .transpose()
+            .map_err(invalid)?;
+
+        groups.push(WalletGroup {
+            name: entry.name.clone(),
+            wallets: start..=end,
+            assets,
+            threshold: entry.threshold,
+            top_up_amount: entry.top_up_amount,
+            max_balance: entry.max_balance,
+            schedule,
+        });
+    }
+    Ok(groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn parses_groups_and_rejects_overlaps() {
+        let groups = parse(
+            r#"{"groups": [
+                {"name": "market_makers", "wallets": "0-9", "threshold": "0.01",
+                 "max_balance": "0.05", "schedule": "every 10s"},
+                {"name": "arbitrage", "wallets": "10-49",
+                 "assets": ["0x0101010101010101010101010101010101010101010101010101010101010101"]}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(groups[0].wallets, 0..=9);
+        assert_eq!(groups[0].threshold.as_deref(), Some("0.01"));
+        assert_eq!(groups[0].max_balance.as_deref(), Some("0.05"));
+        assert_eq!(
+            groups[0].schedule,
+            Some(Schedule::Every(Duration::from_secs(10)))
+        );
+        assert_eq!(groups[1].wallets, 10..=49);
+        assert_eq!(groups[1].assets, vec![AssetId::from([1u8; 32])]);
+
+        assert!(parse(
+            r#"{"groups": [{"name": "a", "wallets": "0-9"}, {"name": "b", "wallets": "9-20"}]}"#
+        )
+        .is_err());
+        assert!(parse(r#"{"groups": [{"name": "a", "wallets": "9-0"}]}"#).is_err());
+    }
+}
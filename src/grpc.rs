@@ -0,0 +1,134 @@
+//! gRPC control interface (`proto/distributor.proto`), served next to the HTTP API
+//! and forwarding to the same supervisor.
+
+use crate::{
+    recipients::parse_address,
+    server::{ask, bearer_matches, ApiError, Control, JobKind},
+};
+use axum::http::StatusCode;
+use std::net::SocketAddr;
+use tokio::sync::mpsc;
+use tonic::{transport::Server, Request, Response, Status};
+use tracing::error;
+
+pub mod proto {
+    tonic::include_proto!("fund_distributor.v1");
+}
+
+use proto::{
+    distributor_server::{Distributor, DistributorServer},
+    GetStatusRequest, GetStatusResponse, Job, ReclaimRequest, ReclaimResponse, StartFundingRequest,
+    StartFundingResponse, StopFundingRequest, StopFundingResponse, TriggerDistributionRequest,
+    TriggerDistributionResponse,
+};
+
+struct DistributorService {
+    control: mpsc::Sender<Control>,
+}
+
+impl From<ApiError> for Status {
+    fn from(error: ApiError) -> Self {
+        match error.status {
+            StatusCode::CONFLICT => Status::failed_precondition(error.message),
+            StatusCode::UNAUTHORIZED => Status::unauthenticated(error.message),
+            StatusCode::SERVICE_UNAVAILABLE => Status::unavailable(error.message),
+            _ => Status::internal(error.message),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Distributor for DistributorService {
+    async fn start_funding(
+        &self,
+        _request: Request<StartFundingRequest>,
+    ) -> Result<Response<StartFundingResponse>, Status> {
+        ask(&self.control, Control::StartFunding).await?;
+        Ok(Response::new(StartFundingResponse {}))
+    }
+
+    async fn stop_funding(
+        &self,
+        _request: Request<StopFundingRequest>,
+    ) -> Result<Response<StopFundingResponse>, Status> {
+        ask(&self.control, Control::StopFunding).await?;
+        Ok(Response::new(StopFundingResponse {}))
+    }
+
+    async fn get_status(
+        &self,
+        _request: Request<GetStatusRequest>,
+    ) -> Result<Response<GetStatusResponse>, Status> {
+        let status = ask(&self.control, Control::Status).await?;
+        let job = match status.job {
+            None => Job::Unspecified,
+            Some(JobKind::Distribution) => Job::Distribution,
+            Some(JobKind::ContinualFunding) => Job::ContinualFunding,
+            Some(JobKind::Reclaim) => Job::Reclaim,
+        };
+        Ok(Response::new(GetStatusResponse {
+            job: job.into(),
+            last_result: status.last_result,
+            seconds_since_last_cycle: status.seconds_since_last_cycle,
+            idle_wallets: status
+                .idle_wallets
+                .into_iter()
+                .map(|index| index as u64)
+                .collect(),
+        }))
+    }
+
+    async fn trigger_distribution(
+        &self,
+        _request: Request<TriggerDistributionRequest>,
+    ) -> Result<Response<TriggerDistributionResponse>, Status> {
+        ask(&self.control, Control::Distribute).await?;
+        Ok(Response::new(TriggerDistributionResponse {}))
+    }
+
+    async fn reclaim(
+        &self,
+        request: Request<ReclaimRequest>,
+    ) -> Result<Response<ReclaimResponse>, Status> {
+        let request = request.into_inner();
+        let to = request
+            .to
+            .as_deref()
+            .map(parse_address)
+            .transpose()
+            .map_err(Status::invalid_argument)?;
+        ask(&self.control, |reply| Control::Reclaim {
+            prefund_gas: request.prefund_gas,
+            to,
+            reply,
+        })
+        .await?;
+        Ok(Response::new(ReclaimResponse {}))
+    }
+}
+
+/// Serves the gRPC interface on `addr`, requiring `authorization: Bearer <token>`
+/// metadata when a token is set.
+pub async fn serve(addr: SocketAddr, control: mpsc::Sender<Control>, token: Option<String>) {
+    let service = DistributorServer::with_interceptor(
+        DistributorService { control },
+        move |request: Request<()>| match &token {
+            Some(token) => {
+                let provided = request
+                    .metadata()
+                    .get("authorization")
+                    .and_then(|value| value.to_str().ok());
+                if bearer_matches(provided, token) {
+                    Ok(request)
+                } else {
+                    Err(Status::unauthenticated("invalid API token"))
+                }
+            }
+            None => Ok(request),
+        },
+    );
+
+    if let Err(e) = Server::builder().add_service(service).serve(addr).await {
+        error!("gRPC server failed: {}", e);
+    }
+}
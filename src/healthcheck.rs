@@ -0,0 +1,100 @@
+//! `healthcheck`: a one-shot probe for Kubernetes liveness/readiness checks or
+//! Nagios. It prints a JSON result and the process exits 0 if healthy, 1 if not.
+
+use crate::{
+    daemon::unix_now,
+    manifest::redact_url,
+    network::Network,
+    provider_pool::ProviderPool,
+    refill::RefillNeeded,
+    signer::{ExternalWallet, Funder},
+    wallets::Fleet,
+};
+use fuels::types::AssetId;
+use serde::Serialize;
+use std::{error::Error, time::Instant};
+
+/// Result of a health check. Fields the check did not get to are `null`.
+#[derive(Debug, Default, Serialize)]
+pub struct HealthReport {
+    pub healthy: bool,
+    /// Provider URL with credentials and query string removed.
+    pub provider: Option<String>,
+    /// Time to connect and answer a block height query, in milliseconds.
+    pub provider_latency_ms: Option<u64>,
+    pub block_height: Option<u32>,
+    pub main_wallet: Option<String>,
+    pub asset_id: String,
+    /// Main wallet balance of `asset_id`, in base units.
+    pub balance: Option<u64>,
+    /// Balance below which the main wallet counts as unhealthy, in base units.
+    pub reserve: u64,
+    /// How much to send the main wallet, and where, when it is below the reserve.
+    pub refill: Option<RefillNeeded>,
+    /// Why the check failed.
+    pub error: Option<String>,
+    pub checked_at: u64,
+}
+
+/// Connects to the first healthy provider, then checks that the main wallet (wallet 0,
+/// or `MAIN_SIGNER`'s address) holds at least `reserve` of `asset_id`, which has `decimals`.
+pub async fn healthcheck(
+    fleet: &Fleet,
+    network: Option<Network>,
+    asset_id: AssetId,
+    decimals: u32,
+    reserve: u64,
+) -> HealthReport {
+    let mut report = HealthReport {
+        asset_id: asset_id.to_string(),
+        reserve,
+        checked_at: unix_now(),
+        ..HealthReport::default()
+    };
+    if let Err(e) = check(&mut report, fleet, network, asset_id, decimals).await {
+        report.error = Some(e.to_string());
+    }
+    report
+}
+
+async fn check(
+    report: &mut HealthReport,
+    fleet: &Fleet,
+    network: Option<Network>,
+    asset_id: AssetId,
+    decimals: u32,
+) -> Result<(), Box<dyn Error>> {
+    let mut pool = ProviderPool::from_env(network)?;
+    let started = Instant::now();
+    let provider = pool.connect().await?;
+    report.provider = Some(redact_url(pool.current_url()));
+    report.block_height = Some(provider.latest_block_height().await?);
+    report.provider_latency_ms = Some(started.elapsed().as_millis() as u64);
+
+    let main_wallet = match ExternalWallet::from_env(provider.clone()).await? {
+        Some(wallet) => Funder::External(wallet),
+        None => Funder::from(fleet.wallet(0, None)?),
+    };
+    report.main_wallet = Some(main_wallet.address().to_string());
+    let balance = provider
+        .get_asset_balance(main_wallet.address(), asset_id)
+        .await?;
+    report.balance = Some(balance);
+    report.refill = RefillNeeded::check(
+        main_wallet.address(),
+        asset_id,
+        decimals,
+        balance,
+        report.reserve,
+    );
+
+    if balance < report.reserve {
+        return Err(format!(
+            "main wallet balance {} is below the reserve of {}",
+            balance, report.reserve
+        )
+        .into());
+    }
+    report.healthy = true;
+    Ok(())
+}
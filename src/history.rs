@@ -0,0 +1,183 @@
+use crate::{
+    addresses::ExportFormat,
+    assets,
+    manifest::Manifest,
+    report::ReportWriter,
+    storage::{Storage, TransferFilter},
+};
+use fuels::types::AssetId;
+use std::{collections::HashSet, error::Error, str::FromStr};
+
+/// Prints the transfers matching `filter` to stdout, headed by `manifest`, followed by
+/// per-asset totals on stderr.
+pub async fn print_history(
+    storage: &dyn Storage,
+    filter: &TransferFilter,
+    format: ExportFormat,
+    manifest: &Manifest,
+) -> Result<(), Box<dyn Error>> {
+    let transfers = storage.transfers(filter).await?;
+
+    let mut report = ReportWriter::new(std::io::stdout(), format, Some(manifest), "transfers")?;
+    let mut totals = std::collections::BTreeMap::<&str, u128>::new();
+    for transfer in &transfers {
+        report.write(transfer)?;
+        *totals.entry(&transfer.asset_id).or_default() += u128::from(transfer.amount);
+    }
+    report.finish()?;
+
+    eprintln!("{} transfers", transfers.len());
+    for (asset_id, total) in totals {
+        let total = match AssetId::from_str(asset_id) {
+            Ok(id) => assets::format(total, &id),
+            Err(_) => total.to_string(),
+        };
+        eprintln!("  {}: {}", asset_id, total);
+    }
+
+    Ok(())
+}
+
+/// Result of [`merge_history`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct MergeSummary {
+    /// Transfers copied into the output.
+    pub merged: usize,
+    /// Transfers skipped because the output (or an earlier input) already had them.
+    pub duplicates: usize,
+}
+
+/// Copies the transfers of every input into `output`, oldest first, skipping any whose
+/// tx id and sequence number are already present. Merging the same databases again is a
+/// no-op, so hosts can be merged incrementally into one consolidated history.
+pub async fn merge_history(
+    inputs: &[Box<dyn Storage>],
+    output: &dyn Storage,
+) -> Result<MergeSummary, Box<dyn Error>> {
+    let all = TransferFilter::default();
+    let mut seen: HashSet<(String, Option<u64>)> = output
+        .transfers(&all)
+        .await?
+        .into_iter()
+        .map(|t| (t.tx_id, t.sequence))
+        .collect();
+
+    let mut transfers = Vec::new();
+    let mut duplicates = 0;
+    for input in inputs {
+        for transfer in input.transfers(&all).await? {
+            if seen.insert((transfer.tx_id.clone(), transfer.sequence)) {
+                transfers.push(transfer);
+            } else {
+                duplicates += 1;
+            }
+        }
+    }
+
+    // Stable, so transfers with equal timestamps keep each host's own order
+    transfers.sort_by_key(|t| t.timestamp);
+    for transfer in &transfers {
+        output.record_transfer(transfer).await?;
+    }
+
+    Ok(MergeSummary {
+        merged: transfers.len(),
+        duplicates,
+    })
+}
+
+/// Storage URL for a `history merge` argument: a URL is used as is, anything else is
+/// taken as the path of a SQLite file.
+pub fn storage_url(arg: &str) -> String {
+    if arg.contains("://") {
+        arg.to_string()
+    } else {
+        format!("sqlite://{}", arg)
+    }
+}
+
+/// Parses a history time bound given as unix seconds or a `YYYY-MM-DD` date (UTC midnight).
+pub fn parse_time(value: &str) -> Result<u64, String> {
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Ok(seconds);
+    }
+
+    let invalid = || {
+        format!(
+            "Invalid time '{}': expected unix seconds or YYYY-MM-DD",
+            value
+        )
+    };
+    let mut parts = value.splitn(3, '-');
+    let (year, month, day) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(y), Some(m), Some(d)) => (
+            y.parse::<i64>().map_err(|_| invalid())?,
+            m.parse::<i64>().map_err(|_| invalid())?,
+            d.parse::<i64>().map_err(|_| invalid())?,
+        ),
+        _ => return Err(invalid()),
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || year < 1970 {
+        return Err(invalid());
+    }
+
+    // Days since the unix epoch for a proleptic Gregorian date (Howard Hinnant's algorithm)
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    Ok(days as u64 * 86_400)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{mock::MemoryStorage, TransferRecord};
+
+    fn transfer(tx_id: &str, sequence: u64, timestamp: u64) -> TransferRecord {
+        TransferRecord {
+            timestamp,
+            sequence: Some(sequence),
+            ..TransferRecord::new("cont-fund", Some(0), "from", "to", "asset", 1, tx_id)
+        }
+    }
+
+    fn storage(transfers: Vec<TransferRecord>) -> Box<dyn Storage> {
+        Box::new(MemoryStorage::with_transfers(transfers))
+    }
+
+    #[tokio::test]
+    async fn merges_histories_without_duplicates() {
+        // Host b is a failover replica that also holds a copy of host a's first transfer
+        let a = storage(vec![transfer("0xaa", 1, 100), transfer("0xab", 2, 300)]);
+        let b = storage(vec![transfer("0xaa", 1, 100), transfer("0xba", 1, 200)]);
+        let output = MemoryStorage::default();
+
+        let summary = merge_history(&[a, b], &output).await.unwrap();
+        assert_eq!(
+            summary,
+            MergeSummary {
+                merged: 3,
+                duplicates: 1
+            }
+        );
+        let merged: Vec<_> = output
+            .transfers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|t| t.tx_id.clone())
+            .collect();
+        assert_eq!(merged, ["0xaa", "0xba", "0xab"]);
+
+        // Merging again adds nothing
+        let a = storage(vec![transfer("0xaa", 1, 100), transfer("0xab", 2, 300)]);
+        let summary = merge_history(&[a], &output).await.unwrap();
+        assert_eq!(summary.merged, 0);
+        assert_eq!(summary.duplicates, 2);
+    }
+}
@@ -0,0 +1,485 @@
+use crate::{
+    chain::{ChainClient, Confirmation},
+    context::Context,
+    daemon::unix_now,
+    signer::Funder,
+    storage::TransferRecord,
+    transfer::verify_credit,
+};
+use fuels::types::{bech32::Bech32Address, AssetId, TxId};
+use std::{
+    collections::HashMap,
+    error::Error,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+/// State key holding the top-ups continual funding submitted that are not known to have
+/// confirmed, so a restarted process checks them before funding those wallets again.
+const UNCONFIRMED_TOP_UPS: &str = "cont-fund.unconfirmed";
+
+struct PendingTopUp {
+    tx_id: TxId,
+    record: TransferRecord,
+    since: Instant,
+    /// Times the top-up was sent again with a higher tip (`--resubmit-tip`).
+    resubmissions: u32,
+}
+
+/// Top-ups that were submitted but not confirmed by the end of their cycle,
+/// kept across cycles so the same wallet is not funded twice.
+pub struct InFlight {
+    pending: HashMap<u64, PendingTopUp>,
+    /// Every submitted top-up not yet confirmed, by wallet index, including those still
+    /// in the current cycle's pipeline. Mirrored to storage under [`UNCONFIRMED_TOP_UPS`].
+    journal: HashMap<u64, TransferRecord>,
+    timeout: Duration,
+    /// Top-ups found stuck past the timeout since [`InFlight::take_stuck`] was last called.
+    stuck: usize,
+}
+
+impl InFlight {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            pending: HashMap::new(),
+            journal: HashMap::new(),
+            timeout,
+            stuck: 0,
+        }
+    }
+
+    /// Tracks the journaled top-ups of a previous run (or of a cycle the watchdog
+    /// cancelled) as pending, so their wallets are only funded again once the chain
+    /// shows they failed or they have been unconfirmed for longer than the timeout.
+    pub async fn restore(&mut self, ctx: &Context<'_>) {
+        if let Some(storage) = ctx.sinks.storage {
+            let saved = match storage.get_state(&ctx.state_key(UNCONFIRMED_TOP_UPS)).await {
+                Ok(saved) => saved,
+                Err(e) => {
+                    warn!("Failed to load unconfirmed top-ups from storage: {}", e);
+                    None
+                }
+            };
+            if let Some(saved) = saved {
+                match serde_json::from_str::<Vec<TransferRecord>>(&saved) {
+                    Ok(records) => {
+                        for record in records {
+                            if let Some(wallet_index) = record.wallet_index {
+                                self.journal.entry(wallet_index).or_insert(record);
+                            }
+                        }
+                    }
+                    Err(e) => warn!("Ignoring unreadable unconfirmed top-ups in storage: {}", e),
+                }
+            }
+        }
+
+        let now = unix_now();
+        let mut restored = 0;
+        for (wallet_index, record) in &self.journal {
+            if self.pending.contains_key(wallet_index) {
+                continue;
+            }
+            let tx_id = match TxId::from_str(&record.tx_id) {
+                Ok(tx_id) => tx_id,
+                Err(e) => {
+                    warn!("Ignoring unconfirmed top-up {}: {}", record.tx_id, e);
+                    continue;
+                }
+            };
+            // Age the top-up by the time since it was sent, not since it was restored
+            let age = Duration::from_secs(now.saturating_sub(record.timestamp));
+            self.pending.insert(
+                *wallet_index,
+                PendingTopUp {
+                    tx_id,
+                    record: record.clone(),
+                    since: Instant::now().checked_sub(age).unwrap_or_else(Instant::now),
+                    resubmissions: 0,
+                },
+            );
+            restored += 1;
+        }
+        if restored > 0 {
+            info!(
+                "Restored {} unconfirmed top-ups, checking them before funding those wallets.",
+                restored
+            );
+        }
+    }
+
+    /// Journals a top-up as soon as it is submitted.
+    pub async fn sent(&mut self, ctx: &Context<'_>, record: TransferRecord) {
+        if let Some(wallet_index) = record.wallet_index {
+            self.journal.insert(wallet_index, record);
+            self.persist(ctx).await;
+        }
+    }
+
+    /// Removes top-ups that confirmed (and were reported) from the journal.
+    pub async fn settled(&mut self, ctx: &Context<'_>, confirmed: &[TransferRecord]) {
+        let before = self.journal.len();
+        for record in confirmed {
+            if let Some(wallet_index) = record.wallet_index {
+                if self
+                    .journal
+                    .get(&wallet_index)
+                    .is_some_and(|journaled| journaled.tx_id == record.tx_id)
+                {
+                    self.journal.remove(&wallet_index);
+                }
+            }
+        }
+        if self.journal.len() != before {
+            self.persist(ctx).await;
+        }
+    }
+
+    /// Writes the journal to storage, if configured. Failures are reported but do not
+    /// stop funding, which then only guards against double-funding within this process.
+    async fn persist(&self, ctx: &Context<'_>) {
+        let Some(storage) = ctx.sinks.storage else {
+            return;
+        };
+        let result = if self.journal.is_empty() {
+            storage
+                .delete_state(&ctx.state_key(UNCONFIRMED_TOP_UPS))
+                .await
+        } else {
+            match serde_json::to_string(&self.journal.values().collect::<Vec<_>>()) {
+                Ok(json) => {
+                    storage
+                        .set_state(&ctx.state_key(UNCONFIRMED_TOP_UPS), &json)
+                        .await
+                }
+                Err(e) => Err(e.into()),
+            }
+        };
+        if let Err(e) = result {
+            warn!("Failed to save unconfirmed top-ups to storage: {}", e);
+        }
+    }
+
+    /// Number of top-ups still awaiting confirmation.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Number of top-ups found stuck since the last call.
+    pub fn take_stuck(&mut self) -> usize {
+        std::mem::take(&mut self.stuck)
+    }
+
+    /// Starts tracking transfers the pipeline gave up waiting for.
+    pub fn track(&mut self, unconfirmed: Vec<(TxId, TransferRecord)>) {
+        for (tx_id, record) in unconfirmed {
+            if let Some(wallet_index) = record.wallet_index {
+                self.pending.insert(
+                    wallet_index,
+                    PendingTopUp {
+                        tx_id,
+                        record,
+                        since: Instant::now(),
+                        resubmissions: 0,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Whether `wallet_index` still has an unconfirmed top-up and must be skipped.
+    ///
+    /// A top-up that has landed is reported to the sinks and forgotten; one that
+    /// failed is forgotten so the wallet can be funded again. One still pending after
+    /// the timeout is stuck: it is alerted on and, with `ctx.resubmit_tip`, sent again
+    /// from `source` with a higher tip, else forgotten like a failed one.
+    pub async fn blocks(
+        &mut self,
+        ctx: &Context<'_>,
+        client: &dyn ChainClient,
+        wallet_index: usize,
+        source: &Funder,
+    ) -> Result<bool, Box<dyn Error>> {
+        let key = wallet_index as u64;
+        let Some(pending) = self.pending.get(&key) else {
+            return Ok(false);
+        };
+
+        match client.confirmation(&pending.tx_id).await? {
+            Confirmation::Pending if pending.since.elapsed() < self.timeout => {
+                info!(
+                    "HD Wallet {} has an unconfirmed top-up {:?}, skipping.",
+                    wallet_index, pending.tx_id
+                );
+                return Ok(true);
+            }
+            Confirmation::Pending => {
+                let stuck = self.pending.remove(&key).expect("pending top-up was found");
+                let pending_secs = stuck.since.elapsed().as_secs();
+                warn!(
+                    "Top-up {:?} of HD Wallet {} still unconfirmed after {}s.",
+                    stuck.tx_id, wallet_index, pending_secs
+                );
+                self.stuck += 1;
+
+                let resubmitted = match ctx.resubmit_tip {
+                    Some(tip) => match resubmit(ctx, client, source, &stuck, tip).await {
+                        Ok(resubmitted) => Some(resubmitted),
+                        Err(e) => {
+                            warn!(
+                                "Could not resubmit top-up {:?} of HD Wallet {}: {}",
+                                stuck.tx_id, wallet_index, e
+                            );
+                            None
+                        }
+                    },
+                    None => None,
+                };
+                ctx.sinks
+                    .transaction_stuck(
+                        &stuck.record,
+                        pending_secs,
+                        resubmitted.as_ref().map(|resubmitted| &resubmitted.tx_id),
+                    )
+                    .await;
+                if let Some(resubmitted) = resubmitted {
+                    self.journal.insert(key, resubmitted.record.clone());
+                    self.pending.insert(key, resubmitted);
+                    self.persist(ctx).await;
+                    return Ok(true);
+                }
+                warn!("Funding HD Wallet {} again.", wallet_index);
+            }
+            Confirmation::Confirmed { fee } => {
+                info!(
+                    "Top-up {:?} of HD Wallet {} confirmed.",
+                    pending.tx_id, wallet_index
+                );
+                verify_credit(ctx, client, &pending.record).await;
+                ctx.sinks
+                    .transfer_confirmed(pending.record.clone(), fee)
+                    .await;
+            }
+            Confirmation::Failed(reason) => warn!(
+                "Top-up {:?} of HD Wallet {} did not succeed ({}), funding again.",
+                pending.tx_id, wallet_index, reason
+            ),
+        }
+
+        self.pending.remove(&key);
+        self.journal.remove(&key);
+        self.persist(ctx).await;
+        Ok(false)
+    }
+}
+
+/// Sends a stuck top-up again from `source`, with `tip` doubled for every earlier
+/// resubmission. The stuck transaction may still land as well, as a new top-up would.
+async fn resubmit(
+    ctx: &Context<'_>,
+    client: &dyn ChainClient,
+    source: &Funder,
+    stuck: &PendingTopUp,
+    tip: u64,
+) -> Result<PendingTopUp, Box<dyn Error>> {
+    let record = &stuck.record;
+    if record.from_address != source.address().to_string() {
+        return Err("its funding wallet has changed".into());
+    }
+    let to_address = Bech32Address::from_str(&record.to_address)?;
+    let asset_id = AssetId::from_str(&record.asset_id)?;
+    let tip = tip.saturating_mul(1 << stuck.resubmissions.min(16));
+
+    let tx_id = client
+        .submit_transfer(
+            source,
+            &to_address,
+            record.amount,
+            &asset_id,
+            ctx.tx_policies.with_tip(tip),
+        )
+        .await?;
+    info!(
+        "Resubmitted top-up {:?} with a tip of {} as {:?}.",
+        stuck.tx_id, tip, tx_id
+    );
+
+    let mut record = record.clone();
+    record.tx_id = tx_id.to_string();
+    record.timestamp = unix_now();
+    Ok(PendingTopUp {
+        tx_id,
+        record,
+        since: Instant::now(),
+        resubmissions: stuck.resubmissions + 1,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chain::mock::{address, base_asset, test_context, MockChain},
+        storage::mock::MemoryStorage,
+    };
+
+    /// Submits a top-up of wallet `index` from wallet 0 and returns its record.
+    async fn top_up(ctx: &Context<'_>, chain: &MockChain, index: usize) -> TransferRecord {
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        let tx_id = chain
+            .submit_transfer(
+                &main_wallet,
+                &address(ctx, index),
+                10_000,
+                &base_asset(),
+                ctx.tx_policies,
+            )
+            .await
+            .unwrap();
+        TransferRecord::new(
+            "cont-fund",
+            Some(index),
+            address(ctx, 0),
+            address(ctx, index),
+            base_asset(),
+            10_000,
+            tx_id,
+        )
+    }
+
+    fn journaled(storage: &MemoryStorage) -> Option<String> {
+        storage
+            .state
+            .lock()
+            .unwrap()
+            .get(UNCONFIRMED_TOP_UPS)
+            .cloned()
+    }
+
+    #[tokio::test]
+    async fn journal_survives_a_restart() {
+        let storage: &'static MemoryStorage = Box::leak(Box::default());
+        let mut ctx = test_context(3, base_asset());
+        ctx.sinks.storage = Some(storage);
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(main_wallet.address(), base_asset(), 100_000);
+
+        let mut in_flight = InFlight::new(ctx.in_flight_timeout);
+        for index in 1..3 {
+            let record = top_up(&ctx, &chain, index).await;
+            chain.set_pending(TxId::from_str(&record.tx_id).unwrap());
+            in_flight.sent(&ctx, record).await;
+        }
+        drop(in_flight);
+
+        // A new process picks both top-ups up from storage and holds their wallets
+        let mut restarted = InFlight::new(ctx.in_flight_timeout);
+        restarted.restore(&ctx).await;
+        assert_eq!(restarted.pending(), 2);
+        for index in 1..3 {
+            assert!(restarted
+                .blocks(&ctx, &chain, index, &main_wallet)
+                .await
+                .unwrap());
+        }
+        assert!(!restarted
+            .blocks(&ctx, &chain, 0, &main_wallet)
+            .await
+            .unwrap());
+        assert_eq!(chain.transfers().len(), 2);
+
+        // Restoring twice does not track a top-up twice
+        restarted.restore(&ctx).await;
+        assert_eq!(restarted.pending(), 2);
+    }
+
+    #[tokio::test]
+    async fn confirmed_and_expired_top_ups_leave_the_journal() {
+        let storage: &'static MemoryStorage = Box::leak(Box::default());
+        let mut ctx = test_context(3, base_asset());
+        ctx.sinks.storage = Some(storage);
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(main_wallet.address(), base_asset(), 100_000);
+
+        let confirmed = top_up(&ctx, &chain, 1).await;
+        let mut stuck = top_up(&ctx, &chain, 2).await;
+        chain.set_pending(TxId::from_str(&stuck.tx_id).unwrap());
+        // Sent by a previous run longer ago than the timeout
+        stuck.timestamp = unix_now() - ctx.in_flight_timeout.as_secs() - 1;
+
+        let mut in_flight = InFlight::new(ctx.in_flight_timeout);
+        in_flight.sent(&ctx, confirmed.clone()).await;
+        in_flight.sent(&ctx, stuck).await;
+
+        // Settling another transaction of the same wallet keeps its entry
+        let mut other = confirmed.clone();
+        other.tx_id = TxId::from([7u8; 32]).to_string();
+        in_flight.settled(&ctx, &[other]).await;
+        let mut restarted = InFlight::new(ctx.in_flight_timeout);
+        restarted.restore(&ctx).await;
+        assert_eq!(restarted.pending(), 2);
+
+        // The confirmed top-up is forgotten and its wallet can be funded again
+        assert!(!restarted
+            .blocks(&ctx, &chain, 1, &main_wallet)
+            .await
+            .unwrap());
+        // The one pending past the timeout is stuck and, without a resubmit tip, forgotten
+        assert!(!restarted
+            .blocks(&ctx, &chain, 2, &main_wallet)
+            .await
+            .unwrap());
+        assert_eq!(restarted.take_stuck(), 1);
+        assert_eq!(restarted.pending(), 0);
+        assert_eq!(journaled(storage), None);
+
+        // Top-ups settled at the end of their cycle leave the journal as well
+        let mut in_flight = InFlight::new(ctx.in_flight_timeout);
+        in_flight.sent(&ctx, confirmed.clone()).await;
+        assert!(journaled(storage).is_some());
+        in_flight.settled(&ctx, &[confirmed]).await;
+        assert_eq!(journaled(storage), None);
+    }
+
+    #[tokio::test]
+    async fn ignores_a_corrupt_or_partial_journal() {
+        let storage: &'static MemoryStorage = Box::leak(Box::default());
+        let mut ctx = test_context(3, base_asset());
+        ctx.sinks.storage = Some(storage);
+        let chain = MockChain::default();
+        chain.set_balance(&address(&ctx, 0), base_asset(), 100_000);
+        let record = top_up(&ctx, &chain, 1).await;
+        let json = serde_json::to_string(&vec![&record]).unwrap();
+
+        // A journal cut off mid-write, or not JSON at all, restores nothing
+        for saved in [&json[..json.len() / 2], "not json"] {
+            storage
+                .state
+                .lock()
+                .unwrap()
+                .insert(UNCONFIRMED_TOP_UPS.to_string(), saved.to_string());
+            let mut in_flight = InFlight::new(ctx.in_flight_timeout);
+            in_flight.restore(&ctx).await;
+            assert_eq!(in_flight.pending(), 0);
+        }
+
+        // Entries without a wallet or with an unreadable transaction id are skipped
+        let mut unindexed = record.clone();
+        unindexed.wallet_index = None;
+        let mut unreadable = record.clone();
+        unreadable.wallet_index = Some(2);
+        unreadable.tx_id = "0xnot-a-tx-id".to_string();
+        let saved = serde_json::to_string(&vec![unindexed, unreadable, record]).unwrap();
+        storage
+            .state
+            .lock()
+            .unwrap()
+            .insert(UNCONFIRMED_TOP_UPS.to_string(), saved);
+        let mut in_flight = InFlight::new(ctx.in_flight_timeout);
+        in_flight.restore(&ctx).await;
+        assert_eq!(in_flight.pending(), 1);
+    }
+}
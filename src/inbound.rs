@@ -0,0 +1,98 @@
+use fuels::{
+    accounts::provider::Provider,
+    client::{PageDirection, PaginationRequest},
+    tx::Output,
+    types::{
+        bech32::Bech32Address,
+        transaction::{Transaction, TransactionType},
+        tx_status::TxStatus,
+        Address, AssetId,
+    },
+};
+use std::error::Error;
+
+/// Number of the wallet's most recent transactions inspected per check.
+const PAGE_SIZE: i32 = 20;
+
+/// Settings for skipping wallets that were recently funded by someone else.
+#[derive(Debug, Clone, Copy)]
+pub struct InboundCheck {
+    /// How many of the latest blocks count as "recent".
+    pub lookback_blocks: u32,
+    /// Smallest inbound transfer that makes a top-up unnecessary; defaults to the
+    /// amount the top-up itself would send.
+    pub min_amount: Option<u64>,
+}
+
+/// An inbound transfer found by [`recent_inbound`].
+pub struct Inbound {
+    pub amount: u64,
+    pub block_height: u32,
+}
+
+/// Looks for a successful transfer of at least `min_amount` of `asset_id` to
+/// `address` within the last `lookback_blocks` blocks, from any sender.
+pub async fn recent_inbound(
+    provider: &Provider,
+    address: &Bech32Address,
+    asset_id: AssetId,
+    min_amount: u64,
+    lookback_blocks: u32,
+) -> Result<Option<Inbound>, Box<dyn Error>> {
+    let latest = provider.latest_block_height().await?;
+    let cutoff = latest.saturating_sub(lookback_blocks);
+    let owner = Address::from(address);
+
+    let page = provider
+        .get_transactions_by_owner(
+            address,
+            PaginationRequest {
+                cursor: None,
+                results: PAGE_SIZE,
+                direction: PageDirection::Backward,
+            },
+        )
+        .await?;
+
+    // Newest first, so the scan can stop at the first transaction before the cutoff
+    for response in page.results {
+        let Some(block_id) = response.block_id else {
+            continue;
+        };
+        let Some(block) = provider.block(&block_id).await? else {
+            continue;
+        };
+        let block_height = block.header.height;
+        if block_height < cutoff {
+            break;
+        }
+        if !matches!(response.status, TxStatus::Success { .. }) {
+            continue;
+        }
+
+        let TransactionType::Script(tx) = response.transaction else {
+            continue;
+        };
+        let amount: u64 = tx
+            .outputs()
+            .iter()
+            .filter_map(|output| match output {
+                Output::Coin {
+                    to,
+                    amount,
+                    asset_id: output_asset,
+                } if *to == owner && *output_asset == asset_id => Some(*amount),
+                _ => None,
+            })
+            .sum();
+
+        if amount >= min_amount {
+            return Ok(Some(Inbound {
+                amount,
+                block_height,
+            }));
+        }
+    }
+
+    Ok(None)
+}
@@ -0,0 +1,189 @@
+use crate::{
+    chain::{balances_of, ChainClient},
+    context::Context,
+    signer::Funder,
+    units,
+};
+use serde::Serialize;
+use std::error::Error;
+
+/// Assertions checked after every continual funding cycle, to catch logic bugs
+/// and outside interference (`INVARIANT_*`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Invariants {
+    /// Largest allowed difference, in base units, between the total held by the
+    /// funders and HD wallets and the previous cycle's total minus the fees paid
+    /// in between (`INVARIANT_MAX_DRIFT`).
+    pub max_drift: Option<u64>,
+    /// Balance no HD wallet may exceed, in base units (`INVARIANT_WALLET_CAP`).
+    pub wallet_cap: Option<u64>,
+    /// Stop topping up after a violation until the next reload (`INVARIANT_PAUSE`).
+    pub pause: bool,
+}
+
+impl Invariants {
+    pub fn is_enabled(&self) -> bool {
+        self.max_drift.is_some() || self.wallet_cap.is_some()
+    }
+}
+
+/// A failed invariant.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Violation {
+    /// `max_drift` or `wallet_cap`.
+    pub invariant: &'static str,
+    pub detail: String,
+}
+
+/// Balances of the distributed asset at the end of a cycle.
+#[derive(Debug)]
+pub struct Snapshot {
+    /// Per HD wallet, by index.
+    wallets: Vec<u64>,
+    /// Main wallet plus every funding source wallet.
+    funders: u128,
+}
+
+impl Snapshot {
+    fn total(&self) -> u128 {
+        self.funders + self.wallets.iter().map(|b| u128::from(*b)).sum::<u128>()
+    }
+}
+
+/// Reads the balances the invariants are evaluated on.
+pub async fn snapshot(
+    ctx: &Context<'_>,
+    main_wallet: &Funder,
+    client: &dyn ChainClient,
+    number_of_wallets: usize,
+) -> Result<Snapshot, Box<dyn Error>> {
+    let mut funders = u128::from(client.balance(main_wallet.address(), &ctx.asset_id).await?);
+    for source in ctx
+        .funding_sources
+        .derive(&ctx.fleet, client.provider())?
+        .values()
+    {
+        funders += u128::from(client.balance(source.address(), &ctx.asset_id).await?);
+    }
+
+    // Excluded wallets are counted as empty: their balances are outside our control
+    let mut indices = Vec::with_capacity(number_of_wallets);
+    let mut addresses = Vec::with_capacity(number_of_wallets);
+    for index in 0..number_of_wallets {
+        if !ctx.is_excluded(index)? {
+            indices.push(index);
+            addresses.push(ctx.fleet.wallet(index, None)?.address().clone());
+        }
+    }
+    let balances = balances_of(
+        client,
+        &addresses,
+        &ctx.asset_id,
+        ctx.balance_concurrency,
+        None,
+    )
+    .await?;
+    let mut wallets = vec![0; number_of_wallets];
+    for (index, balance) in indices.into_iter().zip(balances) {
+        wallets[index] = balance;
+    }
+    Ok(Snapshot { wallets, funders })
+}
+
+/// Evaluates `invariants` on `current`. Drift is only checked against a `previous`
+/// snapshot of the same wallets; `fees` are those paid in the distributed asset since.
+pub fn evaluate(
+    invariants: &Invariants,
+    previous: Option<&Snapshot>,
+    current: &Snapshot,
+    fees: u64,
+    decimals: u32,
+) -> Vec<Violation> {
+    let amount = |a: u128| units::format_amount(a, decimals);
+    let mut violations = Vec::new();
+
+    if let (Some(max_drift), Some(previous)) = (invariants.max_drift, previous) {
+        if previous.wallets.len() == current.wallets.len() {
+            let expected = previous.total().saturating_sub(u128::from(fees));
+            let total = current.total();
+            if total.abs_diff(expected) > u128::from(max_drift) {
+                violations.push(Violation {
+                    invariant: "max_drift",
+                    detail: format!(
+                        "total balance is {}, expected {} (previous {} minus {} fees) within {}",
+                        amount(total),
+                        amount(expected),
+                        amount(previous.total()),
+                        amount(u128::from(fees)),
+                        amount(u128::from(max_drift))
+                    ),
+                });
+            }
+        }
+    }
+
+    if let Some(cap) = invariants.wallet_cap {
+        for (index, balance) in current.wallets.iter().enumerate() {
+            if *balance > cap {
+                violations.push(Violation {
+                    invariant: "wallet_cap",
+                    detail: format!(
+                        "HD wallet {} holds {}, above the cap of {}",
+                        index,
+                        amount(u128::from(*balance)),
+                        amount(u128::from(cap))
+                    ),
+                });
+            }
+        }
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(wallets: Vec<u64>, funders: u128) -> Snapshot {
+        Snapshot { wallets, funders }
+    }
+
+    #[test]
+    fn detects_drift_beyond_fees() {
+        let invariants = Invariants {
+            max_drift: Some(10),
+            ..Invariants::default()
+        };
+        let previous = snapshot(vec![100, 100], 1_000);
+
+        // A top-up of 50 that paid 5 in fees moves funds without changing the total
+        let current = snapshot(vec![150, 100], 945);
+        assert!(evaluate(&invariants, Some(&previous), &current, 5, 0).is_empty());
+
+        // 200 left the fleet without a recorded top-up
+        let current = snapshot(vec![100, 100], 800);
+        let violations = evaluate(&invariants, Some(&previous), &current, 0, 0);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].invariant, "max_drift");
+
+        // No baseline yet
+        assert!(evaluate(&invariants, None, &current, 0, 0).is_empty());
+    }
+
+    #[test]
+    fn detects_wallets_above_cap() {
+        let invariants = Invariants {
+            wallet_cap: Some(120),
+            ..Invariants::default()
+        };
+        let violations = evaluate(&invariants, None, &snapshot(vec![100, 150], 0), 0, 0);
+        assert_eq!(
+            violations,
+            vec![Violation {
+                invariant: "wallet_cap",
+                detail: "HD wallet 1 holds 150, above the cap of 120".to_string(),
+            }]
+        );
+    }
+}
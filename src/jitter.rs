@@ -0,0 +1,86 @@
+//! `--jitter` and `--jitter-delay`: randomness in what the HD wallets are sent and
+//! when, so the fleet does not stand out on-chain as many wallets receiving the
+//! same amount at evenly spaced moments. Each amount `--init-dist` and `--cont-fund`
+//! send is drawn uniformly within `--jitter` of its nominal value, and every
+//! transfer of the pipeline first waits a random time up to `--jitter-delay`.
+
+use rand::Rng;
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// Largest `--jitter`, in basis points: beyond it a top-up could be next to nothing.
+pub const MAX_BASIS_POINTS: u64 = 5_000;
+
+/// How far amounts and timing are spread. The default spreads nothing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Jitter {
+    /// Largest deviation of an amount from its nominal value, in basis points of it.
+    pub basis_points: u64,
+    /// Longest random wait before a transfer.
+    pub max_delay: Duration,
+}
+
+impl Jitter {
+    /// `amount`, moved up or down by a random part of `basis_points` of it.
+    pub fn amount(&self, amount: u64) -> u64 {
+        let spread = self.spread(amount);
+        if spread == 0 {
+            return amount;
+        }
+        rand::thread_rng().gen_range(amount - spread..=amount.saturating_add(spread))
+    }
+
+    /// The most [`Jitter::amount`] returns for `amount`, for checks that have to
+    /// cover whatever is drawn.
+    pub fn max_amount(&self, amount: u64) -> u64 {
+        amount.saturating_add(self.spread(amount))
+    }
+
+    /// A jittered top-up of nominally `amount` that never falls short of `shortfall`
+    /// (what brings the wallet to its threshold) unless `amount` itself does, so
+    /// jitter never leaves a wallet below the threshold.
+    pub fn top_up(&self, amount: u64, shortfall: u64) -> u64 {
+        self.amount(amount).max(amount.min(shortfall))
+    }
+
+    /// Waits a random time up to `max_delay`.
+    pub async fn pause(&self) {
+        if self.max_delay.is_zero() {
+            return;
+        }
+        let millis = rand::thread_rng().gen_range(0..=self.max_delay.as_millis() as u64);
+        sleep(Duration::from_millis(millis)).await;
+    }
+
+    fn spread(&self, amount: u64) -> u64 {
+        (u128::from(amount) * u128::from(self.basis_points) / 10_000) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spreads_amounts_within_bounds() {
+        let none = Jitter::default();
+        assert_eq!(none.amount(5_000_000), 5_000_000);
+        assert_eq!(none.top_up(5_000_000, 7_000_000), 5_000_000);
+
+        let jitter = Jitter {
+            basis_points: 1_000,
+            max_delay: Duration::ZERO,
+        };
+        assert_eq!(jitter.max_amount(5_000_000), 5_500_000);
+        let amounts: Vec<u64> = (0..100).map(|_| jitter.amount(5_000_000)).collect();
+        assert!(amounts
+            .iter()
+            .all(|amount| (4_500_000..=5_500_000).contains(amount)));
+        assert!(amounts.iter().any(|amount| *amount != amounts[0]));
+
+        // Never below what brings the wallet to its threshold
+        assert!((0..100).all(|_| jitter.top_up(5_000_000, 4_900_000) >= 4_900_000));
+        // Unless the nominal top-up does not either
+        assert!((0..100).all(|_| jitter.top_up(5_000_000, 9_000_000) >= 5_000_000));
+    }
+}
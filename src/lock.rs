@@ -0,0 +1,202 @@
+//! Lock file that keeps two `--init-dist` or `--cont-fund` runs with the same main
+//! wallet from spending it at once. The lock is keyed by the main wallet, so fleets
+//! sharing one main wallet share its lock.
+
+use crate::daemon::unix_now;
+use fuels::types::{bech32::Bech32Address, Address};
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+
+/// The run holding a lock, as written to the lock file.
+#[derive(Debug, Serialize, Deserialize)]
+struct Holder {
+    pid: u32,
+    command: String,
+    started_at: u64,
+}
+
+/// A lock on one main wallet, released when dropped.
+pub struct RunLock {
+    path: PathBuf,
+}
+
+impl RunLock {
+    /// Takes the lock for `main_wallet` in `dir` on behalf of `command`. A lock left by
+    /// another run is an error, unless `force` breaks it.
+    pub fn acquire(
+        dir: &Path,
+        main_wallet: &Bech32Address,
+        command: &str,
+        force: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        let key = format!("{:x}", Address::from(main_wallet));
+        let path = dir.join(format!("fund_distributor-{}.lock", &key[..16]));
+        let holder = Holder {
+            pid: std::process::id(),
+            command: command.to_string(),
+            started_at: unix_now(),
+        };
+
+        let mut broken = false;
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    file.write_all(serde_json::to_string(&holder)?.as_bytes())?;
+                    return Ok(Self { path });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    let seen = fs::read(&path).ok();
+                    let existing = describe_holder(seen.as_deref());
+                    if broken {
+                        // Broken at most once: this lock is a run's that started meanwhile
+                        return Err(taken_meanwhile(&path, &existing));
+                    }
+                    if !force {
+                        return Err(format!(
+                            "Another run for main wallet {} holds the lock {}: {}. If it is no longer running, pass --force to break the lock.",
+                            main_wallet,
+                            path.display(),
+                            existing
+                        )
+                        .into());
+                    }
+                    warn!(
+                        "Breaking the lock {} held by {} (--force).",
+                        path.display(),
+                        existing
+                    );
+                    broken = true;
+                    if let Some(seen) = seen {
+                        break_lock(&path, &seen)?;
+                    }
+                }
+                Err(e) => {
+                    return Err(format!("Failed to create lock {}: {}", path.display(), e).into())
+                }
+            }
+        }
+    }
+}
+
+impl RunLock {
+    /// The lock file, for [`release`] by a process about to exit without dropping it.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        release(&self.path);
+    }
+}
+
+/// Removes the lock file at `path` if this process holds it.
+pub fn release(path: &Path) {
+    let holder = fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| serde_json::from_str::<Holder>(&contents).ok());
+    if holder.is_some_and(|holder| holder.pid == std::process::id()) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// Removes the lock at `path`, but only if it still is `seen`, the one reported as
+/// its holder: the file is first moved aside, which only one process can do, and put
+/// back if it turns out to be a lock another run took in the meantime.
+fn break_lock(path: &Path, seen: &[u8]) -> Result<(), Box<dyn Error>> {
+    let aside = path.with_extension(format!("lock.broken-{}", std::process::id()));
+    match fs::rename(path, &aside) {
+        Ok(()) => {}
+        // Another run broke or released it first
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(format!("Failed to break lock {}: {}", path.display(), e).into()),
+    }
+    let moved = fs::read(&aside)?;
+    if moved == seen {
+        fs::remove_file(&aside)?;
+        return Ok(());
+    }
+    // Put the newer run's lock back, unless yet another run holds the path by now
+    let _ = fs::hard_link(&aside, path);
+    let _ = fs::remove_file(&aside);
+    Err(taken_meanwhile(path, &describe_holder(Some(&moved))))
+}
+
+fn taken_meanwhile(path: &Path, holder: &str) -> Box<dyn Error> {
+    format!(
+        "Another run took the lock {} while it was being broken: {}",
+        path.display(),
+        holder
+    )
+    .into()
+}
+
+/// Who holds a lock with `contents`, and whether it is still running where that can
+/// be told.
+fn describe_holder(contents: Option<&[u8]>) -> String {
+    let holder = contents.and_then(|contents| serde_json::from_slice::<Holder>(contents).ok());
+    let Some(holder) = holder else {
+        return "an unknown run".to_string();
+    };
+    let status = if cfg!(target_os = "linux") && Path::new("/proc/self").exists() {
+        if Path::new("/proc").join(holder.pid.to_string()).exists() {
+            ", still running on this host"
+        } else {
+            ", no longer running on this host"
+        }
+    } else {
+        ""
+    };
+    format!(
+        "{} (pid {}, started at {}{})",
+        holder.command, holder.pid, holder.started_at, status
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fuels::types::bech32::FUEL_BECH32_HRP;
+
+    #[test]
+    fn refuses_a_second_run_unless_forced() {
+        let dir =
+            std::env::temp_dir().join(format!("fund_distributor-lock-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let main_wallet = Bech32Address::new(FUEL_BECH32_HRP, [3u8; 32]);
+
+        let lock = RunLock::acquire(&dir, &main_wallet, "cont-fund", false).unwrap();
+        let err = RunLock::acquire(&dir, &main_wallet, "init-dist", false)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("cont-fund"));
+
+        // Another fleet is not affected
+        let other = Bech32Address::new(FUEL_BECH32_HRP, [4u8; 32]);
+        drop(RunLock::acquire(&dir, &other, "cont-fund", false).unwrap());
+
+        let forced = RunLock::acquire(&dir, &main_wallet, "init-dist", true).unwrap();
+        std::mem::forget(lock);
+        // Only a lock this process holds is released
+        fs::write(
+            &forced.path,
+            r#"{"pid":0,"command":"cont-fund","started_at":0}"#,
+        )
+        .unwrap();
+        release(forced.path());
+        assert!(forced.path().exists());
+        drop(forced);
+        assert!(RunLock::acquire(&dir, &main_wallet, "init-dist", false).is_err());
+        let forced = RunLock::acquire(&dir, &main_wallet, "init-dist", true).unwrap();
+        drop(forced);
+        assert!(RunLock::acquire(&dir, &main_wallet, "init-dist", false).is_ok());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,66 @@
+use crate::{
+    progress::{self, LogWriter},
+    telemetry,
+};
+use clap::{Args, ValueEnum};
+use std::error::Error;
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+/// Logging options shared by all commands.
+#[derive(Args, Debug)]
+pub struct LogArgs {
+    /// Only log warnings and errors.
+    #[clap(long, short = 'q', conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Log more detail (-v for debug, -vv for trace).
+    #[clap(long, short = 'v', parse(from_occurrences))]
+    pub verbose: u8,
+
+    /// Log line format.
+    #[clap(
+        long = "log-format",
+        value_enum,
+        env = "LOG_FORMAT",
+        default_value = "text"
+    )]
+    pub log_format: LogFormat,
+
+    /// Show a progress bar for --init-dist and --reclaim instead of per-wallet log
+    /// lines (only warnings and errors are logged, unless -v or RUST_LOG ask for more).
+    #[clap(long, conflicts_with = "quiet")]
+    pub progress: bool,
+}
+
+/// Installs the global tracing subscriber, exporting traces as well if configured
+/// (see [`telemetry`]). `RUST_LOG` takes precedence over `--quiet`/`--verbose` when set.
+pub fn init(args: &LogArgs) -> Result<(), Box<dyn Error>> {
+    let default_level = match (args.quiet, args.verbose) {
+        (true, _) => "warn",
+        (false, 0) if args.progress => "warn",
+        (false, 0) => "info",
+        (false, 1) => "debug",
+        (false, _) => "trace",
+    };
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default_level));
+
+    if args.progress {
+        progress::enable();
+    }
+    let logs = match args.log_format {
+        LogFormat::Text => fmt::layer().with_writer(LogWriter).boxed(),
+        LogFormat::Json => fmt::layer().json().with_writer(LogWriter).boxed(),
+    };
+    tracing_subscriber::registry()
+        .with(logs.with_filter(filter))
+        .with(telemetry::layer()?)
+        .init();
+    Ok(())
+}
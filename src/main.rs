@@ -1,13 +1,144 @@
-use clap::Parser;
+mod activity;
+mod address_book;
+mod addresses;
+mod approval;
+mod assets;
+mod audit;
+mod bus;
+mod capabilities;
+mod chain;
+mod coins;
+mod context;
+mod daemon;
+#[cfg(feature = "api")]
+mod dashboard;
+#[cfg(feature = "disperser")]
+mod disperser;
+mod distribute;
+mod exclusions;
+mod export;
+mod failure;
+mod fast;
+mod faucet;
+mod fleets;
+mod fund;
+mod funding_sources;
+mod gas;
+mod groups;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod healthcheck;
+mod history;
+mod in_flight;
+mod inbound;
+mod invariants;
+mod jitter;
+mod lock;
+mod logging;
+mod manifest;
+mod mnemonic;
+mod network;
+mod pipeline;
+mod plan;
+mod predicate;
+mod price;
+mod profiles;
+mod progress;
+mod provider_pool;
+mod queue;
+#[cfg(feature = "api")]
+mod rate_limit;
+mod recipients;
+mod reclaim;
+mod refill;
+mod relay;
+mod report;
+mod rotate;
+mod schedule;
+#[cfg(feature = "api")]
+mod server;
+mod service;
+mod signer;
+#[cfg(feature = "simulate")]
+mod simulate;
+mod single_wallet;
+mod sinks;
+mod storage;
+mod telemetry;
+mod tor;
+mod transfer;
+mod units;
+mod vault;
+mod velocity;
+mod verify;
+mod wallets;
+
+use address_book::AddressBook;
+use addresses::{derive_address, export_addresses, export_manifest, verify_manifest, ExportFormat};
+use approval::ApprovalPolicy;
+use capabilities::Capabilities;
+use chain::{ChainClient, TimeoutClient};
+use clap::{Parser, Subcommand};
+use context::Context;
+use daemon::{notify_systemd, serve_health, shutdown_signal, HealthState, PidFile};
+#[cfg(feature = "disperser")]
+use disperser::Disperser;
+#[cfg(feature = "disperser")]
+use distribute::disperse_initial_distribution;
+use distribute::initial_distribution;
 use dotenv::dotenv;
-use fuels::prelude::TxPolicies;
-use fuels::types::bech32::Bech32Address;
+use exclusions::Exclusions;
+use export::AccountingFormat;
+use faucet::Faucet;
+use fleets::FleetConfig;
 use fuels::{
-    accounts::{provider::Provider, wallet::WalletUnlocked, Account},
-    types::AssetId,
+    accounts::provider::Provider,
+    types::{bech32::Bech32Address, AssetId},
+};
+use fund::{continual_funding, FundingSettings};
+use funding_sources::FundingSources;
+use groups::WalletGroup;
+use history::print_history;
+use inbound::InboundCheck;
+use jitter::Jitter;
+use lock::RunLock;
+use logging::LogArgs;
+use manifest::Manifest;
+use mnemonic::MnemonicArgs;
+use network::Network;
+use plan::{
+    drift_check, execute_plan, plan_continual_funding, plan_initial_distribution, print_plan,
+    TransferPlan,
+};
+use predicate::ReclaimPredicate;
+use price::PriceOracle;
+use provider_pool::ProviderPool;
+#[cfg(feature = "disperser")]
+use recipients::disperse_to_recipients;
+use recipients::{distribute_to_recipients, validate_recipients};
+use reclaim::{
+    plan_reclaim, reclaim_funds, AssetSelection, GasPolicy, ReclaimOptions, WalletSelection,
 };
-use std::{env, error::Error, str::FromStr, time::Duration};
-use tokio::time::sleep;
+use refill::RefillNeeded;
+use secrecy::SecretString;
+use service::ServiceSpec;
+use signer::{ExternalWallet, Funder};
+use sinks::{Sinks, Webhook};
+use std::{
+    env,
+    error::Error,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::RwLock,
+    time::Duration,
+};
+use storage::TransferFilter;
+use tokio::net::TcpListener;
+use tracing::{error, info, info_span, warn, Instrument, Span};
+use transfer::TxPolicyArgs;
+use vault::Vault;
+use wallets::{Derivation, Fleet};
 
 /// CLI tool for managing Fuel HD wallets.
 #[derive(Parser)]
@@ -20,285 +151,2154 @@ struct Cli {
     #[clap(long = "init-dist", conflicts_with_all = &["cont_fund", "reclaim"])]
     init_dist: bool,
 
-    /// Monitor wallets every 20 seconds and fund if balance is below 0.005 ETH.
+    /// Monitor wallets every 20 seconds (or on FUND_SCHEDULE) and fund if balance is below 0.005 ETH.
     #[clap(long = "cont-fund", conflicts_with_all = &["init_dist", "reclaim"])]
     cont_fund: bool,
 
+    /// Run a single --cont-fund cycle and exit: 0 if no wallet needed funding, 2 if
+    /// wallets were funded, 3 if the main wallet is left below its reserve, 1 on failure.
+    #[clap(long = "once", requires = "cont_fund", conflicts_with_all = &["daemon", "plan_only"])]
+    once: bool,
+
+    /// Fund wallets within a block or two of dropping below the threshold: follow
+    /// every block and keep balances in memory, reading them all on FUND_SCHEDULE.
+    #[clap(long = "fast", requires = "cont_fund", conflicts_with_all = &["once", "plan_only"])]
+    fast: bool,
+
+    /// Fund --init-dist and --cont-fund through the FUNDING_SOURCES wallets as hubs:
+    /// the main wallet first sends each hub what it is about to relay, plus fees.
+    #[clap(
+        long = "relay",
+        conflicts_with_all = &["fast", "disperser", "plan_only", "execute_plan"]
+    )]
+    relay: bool,
+
+    /// Vary each amount --init-dist and --cont-fund send by up to this percent of it
+    /// (e.g. 10%), so the HD wallets do not all receive identical amounts.
+    #[clap(long = "jitter", env = "JITTER")]
+    jitter: Option<String>,
+
+    /// Wait a random time of up to this many seconds before each transfer, so they
+    /// are not evenly spaced on-chain.
+    #[clap(long = "jitter-delay", env = "JITTER_DELAY_SECS", default_value = "0")]
+    jitter_delay: u64,
+
     /// Reclaim all funds from HD wallets back to the main wallet.
     #[clap(long = "reclaim", conflicts_with_all = &["init_dist", "cont_fund"])]
     reclaim: bool,
+
+    /// Pay the external addresses of a recipients file (CSV `address,amount[,memo]`,
+    /// or JSON if it ends in .json) from the main wallet, in ETH_ASSET_ID.
+    #[clap(
+        long = "recipients",
+        conflicts_with_all = &["init_dist", "cont_fund", "reclaim", "execute_plan", "plan_only"]
+    )]
+    recipients: Option<PathBuf>,
+
+    /// Pay --init-dist or --recipients through the disperser contract at
+    /// DISPERSER_CONTRACT, up to DISPERSER_BATCH_SIZE transfers per call, all from
+    /// the main wallet.
+    #[clap(long = "disperser", conflicts_with_all = &["cont_fund", "reclaim"])]
+    disperser: bool,
+
+    /// Run continual funding as a daemon with a PID file and a `/healthz` endpoint.
+    #[clap(long = "daemon", requires = "cont_fund")]
+    daemon: bool,
+
+    /// PID file written while running in daemon mode.
+    #[clap(long = "pid-file", default_value = "fund_distributor.pid")]
+    pid_file: PathBuf,
+
+    /// Directory of the lock files that keep two --init-dist or --cont-fund runs for
+    /// the same main wallet from running at once.
+    #[clap(long = "lock-dir", env = "LOCK_DIR", default_value = ".")]
+    lock_dir: PathBuf,
+
+    /// Break a lock left behind by another run, e.g. after a crash. Make sure that
+    /// run is really gone: two runs would double-spend the treasury.
+    #[clap(long = "force")]
+    force: bool,
+
+    /// Address the `/healthz` endpoint listens on in daemon mode.
+    #[clap(long = "health-addr", default_value = "127.0.0.1:8080")]
+    health_addr: SocketAddr,
+
+    /// Seconds without a successful cycle before `/healthz` reports unhealthy.
+    #[clap(long = "health-max-age", default_value = "120")]
+    health_max_age: u64,
+
+    /// URL POSTed a JSON payload (tx id, amount, fee) when each transfer confirms.
+    /// Defaults to `CALLBACK_URL` from the environment.
+    #[clap(long = "callback-url")]
+    callback_url: Option<String>,
+
+    /// When reclaiming a non-base asset, top up HD wallets that lack base asset
+    /// for gas (up to GAS_RESERVE) instead of skipping them. Also applies to
+    /// reclaims scheduled with RECLAIM_SCHEDULE.
+    #[clap(long = "prefund-gas")]
+    prefund_gas: bool,
+
+    /// Reclaim every asset each HD wallet holds, not just ETH_ASSET_ID; the base
+    /// asset is swept last since it pays the gas. Also applies to `serve` reclaims.
+    #[clap(long = "all-assets", env = "RECLAIM_ALL_ASSETS")]
+    all_assets: bool,
+
+    /// Comma-separated asset ids --all-assets is limited to (include the base
+    /// asset to sweep it too).
+    #[clap(
+        long = "assets",
+        env = "RECLAIM_ASSETS",
+        use_value_delimiter = true,
+        requires = "all_assets"
+    )]
+    assets: Vec<AssetId>,
+
+    /// Send reclaimed funds to this address (bech32 or hex), e.g. a treasury
+    /// multisig, instead of back to the funding wallets. Also applies to
+    /// reclaims scheduled with RECLAIM_SCHEDULE. Defaults to RECLAIM_PREDICATE.
+    #[clap(long = "to", value_parser = recipients::parse_address)]
+    to: Option<Bech32Address>,
+
+    /// Reclaim the HD wallets holding the most ETH_ASSET_ID first, rather than in
+    /// index order. Also applies to reclaims scheduled with RECLAIM_SCHEDULE.
+    #[clap(long = "largest-first")]
+    largest_first: bool,
+
+    /// Leave HD wallets holding less ETH_ASSET_ID than this alone, as a decimal
+    /// amount or in base units.
+    #[clap(long = "min-balance")]
+    min_balance: Option<String>,
+
+    /// Stop reclaiming once this much ETH_ASSET_ID has come back, taking only part
+    /// of the last wallet's balance if needed; a decimal amount or base units.
+    #[clap(long = "up-to")]
+    up_to: Option<String>,
+
+    /// Reclaim only this share of each balance, in percent (e.g. 99.9), leaving the
+    /// rest in the HD wallets. Applies to every asset reclaimed.
+    #[clap(long = "reclaim-percent")]
+    reclaim_percent: Option<String>,
+
+    /// Leave this much ETH_ASSET_ID in each HD wallet as operational float when
+    /// reclaiming, as a decimal amount or in base units.
+    #[clap(long = "leave")]
+    leave: Option<String>,
+
+    /// Only plan the --init-dist, --cont-fund (one cycle) or --reclaim run: query the
+    /// balances, print the transfers it would make and exit without sending any.
+    #[clap(long = "plan-only", conflicts_with = "daemon")]
+    plan_only: bool,
+
+    /// Also write the plan as JSON to this file, for review and --execute-plan.
+    #[clap(long = "plan-output", requires = "plan_only")]
+    plan_output: Option<PathBuf>,
+
+    /// Carry out a plan written with --plan-output, skipping transfers whose HD
+    /// wallet balance has changed since the plan was made.
+    #[clap(
+        long = "execute-plan",
+        conflicts_with_all = &["init_dist", "cont_fund", "reclaim", "plan_only"]
+    )]
+    execute_plan: Option<PathBuf>,
+
+    /// Run --init-dist, --cont-fund (one cycle) or --reclaim through the work queue in
+    /// STORAGE_URL: plan the transfers, enqueue them as jobs and work them off, so an
+    /// interrupted run resumes with `queue run` rather than starting over.
+    #[clap(
+        long = "queue",
+        conflicts_with_all = &["plan_only", "execute_plan", "daemon", "once", "fast", "relay", "disperser", "simulate"]
+    )]
+    queue: bool,
+
+    /// Rehearse the run against an in-process fuel-core node seeded with the main and
+    /// HD wallets' coins and the live chain's parameters and gas price. Nothing is sent
+    /// to the real network, recorded in history or reported to callbacks.
+    #[clap(long = "simulate", conflicts_with_all = &["cont_fund", "tor"])]
+    simulate: bool,
+
+    /// Route provider traffic through the Tor SOCKS proxy at this address (e.g.
+    /// 127.0.0.1:9050), with a separate circuit per batch of reclaimed wallets.
+    #[clap(long = "tor", env = "TOR_SOCKS_ADDR", requires = "reclaim")]
+    tor: Option<String>,
+
+    /// Number of HD wallets reclaimed over each Tor circuit.
+    #[clap(long = "tor-batch-size", default_value = "1", requires = "tor")]
+    tor_batch_size: usize,
+
+    /// Network the provider must serve; its chain id is verified before any transfer.
+    #[clap(long, value_enum, env = "NETWORK")]
+    network: Option<Network>,
+
+    /// Configuration profile from PROFILES (e.g. testnet, mainnet) whose settings
+    /// take precedence over .env and the environment.
+    #[clap(long, env = "PROFILE")]
+    profile: Option<String>,
+
+    /// Tag recorded with every transfer of this run (e.g. `q3-rebalance`), for filtering history.
+    #[clap(long)]
+    tag: Option<String>,
+
+    /// Label recorded with every transfer of this run (e.g. `manual`) in place of the
+    /// default `initial`, `topup-cycle-<n>`, `reclaim` or `gas-prefund`.
+    #[clap(long)]
+    label: Option<String>,
+
+    /// Restrict the command to this wallet group from WALLET_GROUPS, applying its
+    /// assets, amounts and schedule.
+    #[clap(long)]
+    group: Option<String>,
+
+    /// Skip topping up wallets that received an inbound transfer (from any sender)
+    /// within this many of the latest blocks.
+    #[clap(long = "skip-recent-inbound", env = "SKIP_RECENT_INBOUND_BLOCKS")]
+    skip_recent_inbound: Option<u32>,
+
+    /// Smallest inbound transfer that counts for --skip-recent-inbound, as a decimal
+    /// amount (`0.005`) or in base units (`5000000`). Defaults to the top-up amount.
+    #[clap(long = "inbound-min-amount", env = "INBOUND_MIN_AMOUNT")]
+    inbound_min_amount: Option<String>,
+
+    /// Maximum number of transfers submitted but not yet confirmed. Values above 1
+    /// pipeline submission with confirmation when the sender holds several coins.
+    #[clap(long = "max-in-flight", env = "MAX_IN_FLIGHT", default_value = "1")]
+    max_in_flight: usize,
+
+    /// With --max-in-flight above 1, split a funding wallet's balance into enough
+    /// separate coins first if it holds too few, instead of refusing to start.
+    #[clap(long = "split-coins", env = "SPLIT_COINS")]
+    split_coins: bool,
+
+    /// Maximum number of HD wallets --reclaim sweeps at once. A wallet whose
+    /// transfers fail does not stop the others; failed wallets are retried at the end.
+    #[clap(
+        long = "reclaim-concurrency",
+        env = "RECLAIM_CONCURRENCY",
+        default_value = "8"
+    )]
+    reclaim_concurrency: usize,
+
+    /// Maximum number of wallet balance queries sent to the node at once.
+    #[clap(
+        long = "balance-concurrency",
+        env = "BALANCE_CONCURRENCY",
+        default_value = "32"
+    )]
+    balance_concurrency: usize,
+
+    /// Seconds continual funding skips a wallet whose earlier top-up is still
+    /// unconfirmed, before funding it again.
+    #[clap(
+        long = "in-flight-timeout",
+        env = "IN_FLIGHT_TIMEOUT_SECS",
+        default_value = "300"
+    )]
+    in_flight_timeout: u64,
+
+    /// Resubmit top-ups still unconfirmed after --in-flight-timeout with this tip,
+    /// doubled on every further resubmission, rather than funding the wallet afresh.
+    #[clap(long = "resubmit-tip", env = "RESUBMIT_TIP")]
+    resubmit_tip: Option<u64>,
+
+    /// Seconds a continual funding cycle may run before the watchdog cancels it
+    /// (hung RPC, deadlocked task) and starts the next one.
+    #[clap(
+        long = "max-cycle-duration",
+        env = "MAX_CYCLE_SECS",
+        default_value = "600"
+    )]
+    max_cycle_duration: u64,
+
+    /// Seconds an --init-dist or --reclaim run may take before it stops with an error;
+    /// each provider request then also fails after PROVIDER_TIMEOUT_SECS.
+    #[clap(
+        long = "timeout",
+        env = "RUN_TIMEOUT_SECS",
+        conflicts_with = "cont_fund"
+    )]
+    timeout: Option<u64>,
+
+    /// Restart continual funding when it has not checked in for this many schedule
+    /// intervals (and at least --max-cycle-duration); 0 disables the deadman switch.
+    #[clap(
+        long = "deadman-intervals",
+        env = "DEADMAN_INTERVALS",
+        default_value = "5"
+    )]
+    deadman_intervals: u32,
+
+    #[clap(flatten)]
+    tx_policies: TxPolicyArgs,
+
+    #[clap(flatten)]
+    log: LogArgs,
+
+    #[clap(flatten)]
+    mnemonic: MnemonicArgs,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Derive and print all HD wallet addresses without making any transfers.
+    Addresses {
+        /// Also write the addresses to this file.
+        #[clap(long)]
+        output: Option<PathBuf>,
+
+        /// Format of the output file.
+        #[clap(long, value_enum, default_value = "json")]
+        format: ExportFormat,
+    },
+
+    /// Write a JSON manifest of HD wallet indices and addresses, signed by the main
+    /// wallet, for services that whitelist the fleet's addresses.
+    ExportManifest {
+        /// Write the manifest to this file instead of printing it.
+        #[clap(long)]
+        output: Option<PathBuf>,
+
+        /// Include each wallet's public key.
+        #[clap(long)]
+        public_keys: bool,
+    },
+
+    /// Check that a manifest from export-manifest is signed by the main wallet and
+    /// matches the current mnemonic and derivation path. Exits 1 if it does not.
+    VerifyManifest {
+        /// The manifest file to verify.
+        file: PathBuf,
+    },
+
+    /// Approve a plan written by --plan-only --plan-output, signing it with the main
+    /// wallet of the configured mnemonic (the reviewer's own), for APPROVAL_LIMIT.
+    ApprovePlan {
+        /// The plan file; the approval is added to it.
+        file: PathBuf,
+    },
+
+    /// Act on a single HD wallet without touching the rest of the fleet.
+    Wallet {
+        /// HD wallet index.
+        index: usize,
+
+        #[clap(subcommand)]
+        action: WalletCommand,
+    },
+
+    /// Check a recipients file (CSV address,amount[,memo], or JSON) without touching the chain:
+    /// address formats, duplicates, our own wallets, and amounts under ASSET_DECIMALS.
+    ValidateRecipients {
+        /// The recipients file to validate.
+        file: PathBuf,
+
+        /// Asset the recipients will be paid in, checked against ADDRESS_BOOK
+        /// profiles. Defaults to ETH_ASSET_ID when set.
+        #[clap(long)]
+        asset: Option<AssetId>,
+    },
+
+    /// Query the transfer history in STORAGE_URL, optionally filtered.
+    #[clap(args_conflicts_with_subcommands = true)]
+    History {
+        #[clap(subcommand)]
+        action: Option<HistoryCommand>,
+
+        /// Only transfers made by runs with this tag.
+        #[clap(long)]
+        tag: Option<String>,
+
+        /// Only transfers with this label (e.g. `initial`, `topup-cycle-142`, `manual`).
+        #[clap(long)]
+        label: Option<String>,
+
+        /// Only transfers at or after this time (unix seconds or YYYY-MM-DD, UTC).
+        #[clap(long, value_parser = history::parse_time)]
+        since: Option<u64>,
+
+        /// Only transfers before this time (unix seconds or YYYY-MM-DD, UTC).
+        #[clap(long, value_parser = history::parse_time)]
+        until: Option<u64>,
+
+        /// Only transfers of this asset id.
+        #[clap(long)]
+        asset: Option<AssetId>,
+
+        /// Only transfers to or from this HD wallet index.
+        #[clap(long)]
+        wallet: Option<u64>,
+
+        /// Output format.
+        #[clap(long, value_enum, default_value = "json")]
+        format: ExportFormat,
+    },
+
+    /// Write a snapshot of the main and HD wallets' balances and the transfer history
+    /// in STORAGE_URL, with fees and tx ids, as files for accounting.
+    Export {
+        /// File format.
+        #[clap(long, value_enum, default_value = "csv")]
+        format: AccountingFormat,
+
+        /// Directory the `balances-<time>` and `transfers-<time>` files are written to.
+        #[clap(long, default_value = ".")]
+        output_dir: PathBuf,
+
+        /// Only transfers at or after this time (unix seconds or YYYY-MM-DD, UTC).
+        #[clap(long, value_parser = history::parse_time)]
+        since: Option<u64>,
+
+        /// Only transfers before this time (unix seconds or YYYY-MM-DD, UTC).
+        #[clap(long, value_parser = history::parse_time)]
+        until: Option<u64>,
+    },
+
+    /// Deploy the disperser contract from the main wallet, for --disperser, and
+    /// print its id. Needs a build with the `disperser` feature.
+    DeployDisperser {
+        /// The contract's bytecode, from `forc build --release` in contracts/disperser.
+        #[clap(long, default_value = "contracts/disperser/out/release/disperser.bin")]
+        bin: PathBuf,
+    },
+
+    /// Check whether any HD wallet is below the funding threshold without sending
+    /// anything. Exits 0 if none are, 1 if top-ups are needed (printing them), 2 on error.
+    DriftCheck,
+
+    /// Check the fleet's on-chain state against the funding policy: every HD wallet at
+    /// or above the threshold and at most MAX_WALLET_BALANCE, the main wallet at or
+    /// above MAIN_WALLET_RESERVE, and no unexpected assets in the HD wallets. Exits 0
+    /// if all hold, 1 listing the violations, 2 on error.
+    Verify {
+        /// Assets the HD wallets may hold besides ETH_ASSET_ID, the base asset and
+        /// the --group's assets (comma-separated).
+        #[clap(long = "allow-assets", use_value_delimiter = true)]
+        allow_assets: Vec<AssetId>,
+    },
+
+    /// Connect to the provider and check the main wallet holds at least the reserve,
+    /// printing a JSON result. Exits 0 if healthy, 1 if not; for liveness/readiness
+    /// probes and Nagios-style checks.
+    Healthcheck {
+        /// Main wallet balance below which the check fails, as a decimal amount or in
+        /// base units. Defaults to one top-up (TOP_UP_AMOUNT).
+        #[clap(long, env = "MAIN_WALLET_RESERVE")]
+        reserve: Option<String>,
+    },
+
+    /// Request testnet funds for the main wallet from a faucet (FAUCET_URL, Fuel's
+    /// faucet for --network by default) and, with --wallets, for HD wallets directly.
+    /// Refused on mainnet.
+    Faucet {
+        /// HD wallets to request funds for as well, e.g. `0-9` or `0,3,5`.
+        #[clap(long)]
+        wallets: Option<String>,
+
+        /// Only request funds for the --wallets, not for the main wallet.
+        #[clap(long = "skip-main", requires = "wallets")]
+        skip_main: bool,
+
+        /// Captcha response token, for faucets that ask for one.
+        #[clap(long, env = "FAUCET_CAPTCHA")]
+        captcha: Option<String>,
+
+        /// Wait until the funds have arrived, up to two minutes.
+        #[clap(long)]
+        wait: bool,
+    },
+
+    /// Show the top-ups continual funding would make now and their fees, as
+    /// estimated by the node for the actual transactions. Sends nothing.
+    Plan {
+        /// Estimate one multi-output transaction per funding source instead of
+        /// one transaction per top-up.
+        #[clap(long)]
+        batched: bool,
+    },
+
+    /// Retire the HD wallets: reclaim them, then fund as many fresh wallets further
+    /// along the derivation path, printing which new address replaces which.
+    Rotate {
+        /// WALLET_INDEX_OFFSET of the new wallets. Defaults to right after the current ones.
+        #[clap(long)]
+        offset: Option<usize>,
+
+        /// Send each new wallet this amount, as a decimal amount or in base units,
+        /// instead of what its predecessor held.
+        #[clap(long)]
+        amount: Option<String>,
+
+        /// Also write the old to new mapping to this file.
+        #[clap(long)]
+        output: Option<PathBuf>,
+
+        /// Format of the output file.
+        #[clap(long, value_enum, default_value = "json")]
+        format: ExportFormat,
+    },
+
+    /// Work with the audit log (AUDIT_LOG).
+    Audit {
+        #[clap(subcommand)]
+        action: AuditCommand,
+    },
+
+    /// Work with the work queue in STORAGE_URL that --queue runs go through.
+    Queue {
+        #[clap(subcommand)]
+        action: QueueCommand,
+    },
+
+    /// Run continual funding as a system service: a systemd unit on Linux, a Windows
+    /// service with the `windows-service` feature.
+    Service {
+        #[clap(subcommand)]
+        action: ServiceCommand,
+    },
+
+    /// Serve an HTTP API to trigger distributions, start/stop continual funding,
+    /// and query balances and history.
+    Serve {
+        /// Address the API listens on.
+        #[clap(long, default_value = "127.0.0.1:8081")]
+        addr: SocketAddr,
+
+        /// Bearer token required on every request. Mandatory unless the API
+        /// only listens on a loopback address.
+        #[clap(long, env = "API_TOKEN")]
+        token: Option<String>,
+
+        /// Also serve the gRPC control interface (proto/distributor.proto) here.
+        #[clap(long = "grpc-addr")]
+        grpc_addr: Option<SocketAddr>,
+
+        /// Serve an unauthenticated, read-only `/public/status` with coarse
+        /// aggregates only (wallet count, % funded, last cycle time).
+        #[clap(long = "public-status")]
+        public_status: bool,
+
+        /// Requests per minute each client address may make to `/public/status`.
+        #[clap(long = "public-rate-limit", default_value = "30")]
+        public_rate_limit: u32,
+    },
+}
+
+#[derive(Subcommand)]
+enum WalletCommand {
+    /// Print the wallet's address (bech32 and hex) and derivation path.
+    Address,
+
+    /// Print every asset the wallet holds.
+    Balance,
+
+    /// Send ETH_ASSET_ID to the wallet from its funding wallet.
+    Fund {
+        /// Decimal amount in ASSET_DECIMALS, or base units.
+        #[clap(long)]
+        amount: String,
+    },
+
+    /// Sweep the wallet back to its funding wallet (or --to), as --reclaim does.
+    Reclaim,
+
+    /// Send funds from the wallet to any address.
+    Send {
+        /// Recipient address (bech32 or hex).
+        #[clap(long, value_parser = recipients::parse_address)]
+        to: Bech32Address,
+
+        /// Decimal amount (for ETH_ASSET_ID or the base asset), or base units.
+        #[clap(long)]
+        amount: String,
+
+        /// Asset to send; defaults to ETH_ASSET_ID.
+        #[clap(long)]
+        asset: Option<AssetId>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ServiceCommand {
+    /// Register and start the service, running `service run` from the current
+    /// directory (for `.env`) with the active profile.
+    Install {
+        /// Name of the systemd unit or Windows service.
+        #[clap(long, default_value = service::DEFAULT_NAME)]
+        name: String,
+
+        /// Account the systemd unit runs as; root by default.
+        #[clap(long)]
+        user: Option<String>,
+
+        /// Print the systemd unit instead of installing it.
+        #[clap(long)]
+        print: bool,
+
+        /// Options continual funding runs with, after `--`
+        /// (e.g. `-- --health-addr 127.0.0.1:9090`).
+        #[clap(last = true)]
+        args: Vec<String>,
+    },
+
+    /// Stop the service and remove it.
+    Uninstall {
+        /// Name of the systemd unit or Windows service.
+        #[clap(long, default_value = service::DEFAULT_NAME)]
+        name: String,
+    },
+
+    /// What the service runs: continual funding in daemon mode, stopped by the
+    /// service manager.
+    Run {
+        /// Name of the service, as installed.
+        #[clap(long, default_value = service::DEFAULT_NAME)]
+        name: String,
+
+        /// Directory to run in, for `.env` and relative paths; set by `service install`
+        /// on Windows, where services start in the system directory.
+        #[clap(long)]
+        dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum AuditCommand {
+    /// Check the log's hash chain, and its signatures if AUDIT_HMAC_KEY is set.
+    /// Exits 1 at the first entry that was modified, removed or reordered.
+    Verify {
+        /// The log to verify; defaults to AUDIT_LOG.
+        file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum QueueCommand {
+    /// Show each batch's jobs by status, and every job that failed or awaits
+    /// confirmation.
+    Status,
+
+    /// Work off the pending jobs and check the submitted ones, e.g. after a crash.
+    Run,
+
+    /// Set the failed jobs back to pending, then work off the queue. A job whose
+    /// wallet balance has changed since it was planned is skipped as stale.
+    RetryFailed,
+}
+
+#[derive(Subcommand)]
+enum HistoryCommand {
+    /// Merge the histories of several hosts into one, skipping transfers (same tx id and
+    /// sequence number) the output already has.
+    Merge {
+        /// History databases to merge: SQLite file paths or storage URLs.
+        #[clap(required = true)]
+        inputs: Vec<String>,
+
+        /// Database to merge into (SQLite file path or storage URL); created if missing.
+        #[clap(short, long)]
+        output: String,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     dotenv().ok();
 
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
+    if let Some(Command::Service {
+        action: ServiceCommand::Run { dir: Some(dir), .. },
+    }) = &cli.command
+    {
+        env::set_current_dir(dir)
+            .map_err(|e| format!("Failed to change to {}: {}", dir.display(), e))?;
+        dotenv().ok();
+        cli = Cli::parse();
+    }
+    // A profile takes precedence over .env; parse again so options read from the
+    // environment see it
+    if let Some(profile) = &cli.profile {
+        profiles::apply(profile)?;
+        cli = Cli::parse();
+    }
+    logging::init(&cli.log)?;
+    // Every log line of the run carries the profile
+    let span = match profiles::active() {
+        Some(profile) => info_span!("profile", name = profile),
+        None => Span::none(),
+    };
+    let service = match &cli.command {
+        Some(Command::Service {
+            action: ServiceCommand::Run { name, .. },
+        }) => Some(service::start(name)?),
+        _ => None,
+    };
+    let result = run(cli).instrument(span).await;
+    match &result {
+        Ok(()) => audit::finished(0, None),
+        Err(e) => audit::finished(1, Some(&e.to_string())),
+    }
+    telemetry::shutdown();
+    if let Some(service) = service {
+        service.stopped(result.is_ok());
+    }
+    result
+}
+
+async fn run(mut cli: Cli) -> Result<(), Box<dyn Error>> {
+    if let Some(profile) = profiles::active() {
+        info!("Using profile '{}'", profile);
+    }
+    // Secrets from Vault take precedence over .env; parse again so options read from
+    // the environment (e.g. API_TOKEN) see them
+    if let Some(vault) = Vault::from_env().await? {
+        let lease = vault.load().await?;
+        cli = Cli::parse();
+        tokio::spawn(vault.refresh(lease).in_current_span());
+    }
+    check_features(&cli)?;
+    assets::load_from_env()?;
+    match &cli.command {
+        Some(Command::Service {
+            action:
+                ServiceCommand::Install {
+                    name,
+                    user,
+                    print,
+                    args,
+                },
+        }) => {
+            let mut options = Vec::new();
+            if let Some(profile) = profiles::active() {
+                options.extend(["--profile".to_string(), profile.to_string()]);
+            }
+            options.extend(args.iter().cloned());
+            // Options that would not parse would only fail once the service starts
+            Cli::try_parse_from(
+                std::iter::once("fund_distributor".to_string())
+                    .chain(options.iter().cloned())
+                    .chain(["service".into(), "run".into()]),
+            )
+            .map_err(|e| format!("Invalid service options: {}", e))?;
+            let executable = env::current_exe()?;
+            let working_dir = env::current_dir()?;
+            let spec = ServiceSpec {
+                name,
+                executable: &executable,
+                working_dir: &working_dir,
+                args: options,
+                user: user.as_deref(),
+            };
+            if *print {
+                print!("{}", spec.systemd_unit());
+                return Ok(());
+            }
+            return service::install(&spec);
+        }
+        Some(Command::Service {
+            action: ServiceCommand::Uninstall { name },
+        }) => return service::uninstall(name),
+        Some(Command::Audit {
+            action: AuditCommand::Verify { file },
+        }) => return verify_audit_log(file.as_deref()),
+        Some(Command::Service {
+            action: ServiceCommand::Run { .. },
+        }) => {
+            cli.command = None;
+            cli.cont_fund = true;
+            cli.daemon = true;
+        }
+        _ => {}
+    }
+    if cli.disperser && !cli.init_dist && cli.recipients.is_none() {
+        return Err("--disperser needs --init-dist or --recipients".into());
+    }
+    // Reclaims go to the predicate once its address is verified, wherever they run
+    if let Some(predicate) = ReclaimPredicate::from_env()? {
+        cli.to = Some(predicate.destination(cli.to.as_ref())?);
+        info!(
+            "Reclaiming into predicate {} (address verified against RECLAIM_PREDICATE_ROOT).",
+            predicate.address
+        );
+    }
+
+    // Several fleets, each with its own mnemonic, replace MNEMONIC for continual funding
+    if cli.cont_fund {
+        if let Some(fleets) = fleets::load_all()? {
+            if cli.group.is_some() {
+                return Err("--group cannot be combined with FLEETS".into());
+            }
+            if cli.plan_only {
+                return Err("--plan-only cannot be combined with FLEETS".into());
+            }
+            if cli.once || cli.fast {
+                return Err("--once and --fast cannot be combined with FLEETS".into());
+            }
+            if cli.relay {
+                return Err("--relay cannot be combined with FLEETS".into());
+            }
+            if cli.queue {
+                return Err("--queue cannot be combined with FLEETS".into());
+            }
+            return run_fleets(&cli, fleets).await;
+        }
+    }
 
     // Environment variables
-    let mnemonic =
-        env::var("MNEMONIC").map_err(|_| "MNEMONIC not set in the environment".to_string())?;
-    let provider_url =
-        env::var("PROVIDER").map_err(|_| "PROVIDER not set in the environment".to_string())?;
-    let eth_asset_id_str = env::var("ETH_ASSET_ID")
-        .map_err(|_| "ETH_ASSET_ID not set in the environment".to_string())?;
-    let number_of_wallets_str = env::var("NUMBER_OF_WALLETS")
-        .map_err(|_| "NUMBER_OF_WALLETS not set in the environment".to_string())?;
-
-    // Parse NUMBER_OF_WALLETS
-    let number_of_wallets = number_of_wallets_str.parse::<usize>().map_err(|e| {
-        format!(
-            "Failed to parse NUMBER_OF_WALLETS ('{}') as a positive integer: {}",
-            number_of_wallets_str, e
-        )
-    })?;
+    let mnemonic = cli.mnemonic.read()?;
+    let number_of_wallets = fund::number_of_wallets_from_env()?;
+    let decimals = units::decimals_from_env()?;
+
+    let derivation = Derivation::from_env()?;
+    let fleet = Fleet::new(mnemonic, derivation);
+
+    let group = cli.group.as_deref().map(groups::load).transpose()?;
+    if let Some(group) = &group {
+        if *group.wallets.end() >= number_of_wallets {
+            return Err(format!(
+                "Wallet group '{}' ends at index {}, beyond NUMBER_OF_WALLETS ({})",
+                group.name,
+                group.wallets.end(),
+                number_of_wallets
+            )
+            .into());
+        }
+        info!(
+            "Restricted to wallet group '{}' (HD wallets {}-{})",
+            group.name,
+            group.wallets.start(),
+            group.wallets.end()
+        );
+    }
+
+    // Offline subcommands only need the mnemonic and wallet count
+    match &cli.command {
+        Some(Command::Addresses { output, format }) => {
+            let wallets = match &group {
+                Some(group) => *group.wallets.start()..*group.wallets.end() + 1,
+                None => 0..number_of_wallets,
+            };
+            return export_addresses(&fleet, wallets, output.as_deref(), *format);
+        }
+        Some(Command::ExportManifest {
+            output,
+            public_keys,
+        }) => {
+            let wallets = match &group {
+                Some(group) => *group.wallets.start()..*group.wallets.end() + 1,
+                None => 0..number_of_wallets,
+            };
+            return export_manifest(&fleet, wallets, *public_keys, output.as_deref()).await;
+        }
+        Some(Command::VerifyManifest { file }) => return verify_manifest(&fleet, file).await,
+        Some(Command::ApprovePlan { file }) => return approval::approve_plan(&fleet, file).await,
+        Some(Command::Wallet {
+            index,
+            action: WalletCommand::Address,
+        }) => {
+            let address = derive_address(&fleet, *index)?;
+            println!(
+                "{}\t{}\t{}\t{}",
+                address.index, address.bech32, address.hex, address.path
+            );
+            return Ok(());
+        }
+        Some(Command::ValidateRecipients { file, asset }) => {
+            let asset = match asset {
+                Some(asset) => Some(*asset),
+                None => env::var("ETH_ASSET_ID")
+                    .ok()
+                    .map(|id| AssetId::from_str(&id))
+                    .transpose()
+                    .map_err(|_| "Invalid ETH_ASSET_ID format".to_string())?,
+            };
+            return validate_recipients(
+                file,
+                &fleet,
+                number_of_wallets,
+                decimals,
+                &AddressBook::from_env()?,
+                asset.as_ref(),
+            );
+        }
+        Some(Command::History {
+            action: Some(HistoryCommand::Merge { inputs, output }),
+            ..
+        }) => {
+            let output_url = history::storage_url(output);
+            let mut storages = Vec::with_capacity(inputs.len());
+            for input in inputs {
+                let url = history::storage_url(input);
+                if url == output_url {
+                    return Err(format!("'{}' is both an input and the output", input).into());
+                }
+                storages.push(storage::open(&url).await?);
+            }
+            let summary =
+                history::merge_history(&storages, storage::open(&output_url).await?.as_ref())
+                    .await?;
+            info!(
+                "Merged {} transfers into {} ({} duplicates skipped)",
+                summary.merged, output, summary.duplicates
+            );
+            return Ok(());
+        }
+        Some(Command::History {
+            action: None,
+            tag,
+            label,
+            since,
+            until,
+            asset,
+            wallet,
+            format,
+        }) => {
+            let url = env::var("STORAGE_URL")
+                .map_err(|_| "STORAGE_URL not set in the environment".to_string())?;
+            let storage = storage::open(&url).await?;
+            let filter = TransferFilter {
+                tag: tag.clone(),
+                label: label.clone(),
+                since: *since,
+                until: *until,
+                asset_id: asset.map(|asset| asset.to_string()),
+                wallet_index: *wallet,
+            };
+            let manifest = Manifest::new("history")
+                .parameter("tag", tag.as_deref().unwrap_or_default())
+                .parameter("label", label.as_deref().unwrap_or_default())
+                .parameter("since", format!("{:?}", since))
+                .parameter("until", format!("{:?}", until))
+                .parameter("asset", format!("{:?}", filter.asset_id))
+                .parameter("wallet", format!("{:?}", wallet));
+            return print_history(storage.as_ref(), &filter, *format, &manifest).await;
+        }
+        Some(Command::Serve {
+            addr,
+            token: None,
+            grpc_addr,
+            ..
+        }) if !addr.ip().is_loopback() || grpc_addr.is_some_and(|a| !a.ip().is_loopback()) => {
+            return Err(
+                "Refusing to serve the API on a non-loopback address without --token (or API_TOKEN)"
+                    .into(),
+            );
+        }
+        Some(Command::Healthcheck { reserve }) => {
+            // Dollar amounts need a price, so the reserve is resolved ahead of the check
+            let reserve = async {
+                Ok::<_, Box<dyn Error>>(match reserve {
+                    Some(reserve) => units::parse_setting(reserve, decimals)
+                        .map_err(|e| format!("Invalid --reserve: {}", e))?,
+                    None => {
+                        FundingSettings::from_env(None, cli.group.clone())?
+                            .priced(&mut PriceOracle::from_env()?, decimals)
+                            .await?
+                            .top_up_amount
+                    }
+                })
+            }
+            .await;
+            let report = match main_asset_id(group.as_ref())
+                .and_then(|asset_id| Ok((asset_id, reserve?)))
+            {
+                Ok((asset_id, reserve)) => {
+                    healthcheck::healthcheck(&fleet, cli.network, asset_id, decimals, reserve).await
+                }
+                Err(e) => healthcheck::HealthReport {
+                    error: Some(e.to_string()),
+                    checked_at: daemon::unix_now(),
+                    ..Default::default()
+                },
+            };
+            println!("{}", serde_json::to_string(&report)?);
+            exit(if report.healthy { 0 } else { 1 }, None);
+        }
+        Some(Command::Rotate { .. }) if group.is_some() => {
+            return Err("rotate moves the whole fleet and cannot be combined with --group".into())
+        }
+        Some(Command::DriftCheck)
+        | Some(Command::Verify { .. })
+        | Some(Command::Faucet { .. })
+        | Some(Command::Rotate { .. })
+        | Some(Command::DeployDisperser { .. })
+        | Some(Command::Plan { .. })
+        | Some(Command::Export { .. })
+        | Some(Command::Wallet { .. })
+        | Some(Command::Serve { .. })
+        | Some(Command::Service { .. })
+        | Some(Command::Audit { .. })
+        | Some(Command::Queue { .. })
+        | None => {}
+    }
+
+    let mut provider_pool = ProviderPool::from_env(cli.network)?;
+    let eth_asset_id = main_asset_id(group.as_ref())?;
+
+    // Open the history/state storage, if configured
+    let storage = match env::var("STORAGE_URL") {
+        Ok(url) if !cli.simulate => Some(storage::open(&url).await?),
+        _ => None,
+    };
+    let queued = cli.queue || matches!(cli.command, Some(Command::Queue { .. }));
+    if queued && storage.is_none() {
+        return Err("The work queue needs STORAGE_URL".into());
+    }
+    if let Some(Command::Queue {
+        action: QueueCommand::Status,
+    }) = &cli.command
+    {
+        return queue::print_status(storage.as_deref().expect("checked above")).await;
+    }
+    // Settings continual funding can reload on SIGHUP
+    let settings = FundingSettings::from_env(cli.callback_url.clone(), cli.group.clone())?
+        .priced(&mut PriceOracle::from_env()?, decimals)
+        .await?;
+    let inbound_min_amount = cli
+        .inbound_min_amount
+        .as_deref()
+        .map(|amount| units::parse_setting(amount, decimals))
+        .transpose()
+        .map_err(|e| format!("Invalid --inbound-min-amount: {}", e))?;
+    // Optional message bus for funding lifecycle events
+    let bus = match env::var("EVENT_BUS_URL") {
+        Ok(url) if !cli.simulate => {
+            let topic = env::var("EVENT_BUS_TOPIC").unwrap_or_else(|_| bus::DEFAULT_TOPIC.into());
+            Some(bus::EventBus::connect(&url, &topic).await?)
+        }
+        _ => None,
+    };
+    let sinks = Sinks {
+        storage: storage.as_deref(),
+        webhook: RwLock::new(
+            settings
+                .callback_url
+                .clone()
+                .filter(|_| !cli.simulate)
+                .map(Webhook::new),
+        ),
+        tag: cli.tag.clone(),
+        label: cli.label.clone(),
+        bus,
+    };
 
-    if number_of_wallets == 0 {
-        return Err("NUMBER_OF_WALLETS must be greater than 0".into());
+    // Optional per-range funding sources (budget envelopes)
+    let funding_sources = FundingSources::from_env(number_of_wallets)?;
+    if cli.relay {
+        if !cli.init_dist && !cli.cont_fund {
+            return Err("--relay needs --init-dist or --cont-fund".into());
+        }
+        if funding_sources.is_empty() {
+            return Err("--relay needs FUNDING_SOURCES to assign HD wallets to hubs".into());
+        }
     }
 
-    // Parse the ETH_ASSET_ID from the environment variable
-    let eth_asset_id = AssetId::from_str(&eth_asset_id_str)
-        .map_err(|_| format!("Invalid ETH_ASSET_ID format: {}", eth_asset_id_str))?;
+    // Must be in place before the first provider is connected
+    let tor = cli.tor.as_deref().map(tor::TorProxy::install);
+
+    // Connect to the first healthy provider
+    let provider = provider_pool.connect().await?;
+    assets::register_base(*provider.base_asset_id());
+    let mut shown_assets = vec![eth_asset_id];
+    shown_assets.extend(group.iter().flat_map(|group| group.assets.iter().copied()));
+    assets::discover(&shown_assets).await?;
+    assets::check_decimals(&eth_asset_id, decimals)?;
+
+    // The main wallet: signed externally if MAIN_SIGNER is set, else wallet 0
+    let mut main_wallet = match ExternalWallet::from_env(provider.clone()).await? {
+        Some(wallet) => Funder::External(wallet),
+        None => Funder::from(fleet.wallet(0, Some(provider.clone()))?),
+    };
 
-    // Connect to provider
-    let provider = Provider::connect(&provider_url).await?;
+    #[cfg(feature = "simulate")]
+    let provider = if cli.simulate {
+        let mut addresses = vec![main_wallet.address().clone()];
+        for index in 0..number_of_wallets {
+            addresses.push(fleet.wallet(index, None)?.address().clone());
+        }
+        for source in funding_sources.derive(&fleet, None)?.values() {
+            addresses.push(source.address().clone());
+        }
+        addresses.sort();
+        addresses.dedup();
+        let simulated = simulate::fork(&provider, &addresses).await?;
+        main_wallet.set_provider(simulated.clone());
+        warn!(
+            "SIMULATION: transfers go to a local node, not {}.",
+            provider_pool.current_url()
+        );
+        simulated
+    } else {
+        provider
+    };
 
-    // Create the main wallet (wallet 0)
-    let main_wallet = WalletUnlocked::new_from_mnemonic_phrase(&mnemonic, Some(provider.clone()))?;
+    info!("Main Wallet address: {:?}", main_wallet.address());
+    info!("Using AssetId: {:?}", eth_asset_id);
+    info!("Number of HD Wallets: {}", number_of_wallets);
+    info!("Derivation path: {}", derivation);
 
-    println!("Main Wallet address: {:?}", main_wallet.address());
-    println!("Using AssetId: {:?}", eth_asset_id);
-    println!("Number of HD Wallets: {}", number_of_wallets);
+    // Detect optional provider features so behaviour adapts to the node version
+    let capabilities =
+        Capabilities::detect(&provider, main_wallet.address(), &[eth_asset_id]).await;
+    capabilities.log();
+    capabilities::register(capabilities);
 
-    if cli.init_dist {
-        println!("Starting initial distribution...");
-        initial_distribution(
-            &main_wallet,
-            &mnemonic,
-            &provider,
-            &eth_asset_id,
-            number_of_wallets,
+    let mut exclusions = Exclusions::from_env()?;
+    if let Some(group) = &group {
+        exclusions.restrict_to(group.wallets.clone());
+    }
+    if !exclusions.is_empty() {
+        info!("Excluded HD wallets: {}", exclusions);
+    }
+
+    let approvals = ApprovalPolicy::from_env(decimals)?;
+    let ctx = Context {
+        fleet,
+        asset_id: eth_asset_id,
+        decimals,
+        number_of_wallets,
+        funding_sources,
+        relay: cli.relay,
+        jitter: cli.jitter()?,
+        approval_limit: approvals.limit,
+        tx_policies: cli.tx_policies.to_policies(),
+        max_in_flight: cli.max_in_flight,
+        reclaim_concurrency: cli.reclaim_concurrency,
+        balance_concurrency: cli.balance_concurrency,
+        split_coins: cli.split_coins,
+        in_flight_timeout: Duration::from_secs(cli.in_flight_timeout),
+        resubmit_tip: cli.resubmit_tip,
+        max_cycle_duration: Duration::from_secs(cli.max_cycle_duration),
+        deadman_intervals: cli.deadman_intervals,
+        address_book: AddressBook::from_env()?,
+        exclusions,
+        inbound_check: cli.skip_recent_inbound.map(|lookback_blocks| InboundCheck {
+            lookback_blocks,
+            min_amount: inbound_min_amount,
+        }),
+        sinks,
+    };
+
+    let strategy = match &cli.command {
+        Some(Command::Plan { .. }) => "plan",
+        Some(Command::Export { .. }) => "export",
+        Some(Command::DriftCheck) => "drift-check",
+        Some(Command::Verify { .. }) => "verify",
+        Some(Command::Faucet { .. }) => "faucet",
+        Some(Command::DeployDisperser { .. }) => "deploy-disperser",
+        Some(Command::Serve { .. }) => "serve",
+        Some(Command::Wallet { .. }) => "wallet",
+        Some(Command::Rotate { .. }) => "rotate",
+        Some(Command::Queue { .. }) => "queue",
+        _ if cli.execute_plan.is_some() => "execute-plan",
+        _ if cli.init_dist => "init-dist",
+        _ if cli.recipients.is_some() => "recipients",
+        _ if cli.cont_fund => "cont-fund",
+        _ if cli.reclaim => "reclaim",
+        _ => "none",
+    };
+    let manifest = Manifest::for_run(strategy, &ctx, provider_pool.current_url(), &provider)
+        .parameter("threshold", settings.threshold)
+        .parameter("top_up_amount", settings.top_up_amount)
+        .parameter("decimals", decimals)
+        .parameter("prefund_gas", cli.prefund_gas)
+        .parameter("all_assets", cli.all_assets)
+        .parameter(
+            "reclaim_to",
+            cli.to.as_ref().map(|to| to.to_string()).unwrap_or_default(),
         )
-        .await?;
-    } else if cli.cont_fund {
-        println!("Starting continual funding...");
-        continual_funding(
-            &main_wallet,
-            &mnemonic,
-            &provider,
-            &eth_asset_id,
-            number_of_wallets,
+        .parameter("largest_first", cli.largest_first)
+        .parameter(
+            "min_balance",
+            cli.min_balance.as_deref().unwrap_or_default(),
         )
-        .await?;
-    } else if cli.reclaim {
-        println!("Starting fund reclamation...");
-        reclaim_funds(
-            &main_wallet,
-            &mnemonic,
+        .parameter("up_to", cli.up_to.as_deref().unwrap_or_default())
+        .parameter(
+            "reclaim_percent",
+            cli.reclaim_percent.as_deref().unwrap_or_default(),
+        )
+        .parameter("leave", cli.leave.as_deref().unwrap_or_default())
+        .parameter("plan_only", cli.plan_only)
+        .parameter(
+            "execute_plan",
+            cli.execute_plan
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default(),
+        )
+        .parameter("simulate", cli.simulate)
+        .parameter("once", cli.once)
+        .parameter("fast", cli.fast)
+        .parameter("relay", cli.relay)
+        .parameter("jitter", cli.jitter.as_deref().unwrap_or_default())
+        .parameter("jitter_delay", cli.jitter_delay)
+        .parameter("queue", cli.queue)
+        .parameter(
+            "recipients",
+            cli.recipients
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default(),
+        )
+        .parameter("profile", profiles::active().unwrap_or_default());
+    info!("Run manifest: {}", serde_json::to_string(&manifest)?);
+    // Simulated runs move nothing, so they stay out of the audit log like history
+    if !cli.simulate {
+        audit::open_from_env();
+    }
+    audit::record("run_started", &manifest);
+
+    if let Some(Command::Plan { batched }) = cli.command {
+        let manifest = manifest.parameter("batched", batched);
+        return print_plan(&ctx, &main_wallet, &provider, &settings, batched, &manifest).await;
+    }
+
+    if let Some(Command::Export {
+        format,
+        output_dir,
+        since,
+        until,
+    }) = &cli.command
+    {
+        let filter = TransferFilter {
+            since: *since,
+            until: *until,
+            ..TransferFilter::default()
+        };
+        export::export(
+            &ctx,
+            main_wallet.address(),
             &provider,
-            &eth_asset_id,
-            number_of_wallets,
+            &filter,
+            *format,
+            output_dir,
         )
         .await?;
-    } else {
-        println!("No valid command provided. Use --init-dist, --cont-fund, or --reclaim.");
+        return Ok(());
     }
 
-    Ok(())
-}
-
-async fn initial_distribution(
-    main_wallet: &WalletUnlocked,
-    mnemonic: &str,
-    provider: &Provider,
-    asset_id: &AssetId,
-    number_of_wallets: usize,
-) -> Result<(), Box<dyn Error>> {
-    // Define the amount to send (0.005 ETH in base units)
-    let amount = 5_000_000u64; // Adjust based on your asset's base units
-
-    for hd_wallet_number in 0..number_of_wallets {
-        // Derive the HD wallet
-        let path = format!("m/44'/1179993420'/{}'/0/0", hd_wallet_number);
-        let wallet = WalletUnlocked::new_from_mnemonic_phrase_with_path(
-            mnemonic,
-            Some(provider.clone()),
-            &path,
-        )?;
-
-        let wallet_address = wallet.address();
+    #[cfg(feature = "disperser")]
+    if let Some(Command::DeployDisperser { bin }) = &cli.command {
+        let id = disperser::deploy(&main_wallet, bin, ctx.tx_policies).await?;
+        println!("Deployed the disperser contract {}", id);
         println!(
-            "HD Wallet {} address: {:?}",
-            hd_wallet_number, wallet_address
+            "Set DISPERSER_CONTRACT={:#x} to use it with --disperser",
+            fuels::types::ContractId::from(&id)
         );
+        return Ok(());
+    }
 
-        // Send the specified amount to the wallet
-        send_funds(main_wallet, &wallet_address, amount, provider, asset_id).await?;
+    if let Some(Command::Wallet { index, action }) = &cli.command {
+        let index = *index;
+        return match action {
+            WalletCommand::Address => unreachable!("wallet address is printed before connecting"),
+            WalletCommand::Balance => single_wallet::print_balances(&ctx, &provider, index).await,
+            WalletCommand::Fund { amount } => {
+                single_wallet::fund_wallet(&ctx, &main_wallet, &provider, index, amount).await
+            }
+            WalletCommand::Send { to, amount, asset } => {
+                single_wallet::send_from_wallet(&ctx, &provider, index, to, amount, *asset).await
+            }
+            WalletCommand::Reclaim => {
+                let reclaim = ReclaimOptions {
+                    gas_policy: GasPolicy::from_env(cli.prefund_gas)?,
+                    assets: cli.asset_selection(group.as_ref(), provider.base_asset_id()),
+                    destination: cli.to.clone(),
+                    wallets: cli.wallet_selection(decimals)?,
+                };
+                let mut ctx = ctx;
+                single_wallet::reclaim_wallet(&mut ctx, &main_wallet, &provider, index, &reclaim)
+                    .await
+            }
+        };
     }
 
-    println!("Initial distribution completed.");
-    Ok(())
-}
+    #[cfg(feature = "api")]
+    if let Some(Command::Serve {
+        addr,
+        token,
+        grpc_addr,
+        public_status,
+        public_rate_limit,
+    }) = &cli.command
+    {
+        let listener = TcpListener::bind(*addr).await?;
+        let options = server::ServeOptions {
+            token: token.clone(),
+            grpc_addr: *grpc_addr,
+            gas_policy: GasPolicy::from_env(false)?,
+            assets: cli.asset_selection(group.as_ref(), provider.base_asset_id()),
+            settings,
+            public_status: public_status.then_some(*public_rate_limit),
+        };
+        return server::serve(
+            &ctx,
+            &main_wallet,
+            &provider_pool,
+            provider.clone(),
+            listener,
+            options,
+        )
+        .await;
+    }
 
-async fn continual_funding(
-    main_wallet: &WalletUnlocked,
-    mnemonic: &str,
-    provider: &Provider,
-    asset_id: &AssetId,
-    number_of_wallets: usize,
-) -> Result<(), Box<dyn Error>> {
-    // Define the threshold amount (0.005 ETH in base units)
-    let threshold = 5_000_000u64; // Adjust based on your asset's base units
-
-    loop {
-        for hd_wallet_number in 0..number_of_wallets {
-            // Derive the HD wallet
-            let path = format!("m/44'/1179993420'/{}'/0/0", hd_wallet_number);
-            let wallet = WalletUnlocked::new_from_mnemonic_phrase_with_path(
-                mnemonic,
-                Some(provider.clone()),
-                &path,
-            )?;
-
-            let wallet_address = wallet.address();
-
-            // Get the balance of the wallet for the specified AssetId
-            let balance = provider
-                .get_asset_balance(&wallet_address, *asset_id)
-                .await?;
+    if let Some(Command::DriftCheck) = cli.command {
+        let drift = match drift_check(&ctx, &provider, &settings, &manifest).await {
+            Ok(drift) => drift,
+            Err(e) => {
+                error!("Drift check failed: {}", e);
+                exit(2, Some(&e.to_string()));
+            }
+        };
+        exit(if drift { 1 } else { 0 }, None);
+    }
 
+    if let Some(Command::Verify { allow_assets }) = &cli.command {
+        let mut allowed = allow_assets.clone();
+        allowed.extend(group.iter().flat_map(|group| group.assets.iter().copied()));
+        let violations =
+            match verify::verify(&ctx, &main_wallet, &provider, &settings, &allowed).await {
+                Ok(violations) => violations,
+                Err(e) => {
+                    error!("Verification failed: {}", e);
+                    exit(2, Some(&e.to_string()));
+                }
+            };
+        manifest.print_header();
+        for violation in &violations {
+            println!("{}\t{}", violation.invariant, violation.detail);
+        }
+        if violations.is_empty() {
             println!(
-                "HD Wallet {} balance: {} (in base units)",
-                hd_wallet_number, balance
+                "Verified: {} wallets and the main wallet match the funding policy",
+                settings.number_of_wallets
             );
+        } else {
+            println!("{} violations found", violations.len());
+        }
+        exit(if violations.is_empty() { 0 } else { 1 }, None);
+    }
 
-            // Check if balance is less than threshold
-            if balance < threshold {
-                println!(
-                    "HD Wallet {} balance is below threshold, sending funds...",
-                    hd_wallet_number
+    if let Some(Command::Faucet {
+        wallets,
+        skip_main,
+        captcha,
+        wait,
+    }) = &cli.command
+    {
+        if u64::from(provider.chain_id()) == Network::Mainnet.chain_id() {
+            return Err("There is no faucet on mainnet".into());
+        }
+        let faucet = Faucet::from_env(cli.network, captcha.clone())?;
+        let mut requested = Vec::new();
+        if !skip_main {
+            requested.push(("main wallet".to_string(), main_wallet.address().clone()));
+        }
+        for index in
+            faucet::parse_wallets(wallets.as_deref().unwrap_or_default(), number_of_wallets)?
+        {
+            if ctx.is_excluded(index)? {
+                warn!(
+                    "HD wallet {} is excluded; not requesting funds for it.",
+                    index
                 );
+                continue;
+            }
+            requested.push((
+                format!("HD wallet {}", index),
+                ctx.fleet.wallet(index, None)?.address().clone(),
+            ));
+        }
 
-                // Send threshold amount to the wallet
-                send_funds(main_wallet, &wallet_address, threshold, provider, asset_id).await?;
+        manifest.print_header();
+        let base_asset = *provider.base_asset_id();
+        let mut funded = Vec::new();
+        let mut failed = 0;
+        for (label, address) in requested {
+            let before = provider.get_asset_balance(&address, base_asset).await?;
+            match faucet.request(&address).await {
+                Ok(amount) => {
+                    println!(
+                        "{}\t{}\trequested{}",
+                        label,
+                        address,
+                        amount
+                            .map(|amount| format!(
+                                " {}",
+                                assets::format(amount.into(), &base_asset)
+                            ))
+                            .unwrap_or_default()
+                    );
+                    funded.push((label, address, before));
+                }
+                Err(e) => {
+                    error!("Faucet request for {} ({}) failed: {}", label, address, e);
+                    failed += 1;
+                }
             }
         }
+        if *wait && !funded.is_empty() {
+            let waiting = faucet::wait_for_funds(&provider, &base_asset, &funded).await?;
+            if !waiting.is_empty() {
+                return Err(format!("No funds arrived yet for {}", waiting.join(", ")).into());
+            }
+        }
+        if failed > 0 {
+            return Err(format!("{} faucet requests failed", failed).into());
+        }
+        return Ok(());
+    }
 
-        // Wait for 20 seconds before the next check
-        println!("Waiting for 20 seconds before next check...");
-        sleep(Duration::from_secs(20)).await;
+    if cli.plan_only {
+        let plan = plan_run(
+            &cli,
+            &ctx,
+            &main_wallet,
+            &provider,
+            &settings,
+            group.as_ref(),
+            decimals,
+        )
+        .await?
+        .ok_or("--plan-only needs --init-dist, --cont-fund or --reclaim")?;
+        manifest.print_header();
+        plan.print(&ctx, &provider)?;
+        audit::record("plan", &plan);
+        if approvals.requires_approval(plan.total(&ctx.asset_id)) {
+            println!("The plan moves more than APPROVAL_LIMIT: a reviewer must approve-plan it before --execute-plan.");
+        }
+        if let Some(path) = &cli.plan_output {
+            plan.save(path)?;
+            println!("Wrote plan to {}", path.display());
+        }
+        return Ok(());
+    }
+    if let (Some(path), Some(_)) = (&cli.recipients, approvals.limit) {
+        if approvals.requires_approval(recipients::total(path, decimals)?) {
+            return Err("This --recipients run moves more than APPROVAL_LIMIT, and recipient distributions cannot go through a reviewed plan".into());
+        }
     }
-}
 
-async fn reclaim_funds(
-    main_wallet: &WalletUnlocked,
-    mnemonic: &str,
-    provider: &Provider,
-    asset_id: &AssetId,
-    number_of_wallets: usize,
-) -> Result<(), Box<dyn Error>> {
-    // Define the percentage of funds to reclaim (e.g., 99.9%)
-    const RECLAIM_PERCENTAGE: f64 = 99.9;
-
-    // Iterate through all HD wallets
-    for hd_wallet_number in 0..number_of_wallets {
-        // Derive the HD wallet
-        let path = format!("m/44'/1179993420'/{}'/0/0", hd_wallet_number);
-        let wallet = WalletUnlocked::new_from_mnemonic_phrase_with_path(
-            mnemonic,
-            Some(provider.clone()),
-            &path,
-        )?;
-
-        let wallet_address = wallet.address();
-        println!(
-            "Reclaiming funds from HD Wallet {}: {:?}",
-            hd_wallet_number, wallet_address
-        );
+    // Two runs funding from the same main wallet would spend the same coins twice
+    let locked_command = if queued {
+        Some("queue")
+    } else if cli.init_dist {
+        Some("init-dist")
+    } else if cli.recipients.is_some() {
+        Some("recipients")
+    } else if cli.cont_fund {
+        Some("cont-fund")
+    } else if matches!(cli.command, Some(Command::Rotate { .. })) {
+        Some("rotate")
+    } else {
+        cli.execute_plan.as_ref().map(|_| "execute-plan")
+    };
+    let run_lock = match locked_command {
+        Some(command) if !cli.simulate => Some(RunLock::acquire(
+            &cli.lock_dir,
+            main_wallet.address(),
+            command,
+            cli.force,
+        )?),
+        _ => None,
+    };
 
-        // Get the balance of the wallet for the specified AssetId
-        let balance = provider
-            .get_asset_balance(&wallet_address, *asset_id)
-            .await?;
+    // With --timeout, provider requests of init-dist and reclaim fail instead of hanging
+    let timeout_client = cli
+        .timeout
+        .map(|_| TimeoutClient::new(&provider, provider_pool.timeout()));
+    let client: &dyn ChainClient = match &timeout_client {
+        Some(client) => client,
+        None => &provider,
+    };
+    let deadline = cli.timeout.map(Duration::from_secs);
 
-        println!(
-            "HD Wallet {} balance: {} (in base units)",
-            hd_wallet_number, balance
-        );
+    if let Some(Command::Rotate {
+        offset,
+        amount,
+        output,
+        format,
+    }) = &cli.command
+    {
+        let amount = amount
+            .as_deref()
+            .map(|amount| units::parse_setting(amount, decimals))
+            .transpose()
+            .map_err(|e| format!("Invalid --amount: {}", e))?;
+        // Funds go back to the funding wallets the new wallets are paid from
+        let reclaim = ReclaimOptions {
+            gas_policy: GasPolicy::from_env(cli.prefund_gas)?,
+            assets: cli.asset_selection(group.as_ref(), provider.base_asset_id()),
+            destination: None,
+            wallets: WalletSelection::default(),
+        };
+        let result = rotate::rotate(
+            &ctx,
+            &main_wallet,
+            client,
+            offset.unwrap_or_else(|| rotate::next_offset(&ctx)),
+            amount,
+            &reclaim,
+            output.as_deref(),
+            *format,
+        )
+        .await;
+        if let Err(e) = result {
+            failure::report("Rotation", e.as_ref());
+            return Err(e);
+        }
+        return Ok(());
+    }
 
-        if balance > 0 {
-            // Calculate the amount to reclaim (e.g., 99.9% of the balance)
-            let reclaim_amount = ((balance as f64) * (RECLAIM_PERCENTAGE / 100.0)).round() as u64;
+    if queued {
+        let storage = ctx.sinks.storage.expect("checked above");
+        let policy = queue::RetryPolicy::from_env()?;
+        if cli.queue {
+            let plan = plan_run(
+                &cli,
+                &ctx,
+                &main_wallet,
+                &provider,
+                &settings,
+                group.as_ref(),
+                decimals,
+            )
+            .await?
+            .ok_or("--queue needs --init-dist, --cont-fund or --reclaim")?;
+            approval::check_unreviewed(&ctx, "This run", plan.total(&ctx.asset_id))?;
+            audit::record("plan_enqueued", &plan);
+            queue::enqueue(storage, &plan).await?;
+        } else if let Some(Command::Queue {
+            action: QueueCommand::RetryFailed,
+        }) = &cli.command
+        {
+            let retried = queue::retry_failed(storage).await?;
+            info!("Set {} failed jobs back to pending.", retried);
+        }
+        if let Err(e) = queue::work(&ctx, &main_wallet, client, policy).await {
+            failure::report("Work queue", e.as_ref());
+            return Err(e);
+        }
+    } else if let Some(path) = &cli.execute_plan {
+        let plan = TransferPlan::load(path)?;
+        approvals.check(&plan, plan.total(&ctx.asset_id), main_wallet.address())?;
+        audit::record("plan_executed", &plan);
+        if let Err(e) = execute_plan(&ctx, &main_wallet, &provider, &plan).await {
+            failure::report("Plan execution", e.as_ref());
+            return Err(e);
+        }
+    } else if let Some(path) = &cli.recipients {
+        info!("Starting distribution to {}...", path.display());
+        #[cfg(feature = "disperser")]
+        let result = if cli.disperser {
+            let disperser = Disperser::from_env(&main_wallet)?;
+            disperse_to_recipients(&ctx, &provider, path, &disperser).await
+        } else {
+            distribute_to_recipients(&ctx, &main_wallet, &provider, path).await
+        };
+        #[cfg(not(feature = "disperser"))]
+        let result = distribute_to_recipients(&ctx, &main_wallet, &provider, path).await;
+        if let Err(e) = result {
+            failure::report("Recipients distribution", e.as_ref());
+            return Err(e);
+        }
+    } else if cli.init_dist {
+        info!("Starting initial distribution...");
+        #[cfg(feature = "disperser")]
+        let result = if cli.disperser {
+            let disperser = Disperser::from_env(&main_wallet)?;
+            within(
+                deadline,
+                "Initial distribution",
+                disperse_initial_distribution(&ctx, client, &disperser),
+            )
+            .await
+        } else {
+            within(
+                deadline,
+                "Initial distribution",
+                initial_distribution(&ctx, &main_wallet, client),
+            )
+            .await
+        };
+        #[cfg(not(feature = "disperser"))]
+        let result = within(
+            deadline,
+            "Initial distribution",
+            initial_distribution(&ctx, &main_wallet, client),
+        )
+        .await;
+        // Whether or not it ran out, tell treasury ops what the main wallet needs
+        let refill = match provider
+            .get_asset_balance(main_wallet.address(), ctx.asset_id)
+            .await
+        {
+            Ok(balance) => RefillNeeded::check(
+                main_wallet.address(),
+                ctx.asset_id,
+                ctx.decimals,
+                balance,
+                settings.main_reserve,
+            ),
+            Err(e) => {
+                warn!("Could not check the main wallet against its reserve: {}", e);
+                None
+            }
+        };
+        if let Some(refill) = &refill {
+            warn!(
+                "Main wallet is {} short of its reserve: send it to {}.",
+                ctx.format_amount(refill.shortfall),
+                refill.main_wallet
+            );
+            ctx.sinks.refill_needed(refill).await;
+        }
+        if let Err(e) = result {
+            failure::report("Initial distribution", e.as_ref());
+            return Err(e);
+        }
+        if let Some(refill) = refill {
+            println!("{}", serde_json::to_string(&refill)?);
+            drop(run_lock);
+            exit(refill::EXIT_REFILL_NEEDED, None);
+        }
+    } else if cli.cont_fund && cli.once {
+        let outcome = fund::funding_once(
+            &ctx,
+            &main_wallet,
+            &provider,
+            provider_pool.timeout(),
+            &settings,
+        )
+        .await?;
+        drop(run_lock);
+        exit(outcome.exit_code(), None);
+    } else if cli.cont_fund {
+        let health = HealthState::default();
+        if cli.deadman_intervals > 0 {
+            let locks = run_lock.iter().map(|lock| lock.path().to_path_buf());
+            health.spawn_deadman_backstop(
+                fund::DEADMAN_BACKSTOP_GRACE,
+                deadman_exit(locks.collect()),
+            );
+        }
+        // Used by reclaims scheduled with RECLAIM_SCHEDULE
+        let reclaim = ReclaimOptions {
+            gas_policy: GasPolicy::from_env(cli.prefund_gas)?,
+            assets: cli.asset_selection(group.as_ref(), provider.base_asset_id()),
+            destination: cli.to.clone(),
+            wallets: cli.wallet_selection(decimals)?,
+        };
 
-            // Ensure that reclaim_amount is greater than zero
-            if reclaim_amount == 0 {
-                println!(
-                    "Reclaim amount for HD Wallet {} is too small to send.",
-                    hd_wallet_number
-                );
-                continue;
+        let funding = async {
+            if cli.fast {
+                fast::fast_funding(
+                    &ctx,
+                    &mut main_wallet,
+                    &mut provider_pool,
+                    provider.clone(),
+                    &health,
+                    &settings,
+                )
+                .await
+            } else {
+                continual_funding(
+                    &ctx,
+                    &mut main_wallet,
+                    &mut provider_pool,
+                    provider.clone(),
+                    &health,
+                    settings,
+                    &reclaim,
+                )
+                .await
             }
+        };
 
-            println!(
-                "Reclaiming {} units from HD Wallet {} to main wallet.",
-                reclaim_amount, hd_wallet_number
+        if cli.daemon {
+            let _pid_file = PidFile::create(&cli.pid_file)?;
+            let listener = TcpListener::bind(cli.health_addr).await?;
+            info!(
+                "Health endpoint listening on http://{}/healthz",
+                cli.health_addr
             );
-
-            // Send the reclaim amount back to the main wallet
-            send_funds(
-                &wallet,
-                &main_wallet.address().into(),
-                reclaim_amount,
-                provider,
-                asset_id,
-            )
-            .await?;
-            println!(
-                "Successfully reclaimed {} units from HD Wallet {}.",
-                reclaim_amount, hd_wallet_number
+            tokio::spawn(
+                serve_health(
+                    listener,
+                    vec![(String::new(), health.clone())],
+                    cli.health_max_age,
+                )
+                .in_current_span(),
             );
+            notify_systemd("READY=1");
+
+            info!("Starting continual funding in daemon mode...");
+            tokio::select! {
+                result = funding => result?,
+                _ = shutdown_signal() => {
+                    info!("Shutdown signal received, stopping continual funding.");
+                    notify_systemd("STOPPING=1");
+                }
+            }
         } else {
-            println!("HD Wallet {} has no funds to reclaim.", hd_wallet_number);
+            info!("Starting continual funding...");
+            funding.await?;
         }
+    } else if cli.reclaim {
+        let reclaim = ReclaimOptions {
+            gas_policy: GasPolicy::from_env(cli.prefund_gas)?,
+            assets: cli.asset_selection(group.as_ref(), provider.base_asset_id()),
+            destination: cli.to.clone(),
+            wallets: cli.wallet_selection(decimals)?,
+        };
+
+        info!("Starting fund reclamation...");
+        let isolation = tor.map(|proxy| tor::CircuitIsolation {
+            proxy,
+            provider_url: provider_pool.current_url().to_string(),
+            batch_size: cli.tor_batch_size,
+        });
+        if let Err(e) = within(deadline, "Reclaim", async {
+            reclaim::check_approval(&ctx, &main_wallet, client, &reclaim).await?;
+            reclaim_funds(&ctx, &main_wallet, client, &reclaim, isolation.as_ref()).await
+        })
+        .await
+        {
+            failure::report("Reclaim", e.as_ref());
+            return Err(e);
+        }
+    } else {
+        warn!("No valid command provided. Use --init-dist, --cont-fund, --reclaim, --execute-plan, or a subcommand (see --help).");
     }
 
-    println!("Fund reclamation completed.");
     Ok(())
 }
 
-async fn send_funds(
-    from_wallet: &WalletUnlocked,
-    to_address: &Bech32Address,
-    amount: u64,
+/// Ends the process with `exit_code` rather than by returning from `main`, after
+/// recording how the run ended in the audit log and flushing traces.
+fn exit(exit_code: i32, error: Option<&str>) -> ! {
+    audit::finished(exit_code, error);
+    telemetry::shutdown();
+    std::process::exit(exit_code)
+}
+
+/// What the deadman backstop does before it ends the process: releases `locks`, so
+/// the supervisor's restart can take them, and records the exit as [`exit`] does.
+fn deadman_exit(locks: Vec<PathBuf>) -> impl FnOnce() + Send + 'static {
+    move || {
+        for lock in &locks {
+            lock::release(lock);
+        }
+        audit::finished(daemon::EXIT_DEADMAN, Some("deadman switch"));
+        telemetry::shutdown();
+    }
+}
+
+/// Runs `what` to completion, or fails once `deadline` (`--timeout`) has passed.
+/// Transfers it already made are kept: a rerun picks up where it stopped.
+async fn within<T>(
+    deadline: Option<Duration>,
+    what: &str,
+    run: impl std::future::Future<Output = Result<T, Box<dyn Error>>>,
+) -> Result<T, Box<dyn Error>> {
+    let Some(deadline) = deadline else {
+        return run.await;
+    };
+    tokio::time::timeout(deadline, run).await.map_err(|_| {
+        format!(
+            "{} timed out after --timeout {}s; rerun it to continue",
+            what,
+            deadline.as_secs()
+        )
+    })?
+}
+
+/// Plans the `--init-dist`, `--cont-fund` cycle or `--reclaim` that `cli` asks for,
+/// if any.
+async fn plan_run(
+    cli: &Cli,
+    ctx: &Context<'_>,
+    main_wallet: &Funder,
     provider: &Provider,
-    asset_id: &AssetId,
-) -> Result<(), Box<dyn Error>> {
-    let from_address = from_wallet.address();
+    settings: &FundingSettings,
+    group: Option<&WalletGroup>,
+    decimals: u32,
+) -> Result<Option<TransferPlan>, Box<dyn Error>> {
+    let (command, steps) = if cli.init_dist {
+        (
+            "init-dist",
+            plan_initial_distribution(ctx, main_wallet, provider).await?,
+        )
+    } else if cli.cont_fund {
+        (
+            "cont-fund",
+            plan_continual_funding(ctx, main_wallet, provider, settings).await?,
+        )
+    } else if cli.reclaim {
+        let reclaim = ReclaimOptions {
+            gas_policy: GasPolicy::from_env(cli.prefund_gas)?,
+            assets: cli.asset_selection(group, provider.base_asset_id()),
+            destination: cli.to.clone(),
+            wallets: cli.wallet_selection(decimals)?,
+        };
+        (
+            "reclaim",
+            plan_reclaim(ctx, main_wallet, provider, &reclaim).await?,
+        )
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(TransferPlan::new(command, main_wallet, steps)))
+}
+
+/// One fleet of `FLEETS` under continual funding.
+struct FleetRun<'a> {
+    name: String,
+    ctx: Context<'a>,
+    main_wallet: Funder,
+    provider_pool: ProviderPool,
+    provider: Provider,
+    health: HealthState,
+    settings: FundingSettings,
+    /// Held for as long as the fleet runs.
+    _lock: RunLock,
+}
 
-    // Query the balance of the specified AssetId for the from_wallet
-    let balance = provider.get_asset_balance(&from_address, *asset_id).await?;
+/// Runs continual funding for every fleet concurrently. Each fleet has its own
+/// provider connection, main wallet (its wallet 0), health and settings; a fleet
+/// that stops with an error leaves the others running.
+async fn run_fleets(cli: &Cli, fleets: Vec<FleetConfig>) -> Result<(), Box<dyn Error>> {
+    audit::open_from_env();
+    let storage = match env::var("STORAGE_URL") {
+        Ok(url) => Some(storage::open(&url).await?),
+        Err(_) => None,
+    };
 
+    let mut runs = Vec::with_capacity(fleets.len());
+    for config in fleets {
+        let settings = FundingSettings::for_fleet(cli.callback_url.clone(), &config.name)?;
+        let fleet = Fleet::new(config.read_mnemonic()?, config.derivation).named(&config.name);
+        let asset_id = match config.asset_id {
+            Some(asset_id) => asset_id,
+            None => {
+                let id = env::var("ETH_ASSET_ID").map_err(|_| {
+                    format!(
+                        "Fleet '{}' has no asset_id and ETH_ASSET_ID is not set",
+                        config.name
+                    )
+                })?;
+                AssetId::from_str(&id)
+                    .map_err(|_| format!("Invalid ETH_ASSET_ID format: {}", id))?
+            }
+        };
+        let decimals = match config.decimals.or_else(|| assets::decimals(&asset_id)) {
+            Some(decimals) => decimals,
+            None => units::decimals_from_env()?,
+        };
+        let settings = settings
+            .priced(&mut PriceOracle::from_env()?, decimals)
+            .await?;
+        let inbound_min_amount = cli
+            .inbound_min_amount
+            .as_deref()
+            .map(|amount| units::parse_setting(amount, decimals))
+            .transpose()
+            .map_err(|e| format!("Invalid --inbound-min-amount: {}", e))?;
+        let bus = match env::var("EVENT_BUS_URL") {
+            Ok(url) => {
+                let topic =
+                    env::var("EVENT_BUS_TOPIC").unwrap_or_else(|_| bus::DEFAULT_TOPIC.into());
+                Some(bus::EventBus::connect(&url, &topic).await?)
+            }
+            Err(_) => None,
+        };
+
+        let mut provider_pool = ProviderPool::from_env(cli.network)?;
+        let provider = provider_pool.connect().await?;
+        assets::register_base(*provider.base_asset_id());
+        assets::discover(&[asset_id]).await?;
+        assets::check_decimals(&asset_id, decimals)
+            .map_err(|e| format!("Fleet '{}': {}", config.name, e))?;
+        let main_wallet = Funder::from(fleet.wallet(0, Some(provider.clone()))?);
+        let lock = RunLock::acquire(&cli.lock_dir, main_wallet.address(), "cont-fund", cli.force)?;
+        info!(
+            "Fleet '{}': main wallet {}, {} HD wallets, asset {}, derivation path {}",
+            config.name,
+            main_wallet.address(),
+            config.wallets,
+            asset_id,
+            config.derivation
+        );
+        audit::record(
+            "run_started",
+            &Manifest::new("cont-fund")
+                .parameter("fleet", &config.name)
+                .parameter("main_wallet", main_wallet.address().to_string())
+                .parameter("wallets", config.wallets)
+                .parameter("asset_id", asset_id.to_string())
+                .parameter("derivation_path", config.derivation.to_string())
+                .parameter("profile", profiles::active().unwrap_or_default()),
+        );
+
+        let ctx = Context {
+            fleet,
+            asset_id,
+            decimals,
+            number_of_wallets: config.wallets,
+            funding_sources: FundingSources::default(),
+            relay: false,
+            jitter: cli.jitter()?,
+            approval_limit: ApprovalPolicy::from_env(decimals)?.limit,
+            tx_policies: cli.tx_policies.to_policies(),
+            max_in_flight: cli.max_in_flight,
+            reclaim_concurrency: cli.reclaim_concurrency,
+            balance_concurrency: cli.balance_concurrency,
+            split_coins: cli.split_coins,
+            in_flight_timeout: Duration::from_secs(cli.in_flight_timeout),
+            resubmit_tip: cli.resubmit_tip,
+            max_cycle_duration: Duration::from_secs(cli.max_cycle_duration),
+            deadman_intervals: cli.deadman_intervals,
+            address_book: AddressBook::from_env()?,
+            exclusions: Exclusions::parse(&config.excluded, "")?,
+            inbound_check: cli.skip_recent_inbound.map(|lookback_blocks| InboundCheck {
+                lookback_blocks,
+                min_amount: inbound_min_amount,
+            }),
+            sinks: Sinks {
+                storage: storage.as_deref(),
+                webhook: RwLock::new(settings.callback_url.clone().map(Webhook::new)),
+                tag: cli.tag.clone(),
+                label: cli.label.clone(),
+                bus,
+            },
+        };
+        runs.push(FleetRun {
+            name: config.name,
+            ctx,
+            main_wallet,
+            provider_pool,
+            provider,
+            health: HealthState::default(),
+            settings,
+            _lock: lock,
+        });
+    }
+    if cli.deadman_intervals > 0 {
+        // The backstop ends the process, so it releases every fleet's lock
+        let locks: Vec<PathBuf> = runs
+            .iter()
+            .map(|run| run._lock.path().to_path_buf())
+            .collect();
+        for run in &runs {
+            run.health
+                .spawn_deadman_backstop(fund::DEADMAN_BACKSTOP_GRACE, deadman_exit(locks.clone()));
+        }
+    }
+
+    let reclaim = ReclaimOptions {
+        gas_policy: GasPolicy::from_env(cli.prefund_gas)?,
+        assets: cli.asset_selection(None, runs[0].provider.base_asset_id()),
+        destination: cli.to.clone(),
+        wallets: cli.wallet_selection(units::decimals_from_env()?)?,
+    };
+    let health: Vec<(String, HealthState)> = runs
+        .iter()
+        .map(|run| (run.name.clone(), run.health.clone()))
+        .collect();
+    let reclaim = &reclaim;
+    let funding = futures::future::join_all(runs.iter_mut().map(|run| {
+        let span = info_span!("fleet", name = %run.name);
+        async move {
+            let result = continual_funding(
+                &run.ctx,
+                &mut run.main_wallet,
+                &mut run.provider_pool,
+                run.provider.clone(),
+                &run.health,
+                run.settings.clone(),
+                reclaim,
+            )
+            .await;
+            if let Err(e) = &result {
+                failure::report(
+                    &format!("Continual funding of fleet '{}'", run.name),
+                    e.as_ref(),
+                );
+            }
+            result
+        }
+        .instrument(span)
+    }));
+
+    info!("Starting continual funding of {} fleets...", health.len());
+    let results = if cli.daemon {
+        let _pid_file = PidFile::create(&cli.pid_file)?;
+        let listener = TcpListener::bind(cli.health_addr).await?;
+        info!(
+            "Health endpoint listening on http://{}/healthz",
+            cli.health_addr
+        );
+        tokio::spawn(serve_health(listener, health, cli.health_max_age).in_current_span());
+        notify_systemd("READY=1");
+        tokio::select! {
+            results = funding => results,
+            _ = shutdown_signal() => {
+                info!("Shutdown signal received, stopping continual funding.");
+                notify_systemd("STOPPING=1");
+                return Ok(());
+            }
+        }
+    } else {
+        funding.await
+    };
+    results.into_iter().collect()
+}
+
+/// `audit verify`: checks the log at `file`, or `AUDIT_LOG`, and prints how many
+/// entries it holds and the hash of the last, to compare with a copy kept elsewhere.
+fn verify_audit_log(file: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let path = match file {
+        Some(file) => file.to_path_buf(),
+        None => env::var("AUDIT_LOG")
+            .map_err(|_| "audit verify needs a file or AUDIT_LOG")?
+            .into(),
+    };
+    let key = env::var("AUDIT_HMAC_KEY").ok().map(SecretString::new);
+    let (entries, last_hash) = audit::verify(&path, key.as_ref())
+        .map_err(|e| format!("Audit log {} failed verification: {}", path.display(), e))?;
     println!(
-        "Balance of AssetId {:?} for {}: {}",
-        asset_id, from_address, balance
+        "Audit log {} is intact: {} entries{}, last hash {}",
+        path.display(),
+        entries,
+        if key.is_some() { ", all signed" } else { "" },
+        last_hash
     );
+    Ok(())
+}
 
-    // Ensure there are sufficient funds before attempting the transfer
-    if balance < amount {
-        return Err(format!(
-            "Insufficient funds: attempted to send {}, but balance is {}",
-            amount, balance
-        )
-        .into());
+/// The asset the funding commands distribute: a group's first asset takes the
+/// place of `ETH_ASSET_ID`.
+fn main_asset_id(group: Option<&WalletGroup>) -> Result<AssetId, Box<dyn Error>> {
+    match group.and_then(|group| group.assets.first()) {
+        Some(asset_id) => Ok(*asset_id),
+        None => {
+            let eth_asset_id_str = env::var("ETH_ASSET_ID")
+                .map_err(|_| "ETH_ASSET_ID not set in the environment".to_string())?;
+
+            // Parse the ETH_ASSET_ID from the environment variable
+            Ok(AssetId::from_str(&eth_asset_id_str)
+                .map_err(|_| format!("Invalid ETH_ASSET_ID format: {}", eth_asset_id_str))?)
+        }
     }
+}
 
-    // Perform the transfer
-    let (tx_id, _receipts) = from_wallet
-        .transfer(to_address, amount, *asset_id, TxPolicies::default())
-        .await?;
+impl Cli {
+    /// `--all-assets`/`--assets`, else a group's asset list (with the base asset for gas
+    /// leftovers), else `ETH_ASSET_ID` and the base asset.
+    fn asset_selection(&self, group: Option<&WalletGroup>, base_asset: &AssetId) -> AssetSelection {
+        if let Some(group) = group.filter(|group| group.assets.len() > 1 && !self.all_assets) {
+            let mut allowlist = group.assets.clone();
+            allowlist.push(*base_asset);
+            return AssetSelection::All {
+                allowlist: Some(allowlist),
+            };
+        }
+        match self.all_assets {
+            true if self.assets.is_empty() => AssetSelection::All { allowlist: None },
+            true => AssetSelection::All {
+                allowlist: Some(self.assets.clone()),
+            },
+            false => AssetSelection::Configured,
+        }
+    }
+
+    /// `--largest-first`, `--min-balance`, `--up-to`, `--reclaim-percent` and `--leave`,
+    /// amounts in ETH_ASSET_ID's `decimals`.
+    fn wallet_selection(&self, decimals: u32) -> Result<WalletSelection, Box<dyn Error>> {
+        let amount = |flag: &str, value: &Option<String>| -> Result<Option<u64>, Box<dyn Error>> {
+            value
+                .as_deref()
+                .map(|value| units::parse_setting(value, decimals))
+                .transpose()
+                .map_err(|e| format!("Invalid {}: {}", flag, e).into())
+        };
+        let basis_points = self
+            .reclaim_percent
+            .as_deref()
+            .map(|percent| {
+                // Hundredths of a percent, so 99.9 is 9990
+                match units::parse_amount(percent, 2) {
+                    Ok(basis_points) if (1..=10_000).contains(&basis_points) => Ok(basis_points),
+                    Ok(_) => Err("must be greater than 0 and at most 100".to_string()),
+                    Err(e) => Err(e),
+                }
+                .map_err(|e| format!("Invalid --reclaim-percent: {}", e))
+            })
+            .transpose()?;
+        Ok(WalletSelection {
+            largest_first: self.largest_first,
+            min_balance: amount("--min-balance", &self.min_balance)?.unwrap_or(0),
+            up_to: amount("--up-to", &self.up_to)?,
+            basis_points,
+            leave: amount("--leave", &self.leave)?.unwrap_or(0),
+        })
+    }
 
-    println!("Sent transaction: {:?}", tx_id);
+    /// `--jitter` and `--jitter-delay`.
+    fn jitter(&self) -> Result<Jitter, Box<dyn Error>> {
+        let basis_points = match self.jitter.as_deref() {
+            // Hundredths of a percent, so 2.5% is 250
+            Some(percent) => match units::parse_amount(percent.trim().trim_end_matches('%'), 2) {
+                Ok(basis_points) if (1..=jitter::MAX_BASIS_POINTS).contains(&basis_points) => {
+                    Ok(basis_points)
+                }
+                Ok(_) => Err("must be greater than 0 and at most 50%".to_string()),
+                Err(e) => Err(e),
+            }
+            .map_err(|e| format!("Invalid --jitter: {}", e))?,
+            None => 0,
+        };
+        Ok(Jitter {
+            basis_points,
+            max_delay: Duration::from_secs(self.jitter_delay),
+        })
+    }
+}
 
+/// Rejects options whose subsystem was left out of this build by cargo features.
+fn check_features(cli: &Cli) -> Result<(), Box<dyn Error>> {
+    #[cfg(not(feature = "api"))]
+    if matches!(cli.command, Some(Command::Serve { .. })) {
+        return Err("`serve` needs a build with the `api` feature".into());
+    }
+    #[cfg(not(feature = "tor"))]
+    if cli.tor.is_some() {
+        return Err("--tor needs a build with the `tor` feature".into());
+    }
+    #[cfg(not(feature = "disperser"))]
+    if cli.disperser || matches!(cli.command, Some(Command::DeployDisperser { .. })) {
+        return Err(
+            "--disperser and deploy-disperser need a build with the `disperser` feature".into(),
+        );
+    }
+    #[cfg(all(windows, not(feature = "windows-service")))]
+    if matches!(cli.command, Some(Command::Service { .. })) {
+        return Err("`service` needs a build with the `windows-service` feature on Windows".into());
+    }
+    #[cfg(not(feature = "simulate"))]
+    if cli.simulate {
+        return Err("--simulate needs a build with the `simulate` feature".into());
+    }
+    #[cfg(not(feature = "parquet"))]
+    if matches!(
+        cli.command,
+        Some(Command::Export {
+            format: AccountingFormat::Parquet,
+            ..
+        })
+    ) {
+        return Err("export --format parquet needs a build with the `parquet` feature".into());
+    }
+    let _ = cli;
     Ok(())
 }
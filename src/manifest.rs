@@ -0,0 +1,112 @@
+use crate::context::Context;
+use fuels::{accounts::provider::Provider, crypto::Hasher};
+use reqwest::Url;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Describes the binary, configuration and chain a report was produced with, so
+/// any historical report can be reproduced and attributed.
+#[derive(Debug, Serialize)]
+pub struct Manifest {
+    pub version: &'static str,
+    pub git_hash: &'static str,
+    /// Command that produced the report, e.g. `cont-fund` or `plan`.
+    pub strategy: String,
+    /// Provider URL with credentials and query string removed.
+    pub provider: Option<String>,
+    pub chain_id: Option<u64>,
+    /// Every non-secret setting the command ran with.
+    pub parameters: BTreeMap<String, String>,
+    /// Hash of `strategy` and `parameters`; equal for runs with the same configuration.
+    pub config_fingerprint: String,
+    pub generated_at: u64,
+}
+
+impl Manifest {
+    pub fn new(strategy: &str) -> Self {
+        let mut manifest = Self {
+            version: env!("CARGO_PKG_VERSION"),
+            git_hash: option_env!("GIT_HASH").unwrap_or("unknown"),
+            strategy: strategy.to_string(),
+            provider: None,
+            chain_id: None,
+            parameters: BTreeMap::new(),
+            config_fingerprint: String::new(),
+            generated_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        };
+        manifest.fingerprint();
+        manifest
+    }
+
+    /// Manifest of a chain command, with the run configuration taken from `ctx`.
+    pub fn for_run(
+        strategy: &str,
+        ctx: &Context<'_>,
+        provider_url: &str,
+        provider: &Provider,
+    ) -> Self {
+        let mut manifest = Self::new(strategy);
+        manifest.provider = Some(redact_url(provider_url));
+        manifest.chain_id = Some(u64::from(provider.chain_id()));
+        manifest
+            .parameter("asset_id", ctx.asset_id)
+            .parameter("number_of_wallets", ctx.number_of_wallets)
+            .parameter("derivation", ctx.fleet.derivation)
+            .parameter("funding_sources", format!("{:?}", ctx.funding_sources))
+            .parameter("tx_policies", format!("{:?}", ctx.tx_policies))
+            .parameter("max_in_flight", ctx.max_in_flight)
+            .parameter("reclaim_concurrency", ctx.reclaim_concurrency)
+            .parameter("split_coins", ctx.split_coins)
+            .parameter("in_flight_timeout_secs", ctx.in_flight_timeout.as_secs())
+            .parameter("resubmit_tip", ctx.resubmit_tip.unwrap_or_default())
+            .parameter("max_cycle_secs", ctx.max_cycle_duration.as_secs())
+            .parameter("deadman_intervals", ctx.deadman_intervals)
+            .parameter("inbound_check", format!("{:?}", ctx.inbound_check))
+            .parameter("tag", ctx.sinks.tag.as_deref().unwrap_or_default())
+    }
+
+    /// Records a setting and updates the fingerprint.
+    pub fn parameter(mut self, name: &str, value: impl ToString) -> Self {
+        self.parameters.insert(name.to_string(), value.to_string());
+        self.fingerprint();
+        self
+    }
+
+    /// Prints the manifest as a `#` comment line heading a text or CSV report.
+    pub fn print_header(&self) {
+        println!(
+            "# manifest {}",
+            serde_json::to_string(self).expect("manifest serializes")
+        );
+    }
+
+    fn fingerprint(&mut self) {
+        let mut config = self.strategy.clone();
+        for (name, value) in &self.parameters {
+            config.push_str(&format!("\n{}={}", name, value));
+        }
+        let hash = Hasher::hash(config.as_bytes()).to_string();
+        self.config_fingerprint = hash.trim_start_matches("0x")[..16].to_string();
+    }
+}
+
+/// Strips credentials and the query string (where API keys usually live) from a URL.
+pub fn redact_url(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut url) => {
+            let _ = url.set_username("");
+            let _ = url.set_password(None);
+            if url.query().is_some() {
+                url.set_query(Some("redacted"));
+            }
+            url.to_string()
+        }
+        Err(_) => "<unparsable>".to_string(),
+    }
+}
@@ -0,0 +1,136 @@
+use clap::Args;
+use secrecy::{zeroize::Zeroize, SecretString};
+use std::{
+    env,
+    error::Error,
+    fs,
+    io::{self, BufRead, IsTerminal},
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+
+/// Keyring service the mnemonic is stored under by `--mnemonic-keyring`.
+pub const KEYRING_SERVICE: &str = "fund_distributor";
+
+/// Where the mnemonic is read from; `MNEMONIC` from the environment if none is given.
+#[derive(Args, Debug)]
+pub struct MnemonicArgs {
+    /// Read the mnemonic from this file instead of MNEMONIC.
+    #[clap(
+        long = "mnemonic-file",
+        env = "MNEMONIC_FILE",
+        conflicts_with_all = &["mnemonic_stdin", "mnemonic_keyring"]
+    )]
+    pub mnemonic_file: Option<PathBuf>,
+
+    /// Read the mnemonic from the first line of stdin instead of MNEMONIC.
+    #[clap(long = "mnemonic-stdin", conflicts_with = "mnemonic_keyring")]
+    pub mnemonic_stdin: bool,
+
+    /// Read the mnemonic from the OS keyring (Secret Service, macOS Keychain or
+    /// Windows Credential Manager) entry of this account under the
+    /// `fund_distributor` service.
+    #[clap(long = "mnemonic-keyring", env = "MNEMONIC_KEYRING")]
+    pub mnemonic_keyring: Option<String>,
+}
+
+impl MnemonicArgs {
+    /// Reads the mnemonic from the selected source. `MNEMONIC` is removed from the
+    /// process environment either way, so child processes and dumps never see it.
+    pub fn read(&self) -> Result<SecretString, Box<dyn Error>> {
+        let from_env = env::var("MNEMONIC").ok();
+        env::remove_var("MNEMONIC");
+
+        if let Some(path) = &self.mnemonic_file {
+            return read_file(path);
+        }
+        if self.mnemonic_stdin {
+            return read_stdin();
+        }
+        if let Some(account) = &self.mnemonic_keyring {
+            return read_keyring(account);
+        }
+        from_env.map(SecretString::new).ok_or_else(|| {
+            "MNEMONIC not set in the environment (or use --mnemonic-file, --mnemonic-stdin or --mnemonic-keyring)"
+                .into()
+        })
+    }
+}
+
+pub fn read_file(path: &Path) -> Result<SecretString, Box<dyn Error>> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(path)?.permissions().mode();
+        if mode & 0o077 != 0 {
+            warn!(
+                "Mnemonic file {} is accessible by other users (mode {:o}); consider chmod 600",
+                path.display(),
+                mode & 0o777
+            );
+        }
+    }
+    let mut contents = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read mnemonic file {}: {}", path.display(), e))?;
+    let mnemonic = non_empty(&contents, &path.display().to_string());
+    contents.zeroize();
+    mnemonic
+}
+
+fn read_stdin() -> Result<SecretString, Box<dyn Error>> {
+    let stdin = io::stdin();
+    if stdin.is_terminal() {
+        // No echo suppression: prefer piping it in (e.g. from a password manager)
+        eprintln!("Enter mnemonic:");
+    }
+    let mut line = String::new();
+    stdin.lock().read_line(&mut line)?;
+    let mnemonic = non_empty(&line, "stdin");
+    line.zeroize();
+    mnemonic
+}
+
+#[cfg(feature = "keyring")]
+fn read_keyring(account: &str) -> Result<SecretString, Box<dyn Error>> {
+    keyring::Entry::new(KEYRING_SERVICE, account)
+        .and_then(|entry| entry.get_password())
+        .map(SecretString::new)
+        .map_err(|e| {
+            format!(
+                "Failed to read the mnemonic from keyring entry {}/{}: {}",
+                KEYRING_SERVICE, account, e
+            )
+            .into()
+        })
+}
+
+#[cfg(not(feature = "keyring"))]
+fn read_keyring(_account: &str) -> Result<SecretString, Box<dyn Error>> {
+    Err("--mnemonic-keyring needs a build with the `keyring` feature".into())
+}
+
+fn non_empty(contents: &str, source: &str) -> Result<SecretString, Box<dyn Error>> {
+    match contents.trim() {
+        "" => Err(format!("No mnemonic found in {}", source).into()),
+        mnemonic => Ok(SecretString::new(mnemonic.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secrecy::ExposeSecret;
+
+    #[test]
+    fn reads_trimmed_mnemonic_from_file() {
+        let path = env::temp_dir().join(format!("mnemonic-{}.txt", std::process::id()));
+        fs::write(&path, "  test test junk\n").unwrap();
+        let mnemonic = read_file(&path);
+        fs::write(&path, "\n").unwrap();
+        let empty = read_file(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(mnemonic.unwrap().expose_secret(), "test test junk");
+        assert!(empty.is_err());
+    }
+}
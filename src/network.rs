@@ -0,0 +1,44 @@
+use clap::ValueEnum;
+use fuels::accounts::provider::Provider;
+use std::error::Error;
+use tracing::info;
+
+/// Fuel network a run is expected to target, checked against the provider's chain id.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Devnet,
+}
+
+impl Network {
+    pub fn chain_id(self) -> u64 {
+        match self {
+            Network::Mainnet => 9889,
+            Network::Testnet | Network::Devnet => 0,
+        }
+    }
+
+    /// Fails unless `provider` serves this network's chain.
+    pub async fn verify(self, provider: &Provider) -> Result<(), Box<dyn Error>> {
+        let chain_id = u64::from(provider.chain_id());
+        let chain_name = provider.chain_info().await?.name;
+
+        if chain_id != self.chain_id() {
+            return Err(format!(
+                "Provider serves chain '{}' (chain id {}), but --network {:?} expects chain id {}; refusing to continue",
+                chain_name,
+                chain_id,
+                self,
+                self.chain_id()
+            )
+            .into());
+        }
+
+        info!(
+            "Network check passed: chain '{}' (chain id {})",
+            chain_name, chain_id
+        );
+        Ok(())
+    }
+}
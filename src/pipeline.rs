@@ -0,0 +1,376 @@
+use crate::{
+    chain::ChainClient,
+    context::Context,
+    signer::Funder,
+    storage::TransferRecord,
+    transfer::{await_confirmation, verify_credit},
+};
+use fuels::types::{bech32::Bech32Address, AssetId, TxId};
+use std::{collections::VecDeque, error::Error};
+use tracing::{debug, debug_span, info, warn, Instrument};
+
+/// Submits transfers while earlier ones are still awaiting confirmation, keeping
+/// at most `ctx.max_in_flight` of them pending at a time.
+///
+/// Confirmations are awaited oldest first, so transfers confirm in submission
+/// order and each one is reported to the sinks once it is included. Transfers
+/// still pending after the confirmation timeout are set aside as unconfirmed.
+pub struct Pipeline<'c, 'a> {
+    ctx: &'c Context<'a>,
+    client: &'c dyn ChainClient,
+    in_flight: VecDeque<(TxId, TransferRecord)>,
+    unconfirmed: Vec<(TxId, TransferRecord)>,
+    fees: u64,
+    /// Label of every transfer submitted.
+    label: Option<String>,
+}
+
+impl<'c, 'a> Pipeline<'c, 'a> {
+    pub fn new(ctx: &'c Context<'a>, client: &'c dyn ChainClient) -> Self {
+        Self {
+            ctx,
+            client,
+            in_flight: VecDeque::new(),
+            unconfirmed: Vec::new(),
+            fees: 0,
+            label: None,
+        }
+    }
+
+    /// Labels every transfer submitted with `label` (see [`TransferRecord::label`]).
+    pub fn labelled(mut self, label: impl ToString) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    /// Submits a transfer, first waiting for confirmations if the window is full
+    /// or the sender's spendable coins are all held by pending transfers.
+    ///
+    /// Returns the records of any transfers that confirmed in the meantime.
+    pub async fn submit(
+        &mut self,
+        command: &str,
+        wallet_index: usize,
+        from_wallet: &Funder,
+        to_address: &Bech32Address,
+        amount: u64,
+        asset_id: &AssetId,
+    ) -> Result<Vec<TransferRecord>, Box<dyn Error>> {
+        self.submit_record(
+            command,
+            Some(wallet_index),
+            from_wallet,
+            to_address,
+            amount,
+            asset_id,
+            None,
+        )
+        .await
+    }
+
+    /// Like [`Pipeline::submit`], for an external recipient rather than an HD wallet;
+    /// `memo` is checked against the address book and recorded with the transfer.
+    pub async fn submit_to_recipient(
+        &mut self,
+        command: &str,
+        from_wallet: &Funder,
+        to_address: &Bech32Address,
+        amount: u64,
+        asset_id: &AssetId,
+        memo: Option<&str>,
+    ) -> Result<Vec<TransferRecord>, Box<dyn Error>> {
+        self.submit_record(
+            command,
+            None,
+            from_wallet,
+            to_address,
+            amount,
+            asset_id,
+            memo,
+        )
+        .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn submit_record(
+        &mut self,
+        command: &str,
+        wallet_index: Option<usize>,
+        from_wallet: &Funder,
+        to_address: &Bech32Address,
+        amount: u64,
+        asset_id: &AssetId,
+        memo: Option<&str>,
+    ) -> Result<Vec<TransferRecord>, Box<dyn Error>> {
+        self.ctx
+            .address_book
+            .check(to_address, Some(asset_id), amount, memo)?;
+
+        let mut confirmed = Vec::new();
+        while self.in_flight.len() >= self.ctx.max_in_flight.max(1) {
+            confirmed.extend(self.confirm_oldest().await?);
+        }
+        // --jitter-delay: not every transfer right after the last
+        self.ctx.jitter.pause().await;
+
+        loop {
+            match self
+                .client
+                .submit_transfer(
+                    from_wallet,
+                    to_address,
+                    amount,
+                    asset_id,
+                    self.ctx.tx_policies,
+                )
+                .await
+            {
+                Ok(tx_id) => {
+                    info!("Submitted transaction: {:?}", tx_id);
+                    let mut record = TransferRecord::new(
+                        command,
+                        wallet_index,
+                        from_wallet.address(),
+                        to_address,
+                        asset_id,
+                        amount,
+                        tx_id,
+                    );
+                    record.label = self.label.clone();
+                    record.memo = memo.map(str::to_string);
+                    self.in_flight.push_back((tx_id, record));
+                    return Ok(confirmed);
+                }
+                // Change from pending transfers only becomes spendable once they confirm
+                Err(e) if !self.in_flight.is_empty() => {
+                    debug!(
+                        "Submission deferred until a pending transfer confirms: {}",
+                        e
+                    );
+                    confirmed.extend(self.confirm_oldest().await?);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Waits for every pending transfer and returns the records of those that confirmed.
+    pub async fn finish(&mut self) -> Result<Vec<TransferRecord>, Box<dyn Error>> {
+        let mut confirmed = Vec::new();
+        while !self.in_flight.is_empty() {
+            confirmed.extend(self.confirm_oldest().await?);
+        }
+        Ok(confirmed)
+    }
+
+    /// Fees paid by the transfers confirmed so far, in base units of the base asset.
+    pub fn fees_paid(&self) -> u64 {
+        self.fees
+    }
+
+    /// The record of the most recently submitted transfer still awaiting confirmation.
+    pub fn last_submitted(&self) -> Option<&TransferRecord> {
+        self.in_flight.back().map(|(_, record)| record)
+    }
+
+    /// Transfers that did not confirm within the timeout and may still land.
+    pub fn take_unconfirmed(&mut self) -> Vec<(TxId, TransferRecord)> {
+        std::mem::take(&mut self.unconfirmed)
+    }
+
+    async fn confirm_oldest(&mut self) -> Result<Option<TransferRecord>, Box<dyn Error>> {
+        let (tx_id, record) = self
+            .in_flight
+            .pop_front()
+            .expect("confirm_oldest is only called with transfers in flight");
+        let confirmation = await_confirmation(self.client, &tx_id)
+            .instrument(debug_span!(
+                "await_confirmation",
+                %tx_id,
+                wallet = record.wallet_index,
+                amount = record.amount
+            ))
+            .await?;
+        match confirmation {
+            Some(fee) => {
+                info!("Confirmed transaction: {:?}", tx_id);
+                self.fees += fee;
+                verify_credit(self.ctx, self.client, &record).await;
+                self.ctx.sinks.transfer_confirmed(record.clone(), fee).await;
+                Ok(Some(record))
+            }
+            None => {
+                warn!(
+                    "Transaction {:?} is not confirmed yet, still in flight",
+                    tx_id
+                );
+                self.unconfirmed.push((tx_id, record));
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::mock::{address, base_asset, test_context, MockChain, MOCK_FEE};
+    use std::str::FromStr;
+
+    fn funded(ctx: &Context<'_>) -> (MockChain, Funder) {
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(main_wallet.address(), base_asset(), 100_000);
+        (chain, main_wallet)
+    }
+
+    #[tokio::test]
+    async fn keeps_at_most_the_window_in_flight() {
+        let mut ctx = test_context(4, base_asset());
+        ctx.max_in_flight = 2;
+        let (chain, main_wallet) = funded(&ctx);
+        let mut pipeline = Pipeline::new(&ctx, &chain);
+
+        let mut tx_ids = Vec::new();
+        for index in 1..3 {
+            let confirmed = pipeline
+                .submit(
+                    "cont-fund",
+                    index,
+                    &main_wallet,
+                    &address(&ctx, index),
+                    1_000,
+                    &base_asset(),
+                )
+                .await
+                .unwrap();
+            assert!(confirmed.is_empty());
+            let tx_id = TxId::from_str(&pipeline.last_submitted().unwrap().tx_id).unwrap();
+            chain.set_pending(tx_id);
+            tx_ids.push(tx_id);
+        }
+
+        // The window is full: the third transfer waits for the oldest to confirm
+        chain.confirm(&tx_ids[0]);
+        let confirmed = pipeline
+            .submit(
+                "cont-fund",
+                3,
+                &main_wallet,
+                &address(&ctx, 3),
+                1_000,
+                &base_asset(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(confirmed.len(), 1);
+        assert_eq!(confirmed[0].wallet_index, Some(1));
+        assert_eq!(chain.transfers().len(), 3);
+
+        chain.confirm(&tx_ids[1]);
+        let confirmed = pipeline.finish().await.unwrap();
+        assert_eq!(
+            confirmed
+                .iter()
+                .map(|record| record.wallet_index)
+                .collect::<Vec<_>>(),
+            vec![Some(2), Some(3)]
+        );
+        assert_eq!(pipeline.fees_paid(), 3 * MOCK_FEE);
+        assert!(pipeline.take_unconfirmed().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sets_aside_transfers_that_do_not_confirm() {
+        let ctx = test_context(3, base_asset());
+        let (chain, main_wallet) = funded(&ctx);
+        let mut pipeline = Pipeline::new(&ctx, &chain);
+
+        for index in 1..3 {
+            pipeline
+                .submit(
+                    "cont-fund",
+                    index,
+                    &main_wallet,
+                    &address(&ctx, index),
+                    1_000,
+                    &base_asset(),
+                )
+                .await
+                .unwrap();
+            if index == 1 {
+                let record = pipeline.last_submitted().unwrap();
+                chain.set_pending(TxId::from_str(&record.tx_id).unwrap());
+            }
+        }
+
+        // Wallet 1's transfer is still pending after the confirmation timeout
+        let confirmed = pipeline.finish().await.unwrap();
+        assert_eq!(confirmed.len(), 1);
+        assert_eq!(confirmed[0].wallet_index, Some(2));
+        let unconfirmed = pipeline.take_unconfirmed();
+        assert_eq!(unconfirmed.len(), 1);
+        assert_eq!(unconfirmed[0].1.wallet_index, Some(1));
+        assert_eq!(unconfirmed[0].0.to_string(), unconfirmed[0].1.tx_id);
+        assert_eq!(pipeline.fees_paid(), MOCK_FEE);
+        assert!(pipeline.take_unconfirmed().is_empty());
+    }
+
+    #[tokio::test]
+    async fn retries_a_rejected_transfer_without_resubmitting_accepted_ones() {
+        let mut ctx = test_context(3, base_asset());
+        ctx.max_in_flight = 4;
+        let (chain, main_wallet) = funded(&ctx);
+        let mut pipeline = Pipeline::new(&ctx, &chain);
+
+        pipeline
+            .submit(
+                "cont-fund",
+                1,
+                &main_wallet,
+                &address(&ctx, 1),
+                1_000,
+                &base_asset(),
+            )
+            .await
+            .unwrap();
+
+        // The node rejects the next transfer until the first one's change is spendable
+        chain.fail_transfers_from(main_wallet.address(), 1);
+        let confirmed = pipeline
+            .submit(
+                "cont-fund",
+                2,
+                &main_wallet,
+                &address(&ctx, 2),
+                1_000,
+                &base_asset(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(confirmed.len(), 1);
+        assert_eq!(confirmed[0].wallet_index, Some(1));
+        pipeline.finish().await.unwrap();
+
+        let transfers = chain.transfers();
+        assert_eq!(transfers.len(), 2);
+        assert_eq!(transfers[0].to, address(&ctx, 1));
+        assert_eq!(transfers[1].to, address(&ctx, 2));
+        assert_eq!(chain.balance_of(&address(&ctx, 1), base_asset()), 1_000);
+
+        // With nothing in flight to wait for, a rejection is returned as is
+        chain.fail_transfers_from(main_wallet.address(), 1);
+        assert!(pipeline
+            .submit(
+                "cont-fund",
+                2,
+                &main_wallet,
+                &address(&ctx, 2),
+                1_000,
+                &base_asset(),
+            )
+            .await
+            .is_err());
+        assert_eq!(chain.transfers().len(), 2);
+    }
+}
@@ -0,0 +1,675 @@
+use crate::{
+    approval::Approval,
+    chain::{balances_of, ChainClient},
+    context::Context,
+    daemon::unix_now,
+    distribute::INITIAL_AMOUNT,
+    fund::FundingSettings,
+    manifest::Manifest,
+    pipeline::Pipeline,
+    recipients::parse_address,
+    reclaim::describe_amount,
+    signer::Funder,
+    storage::TransferRecord,
+    transfer::estimate_transfer_cost,
+    units,
+};
+use fuels::{
+    accounts::provider::Provider,
+    crypto::Message,
+    types::{bech32::Bech32Address, AssetId},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fs,
+    path::Path,
+    str::FromStr,
+};
+use tracing::{info, warn};
+
+/// A top-up continual funding would make right now.
+#[derive(Debug, Serialize)]
+pub struct PlannedTransfer {
+    pub wallet_index: usize,
+    pub address: String,
+    pub balance: u64,
+    pub amount: u64,
+}
+
+/// Computes the top-ups continual funding would make with `settings` right now,
+/// without sending anything.
+pub async fn plan_top_ups(
+    ctx: &Context<'_>,
+    client: &dyn ChainClient,
+    settings: &FundingSettings,
+) -> Result<Vec<PlannedTransfer>, Box<dyn Error>> {
+    let mut indices = Vec::with_capacity(settings.number_of_wallets);
+    let mut addresses = Vec::with_capacity(settings.number_of_wallets);
+    for wallet_index in 0..settings.number_of_wallets {
+        if !ctx.is_excluded(wallet_index)? {
+            indices.push(wallet_index);
+            addresses.push(ctx.fleet.wallet(wallet_index, None)?.address().clone());
+        }
+    }
+    let balances = balances_of(
+        client,
+        &addresses,
+        &ctx.asset_id,
+        ctx.balance_concurrency,
+        None,
+    )
+    .await?;
+
+    Ok(indices
+        .into_iter()
+        .zip(addresses.iter().zip(balances))
+        .filter(|(_, (_, balance))| *balance < settings.threshold)
+        .map(|(wallet_index, (address, balance))| PlannedTransfer {
+            wallet_index,
+            address: address.to_string(),
+            balance,
+            amount: settings.capped(balance, settings.top_up_amount),
+        })
+        .collect())
+}
+
+/// Implements `drift-check`: prints the pending top-ups and returns whether any are needed.
+pub async fn drift_check(
+    ctx: &Context<'_>,
+    provider: &Provider,
+    settings: &FundingSettings,
+    manifest: &Manifest,
+) -> Result<bool, Box<dyn Error>> {
+    let threshold = settings.threshold;
+    let plan = plan_top_ups(ctx, provider, settings).await?;
+
+    manifest.print_header();
+
+    for transfer in &plan {
+        println!(
+            "{}\t{}\tbalance {}\tneeds {}",
+            transfer.wallet_index,
+            transfer.address,
+            ctx.format_amount(transfer.balance),
+            ctx.format_amount(transfer.amount)
+        );
+    }
+
+    if plan.is_empty() {
+        println!(
+            "No drift: all {} wallets at or above {}",
+            settings.number_of_wallets,
+            ctx.format_amount(threshold)
+        );
+    } else {
+        let total: u128 = plan.iter().map(|t| u128::from(t.amount)).sum();
+        println!(
+            "Drift: {} of {} wallets below {}, {} needed in total",
+            plan.len(),
+            settings.number_of_wallets,
+            ctx.format_amount(threshold),
+            ctx.format_amount(total)
+        );
+    }
+
+    Ok(!plan.is_empty())
+}
+
+/// Node-estimated cost of one transaction of a plan.
+#[derive(Debug, Serialize)]
+pub struct CostEstimate {
+    /// Derivation index of the paying wallet (0 is the main wallet).
+    pub source_index: usize,
+    /// HD wallets paid by the transaction.
+    pub wallet_indices: Vec<usize>,
+    pub amount: u64,
+    pub gas_used: u64,
+    pub fee: u64,
+}
+
+/// Estimates the fees of the transactions that would carry out `plan`, using the
+/// node's estimation endpoint on the real transactions rather than a fixed guess.
+///
+/// Without `batched` every top-up is its own transaction, as `--cont-fund` sends
+/// them; with it each funding source pays all of its wallets in one multi-output
+/// transaction.
+pub async fn estimate_costs(
+    ctx: &Context<'_>,
+    main_wallet: &Funder,
+    provider: &Provider,
+    plan: &[PlannedTransfer],
+    batched: bool,
+) -> Result<Vec<CostEstimate>, Box<dyn Error>> {
+    let sources = ctx
+        .funding_sources
+        .derive(&ctx.fleet, Some(provider.clone()))?;
+
+    // Group the transfers into the transactions that would be sent, keyed by paying wallet
+    let mut transactions: Vec<(usize, Vec<&PlannedTransfer>)> = Vec::new();
+    if batched {
+        let mut by_source: BTreeMap<usize, Vec<&PlannedTransfer>> = BTreeMap::new();
+        for transfer in plan {
+            let source = ctx
+                .funding_sources
+                .source_for(transfer.wallet_index)
+                .unwrap_or(0);
+            by_source.entry(source).or_default().push(transfer);
+        }
+        transactions.extend(by_source);
+    } else {
+        for transfer in plan {
+            let source = ctx
+                .funding_sources
+                .source_for(transfer.wallet_index)
+                .unwrap_or(0);
+            transactions.push((source, vec![transfer]));
+        }
+    }
+
+    let mut estimates = Vec::new();
+    for (source_index, transfers) in transactions {
+        let from_wallet = sources.get(&source_index).unwrap_or(main_wallet);
+        let mut recipients = Vec::new();
+        for transfer in &transfers {
+            let wallet = ctx.fleet.wallet(transfer.wallet_index, None)?;
+            recipients.push((wallet.address().clone(), transfer.amount));
+        }
+
+        let cost = estimate_transfer_cost(
+            from_wallet,
+            &recipients,
+            provider,
+            &ctx.asset_id,
+            ctx.tx_policies,
+        )
+        .await?;
+        estimates.push(CostEstimate {
+            source_index,
+            wallet_indices: transfers.iter().map(|t| t.wallet_index).collect(),
+            amount: transfers.iter().map(|t| t.amount).sum(),
+            gas_used: cost.gas_used,
+            fee: cost.total_fee,
+        });
+    }
+    Ok(estimates)
+}
+
+/// Implements `plan`: prints the pending top-ups and the estimated cost of sending them.
+pub async fn print_plan(
+    ctx: &Context<'_>,
+    main_wallet: &Funder,
+    provider: &Provider,
+    settings: &FundingSettings,
+    batched: bool,
+    manifest: &Manifest,
+) -> Result<(), Box<dyn Error>> {
+    let threshold = settings.threshold;
+    let plan = plan_top_ups(ctx, provider, settings).await?;
+
+    manifest.print_header();
+    if plan.is_empty() {
+        println!(
+            "Nothing to do: all {} wallets at or above {}",
+            settings.number_of_wallets,
+            ctx.format_amount(threshold)
+        );
+        return Ok(());
+    }
+
+    for transfer in &plan {
+        println!(
+            "{}\t{}\tbalance {}\tsend {}",
+            transfer.wallet_index,
+            transfer.address,
+            ctx.format_amount(transfer.balance),
+            ctx.format_amount(transfer.amount)
+        );
+    }
+
+    let estimates = estimate_costs(ctx, main_wallet, provider, &plan, batched).await?;
+    for estimate in &estimates {
+        println!(
+            "tx from wallet {}: {} transfer(s), {} total, gas {}, fee {}",
+            estimate.source_index,
+            estimate.wallet_indices.len(),
+            ctx.format_amount(estimate.amount),
+            estimate.gas_used,
+            estimate.fee
+        );
+    }
+
+    let amount: u128 = plan.iter().map(|t| u128::from(t.amount)).sum();
+    let fees: u128 = estimates.iter().map(|e| u128::from(e.fee)).sum();
+    println!(
+        "{} transfers in {} transaction(s): {} to send, {} estimated fees",
+        plan.len(),
+        estimates.len(),
+        ctx.format_amount(amount),
+        fees
+    );
+    Ok(())
+}
+
+/// The transfers of one `--init-dist`, `--cont-fund` cycle or `--reclaim`, computed
+/// from the balances up front (`--plan-only`) and carried out later, possibly after
+/// review (`--execute-plan`).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransferPlan {
+    /// Command the plan was made for, recorded with each transfer.
+    pub command: String,
+    pub created_at: u64,
+    /// Bech32 address of the main wallet; the plan only executes with the same one.
+    pub main_wallet: String,
+    pub transfers: Vec<PlanStep>,
+    /// Reviewers' approvals, added by `approve-plan`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub approvals: Vec<Approval>,
+}
+
+/// One transfer of a [`TransferPlan`], to or from an HD wallet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanStep {
+    /// HD wallet funded or reclaimed.
+    pub wallet_index: usize,
+    /// Derivation index of the paying wallet, `None` for the main wallet.
+    pub from_index: Option<usize>,
+    pub from: String,
+    pub to: String,
+    pub asset_id: String,
+    /// The HD wallet's balance of the asset when planned. A transfer whose wallet
+    /// holds anything else by the time it runs is stale and skipped.
+    pub balance: u64,
+    pub amount: u64,
+    /// Sweep every coin of the asset rather than send `amount`; a base asset sweep
+    /// pays its fee out of the swept coins.
+    pub sweep: bool,
+}
+
+impl TransferPlan {
+    pub fn new(command: &str, main_wallet: &Funder, transfers: Vec<PlanStep>) -> Self {
+        Self {
+            command: command.to_string(),
+            created_at: unix_now(),
+            main_wallet: main_wallet.address().to_string(),
+            transfers,
+            approvals: Vec::new(),
+        }
+    }
+
+    /// What approvals sign: the plan without its approvals.
+    pub fn message(&self) -> Result<Message, Box<dyn Error>> {
+        let mut body = serde_json::to_value(self)?;
+        if let Some(body) = body.as_object_mut() {
+            body.remove("approvals");
+        }
+        Ok(Message::new(serde_json::to_vec(&body)?))
+    }
+
+    /// Total amount of `asset_id` the plan moves.
+    pub fn total(&self, asset_id: &AssetId) -> u128 {
+        self.transfers
+            .iter()
+            .filter(|step| AssetId::from_str(&step.asset_id).is_ok_and(|id| id == *asset_id))
+            .map(|step| u128::from(step.amount))
+            .sum()
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read plan {}: {}", path.display(), e))?;
+        Ok(serde_json::from_str(&contents)
+            .map_err(|e| format!("Invalid plan {}: {}", path.display(), e))?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)? + "\n")?;
+        Ok(())
+    }
+
+    /// Prints one line per transfer and the total of `ETH_ASSET_ID` moved.
+    pub fn print(&self, ctx: &Context<'_>, client: &dyn ChainClient) -> Result<(), Box<dyn Error>> {
+        for step in &self.transfers {
+            let asset_id = AssetId::from_str(&step.asset_id)?;
+            let from = match step.from_index {
+                Some(index) => format!("wallet {}", index),
+                None => "main wallet".to_string(),
+            };
+            println!(
+                "{}\t{} -> {}\t{}{} of {}",
+                step.wallet_index,
+                from,
+                step.to,
+                if step.sweep { "sweep " } else { "" },
+                describe_amount(ctx, client, &asset_id, step.amount),
+                asset_id
+            );
+        }
+        println!(
+            "{} plan: {} transfers, {} in total",
+            self.command,
+            self.transfers.len(),
+            ctx.format_amount(self.total(&ctx.asset_id))
+        );
+        Ok(())
+    }
+}
+
+/// Plans `--init-dist`: the fixed amount, varied by `--jitter`, to every HD wallet
+/// not excluded.
+pub async fn plan_initial_distribution(
+    ctx: &Context<'_>,
+    main_wallet: &Funder,
+    client: &dyn ChainClient,
+) -> Result<Vec<PlanStep>, Box<dyn Error>> {
+    let mut indices = Vec::with_capacity(ctx.number_of_wallets);
+    let mut addresses = Vec::with_capacity(ctx.number_of_wallets);
+    for wallet_index in 0..ctx.number_of_wallets {
+        if !ctx.is_excluded(wallet_index)? {
+            indices.push(wallet_index);
+            addresses.push(ctx.fleet.wallet(wallet_index, None)?.address().clone());
+        }
+    }
+    let balances = balances_of(
+        client,
+        &addresses,
+        &ctx.asset_id,
+        ctx.balance_concurrency,
+        None,
+    )
+    .await?;
+
+    let sources = ctx.funding_sources.derive(&ctx.fleet, None)?;
+    indices
+        .into_iter()
+        .zip(balances)
+        .map(|(wallet_index, balance)| {
+            funding_step(
+                ctx,
+                main_wallet,
+                &sources,
+                wallet_index,
+                balance,
+                ctx.jitter.amount(INITIAL_AMOUNT),
+            )
+        })
+        .collect()
+}
+
+/// Plans one continual funding cycle: the top-ups [`plan_top_ups`] finds, varied by
+/// `--jitter`.
+pub async fn plan_continual_funding(
+    ctx: &Context<'_>,
+    main_wallet: &Funder,
+    client: &dyn ChainClient,
+    settings: &FundingSettings,
+) -> Result<Vec<PlanStep>, Box<dyn Error>> {
+    let sources = ctx.funding_sources.derive(&ctx.fleet, None)?;
+    plan_top_ups(ctx, client, settings)
+        .await?
+        .into_iter()
+        .map(|transfer| {
+            funding_step(
+                ctx,
+                main_wallet,
+                &sources,
+                transfer.wallet_index,
+                transfer.balance,
+                settings.capped(
+                    transfer.balance,
+                    ctx.jitter.top_up(
+                        settings.top_up_amount,
+                        settings.threshold - transfer.balance,
+                    ),
+                ),
+            )
+        })
+        .collect()
+}
+
+/// A transfer of `amount` to HD wallet `wallet_index` from its funding wallet.
+fn funding_step(
+    ctx: &Context<'_>,
+    main_wallet: &Funder,
+    sources: &HashMap<usize, Funder>,
+    wallet_index: usize,
+    balance: u64,
+    amount: u64,
+) -> Result<PlanStep, Box<dyn Error>> {
+    let from_index = ctx
+        .funding_sources
+        .source_for(wallet_index)
+        .filter(|source| sources.contains_key(source));
+    let from = ctx
+        .funding_sources
+        .wallet_for(wallet_index, main_wallet, sources);
+    Ok(PlanStep {
+        wallet_index,
+        from_index,
+        from: from.address().to_string(),
+        to: ctx.fleet.wallet(wallet_index, None)?.address().to_string(),
+        asset_id: format!("{:#x}", ctx.asset_id),
+        balance,
+        amount,
+        sweep: false,
+    })
+}
+
+/// Implements `--execute-plan`: checks that every transfer of `plan` belongs to this
+/// fleet, then sends them, skipping those whose HD wallet balance has changed since
+/// the plan was made.
+pub async fn execute_plan(
+    ctx: &Context<'_>,
+    main_wallet: &Funder,
+    client: &dyn ChainClient,
+    plan: &TransferPlan,
+) -> Result<(), Box<dyn Error>> {
+    if plan.main_wallet != main_wallet.address().to_string() {
+        return Err(format!(
+            "The plan was made for main wallet {}, not {}",
+            plan.main_wallet,
+            main_wallet.address()
+        )
+        .into());
+    }
+    info!(
+        "Executing {} plan of {} transfers made {}s ago.",
+        plan.command,
+        plan.transfers.len(),
+        unix_now().saturating_sub(plan.created_at)
+    );
+
+    // A plan that does not match the fleet fails as a whole, before anything is sent
+    let resolved = plan
+        .transfers
+        .iter()
+        .map(|step| resolve(ctx, client, main_wallet, step))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut pipeline = Pipeline::new(ctx, client).labelled("plan");
+    let mut sent = 0u64;
+    let mut stale = 0u64;
+    let mut sweep_fees = 0u128;
+    for (step, (from_wallet, to_address, asset_id)) in plan.transfers.iter().zip(&resolved) {
+        let from_wallet = from_wallet.as_ref().unwrap_or(main_wallet);
+        let wallet_address = ctx.fleet.wallet(step.wallet_index, None)?.address().clone();
+        let balance = client.balance(&wallet_address, asset_id).await?;
+        if balance != step.balance {
+            warn!(
+                "HD Wallet {} holds {} of {} now, not the {} planned; skipping.",
+                step.wallet_index,
+                describe_amount(ctx, client, asset_id, balance),
+                asset_id,
+                describe_amount(ctx, client, asset_id, step.balance)
+            );
+            stale += 1;
+            continue;
+        }
+
+        if step.sweep {
+            ctx.address_book
+                .check(to_address, Some(asset_id), step.amount, None)?;
+            loop {
+                let outcome = client
+                    .sweep(from_wallet, to_address, asset_id, ctx.tx_policies)
+                    .await?;
+                ctx.sinks
+                    .transfer_confirmed(
+                        TransferRecord::new(
+                            &plan.command,
+                            Some(step.wallet_index),
+                            from_wallet.address(),
+                            to_address,
+                            asset_id,
+                            outcome.amount,
+                            outcome.tx_id,
+                        )
+                        .labelled("plan"),
+                        outcome.fee,
+                    )
+                    .await;
+                sweep_fees += u128::from(outcome.fee);
+                if outcome.remaining == 0 {
+                    break;
+                }
+            }
+        } else {
+            pipeline
+                .submit(
+                    &plan.command,
+                    step.wallet_index,
+                    from_wallet,
+                    to_address,
+                    step.amount,
+                    asset_id,
+                )
+                .await?;
+        }
+        sent += 1;
+    }
+    pipeline.finish().await?;
+    let unconfirmed = pipeline.take_unconfirmed();
+
+    info!("Plan summary:");
+    info!("  Transfers:       {}", sent);
+    info!("  Stale, skipped:  {}", stale);
+    info!(
+        "  Fees paid:       {}",
+        units::format_fee(u128::from(pipeline.fees_paid()) + sweep_fees)
+    );
+    if !unconfirmed.is_empty() {
+        return Err(format!(
+            "{} transfers are still unconfirmed; check them before planning again",
+            unconfirmed.len()
+        )
+        .into());
+    }
+    if stale > 0 {
+        warn!(
+            "{} transfers were stale; plan again to cover their wallets.",
+            stale
+        );
+    }
+    Ok(())
+}
+
+/// The paying wallet (`None` for the main wallet), destination and asset of `step`,
+/// once checked against the fleet.
+pub fn resolve(
+    ctx: &Context<'_>,
+    client: &dyn ChainClient,
+    main_wallet: &Funder,
+    step: &PlanStep,
+) -> Result<(Option<Funder>, Bech32Address, AssetId), Box<dyn Error>> {
+    let invalid = |reason: &str| {
+        format!(
+            "Plan transfer for HD wallet {}: {}",
+            step.wallet_index, reason
+        )
+    };
+    if step.wallet_index >= ctx.number_of_wallets {
+        return Err(invalid("outside the fleet").into());
+    }
+    if ctx.is_excluded(step.wallet_index)? {
+        return Err(invalid("the wallet is excluded").into());
+    }
+    let from_wallet = match step.from_index {
+        Some(index) => Some(Funder::from(ctx.fleet.wallet(index, client.provider())?)),
+        None => None,
+    };
+    let from_address = from_wallet.as_ref().unwrap_or(main_wallet).address();
+    if from_address.to_string() != step.from {
+        return Err(invalid(&format!(
+            "planned from {}, but this mnemonic derives {}",
+            step.from, from_address
+        ))
+        .into());
+    }
+    let to_address = parse_address(&step.to).map_err(|e| invalid(&e))?;
+    let wallet_address = ctx.fleet.wallet(step.wallet_index, None)?.address().clone();
+    if *from_address != wallet_address && to_address != wallet_address {
+        return Err(invalid("neither pays nor receives").into());
+    }
+    if step.sweep && step.from_index != Some(step.wallet_index) {
+        return Err(invalid("only an HD wallet's own balance can be swept").into());
+    }
+    let asset_id = AssetId::from_str(&step.asset_id)
+        .map_err(|e| invalid(&format!("invalid asset id {}: {}", step.asset_id, e)))?;
+    Ok((from_wallet, to_address, asset_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::mock::{address, base_asset, test_context, MockChain};
+
+    #[tokio::test]
+    async fn plans_top_ups_below_threshold() {
+        let ctx = test_context(3, base_asset());
+        let chain = MockChain::default();
+        chain.set_balance(&address(&ctx, 0), base_asset(), 100);
+        chain.set_balance(&address(&ctx, 1), base_asset(), 5_000_000);
+
+        let settings = FundingSettings::new(5_000_000, 5_000_000, 3);
+        let plan = plan_top_ups(&ctx, &chain, &settings).await.unwrap();
+
+        let indices: Vec<usize> = plan.iter().map(|t| t.wallet_index).collect();
+        assert_eq!(indices, vec![0, 2]);
+        assert!(plan.iter().all(|t| t.amount == 5_000_000));
+        assert_eq!(plan[0].balance, 100);
+    }
+
+    #[tokio::test]
+    async fn executes_a_plan_skipping_stale_transfers() {
+        let ctx = test_context(4, base_asset());
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(main_wallet.address(), base_asset(), 100_000_000);
+        chain.set_balance(&address(&ctx, 2), base_asset(), 100);
+
+        let settings = FundingSettings::new(5_000_000, 5_000_000, 4);
+        let steps = plan_continual_funding(&ctx, &main_wallet, &chain, &settings)
+            .await
+            .unwrap();
+        let json =
+            serde_json::to_string(&TransferPlan::new("cont-fund", &main_wallet, steps)).unwrap();
+        let plan: TransferPlan = serde_json::from_str(&json).unwrap();
+        let indices: Vec<usize> = plan.transfers.iter().map(|t| t.wallet_index).collect();
+        assert_eq!(indices, vec![1, 2, 3]);
+        assert!(chain.transfers().is_empty());
+
+        // Wallet 3 is funded elsewhere between planning and execution
+        chain.set_balance(&address(&ctx, 3), base_asset(), 7_000_000);
+        execute_plan(&ctx, &main_wallet, &chain, &plan)
+            .await
+            .unwrap();
+        assert_eq!(chain.balance_of(&address(&ctx, 1), base_asset()), 5_000_000);
+        assert_eq!(chain.balance_of(&address(&ctx, 2), base_asset()), 5_000_100);
+        assert_eq!(chain.balance_of(&address(&ctx, 3), base_asset()), 7_000_000);
+
+        // A plan only executes with the main wallet it was made for
+        let other = Funder::from(ctx.fleet.wallet(1, None).unwrap());
+        assert!(execute_plan(&ctx, &other, &chain, &plan).await.is_err());
+    }
+}
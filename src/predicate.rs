@@ -0,0 +1,88 @@
+//! Reclaiming into a predicate: `RECLAIM_PREDICATE` names the bytecode of a Fuel
+//! predicate that only the treasury key can spend from, and `RECLAIM_PREDICATE_ROOT`
+//! the address it is expected to have. The address computed from the bytecode must
+//! match before anything is swept to it, so a typo'd destination is caught up front.
+
+use crate::recipients::parse_address;
+use fuels::{accounts::predicate::Predicate, types::bech32::Bech32Address};
+use std::{env, error::Error, fs};
+
+/// The verified address of the reclaim predicate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReclaimPredicate {
+    pub address: Bech32Address,
+}
+
+impl ReclaimPredicate {
+    /// Reads `RECLAIM_PREDICATE` and `RECLAIM_PREDICATE_ROOT`, or `None` if neither
+    /// is set. Each needs the other.
+    pub fn from_env() -> Result<Option<Self>, Box<dyn Error>> {
+        let path = env::var("RECLAIM_PREDICATE").ok();
+        let root = env::var("RECLAIM_PREDICATE_ROOT").ok();
+        match (path, root) {
+            (None, None) => Ok(None),
+            (Some(path), Some(root)) => {
+                let code = fs::read(&path)
+                    .map_err(|e| format!("Failed to read RECLAIM_PREDICATE {}: {}", path, e))?;
+                let root = parse_address(&root)
+                    .map_err(|e| format!("Invalid RECLAIM_PREDICATE_ROOT: {}", e))?;
+                Self::verify(code, &root).map(Some)
+            }
+            (Some(_), None) => Err(
+                "RECLAIM_PREDICATE needs RECLAIM_PREDICATE_ROOT, the address it must have".into(),
+            ),
+            (None, Some(_)) => {
+                Err("RECLAIM_PREDICATE_ROOT needs RECLAIM_PREDICATE, the predicate bytecode".into())
+            }
+        }
+    }
+
+    /// Computes the address of the predicate `code` and checks it is `root`.
+    fn verify(code: Vec<u8>, root: &Bech32Address) -> Result<Self, Box<dyn Error>> {
+        if code.is_empty() {
+            return Err("RECLAIM_PREDICATE is empty".into());
+        }
+        let address = Predicate::from_code(code).address().clone();
+        if address != *root {
+            return Err(format!(
+                "RECLAIM_PREDICATE has the address {}, not RECLAIM_PREDICATE_ROOT {}: wrong bytecode or a typo'd root",
+                address, root
+            )
+            .into());
+        }
+        Ok(Self { address })
+    }
+
+    /// The reclaim destination: the predicate, which `--to` may only repeat.
+    pub fn destination(&self, to: Option<&Bech32Address>) -> Result<Bech32Address, Box<dyn Error>> {
+        match to {
+            Some(to) if *to != self.address => {
+                Err(format!("--to {} is not the reclaim predicate {}", to, self.address).into())
+            }
+            _ => Ok(self.address.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fuels::types::{bech32::FUEL_BECH32_HRP, Address};
+
+    #[test]
+    fn verifies_the_predicate_address() {
+        // `ret 1`: any predicate bytecode will do for its address
+        let code = vec![0x24, 0x04, 0x00, 0x00];
+        let address = Predicate::from_code(code.clone()).address().clone();
+        let predicate = ReclaimPredicate::verify(code.clone(), &address).unwrap();
+        assert_eq!(predicate.address, address);
+
+        let typo = Bech32Address::new(FUEL_BECH32_HRP, Address::from([1u8; 32]));
+        assert!(ReclaimPredicate::verify(code, &typo).is_err());
+        assert!(ReclaimPredicate::verify(Vec::new(), &address).is_err());
+
+        assert_eq!(predicate.destination(Some(&address)).unwrap(), address);
+        assert_eq!(predicate.destination(None).unwrap(), address);
+        assert!(predicate.destination(Some(&typo)).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+//! Amounts in dollars: `FUNDING_THRESHOLD`, `TOP_UP_AMOUNT`, `MAIN_WALLET_RESERVE`
+//! and `MAX_WALLET_BALANCE` may be given as `$15` or `15 USD`, and are converted into
+//! the asset at a price fetched from `PRICE_URL` (CoinGecko by default) before every
+//! funding cycle.
+
+use crate::units;
+use serde_json::Value;
+use std::{
+    env,
+    error::Error,
+    fmt,
+    time::{Duration, Instant},
+};
+use tracing::{debug, warn};
+
+/// ETH in USD from CoinGecko's public API.
+pub const DEFAULT_PRICE_URL: &str =
+    "https://api.coingecko.com/api/v3/simple/price?ids=ethereum&vs_currencies=usd";
+
+/// Where the price is in a response of [`DEFAULT_PRICE_URL`].
+pub const DEFAULT_PRICE_POINTER: &str = "/ethereum/usd";
+
+/// How long a fetched price is used before it is fetched again.
+const DEFAULT_CACHE: Duration = Duration::from_secs(60);
+
+/// Largest change from the last accepted price that is believed, in percent.
+const DEFAULT_MAX_CHANGE: f64 = 20.0;
+
+/// Decimals of [`Usd`] amounts.
+const USD_DECIMALS: u32 = 6;
+
+/// An amount in dollars, in millionths of a dollar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Usd(pub u64);
+
+impl Usd {
+    /// Parses `$15`, `15usd` or `15 USD`, or returns `None` if `value` is not in dollars.
+    pub fn parse(value: &str) -> Option<Result<Self, String>> {
+        let value = value.trim();
+        let number = match value.strip_prefix('$') {
+            Some(number) => number,
+            None => {
+                let split = value.len().checked_sub(3)?;
+                if !value.is_char_boundary(split) || !value[split..].eq_ignore_ascii_case("usd") {
+                    return None;
+                }
+                &value[..split]
+            }
+        };
+        Some(units::parse_amount(number, USD_DECIMALS).map(Usd))
+    }
+
+    /// This amount in base units of an asset with `decimals` worth `price` dollars.
+    pub fn to_units(self, price: f64, decimals: u32) -> Result<u64, String> {
+        let scaled = (price * 10f64.powi(USD_DECIMALS as i32)).round() as u128;
+        if scaled == 0 {
+            return Err("the price is zero".into());
+        }
+        let scale = 10u128
+            .checked_pow(decimals)
+            .ok_or_else(|| format!("{} decimals are too many", decimals))?;
+        u64::try_from(u128::from(self.0) * scale / scaled)
+            .map_err(|_| format!("{} is too large at a price of ${}", self, price))
+    }
+}
+
+impl fmt::Display for Usd {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "${}", units::format_amount(self.0.into(), USD_DECIMALS))
+    }
+}
+
+/// A price source with a cache and bounds on what it believes.
+pub struct PriceOracle {
+    /// Endpoint returning JSON (`PRICE_URL`).
+    url: String,
+    /// JSON pointer to the price in the response (`PRICE_JSON_POINTER`).
+    pointer: String,
+    /// How long a price is reused (`PRICE_CACHE_SECS`).
+    cache: Duration,
+    /// Prices outside these are rejected (`PRICE_MIN`, `PRICE_MAX`).
+    min: Option<f64>,
+    max: Option<f64>,
+    /// Largest change from the last accepted price in percent (`PRICE_MAX_CHANGE`).
+    max_change: f64,
+    /// The last accepted price and when it was fetched.
+    last: Option<(f64, Instant)>,
+    client: reqwest::Client,
+}
+
+impl PriceOracle {
+    /// Reads the oracle's settings; nothing is fetched until a price is needed.
+    pub fn from_env() -> Result<Self, Box<dyn Error>> {
+        let number = |name: &str| {
+            env::var(name)
+                .ok()
+                .map(|value| {
+                    value
+                        .trim()
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|parsed| parsed.is_finite() && *parsed >= 0.0)
+                        .ok_or_else(|| {
+                            format!("Failed to parse {}: '{}' is not a number", name, value)
+                        })
+                })
+                .transpose()
+        };
+        let cache = match number("PRICE_CACHE_SECS")? {
+            Some(secs) => Duration::from_secs_f64(secs),
+            None => DEFAULT_CACHE,
+        };
+        Ok(Self {
+            url: env::var("PRICE_URL").unwrap_or_else(|_| DEFAULT_PRICE_URL.into()),
+            pointer: env::var("PRICE_JSON_POINTER")
+                .unwrap_or_else(|_| DEFAULT_PRICE_POINTER.into()),
+            cache,
+            min: number("PRICE_MIN")?,
+            max: number("PRICE_MAX")?,
+            max_change: number("PRICE_MAX_CHANGE")?.unwrap_or(DEFAULT_MAX_CHANGE),
+            last: None,
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()?,
+        })
+    }
+
+    /// The price in dollars: the cached one while it is fresh, else a newly fetched
+    /// one if it passes the sanity checks. When fetching fails or the new price is
+    /// rejected, the last accepted price is kept; without one, that is an error.
+    pub async fn price(&mut self) -> Result<f64, Box<dyn Error>> {
+        if let Some((price, fetched_at)) = self.last {
+            if fetched_at.elapsed() < self.cache {
+                return Ok(price);
+            }
+        }
+        let fetched = self.fetch().await;
+        match fetched.and_then(|price| self.accept(price, Instant::now())) {
+            Ok(price) => Ok(price),
+            Err(e) => match self.last {
+                Some((price, fetched_at)) => {
+                    warn!(
+                        "Keeping the price of ${} from {}s ago: {}",
+                        price,
+                        fetched_at.elapsed().as_secs(),
+                        e
+                    );
+                    Ok(price)
+                }
+                None => Err(format!("No price available: {}", e).into()),
+            },
+        }
+    }
+
+    async fn fetch(&self) -> Result<f64, Box<dyn Error>> {
+        let response: Value = self
+            .client
+            .get(&self.url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let price = response
+            .pointer(&self.pointer)
+            .and_then(|value| match value {
+                Value::String(price) => price.parse().ok(),
+                value => value.as_f64(),
+            })
+            .ok_or_else(|| format!("{} has no price at {}", self.url, self.pointer))?;
+        debug!("Fetched a price of ${} from {}", price, self.url);
+        Ok(price)
+    }
+
+    /// Checks `price` against the bounds and the last accepted price, and keeps it.
+    fn accept(&mut self, price: f64, now: Instant) -> Result<f64, Box<dyn Error>> {
+        if !price.is_finite() || price <= 0.0 {
+            return Err(format!("${} is not a price", price).into());
+        }
+        if self.min.is_some_and(|min| price < min) || self.max.is_some_and(|max| price > max) {
+            return Err(format!("${} is outside PRICE_MIN/PRICE_MAX", price).into());
+        }
+        if let Some((last, _)) = self.last {
+            let change = (price - last).abs() / last * 100.0;
+            if change > self.max_change {
+                return Err(format!(
+                    "${} is {:.1}% away from the last price of ${}, more than PRICE_MAX_CHANGE",
+                    price, change, last
+                )
+                .into());
+            }
+        }
+        self.last = Some((price, now));
+        Ok(price)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_converts_dollar_amounts() {
+        assert_eq!(Usd::parse("$15"), Some(Ok(Usd(15_000_000))));
+        assert_eq!(Usd::parse("2.5 USD"), Some(Ok(Usd(2_500_000))));
+        assert_eq!(Usd::parse("0.5usd"), Some(Ok(Usd(500_000))));
+        assert_eq!(Usd::parse("0.005"), None);
+        assert!(Usd::parse("$-1").unwrap().is_err());
+
+        // $15 of ETH at $3000 is 0.005 ETH
+        assert_eq!(Usd(15_000_000).to_units(3000.0, 9), Ok(5_000_000));
+        assert_eq!(Usd(1_000_000).to_units(0.5, 6), Ok(2_000_000));
+        assert!(Usd(1).to_units(0.0, 9).is_err());
+        assert_eq!(Usd(15_000_000).to_string(), "$15");
+    }
+
+    #[test]
+    fn rejects_prices_outside_the_bounds() {
+        let mut oracle = PriceOracle::from_env().unwrap();
+        oracle.min = Some(100.0);
+        oracle.max = Some(100_000.0);
+        let now = Instant::now();
+        assert!(oracle.accept(50.0, now).is_err());
+        assert_eq!(oracle.accept(3000.0, now).unwrap(), 3000.0);
+        // More than 20% away from the last price
+        assert!(oracle.accept(2000.0, now).is_err());
+        assert_eq!(oracle.accept(3300.0, now).unwrap(), 3300.0);
+        assert!(oracle.accept(f64::NAN, now).is_err());
+        assert_eq!(oracle.last.map(|(price, _)| price), Some(3300.0));
+    }
+}
@@ -0,0 +1,130 @@
+//! Named configuration profiles (`--profile testnet`) in the JSON file named by
+//! `PROFILES`, so one file covers every environment the tool runs against.
+
+use crate::vault;
+use serde::Deserialize;
+use std::{collections::BTreeMap, env, error::Error, fs, sync::OnceLock};
+
+/// Settings a profile may not carry: secrets belong in `.env`, Vault or a file, and
+/// the profile selection itself is not part of a profile.
+const FORBIDDEN: &[&str] = &["MNEMONIC", "PROFILE", "PROFILES"];
+
+/// The profile applied by [`apply`], for the rest of the run.
+static ACTIVE: OnceLock<String> = OnceLock::new();
+
+/// One environment's configuration (`--profile`): provider URLs, asset ids, amounts,
+/// safety limits, or any other setting read from the environment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub name: String,
+    /// Environment variables the profile sets, overriding `.env` and the environment.
+    pub vars: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct ProfilesFile {
+    profiles: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+/// The profile called `name` in the JSON file named by `PROFILES`.
+pub fn load(name: &str) -> Result<Profile, Box<dyn Error>> {
+    let path = env::var("PROFILES").map_err(|_| "--profile needs PROFILES, the profiles file")?;
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read profiles {}: {}", path, e))?;
+    parse(&contents)
+        .map_err(|e| format!("Profiles {}: {}", path, e))?
+        .into_iter()
+        .find(|profile| profile.name == name)
+        .ok_or_else(|| format!("No profile '{}' in {}", name, path).into())
+}
+
+/// Loads the profile called `name` and exports its settings into the environment.
+pub fn apply(name: &str) -> Result<(), Box<dyn Error>> {
+    let profile = load(name)?;
+    for (key, value) in &profile.vars {
+        env::set_var(key, value);
+    }
+    ACTIVE
+        .set(profile.name)
+        .map_err(|_| "A profile was already applied")?;
+    Ok(())
+}
+
+/// Exports the active profile's settings again, after a reload put back `.env`.
+/// Secrets from Vault keep precedence, as they do at startup.
+pub fn reapply() -> Result<(), Box<dyn Error>> {
+    if let Some(name) = active() {
+        for (key, value) in load(name)?.vars {
+            if !vault::manages(&key) {
+                env::set_var(key, value);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Name of the profile in use, if any.
+pub fn active() -> Option<&'static str> {
+    ACTIVE.get().map(String::as_str)
+}
+
+fn parse(contents: &str) -> Result<Vec<Profile>, Box<dyn Error>> {
+    let file: ProfilesFile = serde_json::from_str(contents)?;
+    if file.profiles.is_empty() {
+        return Err("no profiles configured".into());
+    }
+    let mut profiles = Vec::with_capacity(file.profiles.len());
+    for (name, vars) in file.profiles {
+        for key in vars.keys() {
+            if key.is_empty()
+                || !key
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+            {
+                return Err(format!(
+                    "profile '{}': '{}' is not an environment variable name",
+                    name, key
+                )
+                .into());
+            }
+            if FORBIDDEN.contains(&key.as_str()) {
+                return Err(
+                    format!("profile '{}': {} cannot be set by a profile", name, key).into(),
+                );
+            }
+        }
+        profiles.push(Profile { name, vars });
+    }
+    Ok(profiles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_profiles() {
+        let profiles = parse(
+            r#"{"profiles": {
+                "testnet": {"PROVIDER": "https://testnet.fuel.network/v1/graphql",
+                            "NETWORK": "testnet", "FUNDING_THRESHOLD": "0.005"},
+                "mainnet": {"PROVIDER": "https://mainnet.fuel.network/v1/graphql",
+                            "NETWORK": "mainnet", "MAX_OUTFLOW_PER_HOUR": "0.5"}
+            }}"#,
+        )
+        .unwrap();
+        assert_eq!(profiles.len(), 2);
+        assert_eq!(profiles[0].name, "mainnet");
+        assert_eq!(
+            profiles[1]
+                .vars
+                .get("FUNDING_THRESHOLD")
+                .map(String::as_str),
+            Some("0.005")
+        );
+
+        assert!(parse(r#"{"profiles": {"a": {"MNEMONIC": "test test"}}}"#).is_err());
+        assert!(parse(r#"{"profiles": {"a": {"provider_url": "x"}}}"#).is_err());
+        assert!(parse(r#"{"profiles": {}}"#).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+//! Progress bar for runs across many wallets (`--progress`), drawn on stderr in
+//! place of the per-wallet log lines. Warnings and errors are still logged above it.
+
+use indicatif::{ProgressBar, ProgressStyle};
+use std::{
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// The bar being drawn, if any, so log lines can be printed around it.
+static ACTIVE: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// Shows progress bars for the rest of the run.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Progress of `--init-dist` or `--reclaim` over the HD wallets.
+pub struct Progress {
+    bar: ProgressBar,
+    failures: u64,
+}
+
+impl Progress {
+    /// A bar over `total` wallets, hidden unless progress bars are enabled.
+    pub fn new(label: &str, total: usize) -> Self {
+        if !ENABLED.load(Ordering::Relaxed) {
+            return Self {
+                bar: ProgressBar::hidden(),
+                failures: 0,
+            };
+        }
+        let bar = ProgressBar::new(total as u64);
+        bar.set_style(
+            ProgressStyle::with_template(
+                "{prefix} [{elapsed_precise}] [{bar:40}] {pos}/{len} wallets (ETA {eta}) {msg}",
+            )
+            .expect("valid progress template")
+            .progress_chars("=> "),
+        );
+        bar.set_prefix(label.to_string());
+        *ACTIVE.lock().unwrap() = Some(bar.clone());
+        Self { bar, failures: 0 }
+    }
+
+    /// Starts at `position` wallets done, for runs resumed from a checkpoint.
+    pub fn set_position(&self, position: usize) {
+        self.bar.set_position(position as u64);
+        self.bar.reset_eta();
+    }
+
+    /// Marks HD wallet `index` as done with `status`.
+    pub fn done(&self, index: usize, status: &str) {
+        self.bar.set_message(self.message(index, status));
+        self.bar.inc(1);
+    }
+
+    /// Marks HD wallet `index` as done, counting it as a failure.
+    pub fn failed(&mut self, index: usize, status: &str) {
+        self.failures += 1;
+        self.done(index, status);
+    }
+
+    /// Adds failures noticed after the wallets were processed.
+    pub fn add_failures(&mut self, failures: usize) {
+        self.failures += failures as u64;
+    }
+
+    pub fn finish(self) {
+        self.bar
+            .finish_with_message(format!("{} failed", self.failures));
+        ACTIVE.lock().unwrap().take();
+    }
+
+    fn message(&self, index: usize, status: &str) -> String {
+        format!("{} failed | HD wallet {}: {}", self.failures, index, status)
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        // An aborted run leaves the bar where it stopped
+        if !self.bar.is_finished() {
+            self.bar.abandon();
+            ACTIVE.lock().unwrap().take();
+        }
+    }
+}
+
+/// Log writer that prints each line above the active progress bar.
+pub struct LogWriter;
+
+impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for LogWriter {
+    type Writer = LogLine;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        LogLine(Vec::new())
+    }
+}
+
+/// One buffered log line, written out when dropped.
+pub struct LogLine(Vec<u8>);
+
+impl Write for LogLine {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for LogLine {
+    fn drop(&mut self) {
+        let write = || {
+            let _ = io::stderr().write_all(&self.0);
+        };
+        match ACTIVE.lock().unwrap().as_ref() {
+            Some(bar) => bar.suspend(write),
+            None => write(),
+        }
+    }
+}
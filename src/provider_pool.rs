@@ -0,0 +1,158 @@
+use crate::network::Network;
+use fuels::accounts::provider::Provider;
+use std::{env, error::Error, time::Duration};
+use tokio::time::timeout;
+use tracing::{info, warn};
+
+/// A list of provider endpoints, tried in order, with the active one tracked
+/// so callers can fail over when it stops responding.
+#[derive(Clone)]
+pub struct ProviderPool {
+    urls: Vec<String>,
+    current: usize,
+    timeout: Duration,
+    /// Network every endpoint must serve; a mismatch aborts instead of failing over.
+    network: Option<Network>,
+}
+
+impl ProviderPool {
+    /// Builds a pool from `PROVIDER`, with the per-request timeout from
+    /// `PROVIDER_TIMEOUT_SECS` (default 10s).
+    pub fn from_env(network: Option<Network>) -> Result<Self, Box<dyn Error>> {
+        let list =
+            env::var("PROVIDER").map_err(|_| "PROVIDER not set in the environment".to_string())?;
+        let timeout = match env::var("PROVIDER_TIMEOUT_SECS") {
+            Ok(secs) => Duration::from_secs(secs.parse::<u64>().map_err(|e| {
+                format!("Failed to parse PROVIDER_TIMEOUT_SECS ('{}'): {}", secs, e)
+            })?),
+            Err(_) => Duration::from_secs(10),
+        };
+        Self::from_list(&list, timeout, network)
+    }
+
+    /// Builds a pool from a comma-separated list of provider URLs.
+    pub fn from_list(
+        list: &str,
+        timeout: Duration,
+        network: Option<Network>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let urls: Vec<String> = list
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        if urls.is_empty() {
+            return Err("PROVIDER must contain at least one URL".into());
+        }
+
+        Ok(Self {
+            urls,
+            current: 0,
+            timeout,
+            network,
+        })
+    }
+
+    /// Per-request timeout applied to health checks and provider queries.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn current_url(&self) -> &str {
+        &self.urls[self.current]
+    }
+
+    /// Connects to the first healthy endpoint, starting with the active one.
+    pub async fn connect(&mut self) -> Result<Provider, Box<dyn Error>> {
+        self.first_healthy()
+            .await?
+            .ok_or_else(|| "No healthy provider available".into())
+    }
+
+    /// Marks the active endpoint as failed and connects to the next healthy one.
+    /// With none healthy, it stays on the active endpoint and returns `None`, for the
+    /// caller to try again later; only an endpoint serving the wrong network errors.
+    pub async fn failover(&mut self) -> Result<Option<Provider>, Box<dyn Error>> {
+        let failed = self.current;
+        self.current = (self.current + 1) % self.urls.len();
+        let provider = self.first_healthy().await?;
+        if provider.is_none() {
+            self.current = failed;
+        }
+        Ok(provider)
+    }
+
+    /// The first healthy endpoint, starting with the active one, or `None`.
+    async fn first_healthy(&mut self) -> Result<Option<Provider>, Box<dyn Error>> {
+        for attempt in 0..self.urls.len() {
+            let index = (self.current + attempt) % self.urls.len();
+            let url = &self.urls[index];
+            match self.check(url).await {
+                Ok(provider) => {
+                    if index != self.current {
+                        warn!("Failing over to provider {}", url);
+                    }
+                    self.current = index;
+                    info!("Connected to provider {}", url);
+                    if let Some(network) = self.network {
+                        network.verify(&provider).await?;
+                    }
+                    return Ok(Some(provider));
+                }
+                Err(e) => warn!("Provider {} is unhealthy: {}", url, e),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Connects to `url` and verifies it answers a cheap query within the timeout.
+    async fn check(&self, url: &str) -> Result<Provider, Box<dyn Error>> {
+        timeout(self.timeout, async {
+            let provider = Provider::connect(url).await?;
+            provider.latest_block_height().await?;
+            Ok::<_, Box<dyn Error>>(provider)
+        })
+        .await
+        .map_err(|_| format!("timed out after {:?}", self.timeout))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fuels::test_helpers::setup_test_provider;
+
+    /// Endpoints nothing listens on.
+    const UNHEALTHY: [&str; 2] = ["http://127.0.0.1:1", "http://127.0.0.1:2"];
+
+    #[tokio::test]
+    async fn fails_over_past_unhealthy_endpoints() {
+        let node = setup_test_provider(vec![], vec![], None, None)
+            .await
+            .unwrap();
+        let list = format!("{},{},{}", node.url(), UNHEALTHY.join(","), node.url());
+        let mut pool = ProviderPool::from_list(&list, Duration::from_secs(5), None).unwrap();
+
+        pool.connect().await.unwrap();
+        assert_eq!(pool.current, 0);
+        assert!(pool.failover().await.unwrap().is_some());
+        assert_eq!(pool.current, 3);
+        // Wrapping around to the first endpoint again
+        assert!(pool.failover().await.unwrap().is_some());
+        assert_eq!(pool.current, 0);
+    }
+
+    #[tokio::test]
+    async fn stays_on_the_active_endpoint_when_none_is_healthy() {
+        let mut pool =
+            ProviderPool::from_list(&UNHEALTHY.join(","), Duration::from_secs(5), None).unwrap();
+        pool.current = 1;
+
+        assert!(pool.failover().await.unwrap().is_none());
+        assert_eq!(pool.current_url(), UNHEALTHY[1]);
+        assert!(pool.connect().await.is_err());
+        assert_eq!(pool.current_url(), UNHEALTHY[1]);
+    }
+}
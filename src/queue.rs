@@ -0,0 +1,636 @@
+//! `--queue`: every transfer of a run as a job in the work queue of `STORAGE_URL`.
+//! The planner enqueues one job per transfer of its plan, then a worker sends them
+//! one at a time, saving each job's progress (running, submitted with its
+//! transaction id, done) before the next step. A run that crashes or is killed
+//! loses nothing: `queue run` picks the queue up where it stopped and checks
+//! submitted transactions rather than sending them again. A transfer that keeps
+//! failing is retried up to `QUEUE_MAX_ATTEMPTS` times, then left for
+//! `queue retry-failed`.
+
+use crate::{
+    chain::{ChainClient, Confirmation},
+    context::Context,
+    daemon::unix_now,
+    plan::{resolve, PlanStep, TransferPlan},
+    reclaim::describe_amount,
+    signer::Funder,
+    storage::{JobRecord, Storage, TransferRecord},
+    transfer::{verify_credit, CONFIRMATION_TIMEOUT},
+    units,
+};
+use fuels::types::{bech32::Bech32Address, AssetId, TxId};
+use std::{
+    collections::BTreeMap,
+    env,
+    error::Error,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+
+/// Label of the transfers the worker sends.
+pub const LABEL: &str = "queue";
+
+/// Attempts per job unless `QUEUE_MAX_ATTEMPTS` says otherwise.
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+/// Wait before a job's second attempt; each further attempt waits that much longer.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+pub const PENDING: &str = "pending";
+pub const RUNNING: &str = "running";
+pub const SUBMITTED: &str = "submitted";
+pub const DONE: &str = "done";
+pub const STALE: &str = "stale";
+pub const FAILED: &str = "failed";
+
+/// How the worker retries: `QUEUE_MAX_ATTEMPTS` attempts per job, waiting
+/// `retry_delay` times the attempts made so far between them.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub retry_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_env() -> Result<Self, Box<dyn Error>> {
+        let max_attempts = match env::var("QUEUE_MAX_ATTEMPTS") {
+            Ok(value) => value
+                .parse::<u32>()
+                .ok()
+                .filter(|attempts| *attempts > 0)
+                .ok_or_else(|| {
+                    format!(
+                        "QUEUE_MAX_ATTEMPTS must be a positive number, not '{}'",
+                        value
+                    )
+                })?,
+            Err(_) => DEFAULT_MAX_ATTEMPTS,
+        };
+        Ok(Self {
+            max_attempts,
+            retry_delay: RETRY_DELAY,
+        })
+    }
+}
+
+/// Adds a job for every transfer of `plan`, as one batch named after its command and
+/// creation time. Returns the batch.
+pub async fn enqueue(storage: &dyn Storage, plan: &TransferPlan) -> Result<String, Box<dyn Error>> {
+    let batch = format!("{}-{}", plan.command, plan.created_at);
+    let payloads = plan
+        .transfers
+        .iter()
+        .map(serde_json::to_string)
+        .collect::<Result<Vec<_>, _>>()?;
+    storage
+        .enqueue_jobs(&batch, &plan.command, &payloads)
+        .await?;
+    info!("Enqueued {} transfers as batch {}.", payloads.len(), batch);
+    Ok(batch)
+}
+
+/// What one pass over the queue did.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct QueueSummary {
+    pub done: u64,
+    pub stale: u64,
+    pub failed: u64,
+    /// Submitted, but not confirmed within the timeout; checked again next run.
+    pub unconfirmed: u64,
+    pub fees: u128,
+}
+
+/// Works off every job still pending, running or submitted, oldest first. Fails if
+/// any job failed for good or is still unconfirmed, once the rest are done.
+pub async fn work(
+    ctx: &Context<'_>,
+    main_wallet: &Funder,
+    client: &dyn ChainClient,
+    policy: RetryPolicy,
+) -> Result<QueueSummary, Box<dyn Error>> {
+    let storage = ctx
+        .sinks
+        .storage
+        .ok_or("The work queue needs STORAGE_URL")?;
+    let jobs: Vec<JobRecord> = storage
+        .jobs()
+        .await?
+        .into_iter()
+        .filter(|job| [PENDING, RUNNING, SUBMITTED].contains(&job.status.as_str()))
+        .collect();
+    info!("{} jobs in the work queue.", jobs.len());
+
+    let worker = Worker {
+        ctx,
+        main_wallet,
+        client,
+        storage,
+        policy,
+    };
+    let mut summary = QueueSummary::default();
+    for mut job in jobs {
+        worker.run(&mut job, &mut summary).await?;
+    }
+
+    info!("Queue summary:");
+    info!("  Done:            {}", summary.done);
+    info!("  Stale, skipped:  {}", summary.stale);
+    info!("  Failed:          {}", summary.failed);
+    info!("  Unconfirmed:     {}", summary.unconfirmed);
+    info!("  Fees paid:       {}", units::format_fee(summary.fees));
+    if summary.failed > 0 || summary.unconfirmed > 0 {
+        return Err(format!(
+            "{} jobs failed and {} are still unconfirmed; see `queue status`",
+            summary.failed, summary.unconfirmed
+        )
+        .into());
+    }
+    if summary.stale > 0 {
+        warn!(
+            "{} transfers were stale; plan again to cover their wallets.",
+            summary.stale
+        );
+    }
+    Ok(summary)
+}
+
+/// Sets every failed job back to pending with no attempts made. Returns how many.
+pub async fn retry_failed(storage: &dyn Storage) -> Result<usize, Box<dyn Error>> {
+    let mut retried = 0;
+    for mut job in storage.jobs().await? {
+        if job.status == FAILED {
+            job.status = PENDING.to_string();
+            job.attempts = 0;
+            job.tx_id = None;
+            job.error = None;
+            save(storage, &mut job).await?;
+            retried += 1;
+        }
+    }
+    Ok(retried)
+}
+
+/// Implements `queue status`: the jobs of each batch by status, then every job that
+/// failed or is still awaiting confirmation.
+pub async fn print_status(storage: &dyn Storage) -> Result<(), Box<dyn Error>> {
+    let jobs = storage.jobs().await?;
+    if jobs.is_empty() {
+        println!("The work queue is empty");
+        return Ok(());
+    }
+    // Batches in the order they were enqueued
+    let mut batches: Vec<(&str, &str, BTreeMap<&str, u64>)> = Vec::new();
+    for job in &jobs {
+        let position = match batches.iter().position(|(batch, ..)| *batch == job.batch) {
+            Some(position) => position,
+            None => {
+                batches.push((&job.batch, &job.command, BTreeMap::new()));
+                batches.len() - 1
+            }
+        };
+        *batches[position].2.entry(&job.status).or_default() += 1;
+    }
+    for (batch, command, counts) in &batches {
+        let counts: Vec<String> = [PENDING, RUNNING, SUBMITTED, DONE, STALE, FAILED]
+            .iter()
+            .filter_map(|status| counts.get(status).map(|n| format!("{} {}", n, status)))
+            .collect();
+        println!("{}\t{}\t{}", batch, command, counts.join(", "));
+    }
+    for job in &jobs {
+        let wallet = serde_json::from_str::<PlanStep>(&job.payload)
+            .map_or_else(|_| "?".to_string(), |step| step.wallet_index.to_string());
+        match job.status.as_str() {
+            FAILED => println!(
+                "job {}\tHD wallet {}\tfailed after {} attempts: {}",
+                job.id,
+                wallet,
+                job.attempts,
+                job.error.as_deref().unwrap_or("unknown error")
+            ),
+            SUBMITTED | RUNNING => println!(
+                "job {}\tHD wallet {}\t{} {}",
+                job.id,
+                wallet,
+                job.status,
+                job.tx_id.as_deref().unwrap_or("")
+            ),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+async fn save(storage: &dyn Storage, job: &mut JobRecord) -> Result<(), Box<dyn Error>> {
+    job.updated_at = unix_now();
+    storage.update_job(job).await
+}
+
+struct Worker<'a, 'c> {
+    ctx: &'a Context<'c>,
+    main_wallet: &'a Funder,
+    client: &'a dyn ChainClient,
+    storage: &'a dyn Storage,
+    policy: RetryPolicy,
+}
+
+/// One attempt at a job: `Ok(Err(reason))` if the attempt failed and may be made
+/// again, `Err` if the run has to stop (storage or a provider query failed), leaving
+/// the job where it was for the next run.
+type Attempt = Result<Result<(), String>, Box<dyn Error>>;
+
+impl Worker<'_, '_> {
+    /// Takes `job` as far as it goes: done, stale, failed, or submitted and still
+    /// unconfirmed.
+    async fn run(
+        &self,
+        job: &mut JobRecord,
+        summary: &mut QueueSummary,
+    ) -> Result<(), Box<dyn Error>> {
+        // A job that does not match the fleet is failed without sending anything
+        let step: PlanStep = match serde_json::from_str(&job.payload) {
+            Ok(step) => step,
+            Err(e) => return self.fail(job, summary, format!("invalid job: {}", e)).await,
+        };
+        let (from_wallet, to_address, asset_id) =
+            match resolve(self.ctx, self.client, self.main_wallet, &step) {
+                Ok(resolved) => resolved,
+                Err(e) => return self.fail(job, summary, e.to_string()).await,
+            };
+        let wallet_address = self
+            .ctx
+            .fleet
+            .wallet(step.wallet_index, None)?
+            .address()
+            .clone();
+        let transfer = Transfer {
+            step: &step,
+            from_wallet: from_wallet.as_ref().unwrap_or(self.main_wallet),
+            to_address: &to_address,
+            asset_id: &asset_id,
+        };
+
+        match job.status.as_str() {
+            SUBMITTED => match self.confirm(job, summary, &transfer).await? {
+                Ok(()) => return Ok(()),
+                Err(reason) => {
+                    if !self.failed_attempt(job, summary, reason).await? {
+                        return Ok(());
+                    }
+                }
+            },
+            RUNNING => {
+                // Interrupted between sending and saving the transaction id: only the
+                // balance tells whether it went out, so it is never sent again blindly
+                let balance = self.client.balance(&wallet_address, &asset_id).await?;
+                if balance != step.balance {
+                    job.status = STALE.to_string();
+                    job.error = Some("interrupted after the transfer went out".to_string());
+                    save(self.storage, job).await?;
+                    summary.stale += 1;
+                    return Ok(());
+                }
+                let reason = "interrupted while sending; `queue retry-failed` sends it again";
+                return self.fail(job, summary, reason.to_string()).await;
+            }
+            _ => {}
+        }
+
+        loop {
+            // --jitter-delay, before the balance check so that sees the wallet as sent to
+            self.ctx.jitter.pause().await;
+            let balance = self.client.balance(&wallet_address, &asset_id).await?;
+            if balance != step.balance {
+                warn!(
+                    "HD Wallet {} holds {} of {} now, not the {} planned; skipping job {}.",
+                    step.wallet_index,
+                    describe_amount(self.ctx, self.client, &asset_id, balance),
+                    asset_id,
+                    describe_amount(self.ctx, self.client, &asset_id, step.balance),
+                    job.id
+                );
+                job.status = STALE.to_string();
+                save(self.storage, job).await?;
+                summary.stale += 1;
+                return Ok(());
+            }
+
+            job.attempts += 1;
+            job.status = RUNNING.to_string();
+            job.tx_id = None;
+            save(self.storage, job).await?;
+            let attempt = if step.sweep {
+                self.sweep(job, summary, &transfer).await?
+            } else {
+                self.send(job, summary, &transfer).await?
+            };
+            match attempt {
+                Ok(()) => return Ok(()),
+                Err(reason) => {
+                    if !self.failed_attempt(job, summary, reason).await? {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Submits the transfer, saves its transaction id, then waits for it.
+    async fn send(
+        &self,
+        job: &mut JobRecord,
+        summary: &mut QueueSummary,
+        transfer: &Transfer<'_>,
+    ) -> Attempt {
+        let submitted = async {
+            self.ctx.address_book.check(
+                transfer.to_address,
+                Some(transfer.asset_id),
+                transfer.step.amount,
+                None,
+            )?;
+            self.client
+                .submit_transfer(
+                    transfer.from_wallet,
+                    transfer.to_address,
+                    transfer.step.amount,
+                    transfer.asset_id,
+                    self.ctx.tx_policies,
+                )
+                .await
+        };
+        let tx_id = match submitted.await {
+            Ok(tx_id) => tx_id,
+            Err(e) => return Ok(Err(e.to_string())),
+        };
+        info!(
+            "Job {}: sent {} to HD wallet {} in {:?}",
+            job.id,
+            describe_amount(
+                self.ctx,
+                self.client,
+                transfer.asset_id,
+                transfer.step.amount
+            ),
+            transfer.step.wallet_index,
+            tx_id
+        );
+        job.status = SUBMITTED.to_string();
+        job.tx_id = Some(tx_id.to_string());
+        save(self.storage, job).await?;
+        self.confirm(job, summary, transfer).await
+    }
+
+    /// Waits for the job's submitted transaction and records it once confirmed. One
+    /// still pending after the timeout is left submitted for the next run.
+    async fn confirm(
+        &self,
+        job: &mut JobRecord,
+        summary: &mut QueueSummary,
+        transfer: &Transfer<'_>,
+    ) -> Attempt {
+        let recorded = job.tx_id.as_deref().unwrap_or_default();
+        let tx_id = TxId::from_str(recorded)
+            .map_err(|e| format!("invalid transaction id '{}': {}", recorded, e))?;
+        let started = Instant::now();
+        let fee = loop {
+            match self.client.confirmation(&tx_id).await? {
+                Confirmation::Confirmed { fee } => break fee,
+                Confirmation::Failed(reason) => {
+                    return Ok(Err(format!("transaction {} failed: {}", tx_id, reason)))
+                }
+                Confirmation::Pending if started.elapsed() > CONFIRMATION_TIMEOUT => {
+                    warn!(
+                        "Job {}: transaction {:?} is not confirmed yet; `queue run` checks it again.",
+                        job.id, tx_id
+                    );
+                    summary.unconfirmed += 1;
+                    return Ok(Ok(()));
+                }
+                Confirmation::Pending => sleep(Duration::from_secs(1)).await,
+            }
+        };
+        let record = TransferRecord::new(
+            &job.command,
+            Some(transfer.step.wallet_index),
+            transfer.from_wallet.address(),
+            transfer.to_address,
+            transfer.asset_id,
+            transfer.step.amount,
+            tx_id,
+        )
+        .labelled(LABEL);
+        verify_credit(self.ctx, self.client, &record).await;
+        self.ctx.sinks.transfer_confirmed(record, fee).await;
+        summary.fees += u128::from(fee);
+        self.done(job, summary).await?;
+        Ok(Ok(()))
+    }
+
+    /// Sweeps the HD wallet's coins of the asset, as many transactions as it takes.
+    async fn sweep(
+        &self,
+        job: &mut JobRecord,
+        summary: &mut QueueSummary,
+        transfer: &Transfer<'_>,
+    ) -> Attempt {
+        if let Err(reason) = self.ctx.address_book.check(
+            transfer.to_address,
+            Some(transfer.asset_id),
+            transfer.step.amount,
+            None,
+        ) {
+            return Ok(Err(reason));
+        }
+        loop {
+            let outcome = match self
+                .client
+                .sweep(
+                    transfer.from_wallet,
+                    transfer.to_address,
+                    transfer.asset_id,
+                    self.ctx.tx_policies,
+                )
+                .await
+            {
+                Ok(outcome) => outcome,
+                Err(e) => return Ok(Err(e.to_string())),
+            };
+            job.tx_id = Some(outcome.tx_id.to_string());
+            save(self.storage, job).await?;
+            self.ctx
+                .sinks
+                .transfer_confirmed(
+                    TransferRecord::new(
+                        &job.command,
+                        Some(transfer.step.wallet_index),
+                        transfer.from_wallet.address(),
+                        transfer.to_address,
+                        transfer.asset_id,
+                        outcome.amount,
+                        outcome.tx_id,
+                    )
+                    .labelled(LABEL),
+                    outcome.fee,
+                )
+                .await;
+            summary.fees += u128::from(outcome.fee);
+            if outcome.remaining == 0 {
+                break;
+            }
+        }
+        self.done(job, summary).await?;
+        Ok(Ok(()))
+    }
+
+    async fn done(
+        &self,
+        job: &mut JobRecord,
+        summary: &mut QueueSummary,
+    ) -> Result<(), Box<dyn Error>> {
+        job.status = DONE.to_string();
+        job.error = None;
+        save(self.storage, job).await?;
+        summary.done += 1;
+        Ok(())
+    }
+
+    /// Records a failed attempt. Returns whether the job has attempts left, after
+    /// waiting out the retry delay; if not, it is failed for good.
+    async fn failed_attempt(
+        &self,
+        job: &mut JobRecord,
+        summary: &mut QueueSummary,
+        reason: String,
+    ) -> Result<bool, Box<dyn Error>> {
+        if job.attempts >= self.policy.max_attempts {
+            self.fail(job, summary, reason).await?;
+            return Ok(false);
+        }
+        warn!(
+            "Job {}: attempt {} of {} failed: {}; retrying.",
+            job.id, job.attempts, self.policy.max_attempts, reason
+        );
+        job.status = PENDING.to_string();
+        job.error = Some(reason);
+        save(self.storage, job).await?;
+        sleep(self.policy.retry_delay * job.attempts).await;
+        Ok(true)
+    }
+
+    async fn fail(
+        &self,
+        job: &mut JobRecord,
+        summary: &mut QueueSummary,
+        reason: String,
+    ) -> Result<(), Box<dyn Error>> {
+        error!("Job {} failed: {}", job.id, reason);
+        job.status = FAILED.to_string();
+        job.error = Some(reason);
+        save(self.storage, job).await?;
+        summary.failed += 1;
+        Ok(())
+    }
+}
+
+/// A job's transfer, checked against the fleet.
+struct Transfer<'a> {
+    step: &'a PlanStep,
+    from_wallet: &'a Funder,
+    to_address: &'a Bech32Address,
+    asset_id: &'a AssetId,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chain::mock::{address, base_asset, test_context, MockChain, MOCK_FEE},
+        fund::FundingSettings,
+        plan::plan_continual_funding,
+        storage::mock::MemoryStorage,
+    };
+
+    #[tokio::test]
+    async fn resumes_the_queue_without_sending_twice() {
+        let storage: &'static MemoryStorage = Box::leak(Box::default());
+        let mut ctx = test_context(4, base_asset());
+        ctx.sinks.storage = Some(storage);
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(main_wallet.address(), base_asset(), 100_000_000);
+
+        let settings = FundingSettings::new(5_000_000, 5_000_000, 4);
+        let steps = plan_continual_funding(&ctx, &main_wallet, &chain, &settings)
+            .await
+            .unwrap();
+        enqueue(
+            storage,
+            &TransferPlan::new("cont-fund", &main_wallet, steps),
+        )
+        .await
+        .unwrap();
+
+        // A previous run crashed after sending wallet 1's top-up and saving its
+        // transaction id, and while sending wallet 2's
+        let mut jobs = storage.jobs().await.unwrap();
+        assert_eq!(jobs.len(), 3);
+        let tx_id = chain
+            .submit_transfer(
+                &main_wallet,
+                &address(&ctx, 1),
+                5_000_000,
+                &base_asset(),
+                ctx.tx_policies,
+            )
+            .await
+            .unwrap();
+        jobs[0].status = SUBMITTED.to_string();
+        jobs[0].tx_id = Some(tx_id.to_string());
+        jobs[0].attempts = 1;
+        jobs[1].status = RUNNING.to_string();
+        jobs[1].attempts = 1;
+        storage.update_job(&jobs[0]).await.unwrap();
+        storage.update_job(&jobs[1]).await.unwrap();
+        // Wallet 3's top-up is rejected once, then retried
+        chain.fail_transfers_from(main_wallet.address(), 1);
+        let policy = RetryPolicy {
+            max_attempts: 2,
+            retry_delay: Duration::ZERO,
+        };
+
+        // Wallet 2's top-up may or may not have gone out, so it is failed for review
+        assert!(work(&ctx, &main_wallet, &chain, policy).await.is_err());
+        let statuses = |jobs: Vec<JobRecord>| -> Vec<String> {
+            jobs.into_iter().map(|job| job.status).collect()
+        };
+        assert_eq!(
+            statuses(storage.jobs().await.unwrap()),
+            vec![DONE, FAILED, DONE]
+        );
+        assert_eq!(chain.transfers().len(), 2);
+        assert_eq!(chain.balance_of(&address(&ctx, 2), base_asset()), 0);
+
+        // Its wallet still holds what was planned, so a retry sends it
+        assert_eq!(retry_failed(storage).await.unwrap(), 1);
+        let summary = work(&ctx, &main_wallet, &chain, policy).await.unwrap();
+        assert_eq!(summary.done, 1);
+        assert_eq!(
+            statuses(storage.jobs().await.unwrap()),
+            vec![DONE, DONE, DONE]
+        );
+        for index in 1..4 {
+            assert_eq!(
+                chain.balance_of(&address(&ctx, index), base_asset()),
+                5_000_000
+            );
+        }
+        assert_eq!(
+            chain.balance_of(main_wallet.address(), base_asset()),
+            100_000_000 - 3 * (5_000_000 + MOCK_FEE)
+        );
+        assert_eq!(storage.transfers.lock().unwrap().len(), 3);
+    }
+}
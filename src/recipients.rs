@@ -0,0 +1,536 @@
+#[cfg(feature = "disperser")]
+use crate::disperser::{Disperser, Payment};
+use crate::{
+    address_book::AddressBook,
+    chain::ChainClient,
+    coins::ensure_parallel_coins,
+    context::Context,
+    pipeline::Pipeline,
+    signer::Funder,
+    storage::TransferRecord,
+    units::{self, parse_amount},
+    wallets::Fleet,
+};
+use fuels::types::{
+    bech32::{Bech32Address, FUEL_BECH32_HRP},
+    Address, AssetId,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{collections::HashMap, error::Error, fs, path::Path, str::FromStr};
+use tracing::{info, info_span, Instrument};
+
+/// A raw row of a recipients file: `address,amount[,memo]`.
+pub struct RecipientRow {
+    /// 1-based line number in the file, or position in a JSON file.
+    pub line: usize,
+    pub address: String,
+    pub amount: String,
+    pub memo: Option<String>,
+}
+
+/// An entry of a JSON recipients file; amounts may be strings or numbers.
+#[derive(Deserialize)]
+struct RecipientEntry {
+    address: String,
+    amount: Value,
+    #[serde(default)]
+    memo: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RecipientsFile {
+    recipients: Vec<RecipientEntry>,
+}
+
+/// Reads all rows of a recipients file: JSON (`{"recipients": [{"address", "amount",
+/// "memo"}]}`) if it ends in `.json`, CSV otherwise. A CSV header row starting with
+/// `address` is skipped.
+pub fn read_rows(path: &Path) -> Result<Vec<RecipientRow>, Box<dyn Error>> {
+    if path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+    {
+        let file: RecipientsFile = serde_json::from_str(&fs::read_to_string(path)?)
+            .map_err(|e| format!("Invalid recipients file {}: {}", path.display(), e))?;
+        return Ok(file
+            .recipients
+            .into_iter()
+            .enumerate()
+            .map(|(i, entry)| RecipientRow {
+                line: i + 1,
+                address: entry.address,
+                amount: match entry.amount {
+                    Value::String(amount) => amount,
+                    amount => amount.to_string(),
+                },
+                memo: entry.memo.filter(|m| !m.is_empty()),
+            })
+            .collect());
+    }
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_path(path)?;
+
+    let mut rows = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let record = record?;
+        let line = i + 1;
+        if line == 1
+            && record
+                .get(0)
+                .map_or(false, |f| f.eq_ignore_ascii_case("address"))
+        {
+            continue;
+        }
+        rows.push(RecipientRow {
+            line,
+            address: record.get(0).unwrap_or_default().to_string(),
+            amount: record.get(1).unwrap_or_default().to_string(),
+            memo: record.get(2).filter(|m| !m.is_empty()).map(str::to_string),
+        });
+    }
+    Ok(rows)
+}
+
+/// Sum of the amounts in the recipients file at `path`, skipping rows that do not parse.
+pub fn total(path: &Path, decimals: u32) -> Result<u128, Box<dyn Error>> {
+    Ok(read_rows(path)?
+        .iter()
+        .filter_map(|row| parse_amount(&row.amount, decimals).ok())
+        .map(u128::from)
+        .sum())
+}
+
+/// Parses a bech32 (`fuel1...`) or hex (`0x...`) address.
+pub fn parse_address(address: &str) -> Result<Bech32Address, String> {
+    if address.starts_with(FUEL_BECH32_HRP) {
+        Bech32Address::from_str(address).map_err(|e| format!("invalid bech32 address: {}", e))
+    } else {
+        Address::from_str(address)
+            .map(|address| Bech32Address::new(FUEL_BECH32_HRP, address))
+            .map_err(|_| format!("'{}' is neither a bech32 nor a hex address", address))
+    }
+}
+
+/// Validates a recipients file without touching the chain. Returns the
+/// problems found, one line each; an empty list means the file is valid.
+pub fn validate_rows(
+    rows: &[RecipientRow],
+    decimals: u32,
+    own_wallets: &HashMap<Bech32Address, usize>,
+    address_book: &AddressBook,
+    asset_id: Option<&AssetId>,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut seen: HashMap<Bech32Address, usize> = HashMap::new();
+
+    for row in rows {
+        let address = parse_address(&row.address);
+        let amount = parse_amount(&row.amount, decimals);
+
+        match &address {
+            Ok(address) => {
+                if let Some(first) = seen.get(&address) {
+                    problems.push(format!(
+                        "line {}: duplicate of line {} ({})",
+                        row.line, first, address
+                    ));
+                } else {
+                    seen.insert(address.clone(), row.line);
+                }
+                if let Some(index) = own_wallets.get(address) {
+                    problems.push(format!(
+                        "line {}: {} is our own HD wallet {}",
+                        row.line, address, index
+                    ));
+                }
+            }
+            Err(e) => problems.push(format!("line {}: {}", row.line, e)),
+        }
+
+        match &amount {
+            Ok(0) => problems.push(format!("line {}: amount is zero", row.line)),
+            Ok(_) => {}
+            Err(e) => problems.push(format!("line {}: invalid amount: {}", row.line, e)),
+        }
+
+        if let (Ok(address), Ok(amount)) = (&address, amount) {
+            if let Err(e) = address_book.check(address, asset_id, amount, row.memo.as_deref()) {
+                problems.push(format!("line {}: {}", row.line, e));
+            }
+        }
+    }
+
+    problems
+}
+
+/// Implements `validate-recipients`: prints every problem and fails if any were found.
+/// Rows sent to destinations in the address book must also meet their profile.
+pub fn validate_recipients(
+    path: &Path,
+    fleet: &Fleet,
+    number_of_wallets: usize,
+    decimals: u32,
+    address_book: &AddressBook,
+    asset_id: Option<&AssetId>,
+) -> Result<(), Box<dyn Error>> {
+    let rows = read_rows(path)?;
+
+    let mut own_wallets = HashMap::new();
+    for index in 0..number_of_wallets {
+        own_wallets.insert(fleet.wallet(index, None)?.address().clone(), index);
+    }
+
+    let problems = validate_rows(&rows, decimals, &own_wallets, address_book, asset_id);
+    for problem in &problems {
+        println!("{}", problem);
+    }
+
+    if problems.is_empty() {
+        println!("{}: {} recipients OK", path.display(), rows.len());
+        Ok(())
+    } else {
+        Err(format!(
+            "{}: {} problem(s) found in {} rows",
+            path.display(),
+            problems.len(),
+            rows.len()
+        )
+        .into())
+    }
+}
+
+/// State key prefix of the last row paid by an unfinished `--recipients` run; the
+/// file name completes it, so runs of different files resume independently.
+const RECIPIENTS_CHECKPOINT: &str = "recipients.checkpoint";
+
+/// A validated row of a recipients file.
+struct Recipient {
+    line: usize,
+    address: Bech32Address,
+    amount: u64,
+    memo: Option<String>,
+}
+
+/// Implements `--recipients`: pays every row of the file at `path` from the main
+/// wallet in `ctx.asset_id`, through the same pipeline, records and summary as
+/// `--init-dist`. The whole file is validated and its total checked against the
+/// main wallet's balance before the first transfer; with storage configured, an
+/// interrupted run resumes after the last confirmed row.
+pub async fn distribute_to_recipients(
+    ctx: &Context<'_>,
+    main_wallet: &Funder,
+    client: &dyn ChainClient,
+    path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let (remaining, checkpoint_key) = unpaid_recipients(ctx, path).await?;
+    let Some(largest) = remaining.iter().map(|r| r.amount).max() else {
+        info!("{}: no recipients left to pay.", path.display());
+        return Ok(());
+    };
+
+    let estimated_fees = check_total_cost(ctx, main_wallet, client, &remaining).await?;
+    ensure_parallel_coins(ctx, main_wallet, client, largest).await?;
+    let mut pipeline = Pipeline::new(ctx, client).labelled("recipients");
+    // Transaction id -> line, to advance the checkpoint as transfers confirm
+    let mut lines: HashMap<String, usize> = HashMap::new();
+    let mut sent = 0u128;
+
+    for recipient in &remaining {
+        async {
+            info!(
+                "Paying {} to {}",
+                ctx.format_amount(recipient.amount),
+                recipient.address
+            );
+            let confirmed = pipeline
+                .submit_to_recipient(
+                    "recipients",
+                    main_wallet,
+                    &recipient.address,
+                    recipient.amount,
+                    &ctx.asset_id,
+                    recipient.memo.as_deref(),
+                )
+                .await?;
+            if let Some(submitted) = pipeline.last_submitted() {
+                lines.insert(submitted.tx_id.clone(), recipient.line);
+            }
+            checkpoint(ctx, &checkpoint_key, &lines, &confirmed).await?;
+            sent += u128::from(recipient.amount);
+            Ok::<_, Box<dyn Error>>(())
+        }
+        .instrument(info_span!("recipient", line = recipient.line))
+        .await?;
+    }
+
+    let confirmed = pipeline.finish().await?;
+    checkpoint(ctx, &checkpoint_key, &lines, &confirmed).await?;
+    let unconfirmed = pipeline.take_unconfirmed();
+
+    info!("Recipients distribution summary:");
+    info!("  Transfers:       {}", remaining.len());
+    info!("  Sent:            {}", ctx.format_amount(sent));
+    info!("  Fees estimated:  {}", units::format_fee(estimated_fees));
+    info!(
+        "  Fees paid:       {}{}",
+        units::format_fee(pipeline.fees_paid().into()),
+        if unconfirmed.is_empty() {
+            ""
+        } else {
+            " (confirmed transfers only)"
+        }
+    );
+    if !unconfirmed.is_empty() {
+        return Err(format!(
+            "{} transfers are still unconfirmed; check them before re-running --recipients",
+            unconfirmed.len()
+        )
+        .into());
+    }
+
+    if let Some(storage) = ctx.sinks.storage {
+        storage.delete_state(&checkpoint_key).await?;
+    }
+    info!("Recipients distribution completed.");
+    Ok(())
+}
+
+/// The rows of the recipients file at `path` left to pay, after validating all of
+/// them, and the state key of the file's checkpoint.
+async fn unpaid_recipients(
+    ctx: &Context<'_>,
+    path: &Path,
+) -> Result<(Vec<Recipient>, String), Box<dyn Error>> {
+    let rows = read_rows(path)?;
+    let mut own_wallets = HashMap::new();
+    for index in 0..ctx.number_of_wallets {
+        own_wallets.insert(ctx.fleet.wallet(index, None)?.address().clone(), index);
+    }
+    let problems = validate_rows(
+        &rows,
+        ctx.decimals,
+        &own_wallets,
+        &ctx.address_book,
+        Some(&ctx.asset_id),
+    );
+    if !problems.is_empty() {
+        return Err(format!(
+            "{}: {} problem(s) found, nothing was sent: {}",
+            path.display(),
+            problems.len(),
+            problems.join("; ")
+        )
+        .into());
+    }
+    let recipients = rows
+        .into_iter()
+        .map(|row| {
+            Ok(Recipient {
+                line: row.line,
+                address: parse_address(&row.address)?,
+                amount: parse_amount(&row.amount, ctx.decimals)?,
+                memo: row.memo,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    // Resume after the last paid row if a previous run was interrupted
+    let checkpoint_key = ctx.state_key(&format!(
+        "{}.{}",
+        RECIPIENTS_CHECKPOINT,
+        path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let start = match ctx.sinks.storage {
+        Some(storage) => match storage.get_state(&checkpoint_key).await? {
+            Some(last) => {
+                let last = last.parse::<usize>()?;
+                info!(
+                    "Resuming recipients distribution after line {} (checkpoint found).",
+                    last
+                );
+                last
+            }
+            None => 0,
+        },
+        None => 0,
+    };
+    let remaining = recipients.into_iter().filter(|r| r.line > start).collect();
+    Ok((remaining, checkpoint_key))
+}
+
+/// Checks that the main wallet holds the total of `recipients` plus their estimated
+/// fees. Returns the estimated fees.
+async fn check_total_cost(
+    ctx: &Context<'_>,
+    main_wallet: &Funder,
+    client: &dyn ChainClient,
+    recipients: &[Recipient],
+) -> Result<u128, Box<dyn Error>> {
+    let Some(first) = recipients.first() else {
+        return Ok(0);
+    };
+    let fee = client
+        .estimate_transfer_fee(
+            main_wallet,
+            &first.address,
+            first.amount,
+            &ctx.asset_id,
+            ctx.tx_policies,
+        )
+        .await?;
+    let fees = u128::from(fee) * recipients.len() as u128;
+    let total: u128 = recipients.iter().map(|r| u128::from(r.amount)).sum();
+
+    let base_asset = client.base_asset();
+    let balance = u128::from(client.balance(main_wallet.address(), &ctx.asset_id).await?);
+    let needed = if ctx.asset_id == base_asset {
+        total + fees
+    } else {
+        total
+    };
+    if balance < needed {
+        return Err(format!(
+            "Recipients distribution would run out of funds: the main wallet holds {} but needs {} for {} recipients{}",
+            ctx.format_amount(balance),
+            ctx.format_amount(needed),
+            recipients.len(),
+            if ctx.asset_id == base_asset {
+                format!(" plus {} estimated fees", ctx.format_amount(fees))
+            } else {
+                String::new()
+            }
+        )
+        .into());
+    }
+    if ctx.asset_id != base_asset {
+        let gas = u128::from(client.balance(main_wallet.address(), &base_asset).await?);
+        if gas < fees {
+            return Err(format!(
+                "Recipients distribution would run out of funds: the main wallet holds {} of the base asset but needs {} for estimated fees",
+                gas, fees
+            )
+            .into());
+        }
+    }
+    info!(
+        "Pre-flight check passed: the main wallet covers {} recipients and {} of estimated fees.",
+        recipients.len(),
+        units::format_fee(fees)
+    );
+    Ok(fees)
+}
+
+/// `--recipients --disperser`: pays the recipients file at `path` through the
+/// disperser contract, a batch per call, from the main wallet.
+#[cfg(feature = "disperser")]
+pub async fn disperse_to_recipients(
+    ctx: &Context<'_>,
+    client: &dyn ChainClient,
+    path: &Path,
+    disperser: &Disperser,
+) -> Result<(), Box<dyn Error>> {
+    let (remaining, checkpoint_key) = unpaid_recipients(ctx, path).await?;
+    let payments: Vec<Payment> = remaining
+        .into_iter()
+        .map(|recipient| Payment {
+            wallet_index: None,
+            position: recipient.line,
+            to: recipient.address,
+            amount: recipient.amount,
+            memo: recipient.memo,
+        })
+        .collect();
+    disperser
+        .distribute(ctx, client, "recipients", &payments, &checkpoint_key)
+        .await?;
+    info!("Recipients distribution completed.");
+    Ok(())
+}
+
+/// Advances the checkpoint to the line of the last transfer that confirmed.
+async fn checkpoint(
+    ctx: &Context<'_>,
+    key: &str,
+    lines: &HashMap<String, usize>,
+    confirmed: &[TransferRecord],
+) -> Result<(), Box<dyn Error>> {
+    let last = confirmed.last().and_then(|record| lines.get(&record.tx_id));
+    if let (Some(storage), Some(line)) = (ctx.sinks.storage, last) {
+        storage.set_state(key, &line.to_string()).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::mock::{base_asset, test_context, MockChain, MOCK_FEE};
+
+    fn recipients_file(name: &str, contents: &str) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("fund_distributor-{}-{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn pays_every_recipient_of_a_file() {
+        let ctx = test_context(2, base_asset());
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(main_wallet.address(), base_asset(), 100_000_000);
+        let partner = Bech32Address::new(FUEL_BECH32_HRP, Address::from([7u8; 32]));
+        let other = Bech32Address::new(FUEL_BECH32_HRP, Address::from([8u8; 32]));
+        let path = recipients_file(
+            "recipients.json",
+            &format!(
+                r#"{{"recipients": [{{"address": "{}", "amount": "0.002", "memo": "bot-1"}},
+                                    {{"address": "{}", "amount": 0.001}}]}}"#,
+                partner, other
+            ),
+        );
+
+        distribute_to_recipients(&ctx, &main_wallet, &chain, &path)
+            .await
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(chain.transfers().len(), 2);
+        assert_eq!(chain.balance_of(&partner, base_asset()), 2_000_000);
+        assert_eq!(chain.balance_of(&other, base_asset()), 1_000_000);
+        assert_eq!(
+            chain.balance_of(main_wallet.address(), base_asset()),
+            100_000_000 - 3_000_000 - 2 * MOCK_FEE
+        );
+    }
+
+    #[tokio::test]
+    async fn sends_nothing_from_an_invalid_file() {
+        let ctx = test_context(2, base_asset());
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(main_wallet.address(), base_asset(), 100_000_000);
+        let partner = Bech32Address::new(FUEL_BECH32_HRP, Address::from([7u8; 32]));
+        // The second row pays our own HD wallet 1
+        let path = recipients_file(
+            "recipients.csv",
+            &format!(
+                "address,amount\n{},0.002\n{},0.001\n",
+                partner,
+                ctx.fleet.wallet(1, None).unwrap().address()
+            ),
+        );
+
+        let error = distribute_to_recipients(&ctx, &main_wallet, &chain, &path)
+            .await
+            .unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(error.to_string().contains("our own HD wallet 1"));
+        assert!(chain.transfers().is_empty());
+    }
+}
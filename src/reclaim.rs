@@ -0,0 +1,1438 @@
+use crate::{
+    approval, assets,
+    chain::{balances_of, ChainClient},
+    context::Context,
+    plan::{PlanStep, TransferPlan},
+    progress::Progress,
+    signer::Funder,
+    storage::TransferRecord,
+    tor::CircuitIsolation,
+    transfer::verify_credit,
+    units,
+};
+use fuels::types::{bech32::Bech32Address, AssetId};
+use futures::{future, stream, StreamExt};
+use std::{
+    collections::HashMap,
+    error::Error,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+use tracing::{error, info, info_span, warn, Instrument};
+
+/// Base asset an HD wallet keeps for gas when reclaiming other assets (0.0001 ETH).
+pub const DEFAULT_GAS_RESERVE: u64 = 100_000;
+
+/// Base asset balance below which sweeping it would cost about as much in fees as it
+/// recovers (0.00001 ETH).
+pub const DEFAULT_DUST_THRESHOLD: u64 = 10_000;
+
+/// How reclaiming obtains gas, and which balances are not worth paying gas for.
+#[derive(Clone, Copy)]
+pub struct GasPolicy {
+    /// Base asset balance an HD wallet needs before it can send another asset.
+    pub reserve: u64,
+    /// Top wallets below the reserve up from their funding wallet instead of skipping them.
+    pub prefund: bool,
+    /// Base asset balances below this are left in place.
+    pub dust: u64,
+    /// Balances of a non-base `ETH_ASSET_ID` below this are left in place.
+    pub asset_dust: u64,
+}
+
+impl GasPolicy {
+    /// Reads `GAS_RESERVE` and `DUST_THRESHOLD` (decimal ETH or base units) and
+    /// `ASSET_DUST_THRESHOLD` (in the asset's `ASSET_DECIMALS`).
+    pub fn from_env(prefund: bool) -> Result<Self, Box<dyn Error>> {
+        let env_amount = |name: &str, decimals: u32| -> Result<Option<u64>, Box<dyn Error>> {
+            match std::env::var(name) {
+                Ok(value) => Ok(Some(
+                    units::parse_setting(&value, decimals)
+                        .map_err(|e| format!("Failed to parse {}: {}", name, e))?,
+                )),
+                Err(_) => Ok(None),
+            }
+        };
+        Ok(Self {
+            reserve: env_amount("GAS_RESERVE", units::DEFAULT_DECIMALS)?
+                .unwrap_or(DEFAULT_GAS_RESERVE),
+            prefund,
+            dust: env_amount("DUST_THRESHOLD", units::DEFAULT_DECIMALS)?
+                .unwrap_or(DEFAULT_DUST_THRESHOLD),
+            asset_dust: env_amount("ASSET_DUST_THRESHOLD", units::decimals_from_env()?)?
+                .unwrap_or(0),
+        })
+    }
+}
+
+/// Which assets reclaim sweeps from each HD wallet.
+#[derive(Debug, Clone, Default)]
+pub enum AssetSelection {
+    /// `ETH_ASSET_ID`, then the base asset.
+    #[default]
+    Configured,
+    /// Every asset the wallet holds, limited to `allowlist` if given; the base asset last.
+    All { allowlist: Option<Vec<AssetId>> },
+}
+
+impl AssetSelection {
+    fn allows(&self, asset_id: &AssetId) -> bool {
+        match self {
+            AssetSelection::All {
+                allowlist: Some(allowlist),
+            } => allowlist.contains(asset_id),
+            _ => true,
+        }
+    }
+}
+
+/// Which HD wallets reclaim visits, in what order, how much it takes from each and
+/// when it stops. Balances and amounts are of `ETH_ASSET_ID`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WalletSelection {
+    /// Visit the wallets holding the most first (`--largest-first`), rather than by index.
+    pub largest_first: bool,
+    /// Leave wallets holding less than this alone (`--min-balance`).
+    pub min_balance: u64,
+    /// Stop once this much has been reclaimed (`--up-to`), taking only part of the
+    /// last wallet's balance if that is all that is needed.
+    pub up_to: Option<u64>,
+    /// Share of each balance to take (`--reclaim-percent`), in hundredths of a percent,
+    /// of every asset reclaimed; all of it if `None`.
+    pub basis_points: Option<u64>,
+    /// Left in each wallet (`--leave`) as operational float.
+    pub leave: u64,
+}
+
+/// Reclaim settings beyond the run context, for reclaims started by the scheduler.
+#[derive(Clone)]
+pub struct ReclaimOptions {
+    pub gas_policy: GasPolicy,
+    pub assets: AssetSelection,
+    /// `--to`; funds go back to the funding wallets without it.
+    pub destination: Option<Bech32Address>,
+    pub wallets: WalletSelection,
+}
+
+/// Refuses a reclaim started by hand (`--reclaim`, `wallet reclaim` or the API) that
+/// would move more than `APPROVAL_LIMIT`, which has to go through a reviewed plan
+/// instead. Reclaims on `RECLAIM_SCHEDULE` have no way to present one, and are not held.
+pub async fn check_approval(
+    ctx: &Context<'_>,
+    main_wallet: &Funder,
+    client: &dyn ChainClient,
+    options: &ReclaimOptions,
+) -> Result<(), Box<dyn Error>> {
+    if ctx.approval_limit.is_none() {
+        return Ok(());
+    }
+    let steps = plan_reclaim(ctx, main_wallet, client, options).await?;
+    let plan = TransferPlan::new("reclaim", main_wallet, steps);
+    approval::check_unreviewed(ctx, "The reclaim", plan.total(&ctx.asset_id))
+}
+
+/// Sweeps every HD wallet back to the wallet that funded it, or to `destination` if given.
+///
+/// Up to `ctx.reclaim_concurrency` wallets are reclaimed at once, one at a time with an
+/// `--up-to` target. A wallet that fails does not stop the others: failed wallets are
+/// retried once at the end, and the run fails listing those that still could not be
+/// reclaimed. With `isolation`, each batch of HD wallets talks to the node over its own
+/// Tor circuit.
+pub async fn reclaim_funds(
+    ctx: &Context<'_>,
+    main_wallet: &Funder,
+    client: &dyn ChainClient,
+    options: &ReclaimOptions,
+    isolation: Option<&CircuitIsolation>,
+) -> Result<(), Box<dyn Error>> {
+    let selection = &options.wallets;
+    let destination = options.destination.as_ref();
+    let base_asset_id = client.base_asset();
+    let reclaim = Reclaim {
+        ctx,
+        main_wallet,
+        options,
+        sources: ctx.funding_sources.derive(&ctx.fleet, client.provider())?,
+        // An --up-to target depends on what the wallets before gave
+        concurrency: if selection.up_to.is_some() {
+            1
+        } else {
+            ctx.reclaim_concurrency.max(1)
+        },
+        totals: Mutex::new(Totals::default()),
+        prefunding: tokio::sync::Mutex::new(()),
+        circuits: AtomicUsize::new(0),
+    };
+
+    let mut progress = Progress::new("reclaim", ctx.number_of_wallets);
+    let order = wallet_order(ctx, client, selection, Some(&progress)).await?;
+
+    // One transfer per wallet that holds funds; each other asset swept adds another
+    let wallets = order.len() as u128;
+    // An estimate is informational only: reclaim must still run from an empty main wallet
+    let fee = match client
+        .estimate_transfer_fee(
+            main_wallet,
+            destination.unwrap_or(main_wallet.address()),
+            1,
+            &base_asset_id,
+            ctx.tx_policies,
+        )
+        .await
+    {
+        Ok(fee) => {
+            info!(
+                "Estimated network fees: {} per transfer, up to about {} for {} wallets.",
+                units::format_fee(fee.into()),
+                units::format_fee(u128::from(fee) * wallets),
+                wallets
+            );
+            Some(fee)
+        }
+        Err(e) => {
+            warn!("Could not estimate reclaim fees: {}", e);
+            None
+        }
+    };
+
+    let mut counts = WalletCounts::default();
+    let mut failed = Vec::new();
+    for (index, result) in reclaim.pass(client, &order, isolation).await? {
+        match result {
+            Ok(status) => counts.record(&mut progress, index, status),
+            Err(e) => {
+                warn!(
+                    "Failed to reclaim HD Wallet {}, retrying it later: {}",
+                    index, e
+                );
+                failed.push(index);
+            }
+        }
+    }
+    let mut failures = Vec::new();
+    if !failed.is_empty() && !reclaim.target_reached() {
+        info!("Retrying {} HD wallets that failed...", failed.len());
+        for (index, result) in reclaim.pass(client, &failed, isolation).await? {
+            match result {
+                Ok(status) => counts.record(&mut progress, index, status),
+                Err(e) => {
+                    progress.failed(index, "failed");
+                    failures.push((index, e));
+                }
+            }
+        }
+    }
+    let totals = reclaim.totals.into_inner().unwrap();
+    if let Some(target) = selection
+        .up_to
+        .filter(|_| target_reached(selection, totals.reclaimed))
+    {
+        info!(
+            "Reclaimed {}, reaching the target of {}; stopped.",
+            ctx.format_amount(totals.reclaimed),
+            ctx.format_amount(target)
+        );
+    }
+
+    progress.finish();
+    info!("Reclaim summary:");
+    info!(
+        "  Wallets:         {} reclaimed, {} with nothing to reclaim, {} without gas, {} failed",
+        counts.reclaimed,
+        counts.nothing,
+        counts.no_gas,
+        failures.len()
+    );
+    info!("  Transfers:       {}", totals.transfers);
+    info!("  Gas pre-funds:   {}", totals.prefunds);
+    info!("  Reclaimed:       {}", ctx.format_amount(totals.reclaimed));
+    if let Some(fee) = fee {
+        info!(
+            "  Fees estimated:  {} per transfer",
+            units::format_fee(fee.into())
+        );
+    }
+    info!("  Fees paid:       {}", units::format_fee(totals.fees));
+    if !failures.is_empty() {
+        for (index, error) in &failures {
+            error!("  HD Wallet {} failed: {}", index, error);
+        }
+        let indices: Vec<String> = failures
+            .iter()
+            .map(|(index, _)| index.to_string())
+            .collect();
+        return Err(format!(
+            "{} HD wallets could not be reclaimed, even on a retry: {}",
+            failures.len(),
+            indices.join(", ")
+        )
+        .into());
+    }
+    info!("Fund reclamation completed.");
+    Ok(())
+}
+
+/// One reclaim run, shared by the HD wallets it reclaims concurrently.
+struct Reclaim<'a, 'c> {
+    ctx: &'a Context<'c>,
+    main_wallet: &'a Funder,
+    options: &'a ReclaimOptions,
+    sources: HashMap<usize, Funder>,
+    /// HD wallets reclaimed at once.
+    concurrency: usize,
+    totals: Mutex<Totals>,
+    /// Held while pre-funding gas, so concurrent pre-funds from one funding wallet do
+    /// not spend the same coins.
+    prefunding: tokio::sync::Mutex<()>,
+    /// Tor circuits used so far, with isolation.
+    circuits: AtomicUsize,
+}
+
+impl Reclaim<'_, '_> {
+    /// Reclaims the HD wallets `indices`, `concurrency` at a time, stopping once an
+    /// `--up-to` target is reached. Returns each wallet's outcome, or why it failed.
+    async fn pass(
+        &self,
+        client: &dyn ChainClient,
+        indices: &[usize],
+        isolation: Option<&CircuitIsolation>,
+    ) -> Result<Vec<(usize, Result<Reclaimed, String>)>, Box<dyn Error>> {
+        let batch_size = isolation.map_or(indices.len(), |isolation| isolation.batch_size);
+        let mut results = Vec::with_capacity(indices.len());
+        for batch in indices.chunks(batch_size.max(1)) {
+            let batch_provider = match isolation {
+                Some(isolation) => Some(
+                    isolation
+                        .proxy
+                        .isolated_provider(
+                            &isolation.provider_url,
+                            self.circuits.fetch_add(1, Ordering::Relaxed),
+                        )
+                        .await?,
+                ),
+                None => None,
+            };
+            let client: &dyn ChainClient = match &batch_provider {
+                Some(provider) => provider,
+                None => client,
+            };
+            let batch_results: Vec<_> = stream::iter(batch)
+                .take_while(|_| future::ready(!self.target_reached()))
+                .map(|&index| async move {
+                    // Errors are turned into strings so the stream stays `Send`
+                    let result = self
+                        .wallet(client, index)
+                        .instrument(info_span!("wallet", index))
+                        .await
+                        .map_err(|e| e.to_string());
+                    (index, result)
+                })
+                .buffered(self.concurrency)
+                .collect()
+                .await;
+            results.extend(batch_results);
+        }
+        Ok(results)
+    }
+
+    fn target_reached(&self) -> bool {
+        target_reached(&self.options.wallets, self.reclaimed())
+    }
+
+    /// Amount of `ETH_ASSET_ID` reclaimed so far.
+    fn reclaimed(&self) -> u64 {
+        self.totals.lock().unwrap().reclaimed
+    }
+
+    fn add(&self, totals: Totals) {
+        self.totals.lock().unwrap().add(totals);
+    }
+
+    /// Sweeps one HD wallet back to the wallet that funded it, or to the destination.
+    async fn wallet(
+        &self,
+        client: &dyn ChainClient,
+        hd_wallet_number: usize,
+    ) -> Result<Reclaimed, Box<dyn Error>> {
+        let ctx = self.ctx;
+        let ReclaimOptions {
+            gas_policy,
+            assets,
+            destination,
+            wallets: selection,
+        } = self.options;
+        let base_asset_id = client.base_asset();
+
+        // Derive the HD wallet
+        let wallet = Funder::from(ctx.fleet.wallet(hd_wallet_number, client.provider())?);
+        let mut reclaimed = false;
+        // Funds go back to the wallet that funded this one, which also pre-funds gas
+        let source_wallet =
+            ctx.funding_sources
+                .wallet_for(hd_wallet_number, self.main_wallet, &self.sources);
+        let to_address = destination.as_ref().unwrap_or(source_wallet.address());
+
+        let wallet_address = wallet.address();
+        if wallet_address == to_address {
+            info!(
+                "HD Wallet {} is where its funds would go, skipping.",
+                hd_wallet_number
+            );
+            return Ok(Reclaimed::Nothing);
+        }
+        info!(
+            "Reclaiming funds from HD Wallet {}: {:?}",
+            hd_wallet_number, wallet_address
+        );
+
+        // Other assets are swept first, while the wallet still holds base asset to pay gas
+        let others = other_assets(ctx, client, assets, wallet_address).await?;
+
+        for asset_id in &others {
+            let balance = client.balance(wallet_address, asset_id).await?;
+
+            info!(
+                "HD Wallet {} balance of {}: {}",
+                hd_wallet_number,
+                asset_id,
+                describe_amount(ctx, client, asset_id, balance)
+            );
+
+            // ASSET_DUST_THRESHOLD is in ETH_ASSET_ID's decimals, so only applies to it
+            let dust = if *asset_id == ctx.asset_id {
+                gas_policy.asset_dust
+            } else {
+                0
+            };
+            let partial = partial_amount(ctx, selection, self.reclaimed(), asset_id, balance);
+            if balance == 0 {
+                info!(
+                    "HD Wallet {} has no {} to reclaim.",
+                    hd_wallet_number, asset_id
+                );
+            } else if balance < dust {
+                info!(
+                    "HD Wallet {} holds only dust of {} ({} < {}), leaving it.",
+                    hd_wallet_number,
+                    asset_id,
+                    ctx.format_amount(balance),
+                    ctx.format_amount(dust)
+                );
+            } else if partial == Some(0) {
+                info!(
+                    "HD Wallet {} keeps all of its {}.",
+                    hd_wallet_number, asset_id
+                );
+            } else if self
+                .ensure_gas(client, &wallet, hd_wallet_number, source_wallet)
+                .await?
+            {
+                if let Some(amount) = partial {
+                    let sent = partial_transfer(
+                        ctx,
+                        &wallet,
+                        hd_wallet_number,
+                        to_address,
+                        client,
+                        asset_id,
+                        amount,
+                    )
+                    .await?;
+                    self.add(sent);
+                    // The target is reached: the wallet keeps the rest, and its gas
+                    if self.target_reached() {
+                        return Ok(Reclaimed::Funds);
+                    }
+                } else {
+                    // Fees are paid in the base asset, so every coin can be sent
+                    let sent = reclaim_transfer(
+                        ctx,
+                        &wallet,
+                        hd_wallet_number,
+                        to_address,
+                        client,
+                        asset_id,
+                        balance,
+                    )
+                    .await?;
+                    self.add(sent);
+                }
+                reclaimed = true;
+            } else {
+                return Ok(Reclaimed::NoGas);
+            }
+        }
+
+        if !assets.allows(&base_asset_id) {
+            return Ok(Reclaimed::from(reclaimed));
+        }
+
+        // Get the balance of the wallet for the base asset
+        let balance = client.balance(wallet_address, &base_asset_id).await?;
+
+        info!(
+            "HD Wallet {} base asset balance: {}",
+            hd_wallet_number,
+            eth(balance)
+        );
+
+        let partial = partial_amount(ctx, selection, self.reclaimed(), &base_asset_id, balance);
+        if balance > 0 && balance < gas_policy.dust {
+            info!(
+                "HD Wallet {} holds only dust ({} < {}), not worth the fee.",
+                hd_wallet_number,
+                eth(balance),
+                eth(gas_policy.dust)
+            );
+        } else if balance > 0 && partial == Some(0) {
+            info!(
+                "HD Wallet {} keeps all of its {}.",
+                hd_wallet_number,
+                eth(balance)
+            );
+        } else if let Some(amount) = partial {
+            let sent = partial_transfer(
+                ctx,
+                &wallet,
+                hd_wallet_number,
+                to_address,
+                client,
+                &base_asset_id,
+                amount,
+            )
+            .await?;
+            self.add(sent);
+            reclaimed = true;
+        } else if balance > 0 {
+            // The sweep pays its fee out of the swept coins, emptying the wallet
+            let sent = reclaim_transfer(
+                ctx,
+                &wallet,
+                hd_wallet_number,
+                to_address,
+                client,
+                &base_asset_id,
+                balance,
+            )
+            .await?;
+            self.add(sent);
+            reclaimed = true;
+        } else {
+            info!("HD Wallet {} has no funds to reclaim.", hd_wallet_number);
+        }
+
+        Ok(Reclaimed::from(reclaimed))
+    }
+
+    /// Makes sure an HD wallet holds enough base asset to pay for a transfer,
+    /// pre-funding it from `funder` if the policy allows. Returns whether the
+    /// wallet can now send.
+    async fn ensure_gas(
+        &self,
+        client: &dyn ChainClient,
+        wallet: &Funder,
+        hd_wallet_number: usize,
+        funder: &Funder,
+    ) -> Result<bool, Box<dyn Error>> {
+        let gas_policy = &self.options.gas_policy;
+        let base_asset_id = client.base_asset();
+        let gas_balance = client.balance(wallet.address(), &base_asset_id).await?;
+
+        if gas_balance >= gas_policy.reserve {
+            return Ok(true);
+        }
+
+        if !gas_policy.prefund {
+            warn!(
+                "HD Wallet {} holds {} ETH for gas, below the reserve of {}; skipping (use --prefund-gas).",
+                hd_wallet_number,
+                eth(gas_balance),
+                eth(gas_policy.reserve)
+            );
+            return Ok(false);
+        }
+
+        let top_up = gas_policy.reserve - gas_balance;
+        info!(
+            "Pre-funding HD Wallet {} with {} ETH for gas.",
+            hd_wallet_number,
+            eth(top_up)
+        );
+        let outcome = {
+            let _prefunding = self.prefunding.lock().await;
+            client
+                .transfer(
+                    funder,
+                    wallet.address(),
+                    top_up,
+                    &base_asset_id,
+                    self.ctx.tx_policies,
+                )
+                .await?
+        };
+        let record = TransferRecord::new(
+            "reclaim-gas",
+            Some(hd_wallet_number),
+            funder.address(),
+            wallet.address(),
+            base_asset_id,
+            top_up,
+            outcome.tx_id,
+        )
+        .labelled("gas-prefund");
+        verify_credit(self.ctx, client, &record).await;
+        self.ctx.sinks.transfer_confirmed(record, outcome.fee).await;
+        let mut totals = self.totals.lock().unwrap();
+        totals.prefunds += 1;
+        totals.fees += u128::from(outcome.fee);
+
+        Ok(true)
+    }
+}
+
+/// Plans the reclaim `reclaim_funds` would make with `options`, without sending
+/// anything: a sweep of each balance it would take whole and a transfer of each it
+/// would take in part. Gas pre-funding is not planned, so a wallet that cannot pay
+/// its own fee fails when the plan is executed.
+pub async fn plan_reclaim(
+    ctx: &Context<'_>,
+    main_wallet: &Funder,
+    client: &dyn ChainClient,
+    options: &ReclaimOptions,
+) -> Result<Vec<PlanStep>, Box<dyn Error>> {
+    let ReclaimOptions {
+        gas_policy,
+        assets,
+        destination,
+        wallets: selection,
+    } = options;
+    let base_asset_id = client.base_asset();
+    let sources = ctx.funding_sources.derive(&ctx.fleet, None)?;
+    let mut totals = Totals::default();
+    let mut steps = Vec::new();
+
+    'wallets: for hd_wallet_number in wallet_order(ctx, client, selection, None).await? {
+        if target_reached(selection, totals.reclaimed) {
+            break;
+        }
+        let wallet_address = ctx.fleet.wallet(hd_wallet_number, None)?.address().clone();
+        let source_wallet = ctx
+            .funding_sources
+            .wallet_for(hd_wallet_number, main_wallet, &sources);
+        let to_address = destination.as_ref().unwrap_or(source_wallet.address());
+        if wallet_address == *to_address {
+            continue;
+        }
+
+        let mut planned = other_assets(ctx, client, assets, &wallet_address).await?;
+        if assets.allows(&base_asset_id) {
+            planned.push(base_asset_id);
+        }
+        for asset_id in planned {
+            let balance = client.balance(&wallet_address, &asset_id).await?;
+            let dust = if asset_id == base_asset_id {
+                gas_policy.dust
+            } else if asset_id == ctx.asset_id {
+                gas_policy.asset_dust
+            } else {
+                0
+            };
+            let partial = partial_amount(ctx, selection, totals.reclaimed, &asset_id, balance);
+            if balance == 0 || balance < dust || partial == Some(0) {
+                continue;
+            }
+
+            let amount = partial.unwrap_or(balance);
+            if asset_id == ctx.asset_id {
+                totals.reclaimed += amount;
+            }
+            steps.push(PlanStep {
+                wallet_index: hd_wallet_number,
+                from_index: Some(hd_wallet_number),
+                from: wallet_address.to_string(),
+                to: to_address.to_string(),
+                asset_id: format!("{:#x}", asset_id),
+                balance,
+                amount,
+                sweep: partial.is_none(),
+            });
+            // As in reclaim_funds, the wallet that completes the target keeps the rest
+            if partial.is_some() && target_reached(selection, totals.reclaimed) {
+                break 'wallets;
+            }
+        }
+    }
+    Ok(steps)
+}
+
+/// Transfers a reclaim made and the fees they cost.
+#[derive(Default)]
+struct Totals {
+    transfers: u64,
+    /// Base asset sent to wallets so they could pay for their own transfers.
+    prefunds: u64,
+    /// In base units of the base asset, including the pre-funding transfers.
+    fees: u128,
+    /// Amount of `ETH_ASSET_ID` reclaimed, in base units.
+    reclaimed: u64,
+}
+
+impl Totals {
+    fn sent(&mut self, fee: u64) {
+        self.transfers += 1;
+        self.fees += u128::from(fee);
+    }
+
+    fn add(&mut self, other: Totals) {
+        self.transfers += other.transfers;
+        self.prefunds += other.prefunds;
+        self.fees += other.fees;
+        self.reclaimed += other.reclaimed;
+    }
+}
+
+/// The HD wallets to reclaim, in order: every wallet not excluded, by index, or
+/// filtered and sorted by balance as `selection` asks. Wallets left out are marked
+/// done on `progress`, if given.
+async fn wallet_order(
+    ctx: &Context<'_>,
+    client: &dyn ChainClient,
+    selection: &WalletSelection,
+    progress: Option<&Progress>,
+) -> Result<Vec<usize>, Box<dyn Error>> {
+    let mut indices = Vec::with_capacity(ctx.number_of_wallets);
+    for index in 0..ctx.number_of_wallets {
+        if ctx.is_excluded(index)? {
+            info!("HD Wallet {} is excluded, skipping.", index);
+            if let Some(progress) = progress {
+                progress.done(index, "excluded");
+            }
+        } else {
+            indices.push(index);
+        }
+    }
+    if !selection.largest_first && selection.min_balance == 0 {
+        return Ok(indices);
+    }
+
+    let addresses = indices
+        .iter()
+        .map(|index| Ok(ctx.fleet.wallet(*index, None)?.address().clone()))
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+    let balances = balances_of(
+        client,
+        &addresses,
+        &ctx.asset_id,
+        ctx.balance_concurrency,
+        None,
+    )
+    .await?;
+    let mut ranked: Vec<(usize, u64)> = indices.into_iter().zip(balances).collect();
+    ranked.retain(|(index, balance)| {
+        let keep = *balance >= selection.min_balance;
+        if !keep {
+            info!(
+                "HD Wallet {} holds {}, below the minimum of {}; skipping.",
+                index,
+                ctx.format_amount(*balance),
+                ctx.format_amount(selection.min_balance)
+            );
+            if let Some(progress) = progress {
+                progress.done(*index, "below minimum balance");
+            }
+        }
+        keep
+    });
+    if selection.largest_first {
+        // Stable, so equal balances keep index order
+        ranked.sort_by(|a, b| b.1.cmp(&a.1));
+    }
+    Ok(ranked.into_iter().map(|(index, _)| index).collect())
+}
+
+/// The assets other than the base asset to reclaim from the HD wallet at
+/// `wallet_address`, in the order they are swept.
+async fn other_assets(
+    ctx: &Context<'_>,
+    client: &dyn ChainClient,
+    assets: &AssetSelection,
+    wallet_address: &Bech32Address,
+) -> Result<Vec<AssetId>, Box<dyn Error>> {
+    let base_asset_id = client.base_asset();
+    Ok(match assets {
+        AssetSelection::Configured if ctx.asset_id != base_asset_id => vec![ctx.asset_id],
+        AssetSelection::Configured => Vec::new(),
+        AssetSelection::All { .. } => {
+            let mut held: Vec<AssetId> = client
+                .balances(wallet_address)
+                .await?
+                .into_iter()
+                .filter(|(asset_id, balance)| {
+                    *asset_id != base_asset_id && *balance > 0 && assets.allows(asset_id)
+                })
+                .map(|(asset_id, _)| asset_id)
+                .collect();
+            held.sort();
+            held
+        }
+    })
+}
+
+/// How much of a `balance` of `asset_id` to send when only part of it is to be
+/// reclaimed, or `None` to reclaim all of it: `--reclaim-percent` takes a share of
+/// every balance, `--leave` keeps some `ETH_ASSET_ID` back, and the wallet that
+/// completes an `--up-to` target only gives what is still needed, `reclaimed`
+/// having been reclaimed so far.
+fn partial_amount(
+    ctx: &Context<'_>,
+    selection: &WalletSelection,
+    reclaimed: u64,
+    asset_id: &AssetId,
+    balance: u64,
+) -> Option<u64> {
+    let mut amount = match selection.basis_points {
+        Some(basis_points) => (u128::from(balance) * u128::from(basis_points) / 10_000) as u64,
+        None => balance,
+    };
+    if *asset_id == ctx.asset_id {
+        amount = amount.min(balance.saturating_sub(selection.leave));
+        if let Some(target) = selection.up_to {
+            amount = amount.min(target.saturating_sub(reclaimed));
+        }
+    }
+    (amount < balance).then_some(amount)
+}
+
+/// Whether the `--up-to` target, if any, has been reached with `reclaimed`.
+fn target_reached(selection: &WalletSelection, reclaimed: u64) -> bool {
+    selection.up_to.is_some_and(|target| reclaimed >= target)
+}
+
+/// What a reclaim did with one HD wallet, for the progress bar.
+enum Reclaimed {
+    Funds,
+    Nothing,
+    /// Skipped: too little base asset left to pay the fee.
+    NoGas,
+}
+
+/// How many HD wallets ended each way, for the summary.
+#[derive(Default)]
+struct WalletCounts {
+    reclaimed: usize,
+    nothing: usize,
+    no_gas: usize,
+}
+
+impl WalletCounts {
+    fn record(&mut self, progress: &mut Progress, index: usize, status: Reclaimed) {
+        match status {
+            Reclaimed::Funds => {
+                progress.done(index, "reclaimed");
+                self.reclaimed += 1;
+            }
+            Reclaimed::Nothing => {
+                progress.done(index, "nothing to reclaim");
+                self.nothing += 1;
+            }
+            Reclaimed::NoGas => {
+                progress.failed(index, "skipped, no gas");
+                self.no_gas += 1;
+            }
+        }
+    }
+}
+
+impl From<bool> for Reclaimed {
+    fn from(reclaimed: bool) -> Self {
+        if reclaimed {
+            Reclaimed::Funds
+        } else {
+            Reclaimed::Nothing
+        }
+    }
+}
+
+/// Sweeps every coin of `asset_id` from an HD wallet to the reclaim destination,
+/// in as many transactions as the node's input limit requires. `balance` is the
+/// wallet's balance of the asset, checked against the address book up front.
+async fn reclaim_transfer(
+    ctx: &Context<'_>,
+    wallet: &Funder,
+    hd_wallet_number: usize,
+    to_address: &Bech32Address,
+    client: &dyn ChainClient,
+    asset_id: &AssetId,
+    balance: u64,
+) -> Result<Totals, Box<dyn Error>> {
+    info!(
+        "Reclaiming {} of {} from HD Wallet {} to {}.",
+        describe_amount(ctx, client, asset_id, balance),
+        asset_id,
+        hd_wallet_number,
+        to_address
+    );
+
+    ctx.address_book
+        .check(to_address, Some(asset_id), balance, None)?;
+    let mut totals = Totals::default();
+    loop {
+        let outcome = client
+            .sweep(wallet, to_address, asset_id, ctx.tx_policies)
+            .await?;
+        ctx.sinks
+            .transfer_confirmed(
+                TransferRecord::new(
+                    "reclaim",
+                    Some(hd_wallet_number),
+                    wallet.address(),
+                    to_address,
+                    asset_id,
+                    outcome.amount,
+                    outcome.tx_id,
+                )
+                .labelled("reclaim"),
+                outcome.fee,
+            )
+            .await;
+        totals.sent(outcome.fee);
+        if *asset_id == ctx.asset_id {
+            totals.reclaimed += outcome.amount;
+        }
+
+        info!(
+            "Successfully reclaimed {} ({} coins) from HD Wallet {}.",
+            describe_amount(ctx, client, asset_id, outcome.amount),
+            outcome.coins,
+            hd_wallet_number
+        );
+        if outcome.remaining == 0 {
+            return Ok(totals);
+        }
+        info!(
+            "HD Wallet {} still holds {} coins of {}, sweeping again.",
+            hd_wallet_number, outcome.remaining, asset_id
+        );
+    }
+}
+
+/// Sends `amount` of `asset_id` from an HD wallet to the reclaim destination,
+/// leaving the rest in place.
+async fn partial_transfer(
+    ctx: &Context<'_>,
+    wallet: &Funder,
+    hd_wallet_number: usize,
+    to_address: &Bech32Address,
+    client: &dyn ChainClient,
+    asset_id: &AssetId,
+    amount: u64,
+) -> Result<Totals, Box<dyn Error>> {
+    info!(
+        "Reclaiming {} of {} from HD Wallet {} to {}, leaving the rest.",
+        describe_amount(ctx, client, asset_id, amount),
+        asset_id,
+        hd_wallet_number,
+        to_address
+    );
+    ctx.address_book
+        .check(to_address, Some(asset_id), amount, None)?;
+    let outcome = client
+        .transfer(wallet, to_address, amount, asset_id, ctx.tx_policies)
+        .await?;
+    let record = TransferRecord::new(
+        "reclaim",
+        Some(hd_wallet_number),
+        wallet.address(),
+        to_address,
+        asset_id,
+        amount,
+        outcome.tx_id,
+    )
+    .labelled("reclaim");
+    verify_credit(ctx, client, &record).await;
+    ctx.sinks.transfer_confirmed(record, outcome.fee).await;
+    let mut totals = Totals::default();
+    totals.sent(outcome.fee);
+    totals.reclaimed = amount;
+    Ok(totals)
+}
+
+/// Formats an amount of `asset_id` in decimals where they are known, else in base units.
+pub fn describe_amount(
+    ctx: &Context<'_>,
+    client: &dyn ChainClient,
+    asset_id: &AssetId,
+    amount: u64,
+) -> String {
+    if *asset_id == ctx.asset_id {
+        ctx.format_amount(amount)
+    } else if *asset_id == client.base_asset() && assets::info(asset_id).is_none() {
+        eth(amount)
+    } else {
+        assets::format(amount.into(), asset_id)
+    }
+}
+
+/// Formats base units of the base asset, which has [`units::DEFAULT_DECIMALS`].
+fn eth(amount: u64) -> String {
+    format!(
+        "{} {} ({})",
+        units::format_amount(amount.into(), units::DEFAULT_DECIMALS),
+        assets::BASE_SYMBOL,
+        amount
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::mock::{address, base_asset, other_asset, test_context, MockChain, MOCK_FEE};
+
+    const NO_PREFUND: GasPolicy = GasPolicy {
+        reserve: DEFAULT_GAS_RESERVE,
+        prefund: false,
+        dust: DEFAULT_DUST_THRESHOLD,
+        asset_dust: 0,
+    };
+
+    fn options(
+        gas_policy: &GasPolicy,
+        assets: AssetSelection,
+        destination: Option<Bech32Address>,
+    ) -> ReclaimOptions {
+        ReclaimOptions {
+            gas_policy: *gas_policy,
+            assets,
+            destination,
+            wallets: WalletSelection::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn reclaims_base_asset_to_funding_wallet() {
+        let ctx = test_context(2, base_asset());
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(main_wallet.address(), base_asset(), 100_000_000);
+        chain.set_balance(&address(&ctx, 1), base_asset(), 5_000_000);
+
+        reclaim_funds(
+            &ctx,
+            &main_wallet,
+            &chain,
+            &options(&NO_PREFUND, AssetSelection::Configured, None),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let reclaimed = chain
+            .transfers()
+            .into_iter()
+            .find(|t| t.from == address(&ctx, 1))
+            .unwrap();
+        assert_eq!(reclaimed.to, *main_wallet.address());
+        assert_eq!(reclaimed.amount, 4_999_000);
+        assert_eq!(chain.balance_of(&address(&ctx, 1), base_asset()), 0);
+    }
+
+    #[tokio::test]
+    async fn holds_reclaims_above_the_approval_limit() {
+        let mut ctx = test_context(2, base_asset());
+        ctx.approval_limit = Some(1_000_000);
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(main_wallet.address(), base_asset(), 100_000_000);
+        chain.set_balance(&address(&ctx, 1), base_asset(), 5_000_000);
+
+        // Started by hand, from the CLI, the API or gRPC
+        let options = options(&NO_PREFUND, AssetSelection::Configured, None);
+        let error = check_approval(&ctx, &main_wallet, &chain, &options)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("APPROVAL_LIMIT"));
+        assert!(chain.transfers().is_empty());
+
+        // A scheduled reclaim has no plan to present, and still runs
+        reclaim_funds(&ctx, &main_wallet, &chain, &options, None)
+            .await
+            .unwrap();
+        assert_eq!(chain.balance_of(&address(&ctx, 1), base_asset()), 0);
+
+        ctx.approval_limit = Some(100_000_000);
+        check_approval(&ctx, &main_wallet, &chain, &options)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn sweeps_every_coin_of_a_fragmented_wallet() {
+        let ctx = test_context(2, base_asset());
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(&address(&ctx, 1), base_asset(), 3_000_000);
+        chain.set_coins(
+            &address(&ctx, 1),
+            base_asset(),
+            vec![1_500_000, 1_000_000, 400_000, 100_000],
+        );
+
+        reclaim_funds(
+            &ctx,
+            &main_wallet,
+            &chain,
+            &options(&NO_PREFUND, AssetSelection::Configured, None),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let transfers = chain.transfers();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].amount, 3_000_000 - MOCK_FEE);
+        assert_eq!(chain.balance_of(&address(&ctx, 1), base_asset()), 0);
+    }
+
+    #[tokio::test]
+    async fn reclaims_to_destination() {
+        let ctx = test_context(2, base_asset());
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        let treasury = ctx.fleet.wallet(1000, None).unwrap();
+        chain.set_balance(&address(&ctx, 1), base_asset(), 5_000_000);
+
+        reclaim_funds(
+            &ctx,
+            &main_wallet,
+            &chain,
+            &options(
+                &NO_PREFUND,
+                AssetSelection::Configured,
+                Some(treasury.address().clone()),
+            ),
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            chain.balance_of(treasury.address(), base_asset()),
+            4_999_000
+        );
+    }
+
+    #[tokio::test]
+    async fn skips_other_asset_without_gas() {
+        let ctx = test_context(2, other_asset());
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(&address(&ctx, 1), other_asset(), 1_000);
+
+        reclaim_funds(
+            &ctx,
+            &main_wallet,
+            &chain,
+            &options(&NO_PREFUND, AssetSelection::Configured, None),
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert!(chain.transfers().is_empty());
+        assert_eq!(chain.balance_of(&address(&ctx, 1), other_asset()), 1_000);
+    }
+
+    #[tokio::test]
+    async fn prefunds_gas_for_other_asset() {
+        let ctx = test_context(2, other_asset());
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(main_wallet.address(), base_asset(), 100_000_000);
+        chain.set_balance(&address(&ctx, 1), other_asset(), 1_000);
+        let gas_policy = GasPolicy {
+            reserve: 10_000_000,
+            prefund: true,
+            ..NO_PREFUND
+        };
+
+        reclaim_funds(
+            &ctx,
+            &main_wallet,
+            &chain,
+            &options(&gas_policy, AssetSelection::Configured, None),
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(chain.balance_of(&address(&ctx, 1), other_asset()), 0);
+        assert_eq!(
+            chain.balance_of(main_wallet.address(), other_asset()),
+            1_000
+        );
+    }
+
+    #[tokio::test]
+    async fn leaves_dust_in_place() {
+        let ctx = test_context(3, base_asset());
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(&address(&ctx, 1), base_asset(), DEFAULT_DUST_THRESHOLD - 1);
+        chain.set_balance(&address(&ctx, 2), base_asset(), 5_000_000);
+
+        reclaim_funds(
+            &ctx,
+            &main_wallet,
+            &chain,
+            &options(&NO_PREFUND, AssetSelection::Configured, None),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let transfers = chain.transfers();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].from, address(&ctx, 2));
+        assert_eq!(
+            chain.balance_of(&address(&ctx, 1), base_asset()),
+            DEFAULT_DUST_THRESHOLD - 1
+        );
+    }
+
+    #[tokio::test]
+    async fn skips_other_asset_dust_before_prefunding_gas() {
+        let ctx = test_context(2, other_asset());
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(main_wallet.address(), base_asset(), 100_000_000);
+        chain.set_balance(&address(&ctx, 1), other_asset(), 10);
+        let gas_policy = GasPolicy {
+            prefund: true,
+            asset_dust: 100,
+            ..NO_PREFUND
+        };
+
+        reclaim_funds(
+            &ctx,
+            &main_wallet,
+            &chain,
+            &options(&gas_policy, AssetSelection::Configured, None),
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert!(chain.transfers().is_empty());
+    }
+
+    fn third_asset() -> AssetId {
+        AssetId::from([2u8; 32])
+    }
+
+    #[tokio::test]
+    async fn reclaims_every_held_asset_with_base_asset_last() {
+        let ctx = test_context(2, base_asset());
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(&address(&ctx, 1), base_asset(), 5_000_000);
+        chain.set_balance(&address(&ctx, 1), other_asset(), 1_000);
+        chain.set_balance(&address(&ctx, 1), third_asset(), 2_000);
+        let assets = AssetSelection::All { allowlist: None };
+
+        reclaim_funds(
+            &ctx,
+            &main_wallet,
+            &chain,
+            &options(&NO_PREFUND, assets, None),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let swept: Vec<AssetId> = chain
+            .transfers()
+            .into_iter()
+            .filter(|t| t.from == address(&ctx, 1))
+            .map(|t| t.asset_id)
+            .collect();
+        assert_eq!(swept, vec![other_asset(), third_asset(), base_asset()]);
+        assert_eq!(
+            chain.balance_of(main_wallet.address(), third_asset()),
+            2_000
+        );
+    }
+
+    #[tokio::test]
+    async fn allowlist_limits_reclaimed_assets() {
+        let ctx = test_context(2, base_asset());
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(&address(&ctx, 1), base_asset(), 5_000_000);
+        chain.set_balance(&address(&ctx, 1), other_asset(), 1_000);
+        chain.set_balance(&address(&ctx, 1), third_asset(), 2_000);
+        let assets = AssetSelection::All {
+            allowlist: Some(vec![third_asset()]),
+        };
+
+        reclaim_funds(
+            &ctx,
+            &main_wallet,
+            &chain,
+            &options(&NO_PREFUND, assets, None),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let transfers = chain.transfers();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].asset_id, third_asset());
+        assert_eq!(chain.balance_of(&address(&ctx, 1), other_asset()), 1_000);
+    }
+
+    #[tokio::test]
+    async fn reclaims_largest_first_up_to_a_target() {
+        let ctx = test_context(5, base_asset());
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(&address(&ctx, 1), base_asset(), 2_000_000);
+        chain.set_balance(&address(&ctx, 2), base_asset(), 9_000_000);
+        chain.set_balance(&address(&ctx, 3), base_asset(), 50_000);
+        chain.set_balance(&address(&ctx, 4), base_asset(), 5_000_000);
+        let options = ReclaimOptions {
+            wallets: WalletSelection {
+                largest_first: true,
+                min_balance: 100_000,
+                up_to: Some(10_000_000),
+                ..WalletSelection::default()
+            },
+            ..options(&NO_PREFUND, AssetSelection::Configured, None)
+        };
+
+        reclaim_funds(&ctx, &main_wallet, &chain, &options, None)
+            .await
+            .unwrap();
+
+        // Wallet 2 is swept whole, wallet 4 gives only what the target still needs
+        let transfers: Vec<(Bech32Address, u64)> = chain
+            .transfers()
+            .into_iter()
+            .map(|t| (t.from, t.amount))
+            .collect();
+        assert_eq!(
+            transfers,
+            vec![
+                (address(&ctx, 2), 9_000_000 - MOCK_FEE),
+                (address(&ctx, 4), 10_000_000 - (9_000_000 - MOCK_FEE)),
+            ]
+        );
+        assert_eq!(
+            chain.balance_of(main_wallet.address(), base_asset()),
+            10_000_000
+        );
+        assert_eq!(chain.balance_of(&address(&ctx, 1), base_asset()), 2_000_000);
+        assert_eq!(chain.balance_of(&address(&ctx, 3), base_asset()), 50_000);
+    }
+
+    #[tokio::test]
+    async fn leaves_float_and_a_share_behind() {
+        let ctx = test_context(3, base_asset());
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(&address(&ctx, 1), base_asset(), 10_000_000);
+        chain.set_balance(&address(&ctx, 2), base_asset(), 1_500_000);
+        let options = ReclaimOptions {
+            wallets: WalletSelection {
+                basis_points: Some(9_000),
+                leave: 2_000_000,
+                ..WalletSelection::default()
+            },
+            ..options(&NO_PREFUND, AssetSelection::Configured, None)
+        };
+
+        reclaim_funds(&ctx, &main_wallet, &chain, &options, None)
+            .await
+            .unwrap();
+
+        // 90% of wallet 1 would leave less than the float; wallet 2 holds less than it
+        let transfers: Vec<(Bech32Address, u64)> = chain
+            .transfers()
+            .into_iter()
+            .map(|t| (t.from, t.amount))
+            .collect();
+        assert_eq!(transfers, vec![(address(&ctx, 1), 8_000_000)]);
+        assert_eq!(
+            chain.balance_of(&address(&ctx, 1), base_asset()),
+            2_000_000 - MOCK_FEE
+        );
+        assert_eq!(chain.balance_of(&address(&ctx, 2), base_asset()), 1_500_000);
+    }
+
+    #[tokio::test]
+    async fn retries_failed_wallets_without_stopping_the_others() {
+        let mut ctx = test_context(4, base_asset());
+        ctx.reclaim_concurrency = 3;
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        for index in 1..4 {
+            chain.set_balance(&address(&ctx, index), base_asset(), 5_000_000);
+        }
+        // Wallet 2 fails once and succeeds on the retry; wallet 3 fails both times
+        chain.fail_transfers_from(&address(&ctx, 2), 1);
+        chain.fail_transfers_from(&address(&ctx, 3), 2);
+
+        let error = reclaim_funds(
+            &ctx,
+            &main_wallet,
+            &chain,
+            &options(&NO_PREFUND, AssetSelection::Configured, None),
+            None,
+        )
+        .await
+        .unwrap_err();
+
+        assert!(error.to_string().ends_with(": 3"));
+        assert_eq!(chain.balance_of(&address(&ctx, 1), base_asset()), 0);
+        assert_eq!(chain.balance_of(&address(&ctx, 2), base_asset()), 0);
+        assert_eq!(chain.balance_of(&address(&ctx, 3), base_asset()), 5_000_000);
+        assert_eq!(
+            chain.balance_of(main_wallet.address(), base_asset()),
+            2 * (5_000_000 - MOCK_FEE)
+        );
+    }
+
+    #[tokio::test]
+    async fn plans_what_reclaim_would_take() {
+        let ctx = test_context(4, other_asset());
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(&address(&ctx, 1), other_asset(), 3_000);
+        chain.set_balance(&address(&ctx, 1), base_asset(), 5_000);
+        chain.set_balance(&address(&ctx, 2), other_asset(), 10_000);
+        chain.set_balance(&address(&ctx, 3), other_asset(), 10_000);
+        let options = ReclaimOptions {
+            wallets: WalletSelection {
+                up_to: Some(8_000),
+                ..WalletSelection::default()
+            },
+            ..options(&NO_PREFUND, AssetSelection::Configured, None)
+        };
+
+        let steps = plan_reclaim(&ctx, &main_wallet, &chain, &options)
+            .await
+            .unwrap();
+        let planned: Vec<(usize, String, u64, bool)> = steps
+            .into_iter()
+            .map(|step| (step.wallet_index, step.asset_id, step.amount, step.sweep))
+            .collect();
+        let asset = format!("{:#x}", other_asset());
+        // Wallet 1's base asset is dust; wallet 2 completes the target and keeps the rest
+        assert_eq!(
+            planned,
+            vec![(1, asset.clone(), 3_000, true), (2, asset, 5_000, false)]
+        );
+        assert!(chain.transfers().is_empty());
+    }
+}
@@ -0,0 +1,85 @@
+//! "Refill needed" notices for treasury operations: when the main wallet drops below
+//! its reserve (`MAIN_WALLET_RESERVE`), how much to send it and where.
+
+use crate::units;
+use fuels::types::{bech32::Bech32Address, Address, AssetId};
+use serde::Serialize;
+
+/// Exit code of one-shot runs that leave the main wallet below its reserve.
+pub const EXIT_REFILL_NEEDED: i32 = 3;
+
+/// A main wallet below its reserve, with everything needed to top it back up.
+#[derive(Debug, Clone, Serialize)]
+pub struct RefillNeeded {
+    /// Deposit address, bech32.
+    pub main_wallet: String,
+    /// The same address as 0x-prefixed hex, which most wallets and exchanges expect.
+    pub main_wallet_hex: String,
+    pub asset_id: String,
+    /// Main wallet balance, in base units.
+    pub balance: u64,
+    pub reserve: u64,
+    /// Exact amount to send to bring the balance back to the reserve, in base units.
+    pub shortfall: u64,
+    /// `shortfall` as a decimal amount in the asset's decimals.
+    pub shortfall_decimal: String,
+    /// Payment URI to render as a QR code: `fuel:<hex address>?asset=<id>&amount=<decimal>`.
+    pub qr_payload: String,
+}
+
+impl RefillNeeded {
+    /// The notice for a main wallet at `address` holding `balance` of `asset_id`
+    /// (with `decimals`), or `None` if that is at least `reserve`.
+    pub fn check(
+        address: &Bech32Address,
+        asset_id: AssetId,
+        decimals: u32,
+        balance: u64,
+        reserve: u64,
+    ) -> Option<Self> {
+        if balance >= reserve {
+            return None;
+        }
+        let shortfall = reserve - balance;
+        let hex = format!("{:#x}", Address::from(address));
+        let shortfall_decimal = units::format_amount(shortfall.into(), decimals);
+        Some(Self {
+            main_wallet: address.to_string(),
+            qr_payload: format!(
+                "fuel:{}?asset={:#x}&amount={}",
+                hex, asset_id, shortfall_decimal
+            ),
+            main_wallet_hex: hex,
+            asset_id: format!("{:#x}", asset_id),
+            balance,
+            reserve,
+            shortfall,
+            shortfall_decimal,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fuels::types::bech32::FUEL_BECH32_HRP;
+
+    #[test]
+    fn reports_the_exact_shortfall() {
+        let address = Bech32Address::new(FUEL_BECH32_HRP, [7u8; 32]);
+        let asset_id = AssetId::from([1u8; 32]);
+        assert!(RefillNeeded::check(&address, asset_id, 9, 5_000_000, 5_000_000).is_none());
+
+        let refill = RefillNeeded::check(&address, asset_id, 9, 1_500_000, 5_000_000).unwrap();
+        assert_eq!(refill.shortfall, 3_500_000);
+        assert_eq!(refill.shortfall_decimal, "0.0035");
+        assert_eq!(
+            refill.qr_payload,
+            format!(
+                "fuel:0x{}?asset=0x{}&amount=0.0035",
+                "07".repeat(32),
+                "01".repeat(32)
+            )
+        );
+    }
+}
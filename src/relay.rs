@@ -0,0 +1,258 @@
+//! `--relay`: chained funding through hub wallets. The funding sources
+//! (`FUNDING_SOURCES`) act as hubs: before each initial distribution or funding
+//! cycle, the main wallet tops every hub up to what it is about to send plus fees,
+//! then each hub pays the HD wallets assigned to it, so the main wallet never pays
+//! a bot wallet directly. What each hub received and relayed is logged and kept as
+//! running totals in the state storage.
+
+use crate::{chain::ChainClient, context::Context, pipeline::Pipeline, signer::Funder};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+};
+use tracing::{debug, info, warn};
+
+/// Label and command of the main wallet's transfers to the hubs.
+pub const LABEL: &str = "relay";
+
+/// State key of the hubs' running totals.
+const LEDGER_KEY: &str = "relay.hubs";
+
+/// What one hub is about to send: `transfers` transfers adding up to `amount`.
+#[derive(Debug, Clone, Copy)]
+struct HubNeed {
+    /// An HD wallet the hub pays, to estimate its fees against.
+    first_wallet: usize,
+    amount: u128,
+    transfers: u64,
+}
+
+/// What one hub received from the main wallet and relayed to its HD wallets, in
+/// base units of `ETH_ASSET_ID`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HubLedger {
+    pub received: u128,
+    pub relayed: u128,
+    pub transfers: u64,
+}
+
+/// The hubs of one distribution or cycle: what each is about to send, then what
+/// each received and relayed. Keyed by the hub's derivation index.
+#[derive(Debug, Default)]
+pub struct Relay {
+    needs: BTreeMap<usize, HubNeed>,
+    ledgers: BTreeMap<usize, HubLedger>,
+}
+
+impl Relay {
+    /// Counts `amount` to HD wallet `wallet_index` against its hub. Wallets outside
+    /// every `FUNDING_SOURCES` range have no hub and are paid by the main wallet.
+    pub fn plan(&mut self, ctx: &Context<'_>, wallet_index: usize, amount: u64) {
+        if let Some(hub) = ctx.funding_sources.source_for(wallet_index) {
+            let need = self.needs.entry(hub).or_insert(HubNeed {
+                first_wallet: wallet_index,
+                amount: 0,
+                transfers: 0,
+            });
+            need.amount += u128::from(amount);
+            need.transfers += 1;
+        }
+    }
+
+    /// The first hop: sends every hub what it lacks for its planned transfers and
+    /// their estimated fees, and waits for those transfers to confirm. A hub of
+    /// another asset is also sent the base asset its fees need.
+    pub async fn fund_hubs(
+        &mut self,
+        ctx: &Context<'_>,
+        main_wallet: &Funder,
+        client: &dyn ChainClient,
+        sources: &HashMap<usize, Funder>,
+    ) -> Result<(), Box<dyn Error>> {
+        let base_asset = client.base_asset();
+        let mut pipeline = Pipeline::new(ctx, client).labelled(LABEL);
+        for (hub, need) in &self.needs {
+            let wallet = sources
+                .get(hub)
+                .ok_or_else(|| format!("Hub {} is not a funding source", hub))?;
+            let to = ctx.fleet.wallet(need.first_wallet, None)?;
+            let per_transfer = u64::try_from(need.amount / u128::from(need.transfers))?;
+            let fee = client
+                .estimate_transfer_fee(
+                    wallet,
+                    to.address(),
+                    per_transfer,
+                    &ctx.asset_id,
+                    ctx.tx_policies,
+                )
+                .await?;
+            let fees = u128::from(fee) * u128::from(need.transfers);
+            let (needed, gas_needed) = if ctx.asset_id == base_asset {
+                (need.amount + fees, 0)
+            } else {
+                (need.amount, fees)
+            };
+
+            let balance = client.balance(wallet.address(), &ctx.asset_id).await?;
+            let shortfall = needed.saturating_sub(balance.into());
+            if shortfall == 0 {
+                debug!(
+                    "Hub {} already holds {} for {} transfers.",
+                    hub,
+                    ctx.format_amount(balance),
+                    need.transfers
+                );
+            } else {
+                info!(
+                    "Hub {} holds {} but relays {} in {} transfers, sending it {}...",
+                    hub,
+                    ctx.format_amount(balance),
+                    ctx.format_amount(need.amount),
+                    need.transfers,
+                    ctx.format_amount(shortfall)
+                );
+                pipeline
+                    .submit_to_recipient(
+                        LABEL,
+                        main_wallet,
+                        wallet.address(),
+                        u64::try_from(shortfall)?,
+                        &ctx.asset_id,
+                        None,
+                    )
+                    .await?;
+                self.ledgers.entry(*hub).or_default().received += shortfall;
+            }
+            if gas_needed > 0 {
+                let gas = client.balance(wallet.address(), &base_asset).await?;
+                let gas_shortfall = gas_needed.saturating_sub(gas.into());
+                if gas_shortfall > 0 {
+                    info!(
+                        "Hub {} needs {} base units of the base asset for fees, sending them...",
+                        hub, gas_shortfall
+                    );
+                    pipeline
+                        .submit_to_recipient(
+                            LABEL,
+                            main_wallet,
+                            wallet.address(),
+                            u64::try_from(gas_shortfall)?,
+                            &base_asset,
+                            None,
+                        )
+                        .await?;
+                }
+            }
+        }
+        pipeline.finish().await?;
+        let unconfirmed = pipeline.take_unconfirmed();
+        if !unconfirmed.is_empty() {
+            return Err(format!(
+                "{} transfers to the hubs are still unconfirmed; the hubs cannot relay yet",
+                unconfirmed.len()
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// The second hop: records `amount` sent to HD wallet `wallet_index` by its hub.
+    pub fn relayed(&mut self, ctx: &Context<'_>, wallet_index: usize, amount: u64) {
+        if let Some(hub) = ctx.funding_sources.source_for(wallet_index) {
+            let ledger = self.ledgers.entry(hub).or_default();
+            ledger.relayed += u128::from(amount);
+            ledger.transfers += 1;
+        }
+    }
+
+    /// Logs what each hub received and relayed, and adds it to the running totals in
+    /// the state storage. A failure to save the totals is only warned about.
+    pub async fn finish(self, ctx: &Context<'_>) {
+        if self.ledgers.is_empty() {
+            return;
+        }
+        info!("Relay summary:");
+        for (hub, ledger) in &self.ledgers {
+            info!(
+                "  Hub {}: received {}, relayed {} in {} transfers",
+                hub,
+                ctx.format_amount(ledger.received),
+                ctx.format_amount(ledger.relayed),
+                ledger.transfers
+            );
+        }
+        let Some(storage) = ctx.sinks.storage else {
+            return;
+        };
+        let key = ctx.state_key(LEDGER_KEY);
+        let saved = async {
+            let mut totals: BTreeMap<usize, HubLedger> = match storage.get_state(&key).await? {
+                Some(totals) => serde_json::from_str(&totals)?,
+                None => BTreeMap::new(),
+            };
+            for (hub, ledger) in self.ledgers {
+                let total = totals.entry(hub).or_default();
+                total.received += ledger.received;
+                total.relayed += ledger.relayed;
+                total.transfers += ledger.transfers;
+            }
+            storage
+                .set_state(&key, &serde_json::to_string(&totals)?)
+                .await?;
+            Ok::<_, Box<dyn Error>>(())
+        };
+        if let Err(e) = saved.await {
+            warn!("Failed to update the hub totals ({}): {}", key, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chain::mock::{address, base_asset, test_context, MockChain, MOCK_FEE},
+        distribute::{initial_distribution, INITIAL_AMOUNT},
+        funding_sources::FundingSources,
+        wallets::Derivation,
+    };
+
+    #[tokio::test]
+    async fn funds_wallets_through_their_hubs() {
+        let mut ctx = test_context(4, base_asset());
+        ctx.funding_sources = FundingSources::parse("1-2:1000", 4, &Derivation::default()).unwrap();
+        ctx.relay = true;
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        let hub = ctx.fleet.wallet_at(1000, None).unwrap().address().clone();
+        chain.set_balance(main_wallet.address(), base_asset(), 100_000_000);
+        // Part of what the hub needs is already there
+        chain.set_balance(&hub, base_asset(), 1_000_000);
+
+        initial_distribution(&ctx, &main_wallet, &chain)
+            .await
+            .unwrap();
+
+        // The hub was sent exactly what it lacked, then paid its two wallets
+        let transfers = chain.transfers();
+        assert_eq!(transfers[0].to, hub);
+        let needed = 2 * (INITIAL_AMOUNT + MOCK_FEE);
+        assert_eq!(transfers[0].amount, needed - 1_000_000);
+        for index in 1..4 {
+            assert_eq!(
+                chain.balance_of(&address(&ctx, index), base_asset()),
+                INITIAL_AMOUNT
+            );
+        }
+        assert_eq!(chain.balance_of(&hub, base_asset()), 0);
+        for transfer in &transfers[1..] {
+            if transfer.to == address(&ctx, 1) || transfer.to == address(&ctx, 2) {
+                assert_eq!(&transfer.from, &hub);
+            } else {
+                // Wallet 3 has no hub
+                assert_eq!(&transfer.from, main_wallet.address());
+            }
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use crate::{addresses::ExportFormat, manifest::Manifest};
+use serde::Serialize;
+use std::{
+    error::Error,
+    io::{BufWriter, Write},
+};
+
+/// Rows written between flushes, so consumers tailing a report see progress.
+const FLUSH_EVERY: usize = 1_000;
+
+/// Writes report rows one at a time as they are produced, so reports over large
+/// fleets never hold every row in memory.
+pub struct ReportWriter<W: Write> {
+    output: Output<W>,
+    rows: usize,
+}
+
+enum Output<W: Write> {
+    Csv(csv::Writer<W>),
+    /// A JSON array, optionally wrapped in an object next to the manifest.
+    Json {
+        out: BufWriter<W>,
+        wrapped: bool,
+    },
+    Jsonl(BufWriter<W>),
+}
+
+impl<W: Write> ReportWriter<W> {
+    /// Starts a report, heading it with `manifest` if given: as a `# manifest`
+    /// comment line in CSV, a first line in JSONL, and in JSON as an object
+    /// holding the manifest and the rows under `rows_key`.
+    pub fn new(
+        mut out: W,
+        format: ExportFormat,
+        manifest: Option<&Manifest>,
+        rows_key: &str,
+    ) -> Result<Self, Box<dyn Error>> {
+        let output = match format {
+            ExportFormat::Csv => {
+                if let Some(manifest) = manifest {
+                    writeln!(out, "# manifest {}", serde_json::to_string(manifest)?)?;
+                }
+                Output::Csv(csv::Writer::from_writer(out))
+            }
+            ExportFormat::Json => {
+                let mut out = BufWriter::new(out);
+                match manifest {
+                    Some(manifest) => write!(
+                        out,
+                        "{{\"manifest\":{},{}:[",
+                        serde_json::to_string(manifest)?,
+                        serde_json::to_string(rows_key)?
+                    )?,
+                    None => write!(out, "[")?,
+                }
+                Output::Json {
+                    out,
+                    wrapped: manifest.is_some(),
+                }
+            }
+            ExportFormat::Jsonl => {
+                let mut out = BufWriter::new(out);
+                if let Some(manifest) = manifest {
+                    serde_json::to_writer(&mut out, &serde_json::json!({ "manifest": manifest }))?;
+                    writeln!(out)?;
+                }
+                Output::Jsonl(out)
+            }
+        };
+        Ok(Self { output, rows: 0 })
+    }
+
+    pub fn write<T: Serialize>(&mut self, row: &T) -> Result<(), Box<dyn Error>> {
+        match &mut self.output {
+            Output::Csv(writer) => writer.serialize(row)?,
+            Output::Json { out, .. } => {
+                if self.rows > 0 {
+                    write!(out, ",")?;
+                }
+                writeln!(out)?;
+                serde_json::to_writer(&mut *out, row)?;
+            }
+            Output::Jsonl(out) => {
+                serde_json::to_writer(&mut *out, row)?;
+                writeln!(out)?;
+            }
+        }
+        self.rows += 1;
+        if self.rows % FLUSH_EVERY == 0 {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Completes the report and returns the number of rows written.
+    pub fn finish(mut self) -> Result<usize, Box<dyn Error>> {
+        if let Output::Json { out, wrapped } = &mut self.output {
+            writeln!(out, "\n]{}", if *wrapped { "}" } else { "" })?;
+        }
+        self.flush()?;
+        Ok(self.rows)
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        match &mut self.output {
+            Output::Csv(writer) => writer.flush()?,
+            Output::Json { out, .. } | Output::Jsonl(out) => out.flush()?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+        io,
+    };
+
+    /// Counts the bytes allocated by the current thread, so the test is not
+    /// disturbed by tests running in parallel.
+    struct CountingAllocator;
+
+    thread_local! {
+        static LIVE: Cell<isize> = const { Cell::new(0) };
+        static PEAK: Cell<isize> = const { Cell::new(0) };
+    }
+
+    fn track(delta: isize) {
+        let _ = LIVE.try_with(|live| {
+            live.set(live.get() + delta);
+            let _ = PEAK.try_with(|peak| peak.set(peak.get().max(live.get())));
+        });
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            track(layout.size() as isize);
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            track(-(layout.size() as isize));
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            track(new_size as isize - layout.size() as isize);
+            System.realloc(ptr, layout, new_size)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    #[derive(Serialize)]
+    struct SyntheticWallet {
+        index: usize,
+        address: String,
+        balance: u64,
+    }
+
+    /// Counts bytes instead of keeping them.
+    #[derive(Default)]
+    struct CountingSink(usize);
+
+    impl Write for CountingSink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn large_fleet_report_uses_bounded_memory() {
+        const WALLETS: usize = 200_000;
+
+        for format in [ExportFormat::Csv, ExportFormat::Json, ExportFormat::Jsonl] {
+            let mut sink = CountingSink::default();
+            let start = LIVE.with(Cell::get);
+            PEAK.with(|peak| peak.set(start));
+
+            let mut writer = ReportWriter::new(&mut sink, format, None, "wallets").unwrap();
+            for index in 0..WALLETS {
+                writer
+                    .write(&SyntheticWallet {
+                        index,
+                        address: format!("fuel1{:058}", index),
+                        balance: index as u64 * 1_000,
+                    })
+                    .unwrap();
+            }
+            assert_eq!(writer.finish().unwrap(), WALLETS);
+
+            // Well over 15 MB of output, written through buffers of a few KB
+            let peak = PEAK.with(Cell::get) - start;
+            assert!(sink.0 > 15_000_000);
+            assert!(peak < 256 * 1024, "peak allocation of {} bytes", peak);
+        }
+    }
+}
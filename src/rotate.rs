@@ -0,0 +1,320 @@
+//! `rotate`: retires the HD wallets by reclaiming their funds, then funds as many
+//! fresh wallets further along the derivation path (`WALLET_INDEX_OFFSET`), so hot
+//! wallets are replaced without handling any keys.
+
+use crate::{
+    addresses::ExportFormat,
+    audit,
+    chain::{balances_of, ChainClient},
+    context::Context,
+    pipeline::Pipeline,
+    plan::TransferPlan,
+    reclaim::{plan_reclaim, reclaim_funds, ReclaimOptions},
+    report::ReportWriter,
+    signer::Funder,
+    units,
+    wallets::Fleet,
+};
+use fuels::types::bech32::Bech32Address;
+use serde::Serialize;
+use std::{collections::HashMap, error::Error, fs::File, path::Path};
+use tracing::{info, warn};
+
+/// Label of the transfers funding the new wallets, unless the run has a `--label`.
+const LABEL: &str = "rotate";
+
+/// One HD wallet index moving from its retired wallet to a new one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Rotation {
+    pub index: usize,
+    pub old_address: String,
+    pub old_path: String,
+    pub new_address: String,
+    pub new_path: String,
+    /// What the new wallet is sent, in base units of `ETH_ASSET_ID`.
+    pub amount: u64,
+}
+
+/// Offset of the new wallets unless `--offset` is given: right after the current ones.
+pub fn next_offset(ctx: &Context<'_>) -> usize {
+    ctx.fleet.derivation.offset + ctx.number_of_wallets.saturating_sub(1)
+}
+
+/// Moves every HD wallet but the main wallet to `offset`: writes the old to new
+/// mapping, reclaims the old wallets to their funding wallets, then sends each new
+/// wallet what its predecessor held, or `amount` if given.
+#[allow(clippy::too_many_arguments)]
+pub async fn rotate(
+    ctx: &Context<'_>,
+    main_wallet: &Funder,
+    client: &dyn ChainClient,
+    offset: usize,
+    amount: Option<u64>,
+    reclaim: &ReclaimOptions,
+    output: Option<&Path>,
+    format: ExportFormat,
+) -> Result<(), Box<dyn Error>> {
+    let fresh = ctx.fleet.rotated(offset);
+    let sources = ctx.funding_sources.derive(&ctx.fleet, client.provider())?;
+    check_offset(ctx, &fresh, &sources)?;
+
+    let mut indices = Vec::with_capacity(ctx.number_of_wallets);
+    let mut addresses: Vec<Bech32Address> = Vec::with_capacity(ctx.number_of_wallets);
+    for index in 1..ctx.number_of_wallets {
+        if ctx.is_excluded(index)? {
+            warn!(
+                "HD Wallet {} is excluded: its old wallet keeps its funds and the new one gets none.",
+                index
+            );
+            continue;
+        }
+        indices.push(index);
+        addresses.push(ctx.fleet.wallet(index, None)?.address().clone());
+    }
+    let amounts = match amount {
+        Some(amount) => vec![amount; indices.len()],
+        None => {
+            balances_of(
+                client,
+                &addresses,
+                &ctx.asset_id,
+                ctx.balance_concurrency,
+                None,
+            )
+            .await?
+        }
+    };
+    let mut rotations = Vec::with_capacity(indices.len());
+    for ((index, old_address), amount) in indices.into_iter().zip(addresses).zip(amounts) {
+        rotations.push(Rotation {
+            index,
+            old_address: old_address.to_string(),
+            old_path: ctx.fleet.derivation.path(index),
+            new_address: fresh.wallet(index, None)?.address().to_string(),
+            new_path: fresh.derivation.path(index),
+            amount,
+        });
+    }
+
+    // Neither half can go through a reviewed plan, so above APPROVAL_LIMIT the rotation
+    // is refused before anything moves
+    if let Some(limit) = ctx.approval_limit {
+        let funding: u128 = rotations.iter().map(|r| u128::from(r.amount)).sum();
+        let steps = plan_reclaim(ctx, main_wallet, client, reclaim).await?;
+        let reclaiming = TransferPlan::new("reclaim", main_wallet, steps).total(&ctx.asset_id);
+        let moved = funding.max(reclaiming);
+        if moved > u128::from(limit) {
+            return Err(format!(
+                "The rotation would move {}, more than APPROVAL_LIMIT ({}), and rotations cannot go through a reviewed plan",
+                ctx.format_amount(moved),
+                ctx.format_amount(limit)
+            )
+            .into());
+        }
+    }
+
+    // The mapping is out before anything moves, so an interrupted rotation can be finished
+    write_mapping(&rotations, output, format)?;
+    audit::record("rotation", &rotations);
+
+    info!("Reclaiming {} retiring HD wallets...", rotations.len());
+    reclaim_funds(ctx, main_wallet, client, reclaim, None).await?;
+
+    info!("Funding the new HD wallets at {}...", fresh.derivation);
+    let mut pipeline = Pipeline::new(ctx, client).labelled(LABEL);
+    let mut sent = 0u128;
+    for rotation in rotations.iter().filter(|rotation| rotation.amount > 0) {
+        let source = ctx
+            .funding_sources
+            .wallet_for(rotation.index, main_wallet, &sources);
+        let wallet = fresh.wallet(rotation.index, None)?;
+        pipeline
+            .submit(
+                "rotate",
+                rotation.index,
+                source,
+                wallet.address(),
+                rotation.amount,
+                &ctx.asset_id,
+            )
+            .await?;
+        sent += u128::from(rotation.amount);
+    }
+    pipeline.finish().await?;
+    let unconfirmed = pipeline.take_unconfirmed();
+
+    info!("Rotation summary:");
+    info!("  Wallets rotated:  {}", rotations.len());
+    info!("  Sent:             {}", ctx.format_amount(sent));
+    info!(
+        "  Fees paid:        {}",
+        units::format_fee(pipeline.fees_paid().into())
+    );
+    if !unconfirmed.is_empty() {
+        return Err(format!(
+            "{} transfers to the new wallets are still unconfirmed; check them before switching WALLET_INDEX_OFFSET",
+            unconfirmed.len()
+        )
+        .into());
+    }
+    println!(
+        "Set WALLET_INDEX_OFFSET={} so funding uses the new wallets from now on.",
+        offset
+    );
+    Ok(())
+}
+
+/// Refuses an offset whose wallets are the current ones or a funding source.
+fn check_offset(
+    ctx: &Context<'_>,
+    fresh: &Fleet,
+    sources: &HashMap<usize, Funder>,
+) -> Result<(), Box<dyn Error>> {
+    let in_range = |fleet: &Fleet, derivation_index: usize| {
+        fleet
+            .derivation
+            .hd_index(derivation_index)
+            .is_some_and(|index| index > 0 && index < ctx.number_of_wallets)
+    };
+    if (1..ctx.number_of_wallets).any(|index| in_range(&ctx.fleet, fresh.derivation.index(index))) {
+        return Err(format!(
+            "Offset {} overlaps the current HD wallets at {}",
+            fresh.derivation.offset, ctx.fleet.derivation
+        )
+        .into());
+    }
+    if let Some(source) = sources.keys().find(|source| in_range(fresh, **source)) {
+        return Err(format!(
+            "Offset {} would make funding source {} an HD wallet",
+            fresh.derivation.offset, source
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Prints the old to new mapping and optionally writes it to `output`.
+fn write_mapping(
+    rotations: &[Rotation],
+    output: Option<&Path>,
+    format: ExportFormat,
+) -> Result<(), Box<dyn Error>> {
+    for rotation in rotations {
+        println!(
+            "{}\t{}\t{}",
+            rotation.index, rotation.old_address, rotation.new_address
+        );
+    }
+    if let Some(output) = output {
+        let mut report = ReportWriter::new(File::create(output)?, format, None, "rotations")?;
+        for rotation in rotations {
+            report.write(rotation)?;
+        }
+        let rows = report.finish()?;
+        println!("Wrote {} rotations to {}", rows, output.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chain::mock::{address, base_asset, test_context, MockChain},
+        reclaim::{AssetSelection, GasPolicy, WalletSelection, DEFAULT_GAS_RESERVE},
+    };
+
+    fn reclaim_options() -> ReclaimOptions {
+        ReclaimOptions {
+            gas_policy: GasPolicy {
+                reserve: DEFAULT_GAS_RESERVE,
+                prefund: false,
+                dust: 0,
+                asset_dust: 0,
+            },
+            assets: AssetSelection::Configured,
+            destination: None,
+            wallets: WalletSelection::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn moves_balances_to_fresh_wallets() {
+        let ctx = test_context(3, base_asset());
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(main_wallet.address(), base_asset(), 100_000_000);
+        chain.set_balance(&address(&ctx, 1), base_asset(), 5_000_000);
+        chain.set_balance(&address(&ctx, 2), base_asset(), 7_000_000);
+
+        assert_eq!(next_offset(&ctx), 2);
+        // The new wallets may not be the current ones
+        assert!(rotate(
+            &ctx,
+            &main_wallet,
+            &chain,
+            1,
+            None,
+            &reclaim_options(),
+            None,
+            ExportFormat::Json,
+        )
+        .await
+        .is_err());
+        assert!(chain.transfers().is_empty());
+
+        rotate(
+            &ctx,
+            &main_wallet,
+            &chain,
+            2,
+            None,
+            &reclaim_options(),
+            None,
+            ExportFormat::Json,
+        )
+        .await
+        .unwrap();
+
+        let fresh = ctx.fleet.rotated(2);
+        for (index, balance) in [(1, 5_000_000), (2, 7_000_000)] {
+            assert_eq!(chain.balance_of(&address(&ctx, index), base_asset()), 0);
+            let new_address = fresh.wallet(index, None).unwrap().address().clone();
+            assert_eq!(chain.balance_of(&new_address, base_asset()), balance);
+        }
+        assert_eq!(
+            fresh.wallet(0, None).unwrap().address(),
+            main_wallet.address()
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_rotations_above_the_approval_limit() {
+        let mut ctx = test_context(3, base_asset());
+        ctx.approval_limit = Some(10_000_000);
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(main_wallet.address(), base_asset(), 100_000_000);
+        chain.set_balance(&address(&ctx, 1), base_asset(), 5_000_000);
+        chain.set_balance(&address(&ctx, 2), base_asset(), 7_000_000);
+
+        let options = reclaim_options();
+        let rotated = |amount| {
+            rotate(
+                &ctx,
+                &main_wallet,
+                &chain,
+                2,
+                amount,
+                &options,
+                None,
+                ExportFormat::Json,
+            )
+        };
+        // Reclaiming 12_000_000 is above the limit, and so is funding 2 x 6_000_000
+        let error = rotated(None).await.unwrap_err();
+        assert!(error.to_string().contains("APPROVAL_LIMIT"));
+        assert!(rotated(Some(6_000_000)).await.is_err());
+        assert!(chain.transfers().is_empty());
+    }
+}
@@ -0,0 +1,307 @@
+//! Interval, cron and block schedules for the recurring tasks of continual funding.
+
+use std::{fmt, str::FromStr, time::Duration};
+
+/// How far ahead a cron expression is searched for its next match (covers a leap day).
+const SEARCH_DAYS: u64 = 5 * 366;
+
+/// Fuel's block time, in seconds. A block schedule polls the chain height rather than
+/// subscribing to blocks, and waits this long for each block it still needs first.
+pub const BLOCK_TIME_SECS: u64 = 1;
+
+/// When a recurring task runs: `every 20s` / `every 5m` / `every 1h`, a
+/// five-field cron expression (`minute hour day-of-month month day-of-week`, UTC),
+/// or `every block` / `every 5 blocks`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    Every(Duration),
+    Cron(Cron),
+    /// Once the chain has advanced this many blocks.
+    Blocks(u32),
+}
+
+impl Schedule {
+    /// Unix time (seconds) the task next runs after `now`. Block schedules are due
+    /// when their blocks should have been produced, [`BLOCK_TIME_SECS`] apart, and the
+    /// caller checks the height to see whether they were.
+    pub fn next_after(&self, now: u64) -> u64 {
+        match self {
+            Schedule::Every(interval) => now + interval.as_secs(),
+            // Expressions that never match are rejected when parsed
+            Schedule::Cron(cron) => cron.next_after(now).unwrap_or(u64::MAX),
+            Schedule::Blocks(blocks) => now + u64::from(*blocks) * BLOCK_TIME_SECS,
+        }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s.strip_prefix("every ").map(str::trim) {
+            Some("block") => Ok(Schedule::Blocks(1)),
+            Some(blocks) if blocks.ends_with(" blocks") => {
+                match blocks.trim_end_matches(" blocks").trim().parse::<u32>() {
+                    Ok(count) if count > 0 => Ok(Schedule::Blocks(count)),
+                    _ => Err(format!("'{}' is not a number of blocks", blocks)),
+                }
+            }
+            Some(interval) => parse_interval(interval).map(Schedule::Every),
+            None => s.parse().map(Schedule::Cron),
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Schedule::Every(interval) => write!(f, "every {}s", interval.as_secs()),
+            Schedule::Cron(cron) => write!(f, "{}", cron.expr),
+            Schedule::Blocks(1) => write!(f, "every block"),
+            Schedule::Blocks(blocks) => write!(f, "every {} blocks", blocks),
+        }
+    }
+}
+
+/// Parses an interval such as `20s`, `5m`, `1h` or `7d`.
+pub fn parse_interval(interval: &str) -> Result<Duration, String> {
+    let split = interval
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(interval.len());
+    let (value, unit) = interval.split_at(split);
+    let value: u64 = value
+        .parse()
+        .map_err(|_| format!("'{}' is not an interval like 20s, 5m or 1h", interval))?;
+    let seconds = match unit {
+        "s" | "" => value,
+        "m" => value * 60,
+        "h" => value * 3600,
+        "d" => value * 86400,
+        _ => {
+            return Err(format!(
+                "Unknown interval unit '{}' (use s, m, h or d)",
+                unit
+            ))
+        }
+    };
+    if seconds == 0 {
+        return Err("Schedule interval must be greater than 0".into());
+    }
+    Ok(Duration::from_secs(seconds))
+}
+
+/// A parsed five-field cron expression; each field is a bit set of allowed values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    expr: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Day-of-month or day-of-week was `*`; if both are restricted either may match.
+    any_day: bool,
+    any_weekday: bool,
+}
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+impl FromStr for Cron {
+    type Err = String;
+
+    fn from_str(expr: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!(
+                "'{}' is neither 'every <interval>' nor a five-field cron expression",
+                expr
+            ));
+        };
+        let mut weekdays = parse_field(weekday, 0, 7, &WEEKDAYS, 0)?;
+        // Both 0 and 7 mean Sunday
+        if weekdays & (1u64 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1u64 << 7);
+        }
+        let cron = Cron {
+            expr: fields.join(" "),
+            minutes: parse_field(minute, 0, 59, &[], 0)?,
+            hours: parse_field(hour, 0, 23, &[], 0)?,
+            days: parse_field(day, 1, 31, &[], 0)?,
+            months: parse_field(month, 1, 12, &MONTHS, 1)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        };
+        if cron.next_after(0).is_none() {
+            return Err(format!("Cron expression '{}' never matches", expr));
+        }
+        Ok(cron)
+    }
+}
+
+/// Parses a comma-separated list of `*`, `n`, `a-b`, each optionally `/step`.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str], first: u32) -> Result<u64, String> {
+    let value = |s: &str| -> Result<u32, String> {
+        let named = names
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(s))
+            .map(|i| i as u32 + first);
+        match named.map_or_else(|| s.parse::<u32>().ok(), Some) {
+            Some(v) if (min..=max).contains(&v) => Ok(v),
+            _ => Err(format!(
+                "'{}' in cron field '{}' is not in {}-{}",
+                s, field, min, max
+            )),
+        }
+    };
+
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("Invalid step in cron field '{}'", field))?,
+            ),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (value(start)?, value(end)?),
+                // `n/step` runs from n to the end of the range
+                None if step > 1 => (value(range)?, max),
+                None => (value(range)?, value(range)?),
+            },
+        };
+        if start > end {
+            return Err(format!("Empty range '{}' in cron field '{}'", range, field));
+        }
+        for v in (start..=end).step_by(step as usize) {
+            mask |= 1u64 << v;
+        }
+    }
+    Ok(mask)
+}
+
+impl Cron {
+    /// First whole minute strictly after `now` that matches, if any within [`SEARCH_DAYS`].
+    fn next_after(&self, now: u64) -> Option<u64> {
+        let mut t = (now / 60 + 1) * 60;
+        let end = now + SEARCH_DAYS * 86400;
+        while t <= end {
+            let days = t / 86400;
+            let (_, month, day) = civil_from_days(days);
+            let weekday = (days + 4) % 7; // 1970-01-01 was a Thursday
+            let day_matches = match (self.any_day, self.any_weekday) {
+                (false, false) => {
+                    self.days & (1u64 << day) != 0 || self.weekdays & (1u64 << weekday) != 0
+                }
+                _ => self.days & (1u64 << day) != 0 && self.weekdays & (1u64 << weekday) != 0,
+            };
+            if self.months & (1u64 << month) == 0 || !day_matches {
+                t = (days + 1) * 86400;
+                continue;
+            }
+            let hour = (t % 86400) / 3600;
+            if self.hours & (1u64 << hour) == 0 {
+                t = (t / 3600 + 1) * 3600;
+                continue;
+            }
+            let minute = (t % 3600) / 60;
+            if self.minutes & (1u64 << minute) == 0 {
+                t += 60;
+                continue;
+            }
+            return Some(t);
+        }
+        None
+    }
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) civil date.
+fn civil_from_days(days: u64) -> (u64, u32, u32) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-01-01 00:00 UTC, a Monday.
+    const NEW_YEAR_2024: u64 = 1_704_067_200;
+
+    fn next(expr: &str, now: u64) -> u64 {
+        expr.parse::<Schedule>().unwrap().next_after(now)
+    }
+
+    #[test]
+    fn parses_intervals() {
+        assert_eq!(
+            "every 20s".parse::<Schedule>(),
+            Ok(Schedule::Every(Duration::from_secs(20)))
+        );
+        assert_eq!(next("every 5m", 100), 400);
+        assert!("every 0s".parse::<Schedule>().is_err());
+        assert!("every 5x".parse::<Schedule>().is_err());
+    }
+
+    #[test]
+    fn parses_block_schedules() {
+        assert_eq!("every block".parse::<Schedule>(), Ok(Schedule::Blocks(1)));
+        assert_eq!(
+            "every 5 blocks".parse::<Schedule>(),
+            Ok(Schedule::Blocks(5))
+        );
+        assert_eq!(Schedule::Blocks(5).to_string(), "every 5 blocks");
+        assert_eq!(next("every 3 blocks", 100), 100 + 3 * BLOCK_TIME_SECS);
+        assert!("every 0 blocks".parse::<Schedule>().is_err());
+    }
+
+    #[test]
+    fn finds_next_cron_match() {
+        // Sunday 02:00 UTC
+        assert_eq!(
+            next("0 2 * * 0", NEW_YEAR_2024),
+            NEW_YEAR_2024 + 6 * 86400 + 7200
+        );
+        assert_eq!(
+            next("0 2 * * sun", NEW_YEAR_2024),
+            NEW_YEAR_2024 + 6 * 86400 + 7200
+        );
+        // Strictly after now
+        assert_eq!(next("*/15 * * * *", NEW_YEAR_2024), NEW_YEAR_2024 + 900);
+        assert_eq!(next("* * * * *", NEW_YEAR_2024 + 30), NEW_YEAR_2024 + 60);
+        // Leap day
+        assert_eq!(
+            next("0 0 29 feb *", NEW_YEAR_2024),
+            NEW_YEAR_2024 + 59 * 86400
+        );
+        // Day-of-month or day-of-week when both are restricted: the 3rd, a Wednesday
+        assert_eq!(next("0 0 15 * 3", NEW_YEAR_2024), NEW_YEAR_2024 + 2 * 86400);
+    }
+
+    #[test]
+    fn rejects_invalid_cron() {
+        assert!("61 * * * *".parse::<Schedule>().is_err());
+        assert!("* * * *".parse::<Schedule>().is_err());
+        assert!("0 0 31 2 *".parse::<Schedule>().is_err());
+        assert!("5-1 * * * *".parse::<Schedule>().is_err());
+        assert!("*/0 * * * *".parse::<Schedule>().is_err());
+    }
+}
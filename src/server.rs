@@ -0,0 +1,583 @@
+//! `serve`: control distribution and continual funding over HTTP.
+//!
+//! Requests are handled by axum on a spawned task and forwarded over a channel
+//! to a supervisor running on the caller's task, which owns the run context and
+//! drives at most one job (distribution or continual funding) at a time.
+
+#[cfg(feature = "grpc")]
+use crate::grpc;
+use crate::{
+    chain::ChainClient,
+    context::Context,
+    daemon::{shutdown_signal, HealthState},
+    dashboard::{fleet_status, WalletStatus},
+    distribute::initial_distribution,
+    failure,
+    fund::{continual_funding, FundingSettings},
+    provider_pool::ProviderPool,
+    rate_limit::RateLimiter,
+    reclaim::{self, reclaim_funds, AssetSelection, GasPolicy, ReclaimOptions, WalletSelection},
+    signer::Funder,
+    storage::{TransferFilter, TransferRecord},
+};
+use axum::{
+    extract::{ConnectInfo, Query, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use fuels::{accounts::provider::Provider, types::bech32::Bech32Address};
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    future::Future,
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use subtle::ConstantTimeEq;
+use tokio::{
+    net::TcpListener,
+    sync::{mpsc, oneshot},
+};
+use tracing::{info, Instrument};
+#[cfg(feature = "dashboard")]
+use {crate::dashboard::DASHBOARD_HTML, axum::response::Html};
+
+/// Error returned to API clients as `{"error": "..."}`.
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: impl ToString) -> Self {
+        Self {
+            status,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (
+            self.status,
+            Json(serde_json::json!({ "error": self.message })),
+        )
+            .into_response()
+    }
+}
+
+pub type Reply<T> = oneshot::Sender<Result<T, ApiError>>;
+
+/// Requests forwarded from the HTTP and gRPC handlers to the supervisor.
+pub enum Control {
+    Distribute(Reply<()>),
+    StartFunding(Reply<()>),
+    Reclaim {
+        prefund_gas: bool,
+        to: Option<Bech32Address>,
+        reply: Reply<()>,
+    },
+    StopFunding(Reply<()>),
+    Status(Reply<Status>),
+    Balances(Reply<Vec<WalletBalance>>),
+    Fleet(Reply<Vec<WalletStatus>>),
+    History(TransferFilter, Reply<Vec<TransferRecord>>),
+    PublicStatus(Reply<PublicStatus>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum JobKind {
+    Distribution,
+    ContinualFunding,
+    Reclaim,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Status {
+    /// Job currently running, if any.
+    pub job: Option<JobKind>,
+    /// Outcome of the last job that ended, e.g. `"distribution failed: ..."`.
+    pub last_result: Option<String>,
+    pub seconds_since_last_cycle: Option<u64>,
+    /// HD wallets continual funding found idle (`IDLE_AFTER`) and stopped topping up.
+    pub idle_wallets: Vec<usize>,
+}
+
+/// Coarse aggregates for `/public/status`: no addresses, balances or job details.
+#[derive(Debug, Clone, Serialize)]
+pub struct PublicStatus {
+    pub total_wallets: usize,
+    /// Share of wallets at or above the funding threshold, in percent.
+    pub percent_funded: u8,
+    /// Unix time of the last completed continual funding cycle.
+    pub last_cycle: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WalletBalance {
+    wallet_index: usize,
+    address: String,
+    balance: u64,
+    /// Spent nothing for `IDLE_AFTER`, so no longer topped up.
+    idle: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryQuery {
+    tag: Option<String>,
+    label: Option<String>,
+    since: Option<u64>,
+    until: Option<u64>,
+    asset: Option<String>,
+    wallet: Option<u64>,
+}
+
+#[derive(Clone)]
+struct ApiState {
+    control: mpsc::Sender<Control>,
+    token: Option<String>,
+    public_limiter: Arc<RateLimiter>,
+}
+
+/// How long a computed `/public/status` is served before balances are queried again.
+const PUBLIC_STATUS_TTL: Duration = Duration::from_secs(30);
+
+type Job<'c> = Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + 'c>>;
+
+/// Settings of `serve` beyond the run context.
+pub struct ServeOptions {
+    /// Bearer token required on every API request, HTTP and gRPC.
+    pub token: Option<String>,
+    /// Also serve the gRPC control interface on this address.
+    pub grpc_addr: Option<SocketAddr>,
+    /// Gas reserve and dust thresholds of API reclaims; `prefund` is set per request.
+    pub gas_policy: GasPolicy,
+    /// Assets API reclaims sweep (`--all-assets`, `--assets`).
+    pub assets: AssetSelection,
+    /// Settings continual funding starts with.
+    pub settings: FundingSettings,
+    /// Serve unauthenticated `/public/status`, allowing this many requests per
+    /// minute from each client address.
+    pub public_status: Option<u32>,
+}
+
+/// Serves the API on `listener` until a shutdown signal arrives.
+pub async fn serve(
+    ctx: &Context<'_>,
+    main_wallet: &Funder,
+    provider_pool: &ProviderPool,
+    provider: Provider,
+    listener: TcpListener,
+    options: ServeOptions,
+) -> Result<(), Box<dyn Error>> {
+    #[cfg(not(feature = "grpc"))]
+    if options.grpc_addr.is_some() {
+        return Err("--grpc-addr needs a build with the `grpc` feature".into());
+    }
+
+    let (control, mut commands) = mpsc::channel(16);
+    #[cfg(feature = "grpc")]
+    let grpc = options.grpc_addr.map(|addr| {
+        info!("gRPC control interface listening on {}", addr);
+        tokio::spawn(grpc::serve(addr, control.clone(), options.token.clone()).in_current_span())
+    });
+    let state = ApiState {
+        control,
+        token: options.token,
+        public_limiter: Arc::new(RateLimiter::new(
+            options.public_status.unwrap_or_default(),
+            Duration::from_secs(60),
+        )),
+    };
+    let app = Router::new()
+        .route("/status", get(status))
+        .route("/balances", get(balances))
+        .route("/fleet", get(fleet))
+        .route("/history", get(history))
+        .route("/distribution", post(distribute))
+        .route("/funding/start", post(start_funding))
+        .route("/funding/stop", post(stop_funding))
+        .route_layer(middleware::from_fn_with_state(state.clone(), authorize));
+    // The page itself is public; it asks for the token to call the API
+    #[cfg(feature = "dashboard")]
+    let app = app.route("/", get(dashboard));
+    let app = match options.public_status {
+        Some(_) => app.route("/public/status", get(public_status)),
+        None => app,
+    };
+    let app = app.with_state(state);
+
+    info!("API listening on http://{}", listener.local_addr()?);
+    let server = tokio::spawn(
+        async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+        }
+        .in_current_span(),
+    );
+
+    let health = HealthState::default();
+    let mut job: Option<(JobKind, Job<'_>)> = None;
+    let mut last_result: Option<String> = None;
+    let mut public_cache: Option<(Instant, PublicStatus)> = None;
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
+    loop {
+        // Drive the running job while waiting for the next request
+        let command = match &mut job {
+            Some((kind, future)) => tokio::select! {
+                result = future.as_mut() => {
+                    let outcome = match result {
+                        Ok(()) => format!("{} completed", describe(*kind)),
+                        Err(e) => {
+                            let failure = failure::report(describe(*kind), e.as_ref());
+                            format!(
+                                "{} failed ({}): {}. Hint: {}",
+                                describe(*kind),
+                                failure,
+                                e,
+                                failure.hint()
+                            )
+                        }
+                    };
+                    info!("{}", outcome);
+                    last_result = Some(outcome);
+                    job = None;
+                    continue;
+                }
+                command = commands.recv() => command,
+                _ = &mut shutdown => break,
+            },
+            None => tokio::select! {
+                command = commands.recv() => command,
+                _ = &mut shutdown => break,
+            },
+        };
+        let Some(command) = command else { break };
+
+        match command {
+            Control::Distribute(reply)
+            | Control::StartFunding(reply)
+            | Control::Reclaim { reply, .. }
+                if job.is_some() =>
+            {
+                let running = job.as_ref().map(|(kind, _)| describe(*kind));
+                let _ = reply.send(Err(ApiError::new(
+                    StatusCode::CONFLICT,
+                    format!("{} is already running", running.unwrap_or_default()),
+                )));
+            }
+            Control::Distribute(reply) => {
+                info!("Starting initial distribution (API request)...");
+                let client: &dyn ChainClient = &provider;
+                job = Some((
+                    JobKind::Distribution,
+                    Box::pin(initial_distribution(ctx, main_wallet, client)),
+                ));
+                let _ = reply.send(Ok(()));
+            }
+            Control::StartFunding(reply) => {
+                info!("Starting continual funding (API request)...");
+                let mut wallet = main_wallet.clone();
+                let mut pool = provider_pool.clone();
+                let provider = provider.clone();
+                let health = health.clone();
+                let settings = options.settings.clone();
+                // Scheduled reclaims (RECLAIM_SCHEDULE) go back to the funding wallets
+                let reclaim = ReclaimOptions {
+                    gas_policy: options.gas_policy,
+                    assets: options.assets.clone(),
+                    destination: None,
+                    wallets: WalletSelection::default(),
+                };
+                job = Some((
+                    JobKind::ContinualFunding,
+                    Box::pin(async move {
+                        continual_funding(
+                            ctx,
+                            &mut wallet,
+                            &mut pool,
+                            provider,
+                            &health,
+                            settings,
+                            &reclaim,
+                        )
+                        .await
+                    }),
+                ));
+                let _ = reply.send(Ok(()));
+            }
+            Control::Reclaim {
+                prefund_gas,
+                to,
+                reply,
+            } => {
+                info!("Starting fund reclamation (API request)...");
+                let reclaim = ReclaimOptions {
+                    gas_policy: GasPolicy {
+                        prefund: prefund_gas,
+                        ..options.gas_policy
+                    },
+                    assets: options.assets.clone(),
+                    destination: to,
+                    wallets: WalletSelection::default(),
+                };
+                let client: &dyn ChainClient = &provider;
+                job = Some((
+                    JobKind::Reclaim,
+                    Box::pin(async move {
+                        reclaim::check_approval(ctx, main_wallet, client, &reclaim).await?;
+                        reclaim_funds(ctx, main_wallet, client, &reclaim, None).await
+                    }),
+                ));
+                let _ = reply.send(Ok(()));
+            }
+            Control::StopFunding(reply) => {
+                let result = if matches!(job, Some((JobKind::ContinualFunding, _))) {
+                    info!("Stopping continual funding (API request).");
+                    job = None;
+                    last_result = Some("continual funding stopped".to_string());
+                    Ok(())
+                } else {
+                    Err(ApiError::new(
+                        StatusCode::CONFLICT,
+                        "continual funding is not running",
+                    ))
+                };
+                let _ = reply.send(result);
+            }
+            Control::Status(reply) => {
+                let _ = reply.send(Ok(Status {
+                    job: job.as_ref().map(|(kind, _)| *kind),
+                    last_result: last_result.clone(),
+                    seconds_since_last_cycle: health.seconds_since_last_cycle(),
+                    idle_wallets: health.idle_wallets(),
+                }));
+            }
+            Control::Balances(reply) => {
+                let _ = reply.send(
+                    wallet_balances(ctx, &provider, &health.idle_wallets())
+                        .await
+                        .map_err(|e| ApiError::new(StatusCode::BAD_GATEWAY, e)),
+                );
+            }
+            Control::Fleet(reply) => {
+                let _ = reply.send(
+                    fleet_status(ctx, &provider)
+                        .await
+                        .map_err(|e| ApiError::new(StatusCode::BAD_GATEWAY, e)),
+                );
+            }
+            Control::PublicStatus(reply) => {
+                let cached = public_cache
+                    .as_ref()
+                    .filter(|(at, _)| at.elapsed() < PUBLIC_STATUS_TTL)
+                    .map(|(_, status)| status.clone());
+                let result = match cached {
+                    Some(status) => Ok(status),
+                    None => wallet_balances(ctx, &provider, &health.idle_wallets())
+                        .await
+                        .map(|balances| {
+                            let funded = balances
+                                .iter()
+                                .filter(|b| b.balance >= options.settings.threshold)
+                                .count();
+                            let status = PublicStatus {
+                                total_wallets: balances.len(),
+                                percent_funded: (funded * 100)
+                                    .checked_div(balances.len())
+                                    .unwrap_or(0)
+                                    as u8,
+                                last_cycle: health.last_cycle(),
+                            };
+                            public_cache = Some((Instant::now(), status.clone()));
+                            status
+                        })
+                        .map_err(|_| ApiError::new(StatusCode::BAD_GATEWAY, "status unavailable")),
+                };
+                let _ = reply.send(result);
+            }
+            Control::History(filter, reply) => {
+                let result = match ctx.sinks.storage {
+                    Some(storage) => storage
+                        .transfers(&filter)
+                        .await
+                        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e)),
+                    None => Err(ApiError::new(
+                        StatusCode::NOT_FOUND,
+                        "no STORAGE_URL configured",
+                    )),
+                };
+                let _ = reply.send(result);
+            }
+        }
+    }
+
+    info!("Shutting down API server.");
+    server.abort();
+    #[cfg(feature = "grpc")]
+    if let Some(grpc) = grpc {
+        grpc.abort();
+    }
+    Ok(())
+}
+
+fn describe(kind: JobKind) -> &'static str {
+    match kind {
+        JobKind::Distribution => "distribution",
+        JobKind::ContinualFunding => "continual funding",
+        JobKind::Reclaim => "reclaim",
+    }
+}
+
+async fn wallet_balances(
+    ctx: &Context<'_>,
+    client: &dyn ChainClient,
+    idle: &[usize],
+) -> Result<Vec<WalletBalance>, Box<dyn Error>> {
+    let mut balances = Vec::with_capacity(ctx.number_of_wallets);
+    for wallet_index in 0..ctx.number_of_wallets {
+        let wallet = ctx.fleet.wallet(wallet_index, None)?;
+        balances.push(WalletBalance {
+            wallet_index,
+            address: wallet.address().to_string(),
+            balance: client.balance(wallet.address(), &ctx.asset_id).await?,
+            idle: idle.contains(&wallet_index),
+        });
+    }
+    Ok(balances)
+}
+
+async fn authorize(
+    State(state): State<ApiState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    if let Some(token) = &state.token {
+        let provided = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok());
+        if !bearer_matches(provided, token) {
+            return Err(ApiError::new(StatusCode::UNAUTHORIZED, "invalid API token"));
+        }
+    }
+    Ok(next.run(request).await)
+}
+
+/// Whether the authorization value `provided` is `Bearer <token>`. The comparison
+/// takes the same time wherever a guess goes wrong, so timing cannot recover the
+/// token byte by byte.
+pub fn bearer_matches(provided: Option<&str>, token: &str) -> bool {
+    let expected = format!("Bearer {}", token);
+    provided.is_some_and(|provided| bool::from(provided.as_bytes().ct_eq(expected.as_bytes())))
+}
+
+/// Forwards a request to the supervisor and waits for its reply.
+pub async fn ask<T>(
+    control: &mpsc::Sender<Control>,
+    command: impl FnOnce(Reply<T>) -> Control,
+) -> Result<T, ApiError> {
+    let (reply, response) = oneshot::channel();
+    let unavailable = || ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "shutting down");
+    control
+        .send(command(reply))
+        .await
+        .map_err(|_| unavailable())?;
+    response.await.map_err(|_| unavailable())?
+}
+
+async fn status(State(state): State<ApiState>) -> Result<Json<Status>, ApiError> {
+    ask(&state.control, Control::Status).await.map(Json)
+}
+
+async fn balances(State(state): State<ApiState>) -> Result<Json<Vec<WalletBalance>>, ApiError> {
+    ask(&state.control, Control::Balances).await.map(Json)
+}
+
+async fn fleet(State(state): State<ApiState>) -> Result<Json<Vec<WalletStatus>>, ApiError> {
+    ask(&state.control, Control::Fleet).await.map(Json)
+}
+
+/// Unauthenticated and rate limited per client address; errors carry no details.
+async fn public_status(
+    State(state): State<ApiState>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+) -> Result<Json<PublicStatus>, Response> {
+    if let Err(retry_after) = state.public_limiter.check(client.ip()) {
+        let mut response =
+            ApiError::new(StatusCode::TOO_MANY_REQUESTS, "rate limit exceeded").into_response();
+        response.headers_mut().insert(
+            header::RETRY_AFTER,
+            HeaderValue::from(retry_after.as_secs().max(1)),
+        );
+        return Err(response);
+    }
+    ask(&state.control, Control::PublicStatus)
+        .await
+        .map(Json)
+        .map_err(IntoResponse::into_response)
+}
+
+#[cfg(feature = "dashboard")]
+async fn dashboard() -> Html<&'static str> {
+    Html(DASHBOARD_HTML)
+}
+
+async fn history(
+    State(state): State<ApiState>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Json<Vec<TransferRecord>>, ApiError> {
+    let filter = TransferFilter {
+        tag: query.tag,
+        label: query.label,
+        since: query.since,
+        until: query.until,
+        asset_id: query.asset,
+        wallet_index: query.wallet,
+    };
+    ask(&state.control, |reply| Control::History(filter, reply))
+        .await
+        .map(Json)
+}
+
+async fn distribute(State(state): State<ApiState>) -> Result<StatusCode, ApiError> {
+    ask(&state.control, Control::Distribute).await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn start_funding(State(state): State<ApiState>) -> Result<StatusCode, ApiError> {
+    ask(&state.control, Control::StartFunding).await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn stop_funding(State(state): State<ApiState>) -> Result<StatusCode, ApiError> {
+    ask(&state.control, Control::StopFunding).await?;
+    Ok(StatusCode::OK)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_only_the_exact_bearer_token() {
+        assert!(bearer_matches(Some("Bearer s3cret"), "s3cret"));
+        assert!(!bearer_matches(Some("Bearer s3cres"), "s3cret"));
+        assert!(!bearer_matches(Some("Bearer s3cret "), "s3cret"));
+        assert!(!bearer_matches(Some("s3cret"), "s3cret"));
+        assert!(!bearer_matches(None, "s3cret"));
+    }
+}
@@ -0,0 +1,404 @@
+//! `service install|uninstall|run`: continual funding as a system service, a
+//! systemd unit on Linux and a Windows service, set up from the current working
+//! directory, profile and options instead of a hand-written init script.
+
+use std::{error::Error, path::Path};
+
+/// Unit and service name used when `--name` is not given.
+pub const DEFAULT_NAME: &str = "fund_distributor";
+
+/// What `service install` registers.
+pub struct ServiceSpec<'a> {
+    pub name: &'a str,
+    /// The binary the service runs: the one installing it.
+    pub executable: &'a Path,
+    /// Where `.env` and relative paths (PID and lock files) are found.
+    pub working_dir: &'a Path,
+    /// Options placed before `service run`, starting with the active `--profile`.
+    pub args: Vec<String>,
+    /// Account the systemd unit runs as; root when not given.
+    pub user: Option<&'a str>,
+}
+
+impl ServiceSpec<'_> {
+    /// The arguments the service is started with.
+    fn launch_args(&self) -> Vec<String> {
+        let mut args = self.args.clone();
+        args.extend(["service", "run", "--name", self.name].map(String::from));
+        args
+    }
+
+    /// The systemd unit running `service run`. `Type=notify` matches the readiness
+    /// and watchdog notifications of daemon mode, and a deadman exit (code 4) is
+    /// restarted like any other failure.
+    pub fn systemd_unit(&self) -> String {
+        let mut exec_start = vec![quote(&self.executable.display().to_string())];
+        exec_start.extend(self.launch_args().iter().map(|arg| quote(arg)));
+        let mut unit = format!(
+            "[Unit]\n\
+             Description=fund_distributor continual funding ({})\n\
+             Wants=network-online.target\n\
+             After=network-online.target\n\
+             \n\
+             [Service]\n\
+             Type=notify\n\
+             WorkingDirectory={}\n\
+             ExecStart={}\n\
+             Restart=on-failure\n\
+             RestartSec=10\n",
+            self.name,
+            quote(&self.working_dir.display().to_string()),
+            exec_start.join(" ")
+        );
+        if let Some(user) = self.user {
+            unit.push_str(&format!("User={}\n", user));
+        }
+        unit.push_str("\n[Install]\nWantedBy=multi-user.target\n");
+        unit
+    }
+}
+
+/// `arg` as one word of a systemd command line: quoted when it has spaces or
+/// quotes, with `%` and `$` kept from being expanded.
+fn quote(arg: &str) -> String {
+    let escaped = arg.replace('%', "%%").replace('$', "$$");
+    if !escaped.is_empty()
+        && !escaped
+            .chars()
+            .any(|c| c.is_whitespace() || c == '"' || c == '\'' || c == '\\')
+    {
+        return escaped;
+    }
+    format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Where the unit of service `name` is installed.
+#[cfg(target_os = "linux")]
+fn unit_path(name: &str) -> std::path::PathBuf {
+    Path::new("/etc/systemd/system").join(format!("{}.service", name))
+}
+
+#[cfg(target_os = "linux")]
+fn systemctl(args: &[&str]) -> Result<(), Box<dyn Error>> {
+    let status = std::process::Command::new("systemctl")
+        .args(args)
+        .status()
+        .map_err(|e| format!("Failed to run systemctl: {}", e))?;
+    if !status.success() {
+        return Err(format!("systemctl {} failed: {}", args.join(" "), status).into());
+    }
+    Ok(())
+}
+
+/// Writes the systemd unit, then enables and starts it.
+#[cfg(target_os = "linux")]
+pub fn install(spec: &ServiceSpec) -> Result<(), Box<dyn Error>> {
+    let path = unit_path(spec.name);
+    if path.exists() {
+        return Err(format!(
+            "{} already exists; run `service uninstall --name {}` first",
+            path.display(),
+            spec.name
+        )
+        .into());
+    }
+    std::fs::write(&path, spec.systemd_unit()).map_err(|e| {
+        format!(
+            "Failed to write {}: {} (install as root, or use --print)",
+            path.display(),
+            e
+        )
+    })?;
+    systemctl(&["daemon-reload"])?;
+    systemctl(&["enable", "--now", &format!("{}.service", spec.name)])?;
+    println!("Installed and started {} ({})", spec.name, path.display());
+    Ok(())
+}
+
+/// Stops and disables the systemd unit, and removes it.
+#[cfg(target_os = "linux")]
+pub fn uninstall(name: &str) -> Result<(), Box<dyn Error>> {
+    let path = unit_path(name);
+    if !path.exists() {
+        return Err(format!("{} is not installed ({} not found)", name, path.display()).into());
+    }
+    systemctl(&["disable", "--now", &format!("{}.service", name)])?;
+    std::fs::remove_file(&path)
+        .map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+    systemctl(&["daemon-reload"])?;
+    println!("Stopped and removed {}", name);
+    Ok(())
+}
+
+/// Registers the Windows service, restarted by the service manager when it fails,
+/// and starts it.
+#[cfg(all(windows, feature = "windows-service"))]
+pub fn install(spec: &ServiceSpec) -> Result<(), Box<dyn Error>> {
+    use std::{ffi::OsString, time::Duration};
+    use windows_service::{
+        service::{
+            ServiceAccess, ServiceAction, ServiceActionType, ServiceErrorControl,
+            ServiceFailureActions, ServiceFailureResetPeriod, ServiceInfo, ServiceStartType,
+            ServiceType,
+        },
+        service_manager::{ServiceManager, ServiceManagerAccess},
+    };
+
+    if spec.user.is_some() {
+        return Err("--user only applies to systemd units; set the service account in the Windows service manager".into());
+    }
+    // A Windows service starts in the system directory: `--dir` moves it back here
+    let mut args = spec.launch_args();
+    args.extend(["--dir".to_string(), spec.working_dir.display().to_string()]);
+    let manager = ServiceManager::local_computer(
+        None::<&str>,
+        ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
+    )?;
+    let service = manager.create_service(
+        &ServiceInfo {
+            name: OsString::from(spec.name),
+            display_name: OsString::from(format!("fund_distributor ({})", spec.name)),
+            service_type: ServiceType::OWN_PROCESS,
+            start_type: ServiceStartType::AutoStart,
+            error_control: ServiceErrorControl::Normal,
+            executable_path: spec.executable.to_path_buf(),
+            launch_arguments: args.into_iter().map(OsString::from).collect(),
+            dependencies: vec![],
+            account_name: None,
+            account_password: None,
+        },
+        ServiceAccess::CHANGE_CONFIG | ServiceAccess::START,
+    )?;
+    service.set_description("fund_distributor continual funding")?;
+    service.update_failure_actions(ServiceFailureActions {
+        reset_period: ServiceFailureResetPeriod::After(Duration::from_secs(3600)),
+        reboot_msg: None,
+        command: None,
+        actions: Some(vec![ServiceAction {
+            action_type: ServiceActionType::Restart,
+            delay: Duration::from_secs(10),
+        }]),
+    })?;
+    service.set_failure_actions_on_non_crash_failures(true)?;
+    service.start::<&str>(&[])?;
+    println!("Installed and started {}", spec.name);
+    Ok(())
+}
+
+/// Stops the Windows service if it is running, and deletes it.
+#[cfg(all(windows, feature = "windows-service"))]
+pub fn uninstall(name: &str) -> Result<(), Box<dyn Error>> {
+    use windows_service::{
+        service::{ServiceAccess, ServiceState},
+        service_manager::{ServiceManager, ServiceManagerAccess},
+    };
+
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    let service = manager.open_service(
+        name,
+        ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE,
+    )?;
+    if service.query_status()?.current_state != ServiceState::Stopped {
+        service.stop()?;
+    }
+    service.delete()?;
+    println!("Stopped and removed {}", name);
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", all(windows, feature = "windows-service"))))]
+pub fn install(_spec: &ServiceSpec) -> Result<(), Box<dyn Error>> {
+    Err("`service install` supports systemd on Linux and Windows services; use --print for the systemd unit".into())
+}
+
+#[cfg(not(any(target_os = "linux", all(windows, feature = "windows-service"))))]
+pub fn uninstall(_name: &str) -> Result<(), Box<dyn Error>> {
+    Err("`service uninstall` supports systemd on Linux and Windows services".into())
+}
+
+/// The running service, until [`RunningService::stopped`] reports how it ended.
+/// Under systemd there is nothing to report: readiness goes through
+/// `NOTIFY_SOCKET` and the exit code is the process's own.
+pub struct RunningService {
+    #[cfg(all(windows, feature = "windows-service"))]
+    finished: std::sync::mpsc::Sender<u32>,
+    #[cfg(all(windows, feature = "windows-service"))]
+    dispatcher: std::thread::JoinHandle<()>,
+}
+
+impl RunningService {
+    /// Reports to the service manager that the service stopped, successfully or not.
+    pub fn stopped(self, succeeded: bool) {
+        #[cfg(all(windows, feature = "windows-service"))]
+        {
+            let _ = self.finished.send(if succeeded { 0 } else { 1 });
+            let _ = self.dispatcher.join();
+        }
+        #[cfg(not(all(windows, feature = "windows-service")))]
+        let _ = (self, succeeded);
+    }
+}
+
+/// Connects `service run` to the service manager, where it has one to connect to.
+#[cfg(not(all(windows, feature = "windows-service")))]
+pub fn start(_name: &str) -> Result<RunningService, Box<dyn Error>> {
+    Ok(RunningService {})
+}
+
+/// Resolves once the service manager asked the service to stop; never resolves
+/// elsewhere, where a stop arrives as a signal.
+#[cfg(not(all(windows, feature = "windows-service")))]
+#[cfg_attr(unix, allow(dead_code))]
+pub async fn stop_requested() {
+    std::future::pending::<()>().await;
+}
+
+#[cfg(all(windows, feature = "windows-service"))]
+pub use self::windows::{start, stop_requested};
+
+#[cfg(all(windows, feature = "windows-service"))]
+mod windows {
+    use super::RunningService;
+    use std::{
+        error::Error,
+        ffi::OsString,
+        sync::{mpsc, Mutex, OnceLock},
+        thread,
+        time::Duration,
+    };
+    use tokio::sync::watch;
+    use windows_service::{
+        define_windows_service,
+        service::{
+            ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus,
+            ServiceType,
+        },
+        service_control_handler::{self, ServiceControlHandlerResult},
+        service_dispatcher,
+    };
+
+    /// The service name, for the entry point the dispatcher calls.
+    static NAME: OnceLock<String> = OnceLock::new();
+    /// Hands the entry point's stop report channel back to [`start`].
+    type Started = mpsc::Sender<Result<mpsc::Sender<u32>, String>>;
+    static STARTED: Mutex<Option<Started>> = Mutex::new(None);
+    /// Set once the service manager asks the service to stop.
+    static STOP: OnceLock<watch::Sender<bool>> = OnceLock::new();
+
+    fn stop() -> &'static watch::Sender<bool> {
+        STOP.get_or_init(|| watch::channel(false).0)
+    }
+
+    define_windows_service!(ffi_service_main, service_main);
+
+    /// Runs the service dispatcher on a thread of its own, since it blocks until the
+    /// service stops, and waits for the service manager to start the service.
+    pub fn start(name: &str) -> Result<RunningService, Box<dyn Error>> {
+        let name = NAME.get_or_init(|| name.to_string());
+        let (started, started_rx) = mpsc::channel();
+        *STARTED.lock().unwrap() = Some(started.clone());
+        let dispatcher = thread::spawn(move || {
+            if let Err(e) = service_dispatcher::start(name, ffi_service_main) {
+                let _ = started.send(Err(e.to_string()));
+            }
+        });
+        let finished = started_rx.recv()?.map_err(|e| {
+            format!(
+                "`service run` must be started by the service manager: {}",
+                e
+            )
+        })?;
+        Ok(RunningService {
+            finished,
+            dispatcher,
+        })
+    }
+
+    pub async fn stop_requested() {
+        let mut stopped = stop().subscribe();
+        while !*stopped.borrow_and_update() {
+            if stopped.changed().await.is_err() {
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+
+    fn status(state: ServiceState, exit_code: u32) -> ServiceStatus {
+        ServiceStatus {
+            service_type: ServiceType::OWN_PROCESS,
+            current_state: state,
+            controls_accepted: match state {
+                ServiceState::Running => {
+                    ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN
+                }
+                _ => ServiceControlAccept::empty(),
+            },
+            exit_code: match exit_code {
+                0 => ServiceExitCode::Win32(0),
+                code => ServiceExitCode::ServiceSpecific(code),
+            },
+            checkpoint: 0,
+            wait_hint: Duration::default(),
+            process_id: None,
+        }
+    }
+
+    fn service_main(_arguments: Vec<OsString>) {
+        let (Some(started), Some(name)) = (STARTED.lock().unwrap().take(), NAME.get()) else {
+            return;
+        };
+        let handler = |control| match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                stop().send_replace(true);
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        };
+        let handle = match service_control_handler::register(name, handler) {
+            Ok(handle) => handle,
+            Err(e) => {
+                let _ = started.send(Err(e.to_string()));
+                return;
+            }
+        };
+        let _ = handle.set_service_status(status(ServiceState::Running, 0));
+        let (finished, finished_rx) = mpsc::channel();
+        let _ = started.send(Ok(finished));
+        let exit_code = finished_rx.recv().unwrap_or(1);
+        let _ = handle.set_service_status(status(ServiceState::Stopped, exit_code));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_a_systemd_unit() {
+        let spec = ServiceSpec {
+            name: "funding-testnet",
+            executable: Path::new("/opt/fund distributor/fund_distributor"),
+            working_dir: Path::new("/srv/funding"),
+            args: vec![
+                "--profile".into(),
+                "testnet".into(),
+                "--health-addr".into(),
+                "127.0.0.1:9090".into(),
+            ],
+            user: Some("funding"),
+        };
+        let unit = spec.systemd_unit();
+        assert!(unit.contains("Type=notify\n"));
+        assert!(unit.contains("WorkingDirectory=/srv/funding\n"));
+        assert!(unit.contains(
+            "ExecStart=\"/opt/fund distributor/fund_distributor\" --profile testnet \
+             --health-addr 127.0.0.1:9090 service run --name funding-testnet\n"
+        ));
+        assert!(unit.contains("User=funding\n"));
+        assert!(unit.ends_with("WantedBy=multi-user.target\n"));
+
+        assert_eq!(quote("50%"), "50%%");
+        assert_eq!(quote("say \"hi\""), "\"say \\\"hi\\\"\"");
+    }
+}
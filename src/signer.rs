@@ -0,0 +1,358 @@
+//! The account transfers are sent from. HD wallets and funding sources are always
+//! derived from the mnemonic; the main (treasury) wallet can instead be backed by an
+//! external signer (`MAIN_SIGNER`) so its key never enters this process.
+
+use async_trait::async_trait;
+use fuels::{
+    accounts::{
+        provider::Provider,
+        wallet::{Wallet, WalletUnlocked},
+        Account, ViewOnlyAccount,
+    },
+    core::traits::Signer,
+    crypto::{Message, Signature},
+    types::{
+        bech32::{Bech32Address, FUEL_BECH32_HRP},
+        coin_type_id::CoinTypeId,
+        errors::{Error as FuelsError, Result as FuelsResult},
+        input::Input,
+        transaction_builders::TransactionBuilder,
+        AssetId,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{env, error::Error, fmt, str::FromStr, time::Duration};
+
+/// A wallet that can send transfers: derived locally, or signing externally.
+#[derive(Clone, Debug)]
+pub enum Funder {
+    Local(WalletUnlocked),
+    External(ExternalWallet),
+}
+
+impl Funder {
+    pub fn address(&self) -> &Bech32Address {
+        match self {
+            Funder::Local(wallet) => wallet.address(),
+            Funder::External(wallet) => wallet.view.address(),
+        }
+    }
+
+    pub fn set_provider(&mut self, provider: Provider) {
+        match self {
+            Funder::Local(wallet) => {
+                wallet.set_provider(provider);
+            }
+            Funder::External(wallet) => {
+                wallet.view.set_provider(provider);
+            }
+        }
+    }
+}
+
+impl From<WalletUnlocked> for Funder {
+    fn from(wallet: WalletUnlocked) -> Self {
+        Funder::Local(wallet)
+    }
+}
+
+#[async_trait]
+impl ViewOnlyAccount for Funder {
+    fn address(&self) -> &Bech32Address {
+        Funder::address(self)
+    }
+
+    fn try_provider(&self) -> FuelsResult<&Provider> {
+        match self {
+            Funder::Local(wallet) => wallet.try_provider(),
+            Funder::External(wallet) => wallet.view.try_provider(),
+        }
+    }
+
+    async fn get_asset_inputs_for_amount(
+        &self,
+        asset_id: AssetId,
+        amount: u128,
+        excluded_coins: Option<Vec<CoinTypeId>>,
+    ) -> FuelsResult<Vec<Input>> {
+        match self {
+            Funder::Local(wallet) => {
+                wallet
+                    .get_asset_inputs_for_amount(asset_id, amount, excluded_coins)
+                    .await
+            }
+            Funder::External(wallet) => {
+                wallet
+                    .view
+                    .get_asset_inputs_for_amount(asset_id, amount, excluded_coins)
+                    .await
+            }
+        }
+    }
+}
+
+impl Account for Funder {
+    fn add_witnesses<Tb: TransactionBuilder>(&self, tb: &mut Tb) -> FuelsResult<()> {
+        match self {
+            Funder::Local(wallet) => wallet.add_witnesses(tb),
+            Funder::External(wallet) => {
+                tb.add_signer(wallet.signer.clone())?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The main wallet when its key is held by an external signer.
+#[derive(Clone, Debug)]
+pub struct ExternalWallet {
+    /// Locked view of the signer's address, for balances and coin selection.
+    view: Wallet,
+    signer: ExternalSigner,
+}
+
+impl ExternalWallet {
+    /// Connects to the signer configured in `MAIN_SIGNER`, if any:
+    /// `https://...` for a remote signing endpoint (with `MAIN_SIGNER_ADDRESS`), or
+    /// `aws-kms://<key id or ARN>` for an AWS KMS secp256k1 key.
+    pub async fn from_env(provider: Provider) -> Result<Option<Self>, Box<dyn Error>> {
+        let Ok(spec) = env::var("MAIN_SIGNER") else {
+            return Ok(None);
+        };
+        let signer = ExternalSigner::connect(&spec).await?;
+        Ok(Some(Self {
+            view: Wallet::from_address(signer.address().clone(), Some(provider)),
+            signer,
+        }))
+    }
+}
+
+/// Signs transaction ids outside this process.
+#[derive(Clone)]
+pub enum ExternalSigner {
+    /// POSTs `{"address", "message"}` to `url` and expects `{"signature"}` back (hex).
+    Remote {
+        url: String,
+        token: Option<String>,
+        address: Bech32Address,
+        client: reqwest::Client,
+    },
+    #[cfg(feature = "aws-kms")]
+    AwsKms {
+        client: aws_sdk_kms::Client,
+        key_id: String,
+        public_key: k256::ecdsa::VerifyingKey,
+        address: Bech32Address,
+    },
+}
+
+impl fmt::Debug for ExternalSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExternalSigner::Remote { url, address, .. } => f
+                .debug_struct("Remote")
+                .field("url", url)
+                .field("address", address)
+                .finish_non_exhaustive(),
+            #[cfg(feature = "aws-kms")]
+            ExternalSigner::AwsKms {
+                key_id, address, ..
+            } => f
+                .debug_struct("AwsKms")
+                .field("key_id", key_id)
+                .field("address", address)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SignRequest {
+    address: String,
+    /// Transaction id to sign, hex encoded.
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct SignResponse {
+    /// 64-byte compact secp256k1 signature (recovery id in the top bit of `s`), hex encoded.
+    signature: String,
+}
+
+impl ExternalSigner {
+    async fn connect(spec: &str) -> Result<Self, Box<dyn Error>> {
+        if spec.starts_with("https://") || spec.starts_with("http://") {
+            let address = env::var("MAIN_SIGNER_ADDRESS")
+                .map_err(|_| "MAIN_SIGNER_ADDRESS must be set for a remote MAIN_SIGNER")?;
+            let address = Bech32Address::from_str(&address)
+                .map_err(|e| format!("Invalid MAIN_SIGNER_ADDRESS '{}': {}", address, e))?;
+            return Ok(ExternalSigner::Remote {
+                url: spec.to_string(),
+                token: env::var("MAIN_SIGNER_TOKEN").ok(),
+                address,
+                client: reqwest::Client::builder()
+                    .timeout(Duration::from_secs(30))
+                    .build()?,
+            });
+        }
+
+        if let Some(key_id) = spec.strip_prefix("aws-kms://") {
+            #[cfg(feature = "aws-kms")]
+            return kms::connect(key_id).await;
+            #[cfg(not(feature = "aws-kms"))]
+            return Err(format!(
+                "MAIN_SIGNER key {} needs a build with the `aws-kms` feature",
+                key_id
+            )
+            .into());
+        }
+
+        Err(format!(
+            "Unsupported MAIN_SIGNER '{}': expected https://<signing endpoint> or aws-kms://<key id>",
+            spec
+        )
+        .into())
+    }
+
+    async fn sign_remote(
+        &self,
+        message: &Message,
+        url: &str,
+        token: Option<&str>,
+        client: &reqwest::Client,
+    ) -> Result<Signature, Box<dyn Error>> {
+        let mut request = client.post(url).json(&SignRequest {
+            address: self.address().to_string(),
+            message: to_hex(message.as_ref()),
+        });
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        let response: SignResponse = request.send().await?.error_for_status()?.json().await?;
+        let bytes: [u8; 64] = from_hex(&response.signature)?
+            .try_into()
+            .map_err(|_| "Remote signer returned a signature that is not 64 bytes")?;
+        Ok(Signature::from_bytes(bytes))
+    }
+}
+
+#[async_trait]
+impl Signer for ExternalSigner {
+    async fn sign(&self, message: Message) -> FuelsResult<Signature> {
+        let signature = match self {
+            ExternalSigner::Remote {
+                url, token, client, ..
+            } => self
+                .sign_remote(&message, url, token.as_deref(), client)
+                .await
+                .map_err(|e| FuelsError::Other(format!("Remote signer {}: {}", url, e)))?,
+            #[cfg(feature = "aws-kms")]
+            ExternalSigner::AwsKms {
+                client,
+                key_id,
+                public_key,
+                ..
+            } => kms::sign(client, key_id, public_key, &message)
+                .await
+                .map_err(|e| FuelsError::Other(format!("AWS KMS key {}: {}", key_id, e)))?,
+        };
+
+        // Never hand the node a signature for some other key
+        let public_key = signature
+            .recover(&message)
+            .map_err(|e| FuelsError::Other(format!("Unrecoverable signature: {}", e)))?;
+        if Bech32Address::new(FUEL_BECH32_HRP, public_key.hash()) != *self.address() {
+            return Err(FuelsError::Other(format!(
+                "External signer returned a signature that does not belong to {}",
+                self.address()
+            )));
+        }
+        Ok(signature)
+    }
+
+    fn address(&self) -> &Bech32Address {
+        match self {
+            ExternalSigner::Remote { address, .. } => address,
+            #[cfg(feature = "aws-kms")]
+            ExternalSigner::AwsKms { address, .. } => address,
+        }
+    }
+}
+
+#[cfg(feature = "aws-kms")]
+mod kms {
+    use super::*;
+    use aws_sdk_kms::{
+        primitives::Blob,
+        types::{MessageType, SigningAlgorithmSpec},
+    };
+    use k256::{
+        ecdsa::{RecoveryId, Signature as EcdsaSignature, VerifyingKey},
+        pkcs8::DecodePublicKey,
+    };
+
+    /// Looks up the public key of `key_id` with the default AWS credential chain.
+    pub async fn connect(key_id: &str) -> Result<ExternalSigner, Box<dyn Error>> {
+        let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+        let client = aws_sdk_kms::Client::new(&config);
+        let response = client.get_public_key().key_id(key_id).send().await?;
+        let der = response
+            .public_key()
+            .ok_or("AWS KMS returned no public key")?;
+        let public_key = VerifyingKey::from_public_key_der(der.as_ref())
+            .map_err(|e| format!("KMS key {} is not a secp256k1 key: {}", key_id, e))?;
+        // Fuel addresses hash the 64-byte uncompressed key without its 0x04 prefix
+        let point = public_key.to_encoded_point(false);
+        let fuel_key = fuels::crypto::PublicKey::try_from(&point.as_bytes()[1..])
+            .map_err(|e| format!("Invalid KMS public key: {}", e))?;
+        Ok(ExternalSigner::AwsKms {
+            client,
+            key_id: key_id.to_string(),
+            public_key,
+            address: Bech32Address::new(FUEL_BECH32_HRP, fuel_key.hash()),
+        })
+    }
+
+    pub async fn sign(
+        client: &aws_sdk_kms::Client,
+        key_id: &str,
+        public_key: &VerifyingKey,
+        message: &Message,
+    ) -> Result<Signature, Box<dyn Error>> {
+        let response = client
+            .sign()
+            .key_id(key_id)
+            .message(Blob::new(message.as_ref().to_vec()))
+            .message_type(MessageType::Digest)
+            .signing_algorithm(SigningAlgorithmSpec::EcdsaSha256)
+            .send()
+            .await?;
+        let der = response
+            .signature()
+            .ok_or("AWS KMS returned no signature")?;
+        let signature = EcdsaSignature::from_der(der.as_ref())?;
+        // Fuel only accepts low-s signatures
+        let signature = signature.normalize_s().unwrap_or(signature);
+        let recovery_id =
+            RecoveryId::trial_recovery_from_prehash(public_key, message.as_ref(), &signature)?;
+
+        let mut bytes: [u8; 64] = signature.to_bytes().into();
+        bytes[32] = (recovery_id.is_y_odd() as u8) << 7 | (bytes[32] & 0x7f);
+        Ok(Signature::from_bytes(bytes))
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let hex = hex.strip_prefix("0x").unwrap_or(hex);
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return Err("Not a hex string".into());
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| Ok(u8::from_str_radix(&hex[i..i + 2], 16)?))
+        .collect()
+}
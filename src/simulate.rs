@@ -0,0 +1,48 @@
+//! `--simulate`: rehearses a run against an in-process fuel-core node seeded from the
+//! live chain, so amounts, fees and edge cases show up before anything is sent for real.
+
+use crate::chain::ChainClient;
+use fuels::{
+    accounts::provider::Provider,
+    test_helpers::{setup_single_asset_coins, setup_test_provider, ChainConfig, NodeConfig},
+    types::bech32::Bech32Address,
+};
+use std::error::Error;
+use tracing::info;
+
+/// Starts a local node holding the same coins as `addresses` hold on `live`, with the
+/// live chain's consensus parameters (base asset, fee parameters, transaction limits)
+/// and current gas price, and returns a provider for it.
+///
+/// Coins are copied one by one, so fragmented wallets sweep as they would for real.
+/// Unspent messages, contracts and predicates are not copied.
+pub async fn fork(
+    live: &Provider,
+    addresses: &[Bech32Address],
+) -> Result<Provider, Box<dyn Error>> {
+    let mut coins = Vec::new();
+    for address in addresses {
+        for (asset_id, _) in live.balances(address).await? {
+            for amount in live.coins(address, &asset_id).await? {
+                coins.extend(setup_single_asset_coins(address, asset_id, 1, amount));
+            }
+        }
+    }
+    let gas_price = live.latest_gas_price().await?.gas_price;
+    info!(
+        "Simulating on a local node: {} coins copied from {} addresses, gas price {}.",
+        coins.len(),
+        addresses.len(),
+        gas_price
+    );
+
+    let chain_config = ChainConfig {
+        consensus_parameters: live.consensus_parameters().clone(),
+        ..ChainConfig::local_testnet()
+    };
+    let node_config = NodeConfig {
+        starting_gas_price: gas_price,
+        ..NodeConfig::default()
+    };
+    Ok(setup_test_provider(coins, vec![], Some(node_config), Some(chain_config)).await?)
+}
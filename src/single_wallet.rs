@@ -0,0 +1,280 @@
+//! `wallet <index> ...`: acting on exactly one HD wallet without touching the rest
+//! of the fleet.
+
+use crate::{
+    approval, assets,
+    chain::ChainClient,
+    context::Context,
+    reclaim::{self, describe_amount, reclaim_funds, ReclaimOptions},
+    signer::Funder,
+    storage::TransferRecord,
+    transfer::{verify_credit, TransferOutcome},
+    units,
+};
+use fuels::types::{bech32::Bech32Address, AssetId};
+use std::error::Error;
+use tracing::info;
+
+/// Label of the transfers these commands make, unless the run has a `--label`.
+const LABEL: &str = "manual";
+
+/// Prints every asset the HD wallet holds.
+pub async fn print_balances(
+    ctx: &Context<'_>,
+    client: &dyn ChainClient,
+    index: usize,
+) -> Result<(), Box<dyn Error>> {
+    let address = ctx.fleet.wallet(index, None)?.address().clone();
+    let mut balances = client.balances(&address).await?;
+    balances.sort();
+
+    println!("HD wallet {}: {}", index, address);
+    if balances.is_empty() {
+        println!("  no funds");
+    }
+    for (asset_id, balance) in balances {
+        println!(
+            "  {}: {}",
+            asset_id,
+            describe_amount(ctx, client, &asset_id, balance)
+        );
+    }
+    Ok(())
+}
+
+/// Sends `amount` of `ETH_ASSET_ID` to the HD wallet from its funding wallet.
+pub async fn fund_wallet(
+    ctx: &Context<'_>,
+    main_wallet: &Funder,
+    client: &dyn ChainClient,
+    index: usize,
+    amount: &str,
+) -> Result<(), Box<dyn Error>> {
+    check_index(ctx, index)?;
+    let amount = parse_amount(ctx, client, &ctx.asset_id, amount)?;
+    approval::check_unreviewed(ctx, "wallet fund", amount.into())?;
+    let sources = ctx.funding_sources.derive(&ctx.fleet, client.provider())?;
+    let source = ctx.funding_sources.wallet_for(index, main_wallet, &sources);
+    let wallet = ctx.fleet.wallet(index, None)?;
+    if source.address() == wallet.address() {
+        return Err(format!("HD wallet {} is its own funding wallet", index).into());
+    }
+
+    let outcome = transfer(ctx, client, source, wallet.address(), amount, &ctx.asset_id).await?;
+    let record = TransferRecord::new(
+        "wallet-fund",
+        Some(index),
+        source.address(),
+        wallet.address(),
+        ctx.asset_id,
+        amount,
+        outcome.tx_id,
+    )
+    .labelled(LABEL);
+    verify_credit(ctx, client, &record).await;
+    ctx.sinks.transfer_confirmed(record, outcome.fee).await;
+    println!(
+        "Sent {} to HD wallet {}: {}",
+        ctx.format_amount(amount),
+        index,
+        outcome.tx_id
+    );
+    Ok(())
+}
+
+/// Sends `amount` of `asset_id` (default `ETH_ASSET_ID`) from the HD wallet to `to`.
+pub async fn send_from_wallet(
+    ctx: &Context<'_>,
+    client: &dyn ChainClient,
+    index: usize,
+    to: &Bech32Address,
+    amount: &str,
+    asset_id: Option<AssetId>,
+) -> Result<(), Box<dyn Error>> {
+    check_index(ctx, index)?;
+    let asset_id = asset_id.unwrap_or(ctx.asset_id);
+    let amount = parse_amount(ctx, client, &asset_id, amount)?;
+    if asset_id == ctx.asset_id {
+        approval::check_unreviewed(ctx, "wallet send", amount.into())?;
+    }
+    let wallet = Funder::from(ctx.fleet.wallet(index, client.provider())?);
+
+    let outcome = transfer(ctx, client, &wallet, to, amount, &asset_id).await?;
+    let record = TransferRecord::new(
+        "wallet-send",
+        Some(index),
+        wallet.address(),
+        to,
+        asset_id,
+        amount,
+        outcome.tx_id,
+    )
+    .labelled(LABEL);
+    verify_credit(ctx, client, &record).await;
+    ctx.sinks.transfer_confirmed(record, outcome.fee).await;
+    println!(
+        "Sent {} of {} from HD wallet {} to {}: {}",
+        describe_amount(ctx, client, &asset_id, amount),
+        asset_id,
+        index,
+        to,
+        outcome.tx_id
+    );
+    Ok(())
+}
+
+/// Reclaims the HD wallet as `--reclaim` would, leaving every other wallet alone.
+pub async fn reclaim_wallet(
+    ctx: &mut Context<'_>,
+    main_wallet: &Funder,
+    client: &dyn ChainClient,
+    index: usize,
+    options: &ReclaimOptions,
+) -> Result<(), Box<dyn Error>> {
+    check_index(ctx, index)?;
+    ctx.exclusions.restrict_to(index..=index);
+    reclaim::check_approval(ctx, main_wallet, client, options).await?;
+    reclaim_funds(ctx, main_wallet, client, options, None).await
+}
+
+/// Refuses wallets outside the fleet and excluded ones, which no command touches.
+fn check_index(ctx: &Context<'_>, index: usize) -> Result<(), Box<dyn Error>> {
+    if index >= ctx.number_of_wallets {
+        return Err(format!(
+            "HD wallet {} is outside the fleet (NUMBER_OF_WALLETS is {})",
+            index, ctx.number_of_wallets
+        )
+        .into());
+    }
+    if ctx.is_excluded(index)? {
+        return Err(format!("HD wallet {} is excluded", index).into());
+    }
+    Ok(())
+}
+
+/// Parses an amount of `asset_id`: decimal amounts need the asset's decimals, known
+/// for `ETH_ASSET_ID`, the base asset and the assets in `ASSETS`.
+fn parse_amount(
+    ctx: &Context<'_>,
+    client: &dyn ChainClient,
+    asset_id: &AssetId,
+    amount: &str,
+) -> Result<u64, Box<dyn Error>> {
+    let decimals = if *asset_id == ctx.asset_id {
+        ctx.decimals
+    } else if let Some(decimals) = assets::decimals(asset_id) {
+        decimals
+    } else if *asset_id == client.base_asset() {
+        units::DEFAULT_DECIMALS
+    } else if amount.contains('.') {
+        return Err(format!(
+            "Decimals of {} are unknown, give --amount in base units",
+            asset_id
+        )
+        .into());
+    } else {
+        0
+    };
+    units::parse_setting(amount, decimals).map_err(|e| format!("Invalid --amount: {}", e).into())
+}
+
+/// Checks the address book and sends the transfer.
+async fn transfer(
+    ctx: &Context<'_>,
+    client: &dyn ChainClient,
+    from: &Funder,
+    to: &Bech32Address,
+    amount: u64,
+    asset_id: &AssetId,
+) -> Result<TransferOutcome, Box<dyn Error>> {
+    ctx.address_book.check(to, Some(asset_id), amount, None)?;
+    info!(
+        "Sending {} of {} from {} to {}.",
+        describe_amount(ctx, client, asset_id, amount),
+        asset_id,
+        from.address(),
+        to
+    );
+    client
+        .transfer(from, to, amount, asset_id, ctx.tx_policies)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::mock::{address, base_asset, other_asset, test_context, MockChain};
+
+    #[tokio::test]
+    async fn funds_and_sends_from_one_wallet() {
+        let ctx = test_context(3, base_asset());
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(main_wallet.address(), base_asset(), 100_000_000);
+        chain.set_balance(&address(&ctx, 2), other_asset(), 5_000);
+
+        fund_wallet(&ctx, &main_wallet, &chain, 2, "0.01")
+            .await
+            .unwrap();
+        assert_eq!(
+            chain.balance_of(&address(&ctx, 2), base_asset()),
+            10_000_000
+        );
+        assert_eq!(chain.balance_of(&address(&ctx, 1), base_asset()), 0);
+
+        send_from_wallet(
+            &ctx,
+            &chain,
+            2,
+            &address(&ctx, 1),
+            "2000",
+            Some(other_asset()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(chain.balance_of(&address(&ctx, 1), other_asset()), 2_000);
+
+        // Decimals of other assets are unknown, and wallet 3 is not in the fleet
+        assert!(send_from_wallet(
+            &ctx,
+            &chain,
+            2,
+            &address(&ctx, 1),
+            "0.5",
+            Some(other_asset())
+        )
+        .await
+        .is_err());
+        assert!(fund_wallet(&ctx, &main_wallet, &chain, 3, "0.01")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn holds_single_transfers_above_the_approval_limit() {
+        let mut ctx = test_context(3, base_asset());
+        ctx.approval_limit = Some(5_000_000);
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        chain.set_balance(main_wallet.address(), base_asset(), 100_000_000);
+        chain.set_balance(&address(&ctx, 2), base_asset(), 50_000_000);
+        chain.set_balance(&address(&ctx, 2), other_asset(), 50_000_000);
+
+        assert!(fund_wallet(&ctx, &main_wallet, &chain, 2, "0.01")
+            .await
+            .is_err());
+        let to = address(&ctx, 1);
+        assert!(send_from_wallet(&ctx, &chain, 2, &to, "0.01", None)
+            .await
+            .is_err());
+        assert!(chain.transfers().is_empty());
+
+        // The limit is in ETH_ASSET_ID only
+        send_from_wallet(&ctx, &chain, 2, &to, "10000000", Some(other_asset()))
+            .await
+            .unwrap();
+        fund_wallet(&ctx, &main_wallet, &chain, 2, "0.005")
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,360 @@
+use crate::{
+    audit,
+    bus::EventBus,
+    invariants::Violation,
+    refill::RefillNeeded,
+    storage::{self, Storage, TransferRecord},
+};
+use fuels::types::TxId;
+use serde::Serialize;
+use std::{sync::RwLock, time::Duration};
+use tracing::{info, warn};
+
+/// Callback that is POSTed a JSON payload whenever a transfer confirms.
+#[derive(Clone)]
+pub struct Webhook {
+    url: String,
+    client: reqwest::Client,
+}
+
+#[derive(Serialize)]
+struct TransferConfirmed<'a> {
+    event: &'static str,
+    #[serde(flatten)]
+    transfer: &'a TransferRecord,
+    /// Fee paid by the sender, in base units of the base asset.
+    fee: u64,
+}
+
+/// Event sent when the watchdog cancels a hung continual funding cycle.
+#[derive(Serialize)]
+struct WatchdogTripped {
+    event: &'static str,
+    cycle: u64,
+    max_cycle_secs: u64,
+    tag: Option<String>,
+}
+
+/// Event sent when the deadman switch restarts a continual funding loop that stopped
+/// checking in.
+#[derive(Serialize)]
+struct DeadmanTripped {
+    event: &'static str,
+    overdue_secs: u64,
+    tag: Option<String>,
+}
+
+/// Event sent when an invariant fails after a continual funding cycle.
+#[derive(Serialize)]
+struct InvariantViolated<'a> {
+    event: &'static str,
+    cycle: u64,
+    #[serde(flatten)]
+    violation: &'a Violation,
+    /// Whether funding is now paused until the next reload.
+    paused: bool,
+    tag: Option<String>,
+}
+
+/// Event sent when a confirmed transfer credited its destination with a different
+/// amount than was sent.
+#[derive(Serialize)]
+struct TransferDiscrepancy<'a> {
+    event: &'static str,
+    #[serde(flatten)]
+    transfer: &'a TransferRecord,
+    /// What the transaction's outputs and receipts credited, in base units.
+    credited: u64,
+}
+
+/// Event sent when a top-up is still unconfirmed after the in-flight timeout.
+#[derive(Serialize)]
+struct TransactionStuck<'a> {
+    event: &'static str,
+    #[serde(flatten)]
+    transfer: &'a TransferRecord,
+    pending_secs: u64,
+    /// Transaction the top-up was sent again as, if it was resubmitted.
+    resubmitted_as: Option<String>,
+}
+
+/// Event sent when an HD wallet holds more than it spent and was sent allows for.
+#[derive(Serialize)]
+struct InboundReceived<'a> {
+    event: &'static str,
+    wallet_index: usize,
+    address: &'a str,
+    asset_id: &'a str,
+    /// What arrived since the last check, in base units.
+    amount: u64,
+    /// The wallet's balance after it arrived, in base units.
+    balance: u64,
+    tag: Option<String>,
+}
+
+/// Event sent when the main wallet drops below its reserve.
+#[derive(Serialize)]
+struct RefillEvent<'a> {
+    event: &'static str,
+    #[serde(flatten)]
+    refill: &'a RefillNeeded,
+    tag: Option<String>,
+}
+
+impl Webhook {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .expect("default reqwest client configuration is valid"),
+        }
+    }
+
+    async fn transfer_confirmed(&self, payload: &TransferConfirmed<'_>) {
+        let record = payload.transfer;
+        if let Err(e) = self.post(payload).await {
+            warn!(
+                "Callback to {} for transfer {} failed: {}",
+                self.url, record.tx_id, e
+            );
+        }
+    }
+
+    async fn post<T: Serialize>(&self, payload: &T) -> reqwest::Result<()> {
+        self.client
+            .post(&self.url)
+            .json(payload)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+    }
+}
+
+/// Everything a confirmed transfer or watchdog event is reported to.
+#[derive(Default)]
+pub struct Sinks<'a> {
+    pub storage: Option<&'a dyn Storage>,
+    /// Replaceable while running, see [`Sinks::set_webhook`].
+    pub webhook: RwLock<Option<Webhook>>,
+    /// Tag stamped on every transfer of this run.
+    pub tag: Option<String>,
+    /// Label that replaces each transfer's own (`--label`).
+    pub label: Option<String>,
+    /// Message bus the same events are published to (`EVENT_BUS_URL`).
+    pub bus: Option<EventBus>,
+}
+
+impl Sinks<'_> {
+    /// Points confirmation callbacks at `url` from now on, or turns them off.
+    pub fn set_webhook(&self, url: Option<String>) {
+        let mut webhook = self.webhook.write().expect("webhook lock poisoned");
+        if webhook.as_ref().map(|w| &w.url) != url.as_ref() {
+            *webhook = url.map(Webhook::new);
+        }
+    }
+
+    /// Records a confirmed transfer in history and notifies the callback URL.
+    pub async fn transfer_confirmed(&self, mut record: TransferRecord, fee: u64) {
+        record.tag = self.tag.clone();
+        record.fee = Some(fee);
+        if let Some(label) = &self.label {
+            record.label = Some(label.clone());
+        }
+        if let Some(storage) = self.storage {
+            match storage.next_sequence().await {
+                Ok(sequence) => record.sequence = Some(sequence),
+                Err(e) => warn!(
+                    "Failed to allocate a sequence number for transfer {}: {}",
+                    record.tx_id, e
+                ),
+            }
+        }
+        info!(
+            sequence = record.sequence,
+            tx_id = %record.tx_id,
+            command = %record.command,
+            amount = record.amount,
+            "Transfer confirmed"
+        );
+        let payload = TransferConfirmed {
+            event: "transfer_confirmed",
+            transfer: &record,
+            fee,
+        };
+        audit::record(payload.event, &payload);
+        let webhook = self.webhook.read().expect("webhook lock poisoned").clone();
+        if let Some(webhook) = webhook {
+            webhook.transfer_confirmed(&payload).await;
+        }
+        if let Some(bus) = &self.bus {
+            bus.publish(&record.to_address, &payload).await;
+        }
+        storage::record(self.storage, record).await;
+    }
+
+    /// Alerts the callback URL and message bus that the watchdog cancelled
+    /// continual funding cycle `cycle`.
+    pub async fn watchdog_tripped(&self, cycle: u64, max_cycle_duration: Duration) {
+        let payload = WatchdogTripped {
+            event: "watchdog",
+            cycle,
+            max_cycle_secs: max_cycle_duration.as_secs(),
+            tag: self.tag.clone(),
+        };
+        audit::record(payload.event, &payload);
+        let webhook = self.webhook.read().expect("webhook lock poisoned").clone();
+        if let Some(webhook) = webhook {
+            if let Err(e) = webhook.post(&payload).await {
+                warn!("Watchdog alert to {} failed: {}", webhook.url, e);
+            }
+        }
+        if let Some(bus) = &self.bus {
+            bus.publish("watchdog", &payload).await;
+        }
+    }
+
+    /// Alerts the callback URL and message bus that the deadman switch restarted
+    /// continual funding, `overdue_secs` after it should have checked in.
+    pub async fn deadman_tripped(&self, overdue_secs: u64) {
+        let payload = DeadmanTripped {
+            event: "deadman",
+            overdue_secs,
+            tag: self.tag.clone(),
+        };
+        audit::record(payload.event, &payload);
+        let webhook = self.webhook.read().expect("webhook lock poisoned").clone();
+        if let Some(webhook) = webhook {
+            if let Err(e) = webhook.post(&payload).await {
+                warn!("Deadman alert to {} failed: {}", webhook.url, e);
+            }
+        }
+        if let Some(bus) = &self.bus {
+            bus.publish("watchdog", &payload).await;
+        }
+    }
+
+    /// Alerts the callback URL and message bus that a top-up has been pending for
+    /// `pending_secs`, and whether it was resubmitted.
+    pub async fn transaction_stuck(
+        &self,
+        record: &TransferRecord,
+        pending_secs: u64,
+        resubmitted_as: Option<&TxId>,
+    ) {
+        let mut record = record.clone();
+        record.tag = self.tag.clone();
+        let payload = TransactionStuck {
+            event: "transaction_stuck",
+            transfer: &record,
+            pending_secs,
+            resubmitted_as: resubmitted_as.map(|tx_id| tx_id.to_string()),
+        };
+        audit::record(payload.event, &payload);
+        let webhook = self.webhook.read().expect("webhook lock poisoned").clone();
+        if let Some(webhook) = webhook {
+            if let Err(e) = webhook.post(&payload).await {
+                warn!("Stuck transaction alert to {} failed: {}", webhook.url, e);
+            }
+        }
+        if let Some(bus) = &self.bus {
+            bus.publish("stuck", &payload).await;
+        }
+    }
+
+    /// Alerts the callback URL and message bus that a confirmed transfer credited
+    /// `credited` rather than the amount of `record`.
+    pub async fn transfer_discrepancy(&self, record: &TransferRecord, credited: u64) {
+        let mut record = record.clone();
+        record.tag = self.tag.clone();
+        let payload = TransferDiscrepancy {
+            event: "transfer_discrepancy",
+            transfer: &record,
+            credited,
+        };
+        audit::record(payload.event, &payload);
+        let webhook = self.webhook.read().expect("webhook lock poisoned").clone();
+        if let Some(webhook) = webhook {
+            if let Err(e) = webhook.post(&payload).await {
+                warn!("Discrepancy alert to {} failed: {}", webhook.url, e);
+            }
+        }
+        if let Some(bus) = &self.bus {
+            bus.publish("discrepancy", &payload).await;
+        }
+    }
+
+    /// Alerts the callback URL and message bus that HD wallet `wallet_index` received
+    /// `amount` it was not sent by continual funding.
+    pub async fn inbound_received(
+        &self,
+        wallet_index: usize,
+        address: &str,
+        asset_id: &str,
+        amount: u64,
+        balance: u64,
+    ) {
+        let payload = InboundReceived {
+            event: "inbound_received",
+            wallet_index,
+            address,
+            asset_id,
+            amount,
+            balance,
+            tag: self.tag.clone(),
+        };
+        audit::record(payload.event, &payload);
+        let webhook = self.webhook.read().expect("webhook lock poisoned").clone();
+        if let Some(webhook) = webhook {
+            if let Err(e) = webhook.post(&payload).await {
+                warn!("Inbound transfer alert to {} failed: {}", webhook.url, e);
+            }
+        }
+        if let Some(bus) = &self.bus {
+            bus.publish("inbound", &payload).await;
+        }
+    }
+
+    /// Tells the callback URL and message bus how much the main wallet needs to get
+    /// back to its reserve.
+    pub async fn refill_needed(&self, refill: &RefillNeeded) {
+        let payload = RefillEvent {
+            event: "refill_needed",
+            refill,
+            tag: self.tag.clone(),
+        };
+        audit::record(payload.event, &payload);
+        let webhook = self.webhook.read().expect("webhook lock poisoned").clone();
+        if let Some(webhook) = webhook {
+            if let Err(e) = webhook.post(&payload).await {
+                warn!("Refill notice to {} failed: {}", webhook.url, e);
+            }
+        }
+        if let Some(bus) = &self.bus {
+            bus.publish("refill", &payload).await;
+        }
+    }
+
+    /// Alerts the callback URL and message bus that an invariant failed after cycle `cycle`.
+    pub async fn invariant_violated(&self, cycle: u64, violation: &Violation, paused: bool) {
+        let payload = InvariantViolated {
+            event: "invariant_violated",
+            cycle,
+            violation,
+            paused,
+            tag: self.tag.clone(),
+        };
+        audit::record(payload.event, &payload);
+        let webhook = self.webhook.read().expect("webhook lock poisoned").clone();
+        if let Some(webhook) = webhook {
+            if let Err(e) = webhook.post(&payload).await {
+                warn!("Invariant alert to {} failed: {}", webhook.url, e);
+            }
+        }
+        if let Some(bus) = &self.bus {
+            bus.publish("invariant", &payload).await;
+        }
+    }
+}
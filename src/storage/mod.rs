@@ -0,0 +1,296 @@
+//! Persistent history and state, abstracted over the configured backend.
+
+#[cfg(feature = "postgres")]
+mod postgres;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::warn;
+
+/// A single transfer made by the tool.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferRecord {
+    /// Unix timestamp (seconds) of when the transfer was confirmed.
+    pub timestamp: u64,
+    /// The command that made the transfer (e.g. `init-dist`, `cont-fund`, `reclaim`).
+    pub command: String,
+    /// HD wallet index involved in the transfer, if any.
+    pub wallet_index: Option<u64>,
+    pub from_address: String,
+    pub to_address: String,
+    pub asset_id: String,
+    pub amount: u64,
+    pub tx_id: String,
+    /// Free-form reference carried from the recipient entry (invoice/ticket id).
+    pub memo: Option<String>,
+    /// Tag of the run that made the transfer (`--tag`).
+    pub tag: Option<String>,
+    /// Why the transfer was made: `initial`, `topup-cycle-<n>`, `reclaim`,
+    /// `gas-prefund`, or the run's `--label`.
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Position of the transfer in the total order of all transfers recorded in
+    /// this storage, across runs and hosts.
+    pub sequence: Option<u64>,
+    /// Network fee paid, in base units of the base asset; unknown for transfers
+    /// recorded by older versions.
+    #[serde(default)]
+    pub fee: Option<u64>,
+}
+
+impl TransferRecord {
+    pub fn new(
+        command: &str,
+        wallet_index: Option<usize>,
+        from_address: impl ToString,
+        to_address: impl ToString,
+        asset_id: impl ToString,
+        amount: u64,
+        tx_id: impl ToString,
+    ) -> Self {
+        Self {
+            timestamp: unix_now(),
+            command: command.to_string(),
+            wallet_index: wallet_index.map(|i| i as u64),
+            from_address: from_address.to_string(),
+            to_address: to_address.to_string(),
+            asset_id: asset_id.to_string(),
+            amount,
+            tx_id: tx_id.to_string(),
+            memo: None,
+            tag: None,
+            label: None,
+            sequence: None,
+            fee: None,
+        }
+    }
+
+    pub fn labelled(mut self, label: impl ToString) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+}
+
+/// Criteria for selecting transfers from the history; `None` fields match everything.
+#[derive(Debug, Default, Clone)]
+pub struct TransferFilter {
+    pub tag: Option<String>,
+    pub label: Option<String>,
+    /// Earliest timestamp to include (unix seconds, inclusive).
+    pub since: Option<u64>,
+    /// Latest timestamp to include (unix seconds, exclusive).
+    pub until: Option<u64>,
+    pub asset_id: Option<String>,
+    pub wallet_index: Option<u64>,
+}
+
+/// A job of the work queue (`--queue`): one planned transfer and how far it got.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobRecord {
+    pub id: u64,
+    /// The run that enqueued the job, e.g. `reclaim-1700000000`.
+    pub batch: String,
+    /// The command the job's transfer is recorded with.
+    pub command: String,
+    /// The planned transfer, as JSON.
+    pub payload: String,
+    /// `pending`, `running`, `submitted`, `done`, `stale` or `failed`.
+    pub status: String,
+    pub attempts: u32,
+    /// Transaction id once submitted.
+    pub tx_id: Option<String>,
+    /// Why the last attempt failed.
+    pub error: Option<String>,
+    /// Unix timestamp (seconds) of the last change.
+    pub updated_at: u64,
+}
+
+/// Backend for transfer history, the work queue and small pieces of key/value state
+/// such as distribution checkpoints.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Appends a transfer to the history.
+    async fn record_transfer(&self, record: &TransferRecord) -> Result<(), Box<dyn Error>>;
+
+    /// Returns the transfers matching `filter`, oldest first.
+    async fn transfers(
+        &self,
+        filter: &TransferFilter,
+    ) -> Result<Vec<TransferRecord>, Box<dyn Error>>;
+
+    /// Atomically allocates the next transfer sequence number, starting at 1.
+    async fn next_sequence(&self) -> Result<u64, Box<dyn Error>>;
+
+    async fn get_state(&self, key: &str) -> Result<Option<String>, Box<dyn Error>>;
+
+    async fn set_state(&self, key: &str, value: &str) -> Result<(), Box<dyn Error>>;
+
+    async fn delete_state(&self, key: &str) -> Result<(), Box<dyn Error>>;
+
+    /// Adds a `pending` job to the work queue for each of `payloads`, in order, all
+    /// or none.
+    async fn enqueue_jobs(
+        &self,
+        batch: &str,
+        command: &str,
+        payloads: &[String],
+    ) -> Result<(), Box<dyn Error>>;
+
+    /// Every job of the work queue, oldest first.
+    async fn jobs(&self) -> Result<Vec<JobRecord>, Box<dyn Error>>;
+
+    /// Saves a job's status, attempts, transaction id and error.
+    async fn update_job(&self, job: &JobRecord) -> Result<(), Box<dyn Error>>;
+}
+
+/// Opens the storage backend identified by `url`.
+///
+/// `sqlite://<path>` selects the SQLite backend and `postgres://...` the
+/// Postgres backend; each is only available when built with its feature.
+pub async fn open(url: &str) -> Result<Box<dyn Storage>, Box<dyn Error>> {
+    if let Some(path) = url.strip_prefix("sqlite://") {
+        #[cfg(feature = "sqlite")]
+        return Ok(Box::new(sqlite::SqliteStorage::open(path)?));
+        #[cfg(not(feature = "sqlite"))]
+        return Err(format!("Cannot open '{}': built without the `sqlite` feature", path).into());
+    }
+
+    if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+        #[cfg(feature = "postgres")]
+        return Ok(Box::new(postgres::PostgresStorage::connect(url).await?));
+        #[cfg(not(feature = "postgres"))]
+        return Err("Cannot open Postgres storage: built without the `postgres` feature".into());
+    }
+
+    Err(format!(
+        "Unsupported STORAGE_URL '{}': expected sqlite://<path> or postgres://...",
+        url
+    )
+    .into())
+}
+
+/// Records a transfer if storage is configured. Failures are reported but do
+/// not abort the run, since the transfer itself already happened.
+pub async fn record(storage: Option<&dyn Storage>, record: TransferRecord) {
+    if let Some(storage) = storage {
+        if let Err(e) = storage.record_transfer(&record).await {
+            warn!(
+                "Failed to record transfer {} in history: {}",
+                record.tx_id, e
+            );
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+pub mod mock {
+    use super::*;
+    use std::{collections::HashMap, sync::Mutex};
+
+    /// In-memory storage; transfer filters are ignored.
+    #[derive(Default)]
+    pub struct MemoryStorage {
+        pub transfers: Mutex<Vec<TransferRecord>>,
+        pub state: Mutex<HashMap<String, String>>,
+        pub jobs: Mutex<Vec<JobRecord>>,
+        sequence: Mutex<u64>,
+    }
+
+    impl MemoryStorage {
+        pub fn with_transfers(transfers: Vec<TransferRecord>) -> Self {
+            Self {
+                transfers: Mutex::new(transfers),
+                ..Self::default()
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Storage for MemoryStorage {
+        async fn record_transfer(&self, record: &TransferRecord) -> Result<(), Box<dyn Error>> {
+            self.transfers.lock().unwrap().push(record.clone());
+            Ok(())
+        }
+
+        async fn transfers(
+            &self,
+            _filter: &TransferFilter,
+        ) -> Result<Vec<TransferRecord>, Box<dyn Error>> {
+            Ok(self.transfers.lock().unwrap().clone())
+        }
+
+        async fn next_sequence(&self) -> Result<u64, Box<dyn Error>> {
+            let mut sequence = self.sequence.lock().unwrap();
+            *sequence += 1;
+            Ok(*sequence)
+        }
+
+        async fn get_state(&self, key: &str) -> Result<Option<String>, Box<dyn Error>> {
+            Ok(self.state.lock().unwrap().get(key).cloned())
+        }
+
+        async fn set_state(&self, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
+            self.state
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), value.to_string());
+            Ok(())
+        }
+
+        async fn delete_state(&self, key: &str) -> Result<(), Box<dyn Error>> {
+            self.state.lock().unwrap().remove(key);
+            Ok(())
+        }
+
+        async fn enqueue_jobs(
+            &self,
+            batch: &str,
+            command: &str,
+            payloads: &[String],
+        ) -> Result<(), Box<dyn Error>> {
+            let mut jobs = self.jobs.lock().unwrap();
+            for payload in payloads {
+                let id = jobs.len() as u64 + 1;
+                jobs.push(JobRecord {
+                    id,
+                    batch: batch.to_string(),
+                    command: command.to_string(),
+                    payload: payload.clone(),
+                    status: "pending".to_string(),
+                    attempts: 0,
+                    tx_id: None,
+                    error: None,
+                    updated_at: unix_now(),
+                });
+            }
+            Ok(())
+        }
+
+        async fn jobs(&self) -> Result<Vec<JobRecord>, Box<dyn Error>> {
+            Ok(self.jobs.lock().unwrap().clone())
+        }
+
+        async fn update_job(&self, job: &JobRecord) -> Result<(), Box<dyn Error>> {
+            let mut jobs = self.jobs.lock().unwrap();
+            let stored = jobs
+                .iter_mut()
+                .find(|stored| stored.id == job.id)
+                .ok_or_else(|| format!("No job {}", job.id))?;
+            *stored = job.clone();
+            Ok(())
+        }
+    }
+}
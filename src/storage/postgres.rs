@@ -0,0 +1,239 @@
+use super::{JobRecord, Storage, TransferFilter, TransferRecord};
+use async_trait::async_trait;
+use std::error::Error;
+use tokio_postgres::{Client, NoTls};
+use tracing::error;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS transfers (
+        id BIGSERIAL PRIMARY KEY,
+        timestamp BIGINT NOT NULL,
+        command TEXT NOT NULL,
+        wallet_index BIGINT,
+        from_address TEXT NOT NULL,
+        to_address TEXT NOT NULL,
+        asset_id TEXT NOT NULL,
+        amount BIGINT NOT NULL,
+        tx_id TEXT NOT NULL,
+        memo TEXT,
+        tag TEXT,
+        sequence BIGINT,
+        label TEXT,
+        fee BIGINT
+    );
+    ALTER TABLE transfers ADD COLUMN IF NOT EXISTS memo TEXT;
+    ALTER TABLE transfers ADD COLUMN IF NOT EXISTS tag TEXT;
+    ALTER TABLE transfers ADD COLUMN IF NOT EXISTS sequence BIGINT;
+    ALTER TABLE transfers ADD COLUMN IF NOT EXISTS label TEXT;
+    ALTER TABLE transfers ADD COLUMN IF NOT EXISTS fee BIGINT;
+    CREATE SEQUENCE IF NOT EXISTS transfer_sequence;
+    CREATE TABLE IF NOT EXISTS state (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS jobs (
+        id BIGSERIAL PRIMARY KEY,
+        batch TEXT NOT NULL,
+        command TEXT NOT NULL,
+        payload TEXT NOT NULL,
+        status TEXT NOT NULL,
+        attempts BIGINT NOT NULL,
+        tx_id TEXT,
+        error TEXT,
+        updated_at BIGINT NOT NULL
+    );
+";
+
+pub struct PostgresStorage {
+    client: Client,
+}
+
+impl PostgresStorage {
+    pub async fn connect(url: &str) -> Result<Self, Box<dyn Error>> {
+        let (client, connection) = tokio_postgres::connect(url, NoTls).await?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                error!("Postgres connection error: {}", e);
+            }
+        });
+        client.batch_execute(SCHEMA).await?;
+        Ok(Self { client })
+    }
+}
+
+#[async_trait]
+impl Storage for PostgresStorage {
+    async fn record_transfer(&self, record: &TransferRecord) -> Result<(), Box<dyn Error>> {
+        let timestamp = i64::try_from(record.timestamp)?;
+        let wallet_index = record.wallet_index.map(i64::try_from).transpose()?;
+        let amount = i64::try_from(record.amount)?;
+        let sequence = record.sequence.map(i64::try_from).transpose()?;
+        let fee = record.fee.map(i64::try_from).transpose()?;
+        self.client
+            .execute(
+                "INSERT INTO transfers
+                    (timestamp, command, wallet_index, from_address, to_address, asset_id, amount, tx_id, memo, tag, sequence, label, fee)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)",
+                &[
+                    &timestamp,
+                    &record.command,
+                    &wallet_index,
+                    &record.from_address,
+                    &record.to_address,
+                    &record.asset_id,
+                    &amount,
+                    &record.tx_id,
+                    &record.memo,
+                    &record.tag,
+                    &sequence,
+                    &record.label,
+                    &fee,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn transfers(
+        &self,
+        filter: &TransferFilter,
+    ) -> Result<Vec<TransferRecord>, Box<dyn Error>> {
+        let since = filter.since.map(i64::try_from).transpose()?;
+        let until = filter.until.map(i64::try_from).transpose()?;
+        let wallet_index = filter.wallet_index.map(i64::try_from).transpose()?;
+        let rows = self
+            .client
+            .query(
+                "SELECT timestamp, command, wallet_index, from_address, to_address, asset_id, amount, tx_id, memo, tag, sequence, label, fee
+                 FROM transfers
+                 WHERE ($1::TEXT IS NULL OR tag = $1)
+                   AND ($2::BIGINT IS NULL OR timestamp >= $2)
+                   AND ($3::BIGINT IS NULL OR timestamp < $3)
+                   AND ($4::TEXT IS NULL OR asset_id = $4)
+                   AND ($5::BIGINT IS NULL OR wallet_index = $5)
+                   AND ($6::TEXT IS NULL OR label = $6)
+                 ORDER BY id",
+                &[
+                    &filter.tag,
+                    &since,
+                    &until,
+                    &filter.asset_id,
+                    &wallet_index,
+                    &filter.label,
+                ],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| TransferRecord {
+                timestamp: row.get::<_, i64>(0) as u64,
+                command: row.get(1),
+                wallet_index: row.get::<_, Option<i64>>(2).map(|i| i as u64),
+                from_address: row.get(3),
+                to_address: row.get(4),
+                asset_id: row.get(5),
+                amount: row.get::<_, i64>(6) as u64,
+                tx_id: row.get(7),
+                memo: row.get(8),
+                tag: row.get(9),
+                sequence: row.get::<_, Option<i64>>(10).map(|s| s as u64),
+                label: row.get(11),
+                fee: row.get::<_, Option<i64>>(12).map(|f| f as u64),
+            })
+            .collect())
+    }
+
+    async fn next_sequence(&self) -> Result<u64, Box<dyn Error>> {
+        let row = self
+            .client
+            .query_one("SELECT nextval('transfer_sequence')", &[])
+            .await?;
+        Ok(row.get::<_, i64>(0) as u64)
+    }
+
+    async fn get_state(&self, key: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let row = self
+            .client
+            .query_opt("SELECT value FROM state WHERE key = $1", &[&key])
+            .await?;
+        Ok(row.map(|row| row.get(0)))
+    }
+
+    async fn set_state(&self, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
+        self.client
+            .execute(
+                "INSERT INTO state (key, value) VALUES ($1, $2)
+                 ON CONFLICT (key) DO UPDATE SET value = EXCLUDED.value",
+                &[&key, &value],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_state(&self, key: &str) -> Result<(), Box<dyn Error>> {
+        self.client
+            .execute("DELETE FROM state WHERE key = $1", &[&key])
+            .await?;
+        Ok(())
+    }
+
+    async fn enqueue_jobs(
+        &self,
+        batch: &str,
+        command: &str,
+        payloads: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        let now = i64::try_from(super::unix_now())?;
+        // One statement, so the jobs are enqueued all or none, in order
+        self.client
+            .execute(
+                "INSERT INTO jobs (batch, command, payload, status, attempts, updated_at)
+                 SELECT $1, $2, payload, 'pending', 0, $4
+                 FROM unnest($3::TEXT[]) WITH ORDINALITY AS p(payload, position)
+                 ORDER BY position",
+                &[&batch, &command, &payloads, &now],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn jobs(&self) -> Result<Vec<JobRecord>, Box<dyn Error>> {
+        let rows = self
+            .client
+            .query(
+                "SELECT id, batch, command, payload, status, attempts, tx_id, error, updated_at
+                 FROM jobs
+                 ORDER BY id",
+                &[],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| JobRecord {
+                id: row.get::<_, i64>(0) as u64,
+                batch: row.get(1),
+                command: row.get(2),
+                payload: row.get(3),
+                status: row.get(4),
+                attempts: row.get::<_, i64>(5) as u32,
+                tx_id: row.get(6),
+                error: row.get(7),
+                updated_at: row.get::<_, i64>(8) as u64,
+            })
+            .collect())
+    }
+
+    async fn update_job(&self, job: &JobRecord) -> Result<(), Box<dyn Error>> {
+        let id = i64::try_from(job.id)?;
+        let attempts = i64::from(job.attempts);
+        let updated_at = i64::try_from(job.updated_at)?;
+        self.client
+            .execute(
+                "UPDATE jobs SET status = $2, attempts = $3, tx_id = $4, error = $5, updated_at = $6
+                 WHERE id = $1",
+                &[&id, &job.status, &attempts, &job.tx_id, &job.error, &updated_at],
+            )
+            .await?;
+        Ok(())
+    }
+}
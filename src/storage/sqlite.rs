@@ -0,0 +1,373 @@
+use super::{JobRecord, Storage, TransferFilter, TransferRecord};
+use async_trait::async_trait;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::{error::Error, sync::Mutex};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS transfers (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        timestamp INTEGER NOT NULL,
+        command TEXT NOT NULL,
+        wallet_index INTEGER,
+        from_address TEXT NOT NULL,
+        to_address TEXT NOT NULL,
+        asset_id TEXT NOT NULL,
+        amount INTEGER NOT NULL,
+        tx_id TEXT NOT NULL,
+        memo TEXT,
+        tag TEXT,
+        sequence INTEGER,
+        label TEXT,
+        fee INTEGER
+    );
+    CREATE TABLE IF NOT EXISTS sequence (
+        id INTEGER PRIMARY KEY CHECK (id = 1),
+        value INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS state (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS jobs (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        batch TEXT NOT NULL,
+        command TEXT NOT NULL,
+        payload TEXT NOT NULL,
+        status TEXT NOT NULL,
+        attempts INTEGER NOT NULL,
+        tx_id TEXT,
+        error TEXT,
+        updated_at INTEGER NOT NULL
+    );
+";
+
+pub struct SqliteStorage {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    pub fn open(path: &str) -> Result<Self, Box<dyn Error>> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// Creates the schema on `conn`, or brings a database of an older version up to it.
+    fn from_connection(conn: Connection) -> Result<Self, Box<dyn Error>> {
+        conn.execute_batch(SCHEMA)?;
+        ensure_column(&conn, "transfers", "memo", "TEXT")?;
+        ensure_column(&conn, "transfers", "tag", "TEXT")?;
+        ensure_column(&conn, "transfers", "sequence", "INTEGER")?;
+        ensure_column(&conn, "transfers", "label", "TEXT")?;
+        ensure_column(&conn, "transfers", "fee", "INTEGER")?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn record_transfer(&self, record: &TransferRecord) -> Result<(), Box<dyn Error>> {
+        self.conn().execute(
+            "INSERT INTO transfers
+                (timestamp, command, wallet_index, from_address, to_address, asset_id, amount, tx_id, memo, tag, sequence, label, fee)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            params![
+                i64::try_from(record.timestamp)?,
+                record.command,
+                record.wallet_index.map(i64::try_from).transpose()?,
+                record.from_address,
+                record.to_address,
+                record.asset_id,
+                i64::try_from(record.amount)?,
+                record.tx_id,
+                record.memo,
+                record.tag,
+                record.sequence.map(i64::try_from).transpose()?,
+                record.label,
+                record.fee.map(i64::try_from).transpose()?,
+            ],
+        )?;
+        Ok(())
+    }
+
+    async fn transfers(
+        &self,
+        filter: &TransferFilter,
+    ) -> Result<Vec<TransferRecord>, Box<dyn Error>> {
+        let since = filter.since.map(i64::try_from).transpose()?;
+        let until = filter.until.map(i64::try_from).transpose()?;
+        let wallet_index = filter.wallet_index.map(i64::try_from).transpose()?;
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT timestamp, command, wallet_index, from_address, to_address, asset_id, amount, tx_id, memo, tag, sequence, label, fee
+             FROM transfers
+             WHERE (?1 IS NULL OR tag = ?1)
+               AND (?2 IS NULL OR timestamp >= ?2)
+               AND (?3 IS NULL OR timestamp < ?3)
+               AND (?4 IS NULL OR asset_id = ?4)
+               AND (?5 IS NULL OR wallet_index = ?5)
+               AND (?6 IS NULL OR label = ?6)
+             ORDER BY id",
+        )?;
+        let params = params![
+            filter.tag,
+            since,
+            until,
+            filter.asset_id,
+            wallet_index,
+            filter.label
+        ];
+        let rows = stmt.query_map(params, |row| {
+            Ok(TransferRecord {
+                timestamp: row.get::<_, i64>(0)? as u64,
+                command: row.get(1)?,
+                wallet_index: row.get::<_, Option<i64>>(2)?.map(|i| i as u64),
+                from_address: row.get(3)?,
+                to_address: row.get(4)?,
+                asset_id: row.get(5)?,
+                amount: row.get::<_, i64>(6)? as u64,
+                tx_id: row.get(7)?,
+                memo: row.get(8)?,
+                tag: row.get(9)?,
+                sequence: row.get::<_, Option<i64>>(10)?.map(|s| s as u64),
+                label: row.get(11)?,
+                fee: row.get::<_, Option<i64>>(12)?.map(|f| f as u64),
+            })
+        })?;
+        let transfers = rows.collect::<Result<Vec<_>, _>>()?;
+        Ok(transfers)
+    }
+
+    async fn next_sequence(&self) -> Result<u64, Box<dyn Error>> {
+        let value: i64 = self.conn().query_row(
+            "INSERT INTO sequence (id, value) VALUES (1, 1)
+             ON CONFLICT(id) DO UPDATE SET value = value + 1
+             RETURNING value",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(value as u64)
+    }
+
+    async fn get_state(&self, key: &str) -> Result<Option<String>, Box<dyn Error>> {
+        Ok(self
+            .conn()
+            .query_row("SELECT value FROM state WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()?)
+    }
+
+    async fn set_state(&self, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
+        self.conn().execute(
+            "INSERT INTO state (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            [key, value],
+        )?;
+        Ok(())
+    }
+
+    async fn delete_state(&self, key: &str) -> Result<(), Box<dyn Error>> {
+        self.conn()
+            .execute("DELETE FROM state WHERE key = ?1", [key])?;
+        Ok(())
+    }
+
+    async fn enqueue_jobs(
+        &self,
+        batch: &str,
+        command: &str,
+        payloads: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        let now = i64::try_from(super::unix_now())?;
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        for payload in payloads {
+            tx.execute(
+                "INSERT INTO jobs (batch, command, payload, status, attempts, updated_at)
+                 VALUES (?1, ?2, ?3, 'pending', 0, ?4)",
+                params![batch, command, payload, now],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    async fn jobs(&self) -> Result<Vec<JobRecord>, Box<dyn Error>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT id, batch, command, payload, status, attempts, tx_id, error, updated_at
+             FROM jobs
+             ORDER BY id",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(JobRecord {
+                id: row.get::<_, i64>(0)? as u64,
+                batch: row.get(1)?,
+                command: row.get(2)?,
+                payload: row.get(3)?,
+                status: row.get(4)?,
+                attempts: row.get::<_, i64>(5)? as u32,
+                tx_id: row.get(6)?,
+                error: row.get(7)?,
+                updated_at: row.get::<_, i64>(8)? as u64,
+            })
+        })?;
+        let jobs = rows.collect::<Result<Vec<_>, _>>()?;
+        Ok(jobs)
+    }
+
+    async fn update_job(&self, job: &JobRecord) -> Result<(), Box<dyn Error>> {
+        self.conn().execute(
+            "UPDATE jobs SET status = ?2, attempts = ?3, tx_id = ?4, error = ?5, updated_at = ?6
+             WHERE id = ?1",
+            params![
+                i64::try_from(job.id)?,
+                job.status,
+                i64::from(job.attempts),
+                job.tx_id,
+                job.error,
+                i64::try_from(job.updated_at)?,
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+/// Adds `column` to `table` when opening a database created by an older version.
+fn ensure_column(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>, _>>()?
+        .iter()
+        .any(|name| name == column);
+    if !exists {
+        conn.execute_batch(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table, column, definition
+        ))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(tx_id: &str) -> TransferRecord {
+        let mut record = TransferRecord::new(
+            "cont-fund",
+            Some(3),
+            "fuel1from",
+            "fuel1to",
+            "0xasset",
+            5_000,
+            tx_id,
+        );
+        record.memo = Some("INV-1".to_string());
+        record.label = Some("topup-cycle-1".to_string());
+        record.fee = Some(1_000);
+        record
+    }
+
+    #[tokio::test]
+    async fn migrates_a_database_from_before_memos_and_labels() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE transfers (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp INTEGER NOT NULL,
+                command TEXT NOT NULL,
+                wallet_index INTEGER,
+                from_address TEXT NOT NULL,
+                to_address TEXT NOT NULL,
+                asset_id TEXT NOT NULL,
+                amount INTEGER NOT NULL,
+                tx_id TEXT NOT NULL
+            );
+            INSERT INTO transfers
+                (timestamp, command, wallet_index, from_address, to_address, asset_id, amount, tx_id)
+            VALUES (100, 'init-dist', 1, 'fuel1from', 'fuel1to', '0xasset', 7, '0xold');",
+        )
+        .unwrap();
+
+        let storage = SqliteStorage::from_connection(conn).unwrap();
+        storage.record_transfer(&record("0xnew")).await.unwrap();
+        let transfers = storage.transfers(&TransferFilter::default()).await.unwrap();
+        assert_eq!(transfers.len(), 2);
+        assert_eq!(transfers[0].tx_id, "0xold");
+        assert_eq!(transfers[0].memo, None);
+        assert_eq!(transfers[0].label, None);
+        assert_eq!(transfers[0].fee, None);
+        assert_eq!(transfers[1].memo.as_deref(), Some("INV-1"));
+        assert_eq!(transfers[1].label.as_deref(), Some("topup-cycle-1"));
+        assert_eq!(transfers[1].fee, Some(1_000));
+
+        // Opening the migrated database again leaves it as it is
+        let conn = storage.conn.into_inner().unwrap();
+        let storage = SqliteStorage::from_connection(conn).unwrap();
+        assert_eq!(
+            storage
+                .transfers(&TransferFilter {
+                    label: Some("topup-cycle-1".to_string()),
+                    ..TransferFilter::default()
+                })
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn hands_out_increasing_sequence_numbers() {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+        for expected in 1..=3 {
+            assert_eq!(storage.next_sequence().await.unwrap(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn persists_state_and_jobs() {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+        assert_eq!(storage.get_state("checkpoint").await.unwrap(), None);
+        storage.set_state("checkpoint", "1").await.unwrap();
+        storage.set_state("checkpoint", "2").await.unwrap();
+        assert_eq!(
+            storage.get_state("checkpoint").await.unwrap().as_deref(),
+            Some("2")
+        );
+        storage.delete_state("checkpoint").await.unwrap();
+        assert_eq!(storage.get_state("checkpoint").await.unwrap(), None);
+
+        storage
+            .enqueue_jobs(
+                "reclaim-1",
+                "reclaim",
+                &["{}".to_string(), "[]".to_string()],
+            )
+            .await
+            .unwrap();
+        let mut jobs = storage.jobs().await.unwrap();
+        assert_eq!(jobs.len(), 2);
+        assert!(jobs
+            .iter()
+            .all(|job| job.batch == "reclaim-1" && job.status == "pending" && job.attempts == 0));
+
+        jobs[1].status = "done".to_string();
+        jobs[1].attempts = 1;
+        jobs[1].tx_id = Some("0xdone".to_string());
+        storage.update_job(&jobs[1]).await.unwrap();
+        assert_eq!(storage.jobs().await.unwrap(), jobs);
+    }
+}
@@ -0,0 +1,64 @@
+//! OpenTelemetry trace export: with `OTEL_EXPORTER_OTLP_ENDPOINT` set, the run's spans
+//! (cycles, wallets, provider calls, key derivation, confirmations) are sent over
+//! OTLP/gRPC, so funding latency can be traced next to the services being funded.
+//! Needs a build with the `otel` feature.
+
+use std::{env, error::Error};
+use tracing::Subscriber;
+use tracing_subscriber::{registry::LookupSpan, Layer};
+
+/// `service.name` of the exported spans unless `OTEL_SERVICE_NAME` is set.
+#[cfg(feature = "otel")]
+const SERVICE_NAME: &str = "fund_distributor";
+
+/// The layer exporting spans to `OTEL_EXPORTER_OTLP_ENDPOINT`, if set. It sees this
+/// crate's spans down to debug level whatever `RUST_LOG` says, since those are the
+/// provider calls and derivations that make up a trace.
+#[cfg(feature = "otel")]
+pub fn layer<S>() -> Result<Option<impl Layer<S>>, Box<dyn Error>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    use opentelemetry::{trace::TracerProvider as _, KeyValue};
+    use opentelemetry_sdk::{runtime, trace::Config, Resource};
+    use tracing::Level;
+    use tracing_subscriber::filter::Targets;
+
+    if env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT").is_none() {
+        return Ok(None);
+    }
+    let service_name = env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| SERVICE_NAME.into());
+    // The exporter reads the endpoint, headers and timeout from the OTEL_EXPORTER_OTLP_* variables
+    let provider = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic())
+        .with_trace_config(Config::default().with_resource(Resource::default().merge(
+            &Resource::new([KeyValue::new("service.name", service_name)]),
+        )))
+        .install_batch(runtime::Tokio)
+        .map_err(|e| format!("Failed to start OTLP trace export: {}", e))?;
+    let tracer = provider.tracer(env!("CARGO_CRATE_NAME"));
+    opentelemetry::global::set_tracer_provider(provider);
+    Ok(Some(
+        tracing_opentelemetry::layer()
+            .with_tracer(tracer)
+            .with_filter(Targets::new().with_target(env!("CARGO_CRATE_NAME"), Level::DEBUG)),
+    ))
+}
+
+#[cfg(not(feature = "otel"))]
+pub fn layer<S>() -> Result<Option<impl Layer<S>>, Box<dyn Error>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    if env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT").is_some() {
+        return Err("OTEL_EXPORTER_OTLP_ENDPOINT needs a build with the `otel` feature".into());
+    }
+    Ok(None::<tracing_subscriber::layer::Identity>)
+}
+
+/// Flushes spans not exported yet; called before the process exits.
+pub fn shutdown() {
+    #[cfg(feature = "otel")]
+    opentelemetry::global::shutdown_tracer_provider();
+}
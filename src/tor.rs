@@ -0,0 +1,66 @@
+use fuels::accounts::provider::Provider;
+use std::{
+    env,
+    error::Error,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::info;
+
+/// Routes provider traffic through a Tor SOCKS proxy.
+///
+/// The fuels provider's HTTP client picks its proxy up from the environment when
+/// it is created, so the proxy is installed process-wide. Tor builds a separate
+/// circuit for every distinct SOCKS username (`IsolateSOCKSAuth`, on by default),
+/// so each batch connects a fresh provider with its own credentials.
+pub struct TorProxy {
+    socks_addr: String,
+    /// Distinguishes this run's credentials from earlier runs'.
+    run_id: u64,
+}
+
+impl TorProxy {
+    /// Sends all provider traffic from now on through the proxy at `socks_addr`.
+    pub fn install(socks_addr: &str) -> Self {
+        let proxy = Self {
+            socks_addr: socks_addr.to_string(),
+            run_id: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        };
+        proxy.set_credentials("main");
+        info!("Routing provider traffic through Tor at {}", socks_addr);
+        proxy
+    }
+
+    /// Connects a provider whose traffic uses a circuit of its own for `batch`.
+    pub async fn isolated_provider(
+        &self,
+        url: &str,
+        batch: usize,
+    ) -> Result<Provider, Box<dyn Error>> {
+        self.set_credentials(&format!("batch-{}", batch));
+        let provider = Provider::connect(url).await;
+        // Anything connected later goes back to the shared circuit
+        self.set_credentials("main");
+        Ok(provider?)
+    }
+
+    fn set_credentials(&self, circuit: &str) {
+        // socks5h resolves the provider's host name through Tor as well
+        let proxy = format!(
+            "socks5h://{}-{}:x@{}",
+            self.run_id, circuit, self.socks_addr
+        );
+        for var in ["ALL_PROXY", "HTTPS_PROXY", "HTTP_PROXY"] {
+            env::set_var(var, &proxy);
+        }
+    }
+}
+
+/// Reclaim traffic isolation: a new circuit for every `batch_size` HD wallets.
+pub struct CircuitIsolation {
+    pub proxy: TorProxy,
+    pub provider_url: String,
+    pub batch_size: usize,
+}
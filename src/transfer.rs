@@ -0,0 +1,434 @@
+use crate::{
+    chain::{ChainClient, Confirmation},
+    context::Context,
+    recipients::parse_address,
+    storage::TransferRecord,
+};
+use clap::Args;
+use fuels::{
+    accounts::{
+        provider::{Provider, TransactionCost},
+        Account, ViewOnlyAccount,
+    },
+    prelude::TxPolicies,
+    tx::{Output, Receipt},
+    types::{
+        bech32::Bech32Address,
+        coin_type::CoinType,
+        input::Input,
+        transaction::{Transaction, TransactionType},
+        transaction_builders::{BuildableTransaction, ScriptTransactionBuilder},
+        tx_status::TxStatus,
+        Address, AssetId, TxId,
+    },
+};
+use std::{error::Error, str::FromStr, time::Duration};
+use tokio::time::{sleep, Instant};
+use tracing::{debug, error, info, warn};
+
+/// How long a submitted transfer may stay pending before it is treated as failed.
+pub const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Transaction policy overrides applied to every transfer.
+#[derive(Args, Debug, Clone, Copy)]
+pub struct TxPolicyArgs {
+    /// Tip paid to the block producer to prioritize transfers during congestion.
+    #[clap(long, env = "TX_TIP")]
+    pub tip: Option<u64>,
+
+    /// Maximum fee a single transfer may pay.
+    #[clap(long, env = "TX_MAX_FEE")]
+    pub max_fee: Option<u64>,
+
+    /// Gas limit for the transfer script.
+    #[clap(long, env = "TX_SCRIPT_GAS_LIMIT")]
+    pub script_gas_limit: Option<u64>,
+
+    /// Block height before which transfers cannot be included.
+    #[clap(long, env = "TX_MATURITY")]
+    pub maturity: Option<u64>,
+
+    /// Maximum size of the transaction witnesses, in bytes.
+    #[clap(long, env = "TX_WITNESS_LIMIT")]
+    pub witness_limit: Option<u64>,
+}
+
+impl TxPolicyArgs {
+    pub fn to_policies(self) -> TxPolicies {
+        TxPolicies::new(
+            self.tip,
+            self.witness_limit,
+            self.maturity,
+            self.max_fee,
+            self.script_gas_limit,
+        )
+    }
+}
+
+/// Result of a confirmed transfer.
+pub struct TransferOutcome {
+    pub tx_id: TxId,
+    /// Fee paid by the sender, in base units of the base asset.
+    pub fee: u64,
+}
+
+/// Result of a confirmed [`sweep`].
+pub struct SweepOutcome {
+    pub tx_id: TxId,
+    /// Fee paid by the sender, in base units of the base asset.
+    pub fee: u64,
+    /// Amount the destination received; for the base asset, the coins swept minus the fee.
+    pub amount: u64,
+    /// Coins spent by the sweep.
+    pub coins: usize,
+    /// Coins of the asset left behind because they did not fit in one transaction.
+    pub remaining: usize,
+}
+
+/// Inputs a non-base sweep leaves free for the base asset coins that pay its fee.
+const SWEEP_FEE_INPUTS: usize = 8;
+
+pub async fn send_funds(
+    from_wallet: &impl Account,
+    to_address: &Bech32Address,
+    amount: u64,
+    provider: &Provider,
+    asset_id: &AssetId,
+    tx_policies: TxPolicies,
+) -> Result<TransferOutcome, Box<dyn Error>> {
+    let from_address = from_wallet.address();
+
+    // Query the balance of the specified AssetId for the from_wallet
+    let balance = provider.get_asset_balance(from_address, *asset_id).await?;
+
+    debug!(
+        "Balance of AssetId {:?} for {}: {}",
+        asset_id, from_address, balance
+    );
+
+    // Ensure there are sufficient funds before attempting the transfer
+    if balance < amount {
+        return Err(format!(
+            "Insufficient funds: attempted to send {}, but balance is {}",
+            amount, balance
+        )
+        .into());
+    }
+
+    // Perform the transfer
+    let (tx_id, _receipts) = from_wallet
+        .transfer(to_address, amount, *asset_id, tx_policies)
+        .await?;
+
+    info!("Sent transaction: {:?}", tx_id);
+
+    // The fee the transaction itself reports, as `await_confirmation` reads it
+    let fee = match provider.tx_status(&tx_id).await? {
+        TxStatus::Success { total_fee, .. } => total_fee,
+        status => {
+            return Err(format!("Transaction {} did not succeed: {:?}", tx_id, status).into())
+        }
+    };
+
+    Ok(TransferOutcome { tx_id, fee })
+}
+
+/// Submits a transfer without waiting for it to be included in a block.
+///
+/// Coins spent by still-pending transfers are excluded from input selection by
+/// the `coin-cache` feature, so several transfers from one wallet can be in flight
+/// as long as it holds enough separate coins.
+pub async fn submit_transfer(
+    from_wallet: &impl Account,
+    to_address: &Bech32Address,
+    amount: u64,
+    provider: &Provider,
+    asset_id: &AssetId,
+    tx_policies: TxPolicies,
+) -> Result<TxId, Box<dyn Error>> {
+    let inputs = from_wallet
+        .get_asset_inputs_for_amount(*asset_id, u128::from(amount), None)
+        .await?;
+    let outputs = from_wallet.get_asset_outputs_for_amount(to_address, *asset_id, amount);
+
+    let mut tx_builder = ScriptTransactionBuilder::prepare_transfer(inputs, outputs, tx_policies);
+    from_wallet.add_witnesses(&mut tx_builder)?;
+
+    let used_base_amount = if asset_id == provider.base_asset_id() {
+        u128::from(amount)
+    } else {
+        0
+    };
+    from_wallet
+        .adjust_for_fee(&mut tx_builder, used_base_amount)
+        .await?;
+
+    let tx = tx_builder.build(provider).await?;
+    Ok(provider.send_transaction(tx).await?)
+}
+
+/// Splits part of a wallet's balance into `count` separate coins of `amount` each,
+/// sent to itself, so that many transfers can later be in flight at once.
+pub async fn split_coins(
+    wallet: &impl Account,
+    amount: u64,
+    count: usize,
+    provider: &Provider,
+    asset_id: &AssetId,
+    tx_policies: TxPolicies,
+) -> Result<TransferOutcome, Box<dyn Error>> {
+    let total = u128::from(amount) * count as u128;
+    let own_address = Address::from(wallet.address());
+    let inputs = wallet
+        .get_asset_inputs_for_amount(*asset_id, total, None)
+        .await?;
+    let mut outputs: Vec<Output> = (0..count)
+        .map(|_| Output::coin(own_address, amount, *asset_id))
+        .collect();
+    outputs.push(Output::change(own_address, 0, *asset_id));
+
+    let mut tx_builder = ScriptTransactionBuilder::prepare_transfer(inputs, outputs, tx_policies);
+    wallet.add_witnesses(&mut tx_builder)?;
+
+    let used_base_amount = if asset_id == provider.base_asset_id() {
+        total
+    } else {
+        0
+    };
+    wallet
+        .adjust_for_fee(&mut tx_builder, used_base_amount)
+        .await?;
+
+    let tx = tx_builder.build(provider).await?;
+    let tx_id = provider.send_transaction(tx).await?;
+    match await_confirmation(provider, &tx_id).await? {
+        Some(fee) => Ok(TransferOutcome { tx_id, fee }),
+        None => Err(format!("Coin split {} did not confirm in time", tx_id).into()),
+    }
+}
+
+/// Sends every spendable coin of `asset_id` held by `wallet` to `to_address` in one
+/// transaction with a single output, so a fragmented balance is emptied instead of
+/// coin selection leaving small coins behind. A base asset sweep pays its own fee
+/// out of the swept coins; other assets pay it from the wallet's base asset.
+///
+/// At most the node's input limit of coins is spent, largest first; the rest are
+/// reported in [`SweepOutcome::remaining`] for another sweep.
+pub async fn sweep(
+    wallet: &impl Account,
+    to_address: &Bech32Address,
+    provider: &Provider,
+    asset_id: &AssetId,
+    tx_policies: TxPolicies,
+) -> Result<SweepOutcome, Box<dyn Error>> {
+    let is_base = asset_id == provider.base_asset_id();
+    let mut coins = provider.get_coins(wallet.address(), *asset_id).await?;
+    if coins.is_empty() {
+        return Err(format!(
+            "{} holds no coins of {} to sweep",
+            wallet.address(),
+            asset_id
+        )
+        .into());
+    }
+
+    let max_inputs = usize::from(provider.consensus_parameters().tx_params().max_inputs());
+    let limit = if is_base {
+        max_inputs
+    } else {
+        max_inputs.saturating_sub(SWEEP_FEE_INPUTS)
+    };
+    coins.sort_by(|a, b| b.amount.cmp(&a.amount));
+    let remaining = coins.len().saturating_sub(limit);
+    coins.truncate(limit);
+
+    let count = coins.len();
+    let total = coins
+        .iter()
+        .try_fold(0u64, |total, coin| total.checked_add(coin.amount))
+        .ok_or("Swept coins overflow a u64")?;
+    let inputs: Vec<Input> = coins
+        .into_iter()
+        .map(|coin| Input::resource_signed(CoinType::Coin(coin)))
+        .collect();
+
+    // The base asset's change goes to the destination rather than back to the
+    // wallet, so whatever the fee leaves over is swept too
+    let to = Address::from(to_address);
+    let outputs = if is_base {
+        vec![Output::change(to, 0, *asset_id)]
+    } else {
+        vec![Output::coin(to, total, *asset_id)]
+    };
+
+    let mut tx_builder = ScriptTransactionBuilder::prepare_transfer(inputs, outputs, tx_policies);
+    wallet.add_witnesses(&mut tx_builder)?;
+    wallet.adjust_for_fee(&mut tx_builder, 0).await?;
+
+    let tx = tx_builder.build(provider).await?;
+    let tx_id = provider.send_transaction(tx).await?;
+    info!("Sent sweep transaction: {:?} ({} coins)", tx_id, count);
+    match await_confirmation(provider, &tx_id).await? {
+        Some(fee) => Ok(SweepOutcome {
+            tx_id,
+            fee,
+            amount: if is_base {
+                total.saturating_sub(fee)
+            } else {
+                total
+            },
+            coins: count,
+            remaining,
+        }),
+        None => Err(format!("Sweep {} did not confirm in time", tx_id).into()),
+    }
+}
+
+/// Waits until a submitted transfer is included and returns the fee it paid,
+/// or `None` if it is still pending after the confirmation timeout.
+pub async fn await_confirmation(
+    client: &dyn ChainClient,
+    tx_id: &TxId,
+) -> Result<Option<u64>, Box<dyn Error>> {
+    let started = Instant::now();
+    loop {
+        match client.confirmation(tx_id).await? {
+            Confirmation::Confirmed { fee } => return Ok(Some(fee)),
+            Confirmation::Failed(reason) => {
+                return Err(format!("Transaction {} failed: {}", tx_id, reason).into())
+            }
+            Confirmation::Pending if started.elapsed() > CONFIRMATION_TIMEOUT => return Ok(None),
+            Confirmation::Pending => sleep(Duration::from_secs(1)).await,
+        }
+    }
+}
+
+/// Amount of `asset_id` the successful transaction `tx_id` credited to `to_address`,
+/// read from the transaction itself: its coin outputs to the address, plus the
+/// `TransferOut` receipts of contract calls such as the disperser's.
+pub async fn credited(
+    provider: &Provider,
+    tx_id: &TxId,
+    to_address: &Bech32Address,
+    asset_id: &AssetId,
+) -> Result<u64, Box<dyn Error>> {
+    let response = provider
+        .get_transaction_by_id(tx_id)
+        .await?
+        .ok_or_else(|| format!("Transaction {} not found", tx_id))?;
+    let TxStatus::Success { receipts, .. } = &response.status else {
+        return Err(format!("Transaction {} did not succeed", tx_id).into());
+    };
+    let owner = Address::from(to_address);
+
+    let outputs: u128 = match &response.transaction {
+        TransactionType::Script(tx) => tx
+            .outputs()
+            .iter()
+            .filter_map(|output| match output {
+                Output::Coin {
+                    to,
+                    amount,
+                    asset_id: output_asset,
+                } if *to == owner && output_asset == asset_id => Some(u128::from(*amount)),
+                _ => None,
+            })
+            .sum(),
+        _ => 0,
+    };
+    let transferred: u128 = receipts
+        .iter()
+        .filter_map(|receipt| match receipt {
+            Receipt::TransferOut {
+                to,
+                amount,
+                asset_id: receipt_asset,
+                ..
+            } if *to == owner && receipt_asset == asset_id => Some(u128::from(*amount)),
+            _ => None,
+        })
+        .sum();
+    Ok(u64::try_from(outputs + transferred).unwrap_or(u64::MAX))
+}
+
+/// Checks that the confirmed transfer of `record` credited exactly its amount of
+/// its asset to its destination. A discrepancy is logged and reported to the sinks
+/// without stopping the run; a transaction that cannot be read is only logged.
+pub async fn verify_credit(ctx: &Context<'_>, client: &dyn ChainClient, record: &TransferRecord) {
+    let credited = async {
+        let tx_id = TxId::from_str(&record.tx_id).map_err(|e| e.to_string())?;
+        let to_address = parse_address(&record.to_address)?;
+        let asset_id = AssetId::from_str(&record.asset_id).map_err(|e| e.to_string())?;
+        client
+            .credited(&tx_id, &to_address, &asset_id)
+            .await
+            .map_err(|e| e.to_string())
+    }
+    .await;
+    match credited {
+        Ok(credited) if credited == record.amount => {
+            debug!(
+                "Transaction {} credited {} as sent.",
+                record.tx_id, credited
+            )
+        }
+        Ok(credited) => {
+            error!(
+                discrepancy = true,
+                "Transaction {} credited {} with {} of {}, but {} was sent.",
+                record.tx_id,
+                record.to_address,
+                credited,
+                record.asset_id,
+                record.amount
+            );
+            ctx.sinks.transfer_discrepancy(record, credited).await;
+        }
+        Err(e) => warn!(
+            "Could not verify what transaction {} credited: {}",
+            record.tx_id, e
+        ),
+    }
+}
+
+/// Builds the transaction that would pay every recipient from `from_wallet` in a
+/// single transfer and asks the node what it would cost, without submitting it.
+pub async fn estimate_transfer_cost(
+    from_wallet: &impl Account,
+    recipients: &[(Bech32Address, u64)],
+    provider: &Provider,
+    asset_id: &AssetId,
+    tx_policies: TxPolicies,
+) -> Result<TransactionCost, Box<dyn Error>> {
+    let total: u128 = recipients
+        .iter()
+        .map(|(_, amount)| u128::from(*amount))
+        .sum();
+    let inputs = from_wallet
+        .get_asset_inputs_for_amount(*asset_id, total, None)
+        .await?;
+    let mut outputs: Vec<Output> = recipients
+        .iter()
+        .map(|(to, amount)| Output::coin(Address::from(to), *amount, *asset_id))
+        .collect();
+    outputs.push(Output::change(
+        Address::from(from_wallet.address()),
+        0,
+        *asset_id,
+    ));
+
+    let mut tx_builder = ScriptTransactionBuilder::prepare_transfer(inputs, outputs, tx_policies);
+    from_wallet.add_witnesses(&mut tx_builder)?;
+
+    let used_base_amount = if asset_id == provider.base_asset_id() {
+        total
+    } else {
+        0
+    };
+    from_wallet
+        .adjust_for_fee(&mut tx_builder, used_base_amount)
+        .await?;
+
+    let tx = tx_builder.build(provider).await?;
+    Ok(provider.estimate_transaction_cost(tx, None, None).await?)
+}
@@ -0,0 +1,122 @@
+use crate::assets;
+use fuels::types::AssetId;
+use std::{env, error::Error, str::FromStr};
+
+/// Decimals of Fuel's base asset (ETH), used unless `ASSET_DECIMALS` is set.
+pub const DEFAULT_DECIMALS: u32 = 9;
+
+/// Reads `ASSET_DECIMALS`, falling back to the decimals `ASSETS` gives `ETH_ASSET_ID`,
+/// then to the base asset's decimals.
+pub fn decimals_from_env() -> Result<u32, Box<dyn Error>> {
+    match env::var("ASSET_DECIMALS") {
+        Ok(value) => value
+            .parse::<u32>()
+            .map_err(|e| format!("Failed to parse ASSET_DECIMALS ('{}'): {}", value, e).into()),
+        Err(_) => Ok(env::var("ETH_ASSET_ID")
+            .ok()
+            .and_then(|id| AssetId::from_str(&id).ok())
+            .and_then(|asset_id| assets::decimals(&asset_id))
+            .unwrap_or(DEFAULT_DECIMALS)),
+    }
+}
+
+/// Converts a decimal amount such as `0.005` into base units, rejecting
+/// negative values, excess precision and overflow.
+pub fn parse_amount(amount: &str, decimals: u32) -> Result<u64, String> {
+    let amount = amount.trim();
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+
+    if whole.is_empty() && fraction.is_empty() {
+        return Err(format!("'{}' is not a number", amount));
+    }
+    if !whole
+        .chars()
+        .chain(fraction.chars())
+        .all(|c| c.is_ascii_digit())
+    {
+        return Err(format!("'{}' is not a non-negative decimal number", amount));
+    }
+    if fraction.len() > decimals as usize {
+        return Err(format!(
+            "'{}' has more than {} decimal places",
+            amount, decimals
+        ));
+    }
+
+    let overflow = || format!("'{}' is too large", amount);
+    let scale = 10u64.checked_pow(decimals).ok_or_else(overflow)?;
+    let whole = if whole.is_empty() {
+        0
+    } else {
+        whole.parse::<u64>().map_err(|_| overflow())?
+    };
+    let fraction = if fraction.is_empty() {
+        0
+    } else {
+        format!("{:0<width$}", fraction, width = decimals as usize)
+            .parse::<u64>()
+            .map_err(|_| overflow())?
+    };
+
+    whole
+        .checked_mul(scale)
+        .and_then(|whole| whole.checked_add(fraction))
+        .ok_or_else(overflow)
+}
+
+/// Parses an amount setting: a value with a decimal point (`0.005`) is in decimal
+/// units of the asset, a plain integer (`5000000`) is in base units.
+pub fn parse_setting(value: &str, decimals: u32) -> Result<u64, String> {
+    let value = value.trim();
+    if value.contains('.') {
+        parse_amount(value, decimals)
+    } else {
+        value
+            .parse::<u64>()
+            .map_err(|e| format!("'{}' is not an amount: {}", value, e))
+    }
+}
+
+/// Formats base units as a decimal amount, e.g. `5000000` with 9 decimals as `0.005`.
+pub fn format_amount(amount: u128, decimals: u32) -> String {
+    let scale = match 10u128.checked_pow(decimals) {
+        Some(scale) => scale,
+        None => return amount.to_string(),
+    };
+    let whole = amount / scale;
+    let fraction = amount % scale;
+    if fraction == 0 {
+        return whole.to_string();
+    }
+    let fraction = format!("{:0>width$}", fraction, width = decimals as usize);
+    format!("{}.{}", whole, fraction.trim_end_matches('0'))
+}
+
+/// Formats network fees, which are paid in the base asset, e.g. `0.000002 ETH`.
+pub fn format_fee(fees: u128) -> String {
+    format!("{} ETH", format_amount(fees, DEFAULT_DECIMALS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_decimal_and_base_unit_settings() {
+        assert_eq!(parse_setting("0.005", 9), Ok(5_000_000));
+        assert_eq!(parse_setting("5000000", 9), Ok(5_000_000));
+        assert_eq!(parse_setting("1.5", 6), Ok(1_500_000));
+        assert!(parse_setting("0.0000000001", 9).is_err());
+        assert!(parse_setting("-1", 9).is_err());
+        assert!(parse_setting("18446744073.709551616", 9).is_err());
+    }
+
+    #[test]
+    fn formats_amounts() {
+        assert_eq!(format_amount(5_000_000, 9), "0.005");
+        assert_eq!(format_amount(2_000_000_000, 9), "2");
+        assert_eq!(format_amount(1_234_500_000, 9), "1.2345");
+        assert_eq!(format_amount(7, 0), "7");
+        assert_eq!(format_amount(1, 9), "0.000000001");
+    }
+}
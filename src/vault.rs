@@ -0,0 +1,319 @@
+//! Secrets from HashiCorp Vault (`VAULT_ADDR`): every key of the secret at
+//! `VAULT_SECRET_PATH` is exported into the process environment at startup, so
+//! `MNEMONIC`, `PROVIDER` and other credentials need not sit in a `.env` file.
+
+use secrecy::{ExposeSecret, SecretString};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::{
+    collections::HashSet,
+    env,
+    error::Error,
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+use tracing::{info, warn};
+
+/// Re-fetch interval when neither the token nor the secret carries a lease (KV v2).
+const DEFAULT_REFRESH: Duration = Duration::from_secs(3600);
+/// Never poll Vault more often than this, whatever the lease says.
+const MIN_REFRESH: Duration = Duration::from_secs(30);
+
+/// Environment keys that came from Vault, which a `.env` reload must not overwrite.
+static MANAGED: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+
+/// Whether `key` was set from Vault.
+pub fn manages(key: &str) -> bool {
+    MANAGED
+        .get()
+        .is_some_and(|keys| keys.lock().unwrap().contains(key))
+}
+
+enum Auth {
+    /// `VAULT_TOKEN`, renewed in place if renewable.
+    Token,
+    /// `VAULT_ROLE_ID` + `VAULT_SECRET_ID`, logged in again when the token expires.
+    AppRole {
+        mount: String,
+        role_id: String,
+        secret_id: SecretString,
+    },
+}
+
+pub struct Vault {
+    addr: String,
+    path: String,
+    auth: Auth,
+    token: SecretString,
+    /// Lease of the current token, if it expires.
+    token_lease: Option<Duration>,
+    client: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct AuthResponse {
+    auth: AuthInfo,
+}
+
+#[derive(Deserialize)]
+struct AuthInfo {
+    client_token: String,
+    lease_duration: u64,
+    renewable: bool,
+}
+
+#[derive(Deserialize)]
+struct TokenLookup {
+    data: TokenInfo,
+}
+
+#[derive(Deserialize)]
+struct TokenInfo {
+    ttl: u64,
+    renewable: bool,
+}
+
+#[derive(Deserialize)]
+struct SecretResponse {
+    lease_duration: u64,
+    data: Map<String, Value>,
+}
+
+impl Vault {
+    /// Logs in to the Vault configured in the environment, if `VAULT_ADDR` is set.
+    /// `VAULT_TOKEN` and `VAULT_SECRET_ID` are removed from the environment.
+    pub async fn from_env() -> Result<Option<Self>, Box<dyn Error>> {
+        let Ok(addr) = env::var("VAULT_ADDR") else {
+            return Ok(None);
+        };
+        let path = env::var("VAULT_SECRET_PATH")
+            .map_err(|_| "VAULT_SECRET_PATH must be set with VAULT_ADDR")?;
+        let token = env::var("VAULT_TOKEN").ok().map(SecretString::new);
+        let secret_id = env::var("VAULT_SECRET_ID").ok().map(SecretString::new);
+        env::remove_var("VAULT_TOKEN");
+        env::remove_var("VAULT_SECRET_ID");
+
+        let auth = match (env::var("VAULT_ROLE_ID"), secret_id) {
+            (Ok(role_id), Some(secret_id)) => Auth::AppRole {
+                mount: env::var("VAULT_APPROLE_MOUNT").unwrap_or_else(|_| "approle".into()),
+                role_id,
+                secret_id,
+            },
+            _ if token.is_some() => Auth::Token,
+            _ => {
+                return Err(
+                    "Vault needs VAULT_TOKEN, or VAULT_ROLE_ID and VAULT_SECRET_ID for AppRole"
+                        .into(),
+                )
+            }
+        };
+        let mut vault = Self {
+            addr: addr.trim_end_matches('/').to_string(),
+            path: path.trim_matches('/').to_string(),
+            auth,
+            token: token.unwrap_or_else(|| SecretString::new(String::new())),
+            token_lease: None,
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()?,
+        };
+        match vault.auth {
+            Auth::AppRole { .. } => vault.login().await?,
+            Auth::Token => vault.lookup_token().await?,
+        }
+        Ok(Some(vault))
+    }
+
+    /// Fetches the secret and exports its keys into the environment, overriding
+    /// `.env`. Returns the secret's lease, if it has one.
+    pub async fn load(&self) -> Result<Option<Duration>, Box<dyn Error>> {
+        self.export(&[]).await
+    }
+
+    async fn export(&self, skip: &[&str]) -> Result<Option<Duration>, Box<dyn Error>> {
+        let response: SecretResponse = self
+            .client
+            .get(format!("{}/v1/{}", self.addr, self.path))
+            .header("X-Vault-Token", self.token.expose_secret())
+            .send()
+            .await?
+            .error_for_status()
+            .map_err(|e| format!("Failed to read Vault secret {}: {}", self.path, e))?
+            .json()
+            .await?;
+
+        let values = secret_values(response.data);
+        let mut managed = MANAGED.get_or_init(Default::default).lock().unwrap();
+        for (key, value) in values
+            .iter()
+            .filter(|(key, _)| !skip.contains(&key.as_str()))
+        {
+            env::set_var(key, value);
+            managed.insert(key.clone());
+        }
+        info!("Loaded {} secrets from Vault ({})", values.len(), self.path);
+        Ok(lease(response.lease_duration, true))
+    }
+
+    /// Re-fetches the secret before its lease (or the token's) runs out, renewing
+    /// or re-acquiring the token first. `MNEMONIC` is not re-exported: the wallets
+    /// are derived once at startup.
+    pub async fn refresh(mut self, mut secret_lease: Option<Duration>) {
+        loop {
+            tokio::time::sleep(refresh_after(self.token_lease, secret_lease)).await;
+            let result = async {
+                self.renew().await?;
+                self.export(&["MNEMONIC"]).await
+            }
+            .await;
+            match result {
+                Ok(lease) => secret_lease = lease,
+                Err(e) => warn!("Failed to refresh secrets from Vault: {}", e),
+            }
+        }
+    }
+
+    async fn renew(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.token_lease.is_none() {
+            return Ok(());
+        }
+        match self.auth {
+            Auth::AppRole { .. } => self.login().await,
+            Auth::Token => {
+                let response: AuthResponse = self
+                    .client
+                    .post(format!("{}/v1/auth/token/renew-self", self.addr))
+                    .header("X-Vault-Token", self.token.expose_secret())
+                    .send()
+                    .await?
+                    .error_for_status()
+                    .map_err(|e| format!("Failed to renew the Vault token: {}", e))?
+                    .json()
+                    .await?;
+                self.token_lease = lease(response.auth.lease_duration, response.auth.renewable);
+                Ok(())
+            }
+        }
+    }
+
+    /// Learns whether the given token expires and can be renewed.
+    async fn lookup_token(&mut self) -> Result<(), Box<dyn Error>> {
+        let response: TokenLookup = self
+            .client
+            .get(format!("{}/v1/auth/token/lookup-self", self.addr))
+            .header("X-Vault-Token", self.token.expose_secret())
+            .send()
+            .await?
+            .error_for_status()
+            .map_err(|e| format!("Vault rejected VAULT_TOKEN: {}", e))?
+            .json()
+            .await?;
+        self.token_lease = lease(response.data.ttl, response.data.renewable);
+        if self.token_lease.is_none() && response.data.ttl > 0 {
+            warn!(
+                "VAULT_TOKEN is not renewable and expires in {}s; secrets cannot be refreshed after that",
+                response.data.ttl
+            );
+        }
+        Ok(())
+    }
+
+    async fn login(&mut self) -> Result<(), Box<dyn Error>> {
+        let Auth::AppRole {
+            mount,
+            role_id,
+            secret_id,
+        } = &self.auth
+        else {
+            return Ok(());
+        };
+        let response: AuthResponse = self
+            .client
+            .post(format!("{}/v1/auth/{}/login", self.addr, mount))
+            .json(&serde_json::json!({
+                "role_id": role_id,
+                "secret_id": secret_id.expose_secret(),
+            }))
+            .send()
+            .await?
+            .error_for_status()
+            .map_err(|e| format!("Vault AppRole login failed: {}", e))?
+            .json()
+            .await?;
+        // AppRole tokens are replaced by logging in again, renewable or not
+        self.token_lease = lease(response.auth.lease_duration, true);
+        self.token = SecretString::new(response.auth.client_token);
+        Ok(())
+    }
+}
+
+/// A lease that can be extended; `None` if it never expires or cannot be renewed.
+fn lease(seconds: u64, renewable: bool) -> Option<Duration> {
+    (renewable && seconds > 0).then(|| Duration::from_secs(seconds))
+}
+
+/// Two thirds into the shorter lease, so there is time to retry before it expires.
+fn refresh_after(token_lease: Option<Duration>, secret_lease: Option<Duration>) -> Duration {
+    match (token_lease, secret_lease) {
+        (None, None) => DEFAULT_REFRESH,
+        (a, b) => (a.into_iter().chain(b).min().unwrap() * 2 / 3).max(MIN_REFRESH),
+    }
+}
+
+/// The string values of a KV v1 or v2 secret (v2 nests them under `data`).
+fn secret_values(mut data: Map<String, Value>) -> Vec<(String, String)> {
+    if let (Some(Value::Object(inner)), Some(_)) = (data.get("data"), data.get("metadata")) {
+        data = inner.clone();
+    }
+    data.into_iter()
+        .filter_map(|(key, value)| match value {
+            Value::String(s) => Some((key, s)),
+            Value::Number(n) => Some((key, n.to_string())),
+            Value::Bool(b) => Some((key, b.to_string())),
+            _ => {
+                warn!("Ignoring Vault secret key {}: not a string", key);
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn reads_kv_v1_and_v2_secrets() {
+        let v1 = json!({ "MNEMONIC": "test test junk", "NUMBER_OF_WALLETS": 5 });
+        let v2 = json!({ "data": v1.clone(), "metadata": { "version": 3 } });
+        for secret in [v1, v2] {
+            let Value::Object(data) = secret else {
+                unreachable!()
+            };
+            let mut values = secret_values(data);
+            values.sort();
+            assert_eq!(
+                values,
+                vec![
+                    ("MNEMONIC".to_string(), "test test junk".to_string()),
+                    ("NUMBER_OF_WALLETS".to_string(), "5".to_string()),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn refreshes_before_the_shorter_lease_expires() {
+        let hour = Duration::from_secs(3600);
+        assert_eq!(refresh_after(None, None), DEFAULT_REFRESH);
+        assert_eq!(
+            refresh_after(Some(hour), Some(hour * 24)),
+            Duration::from_secs(2400)
+        );
+        assert_eq!(
+            refresh_after(None, Some(Duration::from_secs(3))),
+            MIN_REFRESH
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use crate::{context::Context, storage::TransferFilter};
+use fuels::accounts::ViewOnlyAccount;
+use std::collections::VecDeque;
+use tracing::{info, warn};
+
+/// Window the velocity limits apply to.
+const WINDOW_SECS: u64 = 3600;
+
+/// Caps on how much continual funding may send within the last hour, so a wallet
+/// that keeps burning its balance cannot drain the main wallet through top-ups.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VelocityLimits {
+    /// Top-ups per wallet (`MAX_TOP_UPS_PER_WALLET_HOUR`).
+    pub max_top_ups_per_wallet: Option<u32>,
+    /// Total amount sent, in base units (`MAX_OUTFLOW_PER_HOUR`).
+    pub max_outflow: Option<u64>,
+}
+
+/// Why a top-up was held back.
+#[derive(Debug, PartialEq, Eq)]
+pub enum LimitHit {
+    /// The wallet already received this many top-ups within the window.
+    WalletTopUps { count: u32 },
+    /// This much was already sent within the window, in base units.
+    Outflow { sent: u128 },
+}
+
+/// Top-ups sent within the last hour: (unix time, wallet index, amount).
+#[derive(Default)]
+pub struct Velocity {
+    sent: VecDeque<(u64, u64, u64)>,
+}
+
+impl Velocity {
+    /// Starts from the continual funding transfers of the last hour in the history
+    /// storage, if configured, so a restart does not reset the limits.
+    pub async fn from_history(ctx: &Context<'_>, now: u64) -> Self {
+        let mut velocity = Self::default();
+        let Some(storage) = ctx.sinks.storage else {
+            return velocity;
+        };
+        let filter = TransferFilter {
+            since: Some(now.saturating_sub(WINDOW_SECS)),
+            asset_id: Some(ctx.asset_id.to_string()),
+            ..TransferFilter::default()
+        };
+        match storage.transfers(&filter).await {
+            Ok(transfers) => {
+                for transfer in transfers.iter().filter(|t| t.command == "cont-fund") {
+                    if let Some(wallet_index) = transfer.wallet_index {
+                        // Fleets sharing the storage number their wallets alike
+                        if ctx.fleet.name.is_some()
+                            && !ctx
+                                .fleet
+                                .wallet(wallet_index as usize, None)
+                                .is_ok_and(|w| w.address().to_string() == transfer.to_address)
+                        {
+                            continue;
+                        }
+                        velocity.sent.push_back((
+                            transfer.timestamp,
+                            wallet_index,
+                            transfer.amount,
+                        ));
+                    }
+                }
+                if !velocity.sent.is_empty() {
+                    info!(
+                        "{} top-ups in the last hour count towards the velocity limits.",
+                        velocity.sent.len()
+                    );
+                }
+            }
+            Err(e) => warn!(
+                "Failed to load recent top-ups, velocity limits start from zero: {}",
+                e
+            ),
+        }
+        velocity
+    }
+
+    /// Checks whether sending `amount` to `wallet_index` at `now` stays within `limits`.
+    pub fn check(
+        &mut self,
+        limits: &VelocityLimits,
+        wallet_index: usize,
+        amount: u64,
+        now: u64,
+    ) -> Result<(), LimitHit> {
+        while self
+            .sent
+            .front()
+            .is_some_and(|(at, _, _)| *at + WINDOW_SECS <= now)
+        {
+            self.sent.pop_front();
+        }
+
+        if let Some(max) = limits.max_top_ups_per_wallet {
+            let count = self
+                .sent
+                .iter()
+                .filter(|(_, wallet, _)| *wallet == wallet_index as u64)
+                .count() as u32;
+            if count >= max {
+                return Err(LimitHit::WalletTopUps { count });
+            }
+        }
+        if let Some(max) = limits.max_outflow {
+            let sent: u128 = self
+                .sent
+                .iter()
+                .map(|(_, _, amount)| u128::from(*amount))
+                .sum();
+            if sent + u128::from(amount) > u128::from(max) {
+                return Err(LimitHit::Outflow { sent });
+            }
+        }
+        Ok(())
+    }
+
+    /// Counts a top-up that was sent.
+    pub fn record(&mut self, wallet_index: usize, amount: u64, now: u64) {
+        self.sent.push_back((now, wallet_index as u64, amount));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_top_ups_per_wallet_and_outflow() {
+        let limits = VelocityLimits {
+            max_top_ups_per_wallet: Some(2),
+            max_outflow: Some(500),
+        };
+        let mut velocity = Velocity::default();
+
+        for now in [0, 10] {
+            assert_eq!(velocity.check(&limits, 0, 100, now), Ok(()));
+            velocity.record(0, 100, now);
+        }
+        assert_eq!(
+            velocity.check(&limits, 0, 100, 20),
+            Err(LimitHit::WalletTopUps { count: 2 })
+        );
+
+        assert_eq!(velocity.check(&limits, 1, 300, 20), Ok(()));
+        velocity.record(1, 300, 20);
+        assert_eq!(
+            velocity.check(&limits, 2, 100, 30),
+            Err(LimitHit::Outflow { sent: 500 })
+        );
+
+        // The first top-ups fall out of the window an hour later
+        assert_eq!(velocity.check(&limits, 0, 100, WINDOW_SECS + 10), Ok(()));
+    }
+}
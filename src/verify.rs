@@ -0,0 +1,138 @@
+//! `verify`: checks the fleet's on-chain state against the funding policy — every
+//! HD wallet at or above the threshold and at most `MAX_WALLET_BALANCE`, the main
+//! wallet at or above its reserve, and no asset in the HD wallets but those the
+//! tool handles. Sends nothing; a post-deploy smoke test or a periodic audit.
+
+use crate::{
+    chain::ChainClient, context::Context, fund::FundingSettings, invariants::Violation,
+    signer::Funder,
+};
+use fuels::types::AssetId;
+use futures::{stream, StreamExt};
+use std::error::Error;
+
+/// Checks every HD wallet that is not excluded, and the main wallet, against
+/// `settings`. HD wallets may hold `ETH_ASSET_ID`, the base asset and
+/// `allowed_assets`. Returns every violation found.
+pub async fn verify(
+    ctx: &Context<'_>,
+    main_wallet: &Funder,
+    client: &dyn ChainClient,
+    settings: &FundingSettings,
+    allowed_assets: &[AssetId],
+) -> Result<Vec<Violation>, Box<dyn Error>> {
+    let base_asset = client.base_asset();
+    let mut violations = Vec::new();
+
+    let main_balance = client.balance(main_wallet.address(), &ctx.asset_id).await?;
+    if main_balance < settings.main_reserve {
+        violations.push(Violation {
+            invariant: "main_reserve",
+            detail: format!(
+                "the main wallet holds {}, below the reserve of {}",
+                ctx.format_amount(main_balance),
+                ctx.format_amount(settings.main_reserve)
+            ),
+        });
+    }
+
+    let mut indices = Vec::with_capacity(settings.number_of_wallets);
+    let mut addresses = Vec::with_capacity(settings.number_of_wallets);
+    for index in 0..settings.number_of_wallets {
+        if !ctx.is_excluded(index)? {
+            indices.push(index);
+            addresses.push(ctx.fleet.wallet(index, None)?.address().clone());
+        }
+    }
+    // Every asset of every wallet, so unexpected ones show up with the same query
+    let holdings: Vec<Result<Vec<(AssetId, u64)>, String>> = stream::iter(&addresses)
+        .map(|address| async move { client.balances(address).await.map_err(|e| e.to_string()) })
+        .buffered(ctx.balance_concurrency.max(1))
+        .collect()
+        .await;
+
+    for (index, holding) in indices.into_iter().zip(holdings) {
+        let holding = holding?;
+        let balance = holding
+            .iter()
+            .find(|(asset_id, _)| *asset_id == ctx.asset_id)
+            .map_or(0, |(_, balance)| *balance);
+        if balance < settings.threshold {
+            violations.push(Violation {
+                invariant: "threshold",
+                detail: format!(
+                    "HD wallet {} holds {}, below the threshold of {}",
+                    index,
+                    ctx.format_amount(balance),
+                    ctx.format_amount(settings.threshold)
+                ),
+            });
+        }
+        if let Some(max_balance) = settings.max_balance.filter(|max| balance > *max) {
+            violations.push(Violation {
+                invariant: "max_balance",
+                detail: format!(
+                    "HD wallet {} holds {}, above the maximum of {}",
+                    index,
+                    ctx.format_amount(balance),
+                    ctx.format_amount(max_balance)
+                ),
+            });
+        }
+        for (asset_id, amount) in &holding {
+            if *asset_id != ctx.asset_id
+                && *asset_id != base_asset
+                && !allowed_assets.contains(asset_id)
+            {
+                violations.push(Violation {
+                    invariant: "unexpected_asset",
+                    detail: format!(
+                        "HD wallet {} holds {} of unexpected asset {}",
+                        index, amount, asset_id
+                    ),
+                });
+            }
+        }
+    }
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::mock::{address, base_asset, other_asset, test_context, MockChain};
+
+    #[tokio::test]
+    async fn reports_every_violation() {
+        let ctx = test_context(4, base_asset());
+        let chain = MockChain::default();
+        let main_wallet = Funder::from(ctx.fleet.wallet(0, None).unwrap());
+        let mut settings = FundingSettings::new(5_000_000, 5_000_000, 4);
+        settings.max_balance = Some(20_000_000);
+        chain.set_balance(main_wallet.address(), base_asset(), 6_000_000);
+        chain.set_balance(&address(&ctx, 1), base_asset(), 5_000_000);
+        chain.set_balance(&address(&ctx, 2), base_asset(), 30_000_000);
+        chain.set_balance(&address(&ctx, 3), base_asset(), 100);
+        chain.set_balance(&address(&ctx, 3), other_asset(), 7);
+
+        let violations = verify(&ctx, &main_wallet, &chain, &settings, &[])
+            .await
+            .unwrap();
+        let invariants: Vec<&str> = violations.iter().map(|v| v.invariant).collect();
+        assert_eq!(
+            invariants,
+            vec!["max_balance", "threshold", "unexpected_asset"]
+        );
+
+        // An allowed asset is not reported; a short main wallet is, and wallet 0 is it
+        chain.set_balance(main_wallet.address(), base_asset(), 100);
+        let violations = verify(&ctx, &main_wallet, &chain, &settings, &[other_asset()])
+            .await
+            .unwrap();
+        let invariants: Vec<&str> = violations.iter().map(|v| v.invariant).collect();
+        assert_eq!(
+            invariants,
+            vec!["main_reserve", "threshold", "max_balance", "threshold"]
+        );
+    }
+}
@@ -0,0 +1,281 @@
+use fuels::{
+    accounts::{provider::Provider, wallet::WalletUnlocked},
+    crypto::SecretKey,
+};
+use secrecy::{ExposeSecret, Secret, SecretString};
+use std::{collections::HashMap, env, error::Error, fmt, str::FromStr, sync::Mutex};
+use tracing::debug_span;
+
+/// Fuel's registered BIP-44 coin type.
+pub const FUEL_COIN_TYPE: u32 = 1179993420;
+
+/// The BIP-44 style derivation convention used for the HD wallets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Derivation {
+    pub purpose: u32,
+    pub coin_type: u32,
+    /// How far along the path HD wallets 1 and up were moved by `rotate`
+    /// (`WALLET_INDEX_OFFSET`); the main wallet stays at 0.
+    pub offset: usize,
+}
+
+impl Default for Derivation {
+    fn default() -> Self {
+        Self {
+            purpose: 44,
+            coin_type: FUEL_COIN_TYPE,
+            offset: 0,
+        }
+    }
+}
+
+impl Derivation {
+    /// Reads `DERIVATION_PURPOSE`, `COIN_TYPE` and `WALLET_INDEX_OFFSET`, falling back
+    /// to Fuel's defaults and no offset.
+    pub fn from_env() -> Result<Self, Box<dyn Error>> {
+        let default = Self::default();
+        Ok(Self {
+            purpose: parse_env_u32("DERIVATION_PURPOSE")?.unwrap_or(default.purpose),
+            coin_type: parse_env_u32("COIN_TYPE")?.unwrap_or(default.coin_type),
+            offset: parse_env_u32("WALLET_INDEX_OFFSET")?.map_or(0, |offset| offset as usize),
+        })
+    }
+
+    /// The same convention with HD wallets 1 and up at `offset`.
+    pub fn with_offset(self, offset: usize) -> Self {
+        Self { offset, ..self }
+    }
+
+    /// Derivation index of the HD wallet with the given index.
+    pub fn index(&self, index: usize) -> usize {
+        if index == 0 {
+            0
+        } else {
+            index + self.offset
+        }
+    }
+
+    /// The HD wallet derived at `derivation_index`, if any is.
+    pub fn hd_index(&self, derivation_index: usize) -> Option<usize> {
+        match derivation_index {
+            0 => Some(0),
+            _ if derivation_index > self.offset => Some(derivation_index - self.offset),
+            _ => None,
+        }
+    }
+
+    /// Derivation path of the HD wallet with the given index.
+    pub fn path(&self, index: usize) -> String {
+        self.path_at(self.index(index))
+    }
+
+    fn path_at(&self, derivation_index: usize) -> String {
+        format!(
+            "m/{}'/{}'/{}'/0/0",
+            self.purpose, self.coin_type, derivation_index
+        )
+    }
+}
+
+impl FromStr for Derivation {
+    type Err = String;
+
+    /// Parses a path template as displayed, e.g. `m/44'/1179993420'/<index>'/0/0`, or
+    /// `<index+20>'` for wallets moved along by 20.
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Invalid derivation path template '{}' (expected m/<purpose>'/<coin type>'/<index>'/0/0)",
+                template
+            )
+        };
+        let parts: Vec<&str> = template.trim().split('/').collect();
+        match parts.as_slice() {
+            ["m", purpose, coin_type, index, "0", "0"] => {
+                let hardened = |part: &str| {
+                    part.strip_suffix('\'')
+                        .and_then(|n| n.parse::<u32>().ok())
+                        .ok_or_else(invalid)
+                };
+                let offset = match index
+                    .strip_prefix("<index")
+                    .and_then(|rest| rest.strip_suffix(">'"))
+                    .ok_or_else(invalid)?
+                {
+                    "" => 0,
+                    offset => offset
+                        .strip_prefix('+')
+                        .and_then(|n| n.parse::<usize>().ok())
+                        .ok_or_else(invalid)?,
+                };
+                Ok(Self {
+                    purpose: hardened(purpose)?,
+                    coin_type: hardened(coin_type)?,
+                    offset,
+                })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Derivation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.offset {
+            0 => write!(f, "m/{}'/{}'/<index>'/0/0", self.purpose, self.coin_type),
+            offset => write!(
+                f,
+                "m/{}'/{}'/<index+{}>'/0/0",
+                self.purpose, self.coin_type, offset
+            ),
+        }
+    }
+}
+
+/// The mnemonic and derivation convention all HD wallets are derived from.
+///
+/// The mnemonic is held in a zeroizing secret that is redacted from `Debug`
+/// output and scrubbed from memory when the fleet is dropped.
+///
+/// Derivation runs PBKDF2 on the mnemonic, so each wallet is derived once and its
+/// key cached by index, in a zeroizing secret scrubbed when the fleet is dropped.
+/// The wallets handed out hold copies of their keys that are not scrubbed, so they
+/// are meant to be dropped once used rather than kept.
+pub struct Fleet {
+    /// Set when several fleets run in one process (`FLEETS`).
+    pub name: Option<String>,
+    mnemonic: SecretString,
+    pub derivation: Derivation,
+    /// Keys of the wallets derived so far, by derivation index.
+    keys: Mutex<HashMap<usize, Secret<[u8; 32]>>>,
+}
+
+impl fmt::Debug for Fleet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Fleet")
+            .field("name", &self.name)
+            .field("mnemonic", &self.mnemonic)
+            .field("derivation", &self.derivation)
+            .field("cached", &self.keys.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl Fleet {
+    pub fn new(mnemonic: SecretString, derivation: Derivation) -> Self {
+        Self {
+            name: None,
+            mnemonic,
+            derivation,
+            keys: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Names the fleet, which keeps its saved state apart from other fleets'.
+    pub fn named(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// The same mnemonic with HD wallets 1 and up at `offset`, as `rotate` moves them.
+    pub fn rotated(&self, offset: usize) -> Self {
+        Self {
+            name: self.name.clone(),
+            mnemonic: SecretString::new(self.mnemonic.expose_secret().clone()),
+            derivation: self.derivation.with_offset(offset),
+            keys: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The HD wallet with the given index, attached to `provider`. Index 0 is the
+    /// main wallet.
+    pub fn wallet(
+        &self,
+        index: usize,
+        provider: Option<Provider>,
+    ) -> Result<WalletUnlocked, Box<dyn Error>> {
+        self.wallet_at(self.derivation.index(index), provider)
+    }
+
+    /// The wallet at `derivation_index` regardless of the offset, such as a funding
+    /// source, attached to `provider`.
+    pub fn wallet_at(
+        &self,
+        derivation_index: usize,
+        provider: Option<Provider>,
+    ) -> Result<WalletUnlocked, Box<dyn Error>> {
+        let mut keys = self.keys.lock().unwrap();
+        let key = match keys.get(&derivation_index) {
+            Some(key) => SecretKey::try_from(key.expose_secret().as_slice())?,
+            None => {
+                let _span = debug_span!("derive_wallet", derivation_index).entered();
+                let wallet = WalletUnlocked::new_from_mnemonic_phrase_with_path(
+                    self.mnemonic.expose_secret(),
+                    None,
+                    &self.derivation.path_at(derivation_index),
+                )?;
+                let key = *wallet.private_key();
+                keys.insert(derivation_index, Secret::new(*key));
+                key
+            }
+        };
+        Ok(WalletUnlocked::new_from_private_key(key, provider))
+    }
+}
+
+fn parse_env_u32(name: &str) -> Result<Option<u32>, Box<dyn Error>> {
+    match env::var(name) {
+        Ok(value) => value
+            .parse::<u32>()
+            .map(Some)
+            .map_err(|e| format!("Failed to parse {} ('{}'): {}", name, value, e).into()),
+        Err(_) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fuels::accounts::ViewOnlyAccount;
+
+    #[test]
+    fn caches_derived_wallets() {
+        let fleet = Fleet::new(
+            SecretString::new(
+                "test test test test test test test test test test test junk".to_string(),
+            ),
+            Derivation::default(),
+        );
+        let first = fleet.wallet(3, None).unwrap();
+        let cached = fleet.wallet(3, None).unwrap();
+        assert_eq!(first.address(), cached.address());
+        assert_ne!(first.address(), fleet.wallet(4, None).unwrap().address());
+        assert_eq!(fleet.keys.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn parses_path_templates() {
+        let derivation: Derivation = "m/44'/60'/<index>'/0/0".parse().unwrap();
+        assert_eq!((derivation.purpose, derivation.coin_type), (44, 60));
+        assert_eq!(
+            Derivation::default()
+                .to_string()
+                .parse::<Derivation>()
+                .unwrap()
+                .coin_type,
+            FUEL_COIN_TYPE
+        );
+        assert!("m/44'/60'/0'/0/<index>".parse::<Derivation>().is_err());
+
+        // Rotated wallets keep the main wallet at 0
+        let rotated: Derivation = "m/44'/60'/<index+20>'/0/0".parse().unwrap();
+        assert_eq!(rotated.offset, 20);
+        assert_eq!(rotated.to_string(), "m/44'/60'/<index+20>'/0/0");
+        assert_eq!(rotated.path(0), "m/44'/60'/0'/0/0");
+        assert_eq!(rotated.path(3), "m/44'/60'/23'/0/0");
+        assert_eq!(rotated.hd_index(23), Some(3));
+        assert_eq!(rotated.hd_index(7), None);
+        assert!("m/44'/60'/<index-1>'/0/0".parse::<Derivation>().is_err());
+        assert!("m/44/60'/<index>'/0/0".parse::<Derivation>().is_err());
+    }
+}
@@ -0,0 +1,140 @@
+//! End-to-end runs of the binary against an in-process fuel-core node.
+
+use fuels::{
+    accounts::{provider::Provider, wallet::WalletUnlocked},
+    test_helpers::{setup_single_asset_coins, setup_test_provider},
+    types::AssetId,
+};
+use std::{
+    process::Output,
+    time::{Duration, Instant},
+};
+use tokio::process::Command;
+
+const MNEMONIC: &str = "test test test test test test test test test test test junk";
+const NUMBER_OF_WALLETS: usize = 3;
+/// Amount `--init-dist` sends and `--cont-fund` tops up to, in base units.
+const AMOUNT: u64 = 5_000_000;
+
+fn wallet(index: usize, provider: &Provider) -> WalletUnlocked {
+    WalletUnlocked::new_from_mnemonic_phrase_with_path(
+        MNEMONIC,
+        Some(provider.clone()),
+        &format!("m/44'/1179993420'/{}'/0/0", index),
+    )
+    .unwrap()
+}
+
+fn other_asset() -> AssetId {
+    AssetId::from([7u8; 32])
+}
+
+/// Starts a node where the main wallet holds base asset and `other_asset()`.
+async fn node() -> Provider {
+    let main = WalletUnlocked::new_from_mnemonic_phrase_with_path(
+        MNEMONIC,
+        None,
+        "m/44'/1179993420'/0'/0/0",
+    )
+    .unwrap();
+    let mut coins = setup_single_asset_coins(main.address(), AssetId::zeroed(), 10, 1_000_000_000);
+    coins.extend(setup_single_asset_coins(
+        main.address(),
+        other_asset(),
+        10,
+        1_000_000_000,
+    ));
+    setup_test_provider(coins, vec![], None, None)
+        .await
+        .unwrap()
+}
+
+fn command(provider: &Provider, asset_id: AssetId, args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_fund_distributor"));
+    command
+        .args(args)
+        // Keep a developer's .env out of the run
+        .current_dir(std::env::temp_dir())
+        .env_clear()
+        .env("MNEMONIC", MNEMONIC)
+        .env("PROVIDER", provider.url())
+        .env("NUMBER_OF_WALLETS", NUMBER_OF_WALLETS.to_string())
+        .env("ETH_ASSET_ID", format!("{:#x}", asset_id))
+        .kill_on_drop(true);
+    command
+}
+
+async fn run(provider: &Provider, asset_id: AssetId, args: &[&str]) -> Output {
+    let output = command(provider, asset_id, args).output().await.unwrap();
+    assert!(
+        output.status.success(),
+        "{:?} failed:\n{}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+async fn balance(provider: &Provider, index: usize, asset_id: AssetId) -> u64 {
+    provider
+        .get_asset_balance(wallet(index, provider).address(), asset_id)
+        .await
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn init_dist_then_reclaim_leaves_only_dust() {
+    let provider = node().await;
+    let base = *provider.base_asset_id();
+
+    run(&provider, base, &["--init-dist"]).await;
+    for index in 1..NUMBER_OF_WALLETS {
+        assert_eq!(balance(&provider, index, base).await, AMOUNT);
+    }
+
+    run(&provider, base, &["--reclaim"]).await;
+    for index in 1..NUMBER_OF_WALLETS {
+        let left = balance(&provider, index, base).await;
+        assert!(left < AMOUNT / 100, "wallet {} kept {}", index, left);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reclaims_other_asset_with_prefunded_gas() {
+    let provider = node().await;
+    let asset = other_asset();
+
+    run(&provider, asset, &["--init-dist"]).await;
+    for index in 1..NUMBER_OF_WALLETS {
+        assert_eq!(balance(&provider, index, asset).await, AMOUNT);
+        assert_eq!(balance(&provider, index, AssetId::zeroed()).await, 0);
+    }
+
+    run(&provider, asset, &["--reclaim", "--prefund-gas"]).await;
+    for index in 1..NUMBER_OF_WALLETS {
+        assert_eq!(balance(&provider, index, asset).await, 0);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn cont_fund_tops_up_empty_wallets() {
+    let provider = node().await;
+    let base = *provider.base_asset_id();
+
+    let mut child = command(&provider, base, &["--cont-fund"]).spawn().unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(60);
+    loop {
+        let mut funded = true;
+        for index in 1..NUMBER_OF_WALLETS {
+            funded &= balance(&provider, index, base).await >= AMOUNT;
+        }
+        if funded {
+            break;
+        }
+        assert!(Instant::now() < deadline, "wallets not funded within 60s");
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+
+    child.kill().await.unwrap();
+}